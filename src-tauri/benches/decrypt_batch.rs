@@ -0,0 +1,66 @@
+//! 批量解密性能基准
+//!
+//! 对比解密 5000 条记录时，逐条由核心密码派生密钥（旧实现）
+//! 与使用会话保险库密钥（当前实现）的耗时。当前实现写入的格式为数据密钥加密的信封
+//!
+//! 运行：`cargo bench --bench decrypt_batch`
+
+use ciftl::crypter::{chacha20, StringCrypter, StringCrypterTrait};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use durian_web_lib::crypto::{decrypt_message, encrypt_message, generate_data_key, VaultKey};
+use durian_web_lib::kdf::KdfParams;
+
+/// 保险库记录数量
+const VAULT_SIZE: usize = 5000;
+
+const CORE_PASSWORD: &str = "bench_core_password";
+
+fn bench_decrypt_batch(c: &mut Criterion) {
+    let crypter = StringCrypter::<chacha20::ChaCha20CipherAlgorithm>::default();
+    let key = VaultKey::derive(CORE_PASSWORD, &KdfParams::vault().unwrap())
+        .unwrap()
+        .with_data_key(generate_data_key().unwrap())
+        .with_legacy_key(CORE_PASSWORD);
+
+    let legacy: Vec<String> = (0..VAULT_SIZE)
+        .map(|i| crypter.encrypt(&format!("password-{}", i), CORE_PASSWORD).unwrap())
+        .collect();
+    let envelopes: Vec<String> = (0..VAULT_SIZE)
+        .map(|i| encrypt_message(&format!("password-{}", i), &key).unwrap())
+        .collect();
+
+    let mut group = c.benchmark_group("decrypt_5000_entries");
+
+    // 旧实现：每条记录都把核心密码交给新的 StringCrypter 重新派生密钥
+    group.bench_function("per_call_password_derivation", |b| {
+        b.iter(|| {
+            for ciphertext in &legacy {
+                black_box(crypter.decrypt(ciphertext, CORE_PASSWORD).unwrap());
+            }
+        })
+    });
+
+    // 当前实现：迁移前的旧版密文使用登录时附加的旧版密钥
+    group.bench_function("session_key_legacy_ciphertext", |b| {
+        b.iter(|| {
+            for ciphertext in &legacy {
+                black_box(decrypt_message(ciphertext, &key).unwrap());
+            }
+        })
+    });
+
+    // 当前实现写入的格式：数据密钥加密、带认证标签的信封
+    group.bench_function("session_key_data_key_envelope", |b| {
+        b.iter(|| {
+            for ciphertext in &envelopes {
+                black_box(decrypt_message(ciphertext, &key).unwrap());
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_decrypt_batch);
+criterion_main!(benches);
@@ -0,0 +1,115 @@
+//! 错误处理模块
+//!
+//! 处理函数返回 `ServerResult`，错误统一转换为 `{ code, msg, data: null }` 响应。
+//! 修订号冲突例外，`data` 为服务器上的当前记录
+
+use std::fmt;
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+
+use crate::models::{AccountItem, ApiResponse};
+
+/// 业务失败的错误码
+pub const CODE_FAILED: i32 = 1;
+
+/// 未认证的错误码
+pub const CODE_UNAUTHORIZED: i32 = 401;
+
+/// 记录不存在的错误码
+pub const CODE_NOT_FOUND: i32 = 404;
+
+/// 修订号冲突的错误码
+pub const CODE_CONFLICT: i32 = 409;
+
+/// 服务器内部错误的错误码
+pub const CODE_INTERNAL: i32 = 500;
+
+/// 服务器错误
+#[derive(Debug)]
+pub enum ServerError {
+    /// 令牌缺失或无效
+    Unauthorized,
+    /// 请求被拒绝（业务失败）
+    Rejected(String),
+    /// 要删除的记录不存在（可能已被其他客户端删除）
+    NotFound(String),
+    /// 记录已被其他客户端修改，附带服务器上的当前记录
+    Conflict(Box<AccountItem>),
+    /// 数据库错误
+    Database(rusqlite::Error),
+    /// 其他内部错误
+    Internal(String),
+}
+
+/// 服务器操作结果
+pub type ServerResult<T> = Result<T, ServerError>;
+
+impl ServerError {
+    /// 创建业务失败错误
+    pub fn rejected(msg: impl Into<String>) -> Self {
+        ServerError::Rejected(msg.into())
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerError::Unauthorized => write!(f, "认证令牌无效"),
+            ServerError::Rejected(msg) | ServerError::NotFound(msg) => write!(f, "{}", msg),
+            ServerError::Conflict(item) => write!(f, "记录 {} 已被其他设备修改", item.rid),
+            ServerError::Database(e) => write!(f, "数据库错误: {}", e),
+            ServerError::Internal(msg) => write!(f, "内部错误: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ServerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ServerError::Database(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for ServerError {
+    fn from(err: rusqlite::Error) -> Self {
+        ServerError::Database(err)
+    }
+}
+
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        let msg = self.to_string();
+        let (status, body) = match self {
+            ServerError::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                ApiResponse::failure(CODE_UNAUTHORIZED, msg),
+            ),
+            ServerError::Rejected(_) => (StatusCode::OK, ApiResponse::failure(CODE_FAILED, msg)),
+            ServerError::NotFound(_) => (
+                StatusCode::NOT_FOUND,
+                ApiResponse::failure(CODE_NOT_FOUND, msg),
+            ),
+            ServerError::Conflict(item) => (
+                StatusCode::CONFLICT,
+                ApiResponse {
+                    code: CODE_CONFLICT,
+                    msg,
+                    data: Some(*item),
+                },
+            ),
+            ServerError::Database(_) | ServerError::Internal(_) => {
+                // 内部细节只记录在服务器日志中
+                eprintln!("{}", msg);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    ApiResponse::failure(CODE_INTERNAL, "服务器内部错误"),
+                )
+            }
+        };
+        (status, Json(body)).into_response()
+    }
+}
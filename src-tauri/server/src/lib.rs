@@ -0,0 +1,82 @@
+//! Durian 参考同步服务器
+//!
+//! 实现桌面客户端使用的 `/v1` 接口，供本地开发和 CI 端到端测试使用，
+//! 不用于生产环境。
+//!
+//! # 模块结构
+//!
+//! - `error` - 错误类型及其到 `ApiResponse` 的转换
+//! - `models` - 请求和响应结构（与客户端的 `models` 对应）
+//! - `store` - SQLite 存储（用户、令牌、数据密钥包装、账户记录）
+//! - `routes` - HTTP 路由和处理函数
+//!
+//! # 接口约定
+//! 所有响应体都是 `{ code, msg, data }` 结构，`code` 为 0 表示成功：
+//! - 业务失败（用户已存在、记录不存在等）返回 HTTP 200 和错误码 1
+//! - 要删除的记录不存在返回 HTTP 404 和错误码 404
+//! - 令牌缺失或无效返回 HTTP 401 和错误码 401
+//! - 服务器内部错误返回 HTTP 500 和错误码 500
+//!
+//! 密码字段是客户端按 KDF 参数计算的哈希，服务器加盐后再次哈希保存；
+//! KDF 参数、数据密钥包装和账户密文对服务器不透明，原样保存和返回
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use tokio::net::TcpListener;
+
+// ============================================
+// 模块声明
+// ============================================
+
+/// 错误类型
+pub mod error;
+
+/// 请求和响应结构
+pub mod models;
+
+/// SQLite 存储
+pub mod store;
+
+/// HTTP 路由
+pub mod routes;
+
+pub use store::Store;
+
+// ============================================
+// 服务器入口
+// ============================================
+
+/// 在指定地址上运行服务器，直到收到 Ctrl+C
+///
+/// # Arguments
+/// * `addr` - 监听地址，例如 `127.0.0.1:8080`
+/// * `store` - 数据存储
+pub async fn serve(addr: &str, store: Store) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    println!("Durian 同步服务器监听于 http://{}", listener.local_addr()?);
+
+    axum::serve(listener, routes::router(Arc::new(store)))
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+}
+
+/// 在后台任务中运行服务器，返回实际监听的地址
+///
+/// 用于测试：地址的端口为 0 时由系统分配空闲端口
+///
+/// # Arguments
+/// * `addr` - 监听地址
+/// * `store` - 数据存储
+pub async fn spawn(addr: &str, store: Store) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr).await?;
+    let local_addr = listener.local_addr()?;
+    let app = routes::router(Arc::new(store));
+    tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+    Ok(local_addr)
+}
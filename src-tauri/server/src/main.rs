@@ -0,0 +1,89 @@
+//! Durian 参考同步服务器
+//!
+//! 用法：`durian-server [--listen <地址>] [--database <路径>]`
+//!
+//! 默认监听 `127.0.0.1:8080`，数据保存在当前目录的 `durian-server.db`；
+//! 也可以使用环境变量 `DURIAN_SERVER_LISTEN` 和 `DURIAN_SERVER_DATABASE` 配置
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use durian_server::Store;
+
+/// 默认监听地址
+const DEFAULT_LISTEN: &str = "127.0.0.1:8080";
+
+/// 默认数据库文件
+const DEFAULT_DATABASE: &str = "durian-server.db";
+
+/// 命令行参数
+struct Options {
+    listen: String,
+    database: PathBuf,
+}
+
+impl Options {
+    /// 解析命令行参数，命令行优先于环境变量
+    fn parse() -> Result<Option<Self>, String> {
+        let mut listen =
+            std::env::var("DURIAN_SERVER_LISTEN").unwrap_or_else(|_| DEFAULT_LISTEN.to_string());
+        let mut database = std::env::var("DURIAN_SERVER_DATABASE")
+            .unwrap_or_else(|_| DEFAULT_DATABASE.to_string());
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--listen" => listen = args.next().ok_or("--listen 缺少地址")?,
+                "--database" => database = args.next().ok_or("--database 缺少路径")?,
+                "-h" | "--help" => return Ok(None),
+                _ => return Err(format!("未知参数: {}", arg)),
+            }
+        }
+
+        Ok(Some(Self {
+            listen,
+            database: PathBuf::from(database),
+        }))
+    }
+}
+
+fn usage() {
+    println!("用法: durian-server [--listen <地址>] [--database <路径>]");
+    println!("  --listen    监听地址（默认 {}）", DEFAULT_LISTEN);
+    println!(
+        "  --database  SQLite 数据库文件（默认 {}）",
+        DEFAULT_DATABASE
+    );
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let options = match Options::parse() {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            usage();
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            usage();
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let store = match Store::open(&options.database) {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("无法打开数据库 {}: {}", options.database.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match durian_server::serve(&options.listen, store).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("服务器运行失败: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,240 @@
+//! 数据模型模块
+//!
+//! 定义接口的请求和响应结构，字段与客户端 `durian_web_lib::models` 一致。
+//! KDF 参数和数据密钥包装对服务器不透明，使用 `serde_json::Value` 原样保存
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// ============================================
+// 通用响应
+// ============================================
+
+/// API 响应的通用结构
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiResponse<T> {
+    pub code: i32,
+    pub msg: String,
+    pub data: Option<T>,
+}
+
+impl<T> ApiResponse<T> {
+    /// 成功响应
+    pub fn success(data: T) -> Self {
+        Self {
+            code: 0,
+            msg: "success".to_string(),
+            data: Some(data),
+        }
+    }
+
+    /// 失败响应
+    pub fn failure(code: i32, msg: impl Into<String>) -> Self {
+        Self {
+            code,
+            msg: msg.into(),
+            data: None,
+        }
+    }
+}
+
+// ============================================
+// 认证相关
+// ============================================
+
+/// 登录前协商 KDF 参数的请求
+#[derive(Debug, Clone, Deserialize)]
+pub struct PreloginRequest {
+    pub username: String,
+}
+
+/// 登录前协商 KDF 参数的响应数据（旧版账户没有参数）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PreloginResponseData {
+    pub login_kdf: Option<Value>,
+    pub core_kdf: Option<Value>,
+    pub vault_kdf: Option<Value>,
+}
+
+/// 登录请求
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoginRequest {
+    pub username: String,
+    /// 登录密码哈希
+    pub password: String,
+    /// 核心密码哈希
+    pub core_password: String,
+}
+
+/// 登录响应数据
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginResponseData {
+    pub token: String,
+}
+
+/// 注册请求（旧版客户端不提交 KDF 参数）
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegisterRequest {
+    pub username: String,
+    pub password: String,
+    pub core_password: String,
+    #[serde(default)]
+    pub login_kdf: Option<Value>,
+    #[serde(default)]
+    pub core_kdf: Option<Value>,
+    /// 保险库密钥参数，注册后不再改变
+    #[serde(default)]
+    pub vault_kdf: Option<Value>,
+}
+
+/// 升级 KDF 参数的请求
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpgradeKdfRequest {
+    pub password: String,
+    pub core_password: String,
+    pub login_kdf: Value,
+    pub core_kdf: Value,
+}
+
+/// 修改核心密码的请求
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChangeCorePasswordRequest {
+    /// 原核心密码哈希
+    pub old_core_password: String,
+    /// 新核心密码哈希
+    pub core_password: String,
+    pub core_kdf: Value,
+    /// 用新核心密码重新包装的数据密钥
+    #[serde(default)]
+    pub wrapped_key: Option<Value>,
+}
+
+// ============================================
+// 保险库密钥相关
+// ============================================
+
+/// 数据密钥包装列表和保险库设置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VaultKeysResponseData {
+    pub keys: Vec<Value>,
+    /// 是否开启了全字段加密（所有设备共用）
+    #[serde(default)]
+    pub full_field_encryption: bool,
+}
+
+/// 修改保险库设置的请求
+#[derive(Debug, Clone, Deserialize)]
+pub struct VaultSettingsRequest {
+    pub full_field_encryption: bool,
+}
+
+// ============================================
+// 账户相关
+// ============================================
+
+/// 数据拉取模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PullMode {
+    /// 全量拉取
+    PullAll,
+    /// 增量拉取：只返回有变化的记录
+    PullUpdated,
+    /// 无更新
+    PullNothing,
+}
+
+/// 查询账户的参数
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct QueryParams {
+    /// 客户端上次同步的时间，0 表示首次同步
+    #[serde(default)]
+    pub update_time: i64,
+}
+
+/// 查询账户的响应数据
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryResponseData {
+    pub pull_mode: PullMode,
+    /// 本次同步的时间，客户端下次查询时提交
+    pub update_time: i64,
+    pub accounts: Vec<AccountItem>,
+    /// 自客户端上次同步以来被删除的记录 ID（墓碑）
+    pub deleted_rids: Vec<i64>,
+}
+
+/// 账户记录
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountItem {
+    pub rid: i64,
+    pub website: String,
+    pub account: String,
+    /// 密码密文
+    pub password: String,
+    /// 修订号，每次修改递增。更新时必须提交客户端所基于的修订号
+    #[serde(default)]
+    pub revision: i64,
+    /// 最后修改的服务器时间（毫秒），只在响应中有意义
+    #[serde(default)]
+    pub updated_at: i64,
+    /// 网站和账户字段是否为客户端加密的密文
+    #[serde(default)]
+    pub fields_encrypted: bool,
+}
+
+/// 插入账户的请求
+#[derive(Debug, Clone, Deserialize)]
+pub struct InsertAccountRequest {
+    pub website: String,
+    pub account: String,
+    pub password: String,
+    /// 网站和账户字段是否为客户端加密的密文
+    #[serde(default)]
+    pub fields_encrypted: bool,
+    /// 客户端生成的操作 ID，用于忽略重复发送的插入
+    #[serde(default)]
+    pub op_id: Option<String>,
+}
+
+/// 删除账户的请求
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeleteAccountRequest {
+    pub rid: i64,
+}
+
+/// 批量更新账户的请求
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchUpdateRequest {
+    pub accounts: Vec<AccountItem>,
+}
+
+// ============================================
+// 缓存校验
+// ============================================
+
+/// 缓存校验的请求
+#[derive(Debug, Clone, Deserialize)]
+pub struct VerifyRequest {
+    /// 客户端缓存每个分桶的摘要，分桶数为其长度
+    pub digests: Vec<String>,
+}
+
+/// 缓存校验的响应数据
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyResponseData {
+    /// 摘要不一致的分桶
+    pub buckets: Vec<usize>,
+}
+
+/// 按分桶查询账户的请求
+#[derive(Debug, Clone, Deserialize)]
+pub struct BucketQueryRequest {
+    pub bucket_count: usize,
+    pub buckets: Vec<usize>,
+}
+
+/// 按分桶查询账户的响应数据
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BucketQueryResponseData {
+    pub accounts: Vec<AccountItem>,
+}
@@ -0,0 +1,412 @@
+//! HTTP 路由模块
+//!
+//! 路径、方法和请求体与客户端 `api_client` 中的 `api_*` 函数一一对应。
+//! 需要认证的接口从 `Authorization` 请求头读取令牌（不带 `Bearer` 前缀）
+
+use std::sync::Arc;
+
+use axum::extract::{FromRequestParts, Query, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::request::Parts;
+use axum::routing::{get, post, put};
+use axum::{Json, Router};
+use serde_json::Value;
+
+use crate::error::{ServerError, ServerResult};
+use crate::models::{
+    AccountItem, ApiResponse, BatchUpdateRequest, BucketQueryRequest, BucketQueryResponseData,
+    ChangeCorePasswordRequest, DeleteAccountRequest, InsertAccountRequest, LoginRequest,
+    LoginResponseData, PreloginRequest, PreloginResponseData, QueryParams, QueryResponseData,
+    RegisterRequest, UpgradeKdfRequest, VaultKeysResponseData, VaultSettingsRequest, VerifyRequest,
+    VerifyResponseData,
+};
+use crate::store::Store;
+
+/// 处理函数的响应
+type ApiResult<T> = ServerResult<Json<ApiResponse<T>>>;
+
+/// 构建路由
+pub fn router(store: Arc<Store>) -> Router {
+    Router::new()
+        .route("/v1/login/params", post(prelogin))
+        .route("/v1/login", post(login))
+        .route("/v1/register", post(register))
+        .route("/v1/auth/verify", get(verify))
+        .route("/v1/auth/kdf", put(upgrade_kdf))
+        .route("/v1/auth/core_password", put(change_core_password))
+        .route("/v1/vault/keys", get(get_vault_keys).post(create_vault_key))
+        .route("/v1/vault/settings", put(set_vault_settings))
+        .route(
+            "/v1/account",
+            get(query_accounts)
+                .post(insert_account)
+                .put(update_account)
+                .delete(delete_account),
+        )
+        .route("/v1/account/batch", put(update_accounts_batch))
+        .route("/v1/account/verify", post(verify_accounts))
+        .route("/v1/account/buckets", post(query_account_buckets))
+        .with_state(store)
+}
+
+/// 已认证的用户（从 `Authorization` 请求头中的令牌解析）
+struct AuthUser(String);
+
+impl FromRequestParts<Arc<Store>> for AuthUser {
+    type Rejection = ServerError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        store: &Arc<Store>,
+    ) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .ok_or(ServerError::Unauthorized)?;
+        store.authenticate(token).map(AuthUser)
+    }
+}
+
+fn success<T>(data: T) -> ApiResult<T> {
+    Ok(Json(ApiResponse::success(data)))
+}
+
+// ============================================
+// 认证
+// ============================================
+
+async fn prelogin(
+    State(store): State<Arc<Store>>,
+    Json(request): Json<PreloginRequest>,
+) -> ApiResult<PreloginResponseData> {
+    // 不存在的用户与旧版账户一样返回空参数：客户端只在收到空参数时使用旧版参数，
+    // 同时避免通过该接口探测用户是否存在
+    success(store.prelogin(&request.username)?.unwrap_or_default())
+}
+
+async fn login(
+    State(store): State<Arc<Store>>,
+    Json(request): Json<LoginRequest>,
+) -> ApiResult<LoginResponseData> {
+    let token = store.login(&request)?;
+    success(LoginResponseData { token })
+}
+
+async fn register(
+    State(store): State<Arc<Store>>,
+    Json(request): Json<RegisterRequest>,
+) -> ApiResult<()> {
+    store.register(&request)?;
+    success(())
+}
+
+async fn verify(AuthUser(_): AuthUser) -> ApiResult<()> {
+    success(())
+}
+
+async fn upgrade_kdf(
+    State(store): State<Arc<Store>>,
+    AuthUser(username): AuthUser,
+    Json(request): Json<UpgradeKdfRequest>,
+) -> ApiResult<()> {
+    store.upgrade_kdf(&username, &request)?;
+    success(())
+}
+
+async fn change_core_password(
+    State(store): State<Arc<Store>>,
+    AuthUser(username): AuthUser,
+    Json(request): Json<ChangeCorePasswordRequest>,
+) -> ApiResult<()> {
+    store.change_core_password(&username, &request)?;
+    success(())
+}
+
+// ============================================
+// 保险库密钥
+// ============================================
+
+async fn get_vault_keys(
+    State(store): State<Arc<Store>>,
+    AuthUser(username): AuthUser,
+) -> ApiResult<VaultKeysResponseData> {
+    let keys = store.vault_keys(&username)?;
+    let full_field_encryption = store.full_field_encryption(&username)?;
+    success(VaultKeysResponseData {
+        keys,
+        full_field_encryption,
+    })
+}
+
+async fn create_vault_key(
+    State(store): State<Arc<Store>>,
+    AuthUser(username): AuthUser,
+    Json(key): Json<Value>,
+) -> ApiResult<()> {
+    store.create_vault_key(&username, &key)?;
+    success(())
+}
+
+async fn set_vault_settings(
+    State(store): State<Arc<Store>>,
+    AuthUser(username): AuthUser,
+    Json(request): Json<VaultSettingsRequest>,
+) -> ApiResult<()> {
+    store.set_full_field_encryption(&username, request.full_field_encryption)?;
+    success(())
+}
+
+// ============================================
+// 账户
+// ============================================
+
+async fn query_accounts(
+    State(store): State<Arc<Store>>,
+    AuthUser(username): AuthUser,
+    Query(params): Query<QueryParams>,
+) -> ApiResult<QueryResponseData> {
+    success(store.query_accounts(&username, params.update_time)?)
+}
+
+async fn insert_account(
+    State(store): State<Arc<Store>>,
+    AuthUser(username): AuthUser,
+    Json(request): Json<InsertAccountRequest>,
+) -> ApiResult<i64> {
+    success(store.insert_account(&username, &request)?)
+}
+
+async fn update_account(
+    State(store): State<Arc<Store>>,
+    AuthUser(username): AuthUser,
+    Json(item): Json<AccountItem>,
+) -> ApiResult<i64> {
+    let revisions = store.update_accounts(&username, std::slice::from_ref(&item))?;
+    success(revisions[0])
+}
+
+async fn update_accounts_batch(
+    State(store): State<Arc<Store>>,
+    AuthUser(username): AuthUser,
+    Json(request): Json<BatchUpdateRequest>,
+) -> ApiResult<Vec<i64>> {
+    success(store.update_accounts(&username, &request.accounts)?)
+}
+
+async fn delete_account(
+    State(store): State<Arc<Store>>,
+    AuthUser(username): AuthUser,
+    Json(request): Json<DeleteAccountRequest>,
+) -> ApiResult<()> {
+    store.delete_account(&username, request.rid)?;
+    success(())
+}
+
+// ============================================
+// 缓存校验
+// ============================================
+
+async fn verify_accounts(
+    State(store): State<Arc<Store>>,
+    AuthUser(username): AuthUser,
+    Json(request): Json<VerifyRequest>,
+) -> ApiResult<VerifyResponseData> {
+    let buckets = store.verify_accounts(&username, &request.digests)?;
+    success(VerifyResponseData { buckets })
+}
+
+async fn query_account_buckets(
+    State(store): State<Arc<Store>>,
+    AuthUser(username): AuthUser,
+    Json(request): Json<BucketQueryRequest>,
+) -> ApiResult<BucketQueryResponseData> {
+    let accounts = store.query_buckets(&username, request.bucket_count, &request.buckets)?;
+    success(BucketQueryResponseData { accounts })
+}
+
+// ============================================
+// 单元测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Request, StatusCode};
+    use tower::ServiceExt;
+
+    async fn call(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        token: Option<&str>,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            request = request.header(AUTHORIZATION, token);
+        }
+        let body = match body {
+            Some(body) => {
+                request = request.header("Content-Type", "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        let response = app
+            .clone()
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_api_contract() {
+        let app = router(Arc::new(Store::in_memory().unwrap()));
+        let credentials = serde_json::json!({
+            "username": "alice",
+            "password": "password_hash",
+            "core_password": "core_hash"
+        });
+
+        let (_, body) = call(
+            &app,
+            "POST",
+            "/v1/login/params",
+            None,
+            Some(serde_json::json!({ "username": "alice" })),
+        )
+        .await;
+        assert_eq!(body["code"], 0);
+        assert!(body["data"]["login_kdf"].is_null());
+
+        let (status, body) = call(
+            &app,
+            "POST",
+            "/v1/register",
+            None,
+            Some(credentials.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["code"], 0);
+
+        let (_, body) = call(
+            &app,
+            "POST",
+            "/v1/login/params",
+            None,
+            Some(serde_json::json!({ "username": "alice" })),
+        )
+        .await;
+        assert_eq!(body["code"], 0);
+        assert!(body["data"]["login_kdf"].is_null());
+
+        let (_, body) = call(&app, "POST", "/v1/login", None, Some(credentials)).await;
+        let token = body["data"]["token"].as_str().unwrap().to_string();
+        let token = Some(token.as_str());
+
+        let (status, _) = call(&app, "GET", "/v1/auth/verify", token, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, body) = call(&app, "GET", "/v1/auth/verify", Some("invalid"), None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["code"], 401);
+
+        // 全字段加密设置保存在服务器上，随数据密钥包装一起返回
+        let (_, body) = call(&app, "GET", "/v1/vault/keys", token, None).await;
+        assert_eq!(body["data"]["full_field_encryption"], false);
+        let settings = serde_json::json!({ "full_field_encryption": true });
+        let (_, body) = call(&app, "PUT", "/v1/vault/settings", token, Some(settings)).await;
+        assert_eq!(body["code"], 0);
+        let (_, body) = call(&app, "GET", "/v1/vault/keys", token, None).await;
+        assert_eq!(body["data"]["full_field_encryption"], true);
+
+        let account = serde_json::json!({
+            "website": "a.com",
+            "account": "alice",
+            "password": "ciphertext",
+            "fields_encrypted": true
+        });
+        let (_, body) = call(&app, "POST", "/v1/account", token, Some(account)).await;
+        assert_eq!(body["code"], 0);
+        let rid = body["data"].as_i64().unwrap();
+
+        let (_, body) = call(&app, "GET", "/v1/account?update_time=0", token, None).await;
+        assert_eq!(body["data"]["pull_mode"], "PULL_ALL");
+        assert_eq!(body["data"]["accounts"][0]["rid"], rid);
+        assert_eq!(body["data"]["accounts"][0]["fields_encrypted"], true);
+        let update_time = body["data"]["update_time"].as_i64().unwrap();
+
+        let uri = format!("/v1/account?update_time={}", update_time);
+        let (_, body) = call(&app, "GET", &uri, token, None).await;
+        assert_eq!(body["data"]["pull_mode"], "PULL_NOTHING");
+
+        let update = serde_json::json!({ "rid": rid, "website": "a.com", "account": "bob", "password": "ciphertext", "revision": 1 });
+        let (_, body) = call(&app, "PUT", "/v1/account", token, Some(update.clone())).await;
+        assert_eq!(body["code"], 0);
+        assert_eq!(body["data"], 2);
+        let (_, body) = call(&app, "GET", &uri, token, None).await;
+        assert_eq!(body["data"]["pull_mode"], "PULL_UPDATED");
+        assert_eq!(body["data"]["accounts"][0]["account"], "bob");
+        assert_eq!(body["data"]["accounts"][0]["revision"], 2);
+        assert_eq!(body["data"]["accounts"][0]["fields_encrypted"], false);
+
+        // 基于旧修订号的修改返回冲突和服务器上的当前记录
+        let (status, body) = call(&app, "PUT", "/v1/account", token, Some(update)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], 409);
+        assert_eq!(body["data"]["revision"], 2);
+
+        let (_, body) = call(
+            &app,
+            "DELETE",
+            "/v1/account",
+            token,
+            Some(serde_json::json!({ "rid": rid })),
+        )
+        .await;
+        assert_eq!(body["code"], 0);
+        let (_, body) = call(&app, "GET", &uri, token, None).await;
+        assert_eq!(body["data"]["pull_mode"], "PULL_UPDATED");
+        assert_eq!(body["data"]["accounts"], serde_json::json!([]));
+        assert_eq!(body["data"]["deleted_rids"], serde_json::json!([rid]));
+
+        // 空缓存与空账户列表一致；分桶数无效时拒绝
+        let empty = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        let (_, body) = call(
+            &app,
+            "POST",
+            "/v1/account/verify",
+            token,
+            Some(serde_json::json!({ "digests": [empty, empty] })),
+        )
+        .await;
+        assert_eq!(body["code"], 0);
+        assert_eq!(body["data"]["buckets"], serde_json::json!([]));
+        let (_, body) = call(
+            &app,
+            "POST",
+            "/v1/account/buckets",
+            token,
+            Some(serde_json::json!({ "bucket_count": 0, "buckets": [] })),
+        )
+        .await;
+        assert_eq!(body["code"], 1);
+
+        // 重复删除返回 404，客户端据此视为已删除
+        let (status, body) = call(
+            &app,
+            "DELETE",
+            "/v1/account",
+            token,
+            Some(serde_json::json!({ "rid": rid })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], 404);
+    }
+}
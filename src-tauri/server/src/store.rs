@@ -0,0 +1,1009 @@
+//! 数据存储模块
+//!
+//! 使用单个 SQLite 连接保存用户、令牌、数据密钥包装和账户记录
+//!
+//! # 同步时间
+//! 服务器维护一个持久化的时间戳（毫秒），每次写入推进时间并记录在变化的记录上，
+//! 保证写入时间严格递增，即使系统时钟回拨。查询账户时：
+//! - 客户端首次同步，或客户端时间晚于服务器时间（例如服务器数据被重置）时返回 `PULL_ALL`
+//! - 其后有记录被插入、修改或删除时返回 `PULL_UPDATED`、变化的记录和被删除记录的墓碑
+//! - 没有任何变化时返回 `PULL_NOTHING`
+//!
+//! # 墓碑
+//! 删除记录时在 `deleted_accounts` 表中保留其 ID 和删除时间，
+//! 使其他设备在增量同步时也能删除本地缓存中的记录
+//!
+//! # 修订号
+//! 每条记录带有从 1 开始的修订号，每次修改递增。更新请求携带客户端所基于的修订号，
+//! 与服务器不一致时拒绝整个请求并返回当前记录（[`ServerError::Conflict`]），
+//! 由客户端决定保留哪个版本。不携带修订号（为 0）的更新请求被拒绝，
+//! 避免未经检查地覆盖其他设备的修改
+//!
+//! # 插入去重
+//! 插入请求可以携带客户端生成的操作 ID。服务器记录每个操作 ID 插入的记录，
+//! 收到重复的操作 ID（客户端未收到响应后重新发送）时直接返回原记录 ID，不再插入
+//!
+//! # 缓存校验
+//! 客户端按 `rid.rem_euclid(分桶数)` 将缓存的 `(rid, revision)` 分桶，
+//! 提交每个分桶按 `rid` 升序拼接 `"{rid}:{revision}\n"` 的 SHA-256（小写十六进制）。
+//! 服务器用相同算法（与客户端 `durian_web_lib::verify` 一致）计算并返回不一致的分桶，
+//! 客户端再按分桶重新拉取记录
+
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ring::digest;
+use ring::rand::{SecureRandom, SystemRandom};
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction};
+use serde_json::Value;
+
+use crate::error::{ServerError, ServerResult};
+use crate::models::{
+    AccountItem, ChangeCorePasswordRequest, InsertAccountRequest, LoginRequest,
+    PreloginResponseData, PullMode, QueryResponseData, RegisterRequest, UpgradeKdfRequest,
+};
+
+/// 令牌长度（字节）
+const TOKEN_LEN: usize = 32;
+
+/// 密码哈希盐值长度（字节）
+const SALT_LEN: usize = 16;
+
+/// 数据库表结构
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS users (
+        username TEXT PRIMARY KEY,
+        salt TEXT NOT NULL,
+        password_hash TEXT NOT NULL,
+        core_password_hash TEXT NOT NULL,
+        login_kdf TEXT,
+        core_kdf TEXT
+    );
+    CREATE TABLE IF NOT EXISTS tokens (
+        token TEXT PRIMARY KEY,
+        username TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS vault_keys (
+        username TEXT NOT NULL,
+        key_id TEXT NOT NULL,
+        body TEXT NOT NULL,
+        PRIMARY KEY (username, key_id)
+    );
+    CREATE TABLE IF NOT EXISTS accounts (
+        rid INTEGER PRIMARY KEY AUTOINCREMENT,
+        username TEXT NOT NULL,
+        website TEXT NOT NULL,
+        account TEXT NOT NULL,
+        password TEXT NOT NULL,
+        update_time INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_accounts_username_time ON accounts(username, update_time);
+    CREATE TABLE IF NOT EXISTS deleted_accounts (
+        rid INTEGER PRIMARY KEY,
+        username TEXT NOT NULL,
+        deleted_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_deleted_accounts_username_time
+        ON deleted_accounts(username, deleted_at);
+    CREATE TABLE IF NOT EXISTS clock (
+        id INTEGER PRIMARY KEY CHECK (id = 0),
+        value INTEGER NOT NULL
+    );
+    INSERT OR IGNORE INTO clock (id, value) VALUES (0, 0);
+";
+
+/// 表结构迁移，第 i 项将 `PRAGMA user_version` 从 i 升级到 i + 1
+const MIGRATIONS: &[&str] = &[
+    // 1: 账户记录的修订号
+    "ALTER TABLE accounts ADD COLUMN revision INTEGER NOT NULL DEFAULT 1;",
+    // 2: 全字段加密设置和记录的字段加密标记
+    "ALTER TABLE users ADD COLUMN full_field_encryption INTEGER NOT NULL DEFAULT 0;
+     ALTER TABLE accounts ADD COLUMN fields_encrypted INTEGER NOT NULL DEFAULT 0;",
+    // 3: 已处理的插入操作 ID
+    "CREATE TABLE IF NOT EXISTS insert_ops (
+         username TEXT NOT NULL,
+         op_id TEXT NOT NULL,
+         rid INTEGER NOT NULL,
+         PRIMARY KEY (username, op_id)
+     );",
+    // 4: 保险库密钥参数
+    "ALTER TABLE users ADD COLUMN vault_kdf TEXT;",
+];
+
+/// 缓存校验允许的最大分桶数
+const MAX_BUCKETS: usize = 4096;
+
+/// 查询账户记录的列，顺序与 [`account_item`] 一致
+const ACCOUNT_COLUMNS: &str =
+    "rid, website, account, password, revision, update_time, fields_encrypted";
+
+/// SQLite 存储
+pub struct Store {
+    conn: Mutex<Connection>,
+    rng: SystemRandom,
+}
+
+impl Store {
+    /// 打开（必要时创建）数据库文件
+    ///
+    /// # Arguments
+    /// * `path` - 数据库文件路径
+    pub fn open(path: &Path) -> ServerResult<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch("PRAGMA journal_mode = WAL;")?;
+        Self::with_connection(conn)
+    }
+
+    /// 创建内存数据库（用于测试，数据不会保存）
+    pub fn in_memory() -> ServerResult<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> ServerResult<Self> {
+        conn.execute_batch(SCHEMA)?;
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            conn.execute_batch(migration)?;
+            conn.pragma_update(None, "user_version", i as i64 + 1)?;
+        }
+        Ok(Self {
+            conn: Mutex::new(conn),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    fn conn(&self) -> ServerResult<MutexGuard<'_, Connection>> {
+        self.conn
+            .lock()
+            .map_err(|_| ServerError::Internal("数据库连接锁已中毒".to_string()))
+    }
+
+    // ============================================
+    // 认证
+    // ============================================
+
+    /// 获取用户的 KDF 参数，用户不存在时返回 `None`
+    pub fn prelogin(&self, username: &str) -> ServerResult<Option<PreloginResponseData>> {
+        let conn = self.conn()?;
+        let row: Option<(Option<String>, Option<String>, Option<String>)> = conn
+            .query_row(
+                "SELECT login_kdf, core_kdf, vault_kdf FROM users WHERE username = ?1",
+                [username],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+
+        row.map(|(login_kdf, core_kdf, vault_kdf)| {
+            Ok(PreloginResponseData {
+                login_kdf: parse_json(login_kdf)?,
+                core_kdf: parse_json(core_kdf)?,
+                vault_kdf: parse_json(vault_kdf)?,
+            })
+        })
+        .transpose()
+    }
+
+    /// 注册用户
+    pub fn register(&self, request: &RegisterRequest) -> ServerResult<()> {
+        require(&request.username, "用户名")?;
+        require(&request.password, "密码")?;
+        require(&request.core_password, "核心密码")?;
+
+        let salt = self.random_hex(SALT_LEN)?;
+        let conn = self.conn()?;
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO users
+                (username, salt, password_hash, core_password_hash, login_kdf, core_kdf, vault_kdf)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                request.username,
+                salt,
+                hash_secret(&salt, &request.password),
+                hash_secret(&salt, &request.core_password),
+                to_json(request.login_kdf.as_ref()),
+                to_json(request.core_kdf.as_ref()),
+                to_json(request.vault_kdf.as_ref()),
+            ],
+        )?;
+        if inserted == 0 {
+            return Err(ServerError::rejected("用户已存在"));
+        }
+        Ok(())
+    }
+
+    /// 校验登录密码和核心密码，成功时签发新令牌
+    pub fn login(&self, request: &LoginRequest) -> ServerResult<String> {
+        let conn = self.conn()?;
+        let user: Option<(String, String, String)> = conn
+            .query_row(
+                "SELECT salt, password_hash, core_password_hash FROM users WHERE username = ?1",
+                [&request.username],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+
+        let valid = user.is_some_and(|(salt, password_hash, core_password_hash)| {
+            hash_secret(&salt, &request.password) == password_hash
+                && hash_secret(&salt, &request.core_password) == core_password_hash
+        });
+        if !valid {
+            return Err(ServerError::rejected("用户名或密码错误"));
+        }
+
+        let token = self.random_hex(TOKEN_LEN)?;
+        conn.execute(
+            "INSERT INTO tokens (token, username, created_at) VALUES (?1, ?2, ?3)",
+            params![token, request.username, unix_millis()],
+        )?;
+        Ok(token)
+    }
+
+    /// 校验令牌，返回对应的用户名
+    pub fn authenticate(&self, token: &str) -> ServerResult<String> {
+        self.conn()?
+            .query_row(
+                "SELECT username FROM tokens WHERE token = ?1",
+                [token],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or(ServerError::Unauthorized)
+    }
+
+    /// 使用新的 KDF 参数替换密码哈希（保险库密钥参数不变）
+    pub fn upgrade_kdf(&self, username: &str, request: &UpgradeKdfRequest) -> ServerResult<()> {
+        require(&request.password, "密码")?;
+        require(&request.core_password, "核心密码")?;
+
+        let conn = self.conn()?;
+        let salt = user_salt(&conn, username)?;
+        conn.execute(
+            "UPDATE users SET password_hash = ?2, core_password_hash = ?3,
+                login_kdf = ?4, core_kdf = ?5
+             WHERE username = ?1",
+            params![
+                username,
+                hash_secret(&salt, &request.password),
+                hash_secret(&salt, &request.core_password),
+                request.login_kdf.to_string(),
+                request.core_kdf.to_string(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// 校验原核心密码后替换核心密码哈希，并原子替换对应的数据密钥包装
+    pub fn change_core_password(
+        &self,
+        username: &str,
+        request: &ChangeCorePasswordRequest,
+    ) -> ServerResult<()> {
+        require(&request.core_password, "核心密码")?;
+        let wrapped_key = request
+            .wrapped_key
+            .as_ref()
+            .map(|key| Ok::<_, ServerError>((key_id(key)?, key.to_string())))
+            .transpose()?;
+
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let (salt, core_password_hash): (String, String) = tx.query_row(
+            "SELECT salt, core_password_hash FROM users WHERE username = ?1",
+            [username],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        if hash_secret(&salt, &request.old_core_password) != core_password_hash {
+            return Err(ServerError::rejected("原核心密码错误"));
+        }
+
+        tx.execute(
+            "UPDATE users SET core_password_hash = ?2, core_kdf = ?3 WHERE username = ?1",
+            params![
+                username,
+                hash_secret(&salt, &request.core_password),
+                request.core_kdf.to_string(),
+            ],
+        )?;
+        if let Some((key_id, body)) = wrapped_key {
+            tx.execute(
+                "INSERT OR REPLACE INTO vault_keys (username, key_id, body) VALUES (?1, ?2, ?3)",
+                params![username, key_id, body],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // ============================================
+    // 保险库密钥
+    // ============================================
+
+    /// 获取用户所有数据密钥包装
+    pub fn vault_keys(&self, username: &str) -> ServerResult<Vec<Value>> {
+        let conn = self.conn()?;
+        let mut stmt =
+            conn.prepare("SELECT body FROM vault_keys WHERE username = ?1 ORDER BY key_id")?;
+        let bodies = stmt
+            .query_map([username], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        bodies
+            .iter()
+            .map(|body| {
+                serde_json::from_str(body).map_err(|e| ServerError::Internal(e.to_string()))
+            })
+            .collect()
+    }
+
+    /// 是否开启了全字段加密
+    pub fn full_field_encryption(&self, username: &str) -> ServerResult<bool> {
+        Ok(self.conn()?.query_row(
+            "SELECT full_field_encryption FROM users WHERE username = ?1",
+            [username],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// 开启或关闭全字段加密
+    pub fn set_full_field_encryption(&self, username: &str, enabled: bool) -> ServerResult<()> {
+        self.conn()?.execute(
+            "UPDATE users SET full_field_encryption = ?2 WHERE username = ?1",
+            params![username, enabled],
+        )?;
+        Ok(())
+    }
+
+    /// 保存数据密钥包装，该 `key_id` 已存在时拒绝
+    pub fn create_vault_key(&self, username: &str, key: &Value) -> ServerResult<()> {
+        let key_id = key_id(key)?;
+        let inserted = self.conn()?.execute(
+            "INSERT OR IGNORE INTO vault_keys (username, key_id, body) VALUES (?1, ?2, ?3)",
+            params![username, key_id, key.to_string()],
+        )?;
+        if inserted == 0 {
+            return Err(ServerError::rejected("数据密钥已存在"));
+        }
+        Ok(())
+    }
+
+    // ============================================
+    // 账户
+    // ============================================
+
+    /// 查询客户端上次同步后的变化
+    ///
+    /// # Arguments
+    /// * `username` - 用户名
+    /// * `update_time` - 客户端上次同步的时间
+    pub fn query_accounts(
+        &self,
+        username: &str,
+        update_time: i64,
+    ) -> ServerResult<QueryResponseData> {
+        let conn = self.conn()?;
+        // 尚未写入过任何数据时服务器时间为 0，返回 1 以免客户端下次同步仍被视为首次同步
+        let now: i64 = conn
+            .query_row("SELECT value FROM clock WHERE id = 0", [], |row| row.get::<_, i64>(0))?
+            .max(1);
+        let pull_all = update_time <= 0 || update_time > now;
+        let since = if pull_all { i64::MIN } else { update_time };
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM accounts
+             WHERE username = ?1 AND update_time > ?2
+             ORDER BY rid",
+            ACCOUNT_COLUMNS
+        ))?;
+        let accounts = stmt
+            .query_map(params![username, since], account_item)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // 全量拉取时客户端会清空缓存，无需墓碑
+        let deleted_rids = if pull_all {
+            Vec::new()
+        } else {
+            let mut stmt = conn.prepare(
+                "SELECT rid FROM deleted_accounts
+                 WHERE username = ?1 AND deleted_at > ?2
+                 ORDER BY rid",
+            )?;
+            let rids = stmt
+                .query_map(params![username, update_time], |row| row.get(0))?
+                .collect::<Result<Vec<i64>, _>>()?;
+            rids
+        };
+
+        let pull_mode = if pull_all {
+            PullMode::PullAll
+        } else if accounts.is_empty() && deleted_rids.is_empty() {
+            PullMode::PullNothing
+        } else {
+            PullMode::PullUpdated
+        };
+        Ok(QueryResponseData {
+            pull_mode,
+            update_time: now,
+            accounts,
+            deleted_rids,
+        })
+    }
+
+    /// 插入账户，返回新记录 ID
+    ///
+    /// 已处理过相同操作 ID 的插入时返回原记录 ID
+    pub fn insert_account(
+        &self,
+        username: &str,
+        request: &InsertAccountRequest,
+    ) -> ServerResult<i64> {
+        require(&request.website, "网站")?;
+        require(&request.password, "密码")?;
+
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        if let Some(op_id) = &request.op_id {
+            let seen = tx
+                .query_row(
+                    "SELECT rid FROM insert_ops WHERE username = ?1 AND op_id = ?2",
+                    params![username, op_id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            if let Some(rid) = seen {
+                return Ok(rid);
+            }
+        }
+        let now = tick(&tx)?;
+        tx.execute(
+            "INSERT INTO accounts
+                 (username, website, account, password, update_time, fields_encrypted)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                username,
+                request.website,
+                request.account,
+                request.password,
+                now,
+                request.fields_encrypted
+            ],
+        )?;
+        let rid = tx.last_insert_rowid();
+        if let Some(op_id) = &request.op_id {
+            tx.execute(
+                "INSERT INTO insert_ops (username, op_id, rid) VALUES (?1, ?2, ?3)",
+                params![username, op_id, rid],
+            )?;
+        }
+        tx.commit()?;
+        Ok(rid)
+    }
+
+    /// 在单个事务中更新账户，返回各记录的新修订号
+    ///
+    /// 任一记录不存在、缺少修订号或修订号冲突时不修改任何记录
+    pub fn update_accounts(
+        &self,
+        username: &str,
+        accounts: &[AccountItem],
+    ) -> ServerResult<Vec<i64>> {
+        for item in accounts {
+            require(&item.website, "网站")?;
+            require(&item.password, "密码")?;
+            if item.revision < 1 {
+                return Err(ServerError::rejected(format!("记录 {} 缺少修订号", item.rid)));
+            }
+        }
+
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let now = tick(&tx)?;
+        let mut revisions = Vec::with_capacity(accounts.len());
+        for item in accounts {
+            // 未提交的事务在释放时回滚
+            let current = tx
+                .query_row(
+                    &format!(
+                        "SELECT {} FROM accounts WHERE rid = ?1 AND username = ?2",
+                        ACCOUNT_COLUMNS
+                    ),
+                    params![item.rid, username],
+                    account_item,
+                )
+                .optional()?
+                .ok_or_else(|| ServerError::rejected(format!("记录 {} 不存在", item.rid)))?;
+            if item.revision != current.revision {
+                return Err(ServerError::Conflict(Box::new(current)));
+            }
+
+            tx.execute(
+                "UPDATE accounts SET website = ?3, account = ?4, password = ?5,
+                    revision = revision + 1, update_time = ?6, fields_encrypted = ?7
+                 WHERE rid = ?1 AND username = ?2",
+                params![
+                    item.rid,
+                    username,
+                    item.website,
+                    item.account,
+                    item.password,
+                    now,
+                    item.fields_encrypted
+                ],
+            )?;
+            revisions.push(current.revision + 1);
+        }
+        tx.commit()?;
+        Ok(revisions)
+    }
+
+    /// 删除账户并记录墓碑
+    ///
+    /// 记录不存在时返回 [`ServerError::NotFound`]，客户端据此将重复的删除视为成功
+    pub fn delete_account(&self, username: &str, rid: i64) -> ServerResult<()> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        let deleted = tx.execute(
+            "DELETE FROM accounts WHERE rid = ?1 AND username = ?2",
+            params![rid, username],
+        )?;
+        if deleted == 0 {
+            return Err(ServerError::NotFound(format!("记录 {} 不存在", rid)));
+        }
+        let now = tick(&tx)?;
+        tx.execute(
+            "INSERT INTO deleted_accounts (rid, username, deleted_at) VALUES (?1, ?2, ?3)",
+            params![rid, username, now],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// 比较客户端提交的分桶摘要，返回不一致的分桶
+    ///
+    /// # Arguments
+    /// * `username` - 用户名
+    /// * `digests` - 客户端缓存每个分桶的摘要
+    pub fn verify_accounts(&self, username: &str, digests: &[String]) -> ServerResult<Vec<usize>> {
+        validate_bucket_count(digests.len())?;
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare("SELECT rid, revision FROM accounts WHERE username = ?1")?;
+        let revisions = stmt
+            .query_map([username], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<(i64, i64)>, _>>()?;
+
+        let expected = bucket_digests(&revisions, digests.len());
+        Ok((0..digests.len())
+            .filter(|&bucket| expected[bucket] != digests[bucket])
+            .collect())
+    }
+
+    /// 查询指定分桶中的全部账户
+    ///
+    /// # Arguments
+    /// * `username` - 用户名
+    /// * `bucket_count` - 分桶数
+    /// * `buckets` - 要查询的分桶
+    pub fn query_buckets(
+        &self,
+        username: &str,
+        bucket_count: usize,
+        buckets: &[usize],
+    ) -> ServerResult<Vec<AccountItem>> {
+        validate_bucket_count(bucket_count)?;
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM accounts WHERE username = ?1 ORDER BY rid",
+            ACCOUNT_COLUMNS
+        ))?;
+        let accounts = stmt
+            .query_map([username], account_item)?
+            .filter(|item| match item {
+                Ok(item) => buckets.contains(&bucket_of(item.rid, bucket_count)),
+                Err(_) => true,
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(accounts)
+    }
+
+    /// 生成十六进制编码的随机字符串
+    fn random_hex(&self, len: usize) -> ServerResult<String> {
+        let mut bytes = vec![0u8; len];
+        self.rng
+            .fill(&mut bytes)
+            .map_err(|_| ServerError::Internal("生成随机数失败".to_string()))?;
+        Ok(hex::encode(bytes))
+    }
+}
+
+// ============================================
+// 辅助函数
+// ============================================
+
+/// 推进并返回服务器时间
+fn tick(tx: &Transaction<'_>) -> ServerResult<i64> {
+    let last: i64 = tx.query_row("SELECT value FROM clock WHERE id = 0", [], |row| row.get(0))?;
+    let now = unix_millis().max(last + 1);
+    tx.execute("UPDATE clock SET value = ?1 WHERE id = 0", [now])?;
+    Ok(now)
+}
+
+/// 读取 [`ACCOUNT_COLUMNS`] 查询的一行
+fn account_item(row: &Row<'_>) -> rusqlite::Result<AccountItem> {
+    Ok(AccountItem {
+        rid: row.get(0)?,
+        website: row.get(1)?,
+        account: row.get(2)?,
+        password: row.get(3)?,
+        revision: row.get(4)?,
+        updated_at: row.get(5)?,
+        fields_encrypted: row.get(6)?,
+    })
+}
+
+/// 当前 Unix 时间（毫秒）
+fn unix_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// 对客户端提交的密码哈希加盐后再次哈希
+fn hash_secret(salt: &str, secret: &str) -> String {
+    let mut ctx = digest::Context::new(&digest::SHA256);
+    ctx.update(salt.as_bytes());
+    ctx.update(secret.as_bytes());
+    hex::encode(ctx.finish())
+}
+
+/// 获取用户的盐值
+fn user_salt(conn: &Connection, username: &str) -> ServerResult<String> {
+    Ok(conn.query_row(
+        "SELECT salt FROM users WHERE username = ?1",
+        [username],
+        |row| row.get(0),
+    )?)
+}
+
+/// 读取数据密钥包装的 `key_id`
+fn key_id(key: &Value) -> ServerResult<String> {
+    key.get("key_id")
+        .and_then(Value::as_str)
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| ServerError::rejected("缺少 key_id"))
+}
+
+/// 校验缓存校验请求的分桶数
+fn validate_bucket_count(bucket_count: usize) -> ServerResult<()> {
+    if bucket_count == 0 || bucket_count > MAX_BUCKETS {
+        return Err(ServerError::rejected(format!(
+            "分桶数必须在 1 到 {} 之间",
+            MAX_BUCKETS
+        )));
+    }
+    Ok(())
+}
+
+/// 记录所在的分桶
+fn bucket_of(rid: i64, bucket_count: usize) -> usize {
+    rid.rem_euclid(bucket_count as i64) as usize
+}
+
+/// 计算每个分桶的摘要
+fn bucket_digests(records: &[(i64, i64)], bucket_count: usize) -> Vec<String> {
+    let mut sorted = records.to_vec();
+    sorted.sort_unstable();
+
+    let mut contexts: Vec<digest::Context> = (0..bucket_count)
+        .map(|_| digest::Context::new(&digest::SHA256))
+        .collect();
+    for (rid, revision) in sorted {
+        let line = format!("{}:{}\n", rid, revision);
+        contexts[bucket_of(rid, bucket_count)].update(line.as_bytes());
+    }
+    contexts
+        .into_iter()
+        .map(|context| hex::encode(context.finish()))
+        .collect()
+}
+
+/// 校验必填字段
+fn require(value: &str, field: &str) -> ServerResult<()> {
+    if value.is_empty() {
+        return Err(ServerError::rejected(format!("{}不能为空", field)));
+    }
+    Ok(())
+}
+
+fn to_json(value: Option<&Value>) -> Option<String> {
+    value.map(Value::to_string)
+}
+
+fn parse_json(text: Option<String>) -> ServerResult<Option<Value>> {
+    text.map(|text| serde_json::from_str(&text).map_err(|e| ServerError::Internal(e.to_string())))
+        .transpose()
+}
+
+// ============================================
+// 单元测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn register(store: &Store, username: &str) -> String {
+        store
+            .register(&RegisterRequest {
+                username: username.to_string(),
+                password: "password_hash".to_string(),
+                core_password: "core_hash".to_string(),
+                login_kdf: Some(serde_json::json!({ "algorithm": "argon2id" })),
+                core_kdf: None,
+                vault_kdf: Some(serde_json::json!({ "salt": "00" })),
+            })
+            .unwrap();
+        store
+            .login(&LoginRequest {
+                username: username.to_string(),
+                password: "password_hash".to_string(),
+                core_password: "core_hash".to_string(),
+            })
+            .unwrap()
+    }
+
+    fn insert(store: &Store, username: &str, website: &str) -> i64 {
+        store
+            .insert_account(
+                username,
+                &InsertAccountRequest {
+                    website: website.to_string(),
+                    account: "account".to_string(),
+                    password: "ciphertext".to_string(),
+                    fields_encrypted: false,
+                    op_id: None,
+                },
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_register_and_login() {
+        let store = Store::in_memory().unwrap();
+        let token = register(&store, "alice");
+        assert_eq!(store.authenticate(&token).unwrap(), "alice");
+        assert!(matches!(
+            store.authenticate("invalid"),
+            Err(ServerError::Unauthorized)
+        ));
+
+        // 重复注册
+        let result = store.register(&RegisterRequest {
+            username: "alice".to_string(),
+            password: "other".to_string(),
+            core_password: "other".to_string(),
+            login_kdf: None,
+            core_kdf: None,
+            vault_kdf: None,
+        });
+        assert!(matches!(result, Err(ServerError::Rejected(_))));
+
+        // 错误的核心密码
+        let result = store.login(&LoginRequest {
+            username: "alice".to_string(),
+            password: "password_hash".to_string(),
+            core_password: "wrong".to_string(),
+        });
+        assert!(matches!(result, Err(ServerError::Rejected(_))));
+
+        let params = store.prelogin("alice").unwrap().unwrap();
+        assert_eq!(params.login_kdf.unwrap()["algorithm"], "argon2id");
+        assert!(params.core_kdf.is_none());
+        assert_eq!(params.vault_kdf.unwrap()["salt"], "00");
+        assert!(store.prelogin("nobody").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_insert_with_op_id_is_idempotent() {
+        let store = Store::in_memory().unwrap();
+        register(&store, "alice");
+        register(&store, "bob");
+        let request = |op_id: &str| InsertAccountRequest {
+            website: "a.com".to_string(),
+            account: "account".to_string(),
+            password: "ciphertext".to_string(),
+            fields_encrypted: false,
+            op_id: Some(op_id.to_string()),
+        };
+
+        let rid = store.insert_account("alice", &request("op-1")).unwrap();
+        assert_eq!(store.insert_account("alice", &request("op-1")).unwrap(), rid);
+        assert_eq!(store.query_accounts("alice", 0).unwrap().accounts.len(), 1);
+
+        // 操作 ID 按用户区分
+        assert_ne!(store.insert_account("bob", &request("op-1")).unwrap(), rid);
+        assert_ne!(store.insert_account("alice", &request("op-2")).unwrap(), rid);
+        assert_eq!(store.query_accounts("alice", 0).unwrap().accounts.len(), 2);
+    }
+
+    #[test]
+    fn test_pull_modes() {
+        let store = Store::in_memory().unwrap();
+        register(&store, "alice");
+        insert(&store, "alice", "a.com");
+
+        let data = store.query_accounts("alice", 0).unwrap();
+        assert_eq!(data.pull_mode, PullMode::PullAll);
+        assert_eq!(data.accounts.len(), 1);
+
+        let data = store.query_accounts("alice", data.update_time).unwrap();
+        assert_eq!(data.pull_mode, PullMode::PullNothing);
+        assert!(data.accounts.is_empty());
+
+        let since = data.update_time;
+        let rid = insert(&store, "alice", "b.com");
+        let data = store.query_accounts("alice", since).unwrap();
+        assert_eq!(data.pull_mode, PullMode::PullUpdated);
+        assert_eq!(data.accounts.len(), 1);
+        assert_eq!(data.accounts[0].rid, rid);
+
+        // 删除通过墓碑增量同步
+        let since = data.update_time;
+        store.delete_account("alice", rid).unwrap();
+        let data = store.query_accounts("alice", since).unwrap();
+        assert_eq!(data.pull_mode, PullMode::PullUpdated);
+        assert!(data.accounts.is_empty());
+        assert_eq!(data.deleted_rids, vec![rid]);
+
+        // 墓碑只返回一次，全量拉取不返回墓碑
+        let data = store.query_accounts("alice", data.update_time).unwrap();
+        assert_eq!(data.pull_mode, PullMode::PullNothing);
+        let data = store.query_accounts("alice", 0).unwrap();
+        assert_eq!(data.accounts.len(), 1);
+        assert!(data.deleted_rids.is_empty());
+
+        // 客户端时间晚于服务器时间
+        let data = store
+            .query_accounts("alice", data.update_time + 1000)
+            .unwrap();
+        assert_eq!(data.pull_mode, PullMode::PullAll);
+    }
+
+    #[test]
+    fn test_verify_and_query_buckets() {
+        let store = Store::in_memory().unwrap();
+        register(&store, "alice");
+        let a = insert(&store, "alice", "a.com");
+        let b = insert(&store, "alice", "b.com");
+
+        // 与服务器一致的摘要没有差异
+        let digests = bucket_digests(&[(a, 1), (b, 1)], 16);
+        assert!(store.verify_accounts("alice", &digests).unwrap().is_empty());
+
+        // 缺少记录或修订号不同的分桶不一致
+        let digests = bucket_digests(&[(a, 2)], 16);
+        let mut expected = vec![bucket_of(a, 16), bucket_of(b, 16)];
+        expected.sort_unstable();
+        expected.dedup();
+        assert_eq!(store.verify_accounts("alice", &digests).unwrap(), expected);
+
+        let accounts = store
+            .query_buckets("alice", 16, &[bucket_of(b, 16)])
+            .unwrap();
+        assert!(accounts.iter().any(|item| item.rid == b));
+        assert!(accounts
+            .iter()
+            .all(|item| bucket_of(item.rid, 16) == bucket_of(b, 16)));
+
+        assert!(store.verify_accounts("alice", &[]).is_err());
+        assert!(store.query_buckets("alice", MAX_BUCKETS + 1, &[0]).is_err());
+    }
+
+    #[test]
+    fn test_update_accounts_is_atomic() {
+        let store = Store::in_memory().unwrap();
+        register(&store, "alice");
+        register(&store, "bob");
+        let rid = insert(&store, "alice", "a.com");
+        let bobs = insert(&store, "bob", "b.com");
+
+        let item = |rid: i64, password: &str| AccountItem {
+            rid,
+            website: "a.com".to_string(),
+            account: "account".to_string(),
+            password: password.to_string(),
+            revision: 1,
+            updated_at: 0,
+            fields_encrypted: false,
+        };
+
+        // 其他用户的记录视为不存在
+        let result = store.update_accounts("alice", &[item(rid, "new"), item(bobs, "new")]);
+        assert!(matches!(result, Err(ServerError::Rejected(_))));
+        let data = store.query_accounts("alice", 0).unwrap();
+        assert_eq!(data.accounts[0].password, "ciphertext");
+
+        store.update_accounts("alice", &[item(rid, "new")]).unwrap();
+        let data = store.query_accounts("alice", 0).unwrap();
+        assert_eq!(data.accounts[0].password, "new");
+        assert!(matches!(
+            store.delete_account("alice", bobs),
+            Err(ServerError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_update_revision_conflict() {
+        let store = Store::in_memory().unwrap();
+        register(&store, "alice");
+        let rid = insert(&store, "alice", "a.com");
+        let current = store.query_accounts("alice", 0).unwrap().accounts[0].clone();
+        assert_eq!(current.revision, 1);
+
+        let mut first = current.clone();
+        first.password = "first".to_string();
+        assert_eq!(store.update_accounts("alice", &[first]).unwrap(), vec![2]);
+
+        // 基于旧修订号的修改被拒绝，返回服务器上的当前记录
+        let mut second = current.clone();
+        second.password = "second".to_string();
+        match store.update_accounts("alice", &[second.clone()]) {
+            Err(ServerError::Conflict(item)) => {
+                assert_eq!(item.rid, rid);
+                assert_eq!(item.password, "first");
+                assert_eq!(item.revision, 2);
+            }
+            other => panic!("expected conflict, got {:?}", other),
+        }
+
+        // 不携带修订号的修改被拒绝
+        second.revision = 0;
+        assert!(matches!(
+            store.update_accounts("alice", &[second.clone()]),
+            Err(ServerError::Rejected(_))
+        ));
+
+        // 基于最新修订号重试
+        second.revision = 2;
+        assert_eq!(store.update_accounts("alice", &[second]).unwrap(), vec![3]);
+        let data = store.query_accounts("alice", 0).unwrap();
+        assert_eq!(data.accounts[0].password, "second");
+        assert_eq!(data.accounts[0].revision, 3);
+    }
+
+    #[test]
+    fn test_vault_keys_and_core_password() {
+        let store = Store::in_memory().unwrap();
+        register(&store, "alice");
+
+        let key = serde_json::json!({ "key_id": "core", "kdf": {}, "wrapped": "old" });
+        store.create_vault_key("alice", &key).unwrap();
+        assert!(store.create_vault_key("alice", &key).is_err());
+        assert!(store
+            .create_vault_key("alice", &serde_json::json!({}))
+            .is_err());
+
+        let mut request = ChangeCorePasswordRequest {
+            old_core_password: "wrong".to_string(),
+            core_password: "new_core_hash".to_string(),
+            core_kdf: serde_json::json!({ "algorithm": "argon2id" }),
+            wrapped_key: Some(serde_json::json!({ "key_id": "core", "kdf": {}, "wrapped": "new" })),
+        };
+        assert!(store.change_core_password("alice", &request).is_err());
+        assert_eq!(store.vault_keys("alice").unwrap()[0]["wrapped"], "old");
+
+        request.old_core_password = "core_hash".to_string();
+        store.change_core_password("alice", &request).unwrap();
+        let keys = store.vault_keys("alice").unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0]["wrapped"], "new");
+
+        let result = store.login(&LoginRequest {
+            username: "alice".to_string(),
+            password: "password_hash".to_string(),
+            core_password: "new_core_hash".to_string(),
+        });
+        assert!(result.is_ok());
+    }
+}
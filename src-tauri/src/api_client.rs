@@ -1,134 +1,1144 @@
 //! API 客户端模块
 //!
 //! 封装与后端服务器的所有 HTTP 通信
+//!
+//! # 异步请求
+//! 所有 API 函数都是异步的，等待服务器响应期间不会阻塞命令处理
+//!
+//! # 重试
+//! 幂等请求（GET 和 DELETE，例如查询、验证令牌、按 rid 删除）在网络错误或服务器暂时
+//! 不可用（429、502、503、504）时按指数退避重试，见 [`RetryPolicy`]。
+//! 其他请求只发送一次，避免重复写入
+//!
+//! # 取消
+//! [`HttpClient::cancel_pending`] 取消该客户端（及其克隆）所有进行中的请求，
+//! 被取消的请求返回 [`DurianError::Cancelled`]。
+//! [`HttpClient::detached`] 得到的客户端取消状态独立，不受其影响
+//!
+//! # 传输层
+//! 请求通过 [`Transport`] 发送，默认实现基于 reqwest；测试中可以替换为模拟实现
+//!
+//! # 接口
+//! 会话通过 [`ApiClient`] 访问服务器，[`HttpApi`] 是基于 `api_*` 函数的实现
 
-use once_cell::sync::Lazy;
-use reqwest::blocking::Client;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use crate::crypto::{hash_core_password, hash_login_password};
+use reqwest::{Client, Method, Proxy, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+
+use crate::crypto::random_bytes;
 use crate::error::{DurianError, DurianResult};
-use crate::models::{ApiResponse, LoginResponseData, QueryResponseData};
+use crate::i18n::{tr, Message};
+use crate::kdf::{KdfParams, KdfSuite};
+use crate::models::{
+    AccountItem, ApiResponse, BucketQueryResponseData, LoginTokenData, PreloginResponseData,
+    QueryResponseData, VaultKeysResponseData, VerifyResponseData, WrappedKey,
+};
+use crate::settings::ServerProfile;
+use crate::tls::{self, TlsFailureSlot};
 
 // ============================================
-// 配置常量
+// 请求与响应
 // ============================================
 
-/// HTTP 请求超时时间（秒）
-const REQUEST_TIMEOUT_SECS: u64 = 30;
+/// HTTP 请求
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    /// 请求方法
+    pub method: Method,
+    /// 完整 URL
+    pub url: String,
+    /// 请求头
+    pub headers: Vec<(String, String)>,
+    /// 请求体（JSON）
+    pub body: Option<Vec<u8>>,
+    /// 请求体序列化失败的原因，发送时返回错误
+    invalid_body: Option<String>,
+}
+
+impl HttpRequest {
+    /// 创建请求
+    pub fn new(method: Method, url: &str) -> Self {
+        Self {
+            method,
+            url: url.to_string(),
+            headers: Vec::new(),
+            body: None,
+            invalid_body: None,
+        }
+    }
+
+    /// 设置请求头（同名请求头会被替换）
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers
+            .retain(|(existing, _)| !existing.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// 设置 JSON 请求体
+    pub fn json<T: Serialize + ?Sized>(mut self, body: &T) -> Self {
+        match serde_json::to_vec(body) {
+            Ok(bytes) => self.body = Some(bytes),
+            Err(e) => self.invalid_body = Some(e.to_string()),
+        }
+        self.header("Content-Type", "application/json")
+    }
+
+    /// 是否为幂等请求（可以安全重试）
+    pub fn is_idempotent(&self) -> bool {
+        self.method == Method::GET || self.method == Method::DELETE
+    }
+}
+
+/// HTTP 响应（已读取完整响应体）
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    /// 状态码
+    pub status: StatusCode,
+    /// 响应体
+    pub body: Vec<u8>,
+}
 
-/// HTTP 连接超时时间（秒）
-const CONNECT_TIMEOUT_SECS: u64 = 10;
+impl HttpResponse {
+    /// 获取状态码
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// 将响应体解析为 JSON
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        serde_json::from_slice(&self.body)
+    }
+
+    /// 将响应体解析为 API 响应，并记录 HTTP 状态码
+    ///
+    /// 响应体不是 API 响应时（例如代理返回的错误页面），状态码表示失败则返回
+    /// [`DurianError::HttpError`] 保留状态码，否则返回网络错误
+    pub fn api_json<T: DeserializeOwned>(&self) -> DurianResult<ApiResponse<T>> {
+        match self.json::<ApiResponse<T>>() {
+            Ok(mut response) => {
+                response.status = Some(self.status.as_u16());
+                Ok(response)
+            }
+            Err(_) if !self.status.is_success() => Err(DurianError::HttpError {
+                status: self.status.as_u16(),
+                message: self.status.canonical_reason().unwrap_or("").to_string(),
+            }),
+            // 成功状态码但响应体无法解析是服务器的问题，重试不会改变结果
+            Err(e) => Err(DurianError::SerializationError(e.to_string())),
+        }
+    }
+}
 
 // ============================================
-// HTTP 客户端
+// 传输层
 // ============================================
 
-/// 全局 HTTP 客户端（单例）
-/// 
-/// 注意：已启用 `danger_accept_invalid_certs` 以支持自签名 SSL 证书。
-/// 这在连接使用自签名证书的 HTTPS 服务器时是必需的。
-/// 安全警告：此设置会跳过证书验证，仅建议在可信网络环境中使用。
-pub static HTTP_CLIENT: Lazy<Client> = Lazy::new(|| {
-    Client::builder()
-        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
-        .connect_timeout(Duration::from_secs(CONNECT_TIMEOUT_SECS))
+/// 传输层返回的 Future
+pub type TransportFuture<'a> =
+    Pin<Box<dyn Future<Output = DurianResult<HttpResponse>> + Send + 'a>>;
+
+/// 发送单个 HTTP 请求的传输层
+///
+/// 重试和取消由 [`HttpClient`] 负责，传输层只需发送一次请求
+pub trait Transport: Send + Sync {
+    /// 发送请求并读取完整响应
+    fn execute(&self, request: HttpRequest) -> TransportFuture<'_>;
+}
+
+/// 基于 reqwest 的传输层
+struct ReqwestTransport {
+    client: Client,
+    profile: ServerProfile,
+}
+
+impl Transport for ReqwestTransport {
+    /// 证书公钥不匹配或不受信任时返回对应的证书错误，而不是普通的网络错误
+    fn execute(&self, request: HttpRequest) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let mut builder = self.client.request(request.method, &request.url);
+            for (name, value) in &request.headers {
+                builder = builder.header(name, value);
+            }
+            if let Some(body) = request.body {
+                builder = builder.body(body);
+            }
+
+            let response = match builder.send().await {
+                Ok(response) => response,
+                Err(e) if e.is_connect() => {
+                    return Err(self.diagnose(&request.url).await.unwrap_or_else(|| e.into()));
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            let status = response.status();
+            let body = response.bytes().await?.to_vec();
+            Ok(HttpResponse { status, body })
+        })
+    }
+}
+
+impl ReqwestTransport {
+    /// 连接失败后重新握手，判断是否由证书校验引起
+    ///
+    /// 使用只供本次诊断的客户端和失败记录，不会与同时进行的其他请求混淆。
+    /// 未固定公钥且未启用首次信任时校验器不记录失败，无需诊断
+    async fn diagnose(&self, url: &str) -> Option<DurianError> {
+        let tls = &self.profile.tls;
+        if tls.pinned_spki_sha256.is_empty() && !tls.trust_on_first_use {
+            return None;
+        }
+
+        let failure = TlsFailureSlot::default();
+        let client = build_client(&self.profile, Some(failure.clone())).ok()?;
+        let _ = client.get(url).send().await;
+        let failure = failure.lock().ok()?.take();
+        failure.map(DurianError::from)
+    }
+}
+
+/// 按服务器配置构建 reqwest 客户端
+///
+/// # Arguments
+/// * `profile` - 服务器配置
+/// * `failure` - 证书校验失败记录（只在诊断连接失败时使用）
+fn build_client(profile: &ServerProfile, failure: Option<TlsFailureSlot>) -> DurianResult<Client> {
+    let user_agent = profile.user_agent.clone().unwrap_or_else(default_user_agent);
+    let mut builder = Client::builder()
+        .timeout(Duration::from_secs(profile.timeouts.request_timeout_secs))
+        .connect_timeout(Duration::from_secs(profile.timeouts.connect_timeout_secs))
         .pool_max_idle_per_host(5)
-        // 允许自签名证书（跳过 SSL 证书验证）
-        .danger_accept_invalid_certs(true)
+        .user_agent(user_agent)
+        .use_preconfigured_tls(tls::client_config(&profile.tls, failure)?);
+
+    if let Some(proxy_url) = &profile.proxy_url {
+        let proxy = Proxy::all(proxy_url)
+            .map_err(|e| DurianError::config(tr(Message::InvalidProxy(&e.to_string()))))?;
+        builder = builder.proxy(proxy);
+    }
+
+    builder
         .build()
-        .expect("创建 HTTP 客户端失败")
-});
+        .map_err(|e| DurianError::config(tr(Message::HttpClientFailed(&e.to_string()))))
+}
+
+// ============================================
+// 重试策略
+// ============================================
+
+/// 幂等请求的重试策略
+///
+/// 第 n 次重试前等待 `initial_backoff * 2^n`（不超过 `max_backoff`），
+/// 并随机缩短至多一半，避免多个客户端同时重试
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// 最大重试次数（不含首次请求）
+    pub max_retries: u32,
+    /// 首次重试前的等待时间
+    pub initial_backoff: Duration,
+    /// 等待时间上限
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 不重试
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// 第 `attempt` 次重试（从 0 开始）前的等待时间
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff);
+        let jitter = random_bytes::<1>().map(|b| b[0]).unwrap_or(0);
+        backoff - backoff / 2 * u32::from(jitter) / 255
+    }
+
+    /// 请求结果是否值得重试
+    fn should_retry(result: &DurianResult<HttpResponse>) -> bool {
+        match result {
+            Ok(response) => matches!(
+                response.status,
+                StatusCode::TOO_MANY_REQUESTS
+                    | StatusCode::BAD_GATEWAY
+                    | StatusCode::SERVICE_UNAVAILABLE
+                    | StatusCode::GATEWAY_TIMEOUT
+            ),
+            Err(DurianError::NetworkError(_)) => true,
+            Err(_) => false,
+        }
+    }
+}
+
+// ============================================
+// HTTP 客户端
+// ============================================
+
+/// HTTP 客户端
+///
+/// 按服务器配置构建：TLS 策略（见 [`crate::tls`]，默认完整校验证书）、
+/// 代理、超时时间和 User-Agent。服务器配置修改后需重新构建。
+/// 克隆得到的客户端共享连接池和取消状态，克隆开销很小
+#[derive(Clone)]
+pub struct HttpClient {
+    transport: Arc<dyn Transport>,
+    retry: RetryPolicy,
+    cancel: Arc<Mutex<CancellationToken>>,
+}
+
+impl HttpClient {
+    /// 按服务器配置创建客户端
+    ///
+    /// 未配置代理时使用系统代理环境变量（`HTTPS_PROXY` 等）
+    ///
+    /// # Arguments
+    /// * `profile` - 服务器配置
+    pub fn new(profile: &ServerProfile) -> DurianResult<Self> {
+        let client = build_client(profile, None)?;
+        Ok(Self::with_transport(
+            Arc::new(ReqwestTransport {
+                client,
+                profile: profile.clone(),
+            }),
+            RetryPolicy::default(),
+        ))
+    }
+
+    /// 使用指定的传输层和重试策略创建客户端
+    ///
+    /// # Arguments
+    /// * `transport` - 传输层
+    /// * `retry` - 幂等请求的重试策略
+    pub fn with_transport(transport: Arc<dyn Transport>, retry: RetryPolicy) -> Self {
+        Self {
+            transport,
+            retry,
+            cancel: Arc::new(Mutex::new(CancellationToken::new())),
+        }
+    }
+
+    /// 创建 GET 请求
+    pub fn get(&self, url: &str) -> HttpRequest {
+        HttpRequest::new(Method::GET, url)
+    }
+
+    /// 创建 POST 请求
+    pub fn post(&self, url: &str) -> HttpRequest {
+        HttpRequest::new(Method::POST, url)
+    }
+
+    /// 创建 PUT 请求
+    pub fn put(&self, url: &str) -> HttpRequest {
+        HttpRequest::new(Method::PUT, url)
+    }
+
+    /// 创建 DELETE 请求
+    pub fn delete(&self, url: &str) -> HttpRequest {
+        HttpRequest::new(Method::DELETE, url)
+    }
+
+    /// 发送请求
+    ///
+    /// 幂等请求失败时按重试策略重试；请求被取消时返回 `DurianError::Cancelled`
+    pub async fn send(&self, request: HttpRequest) -> DurianResult<HttpResponse> {
+        if let Some(e) = &request.invalid_body {
+            return Err(DurianError::SerializationError(e.clone()));
+        }
+
+        let cancel = self.cancel_token();
+        let retries = if request.is_idempotent() {
+            self.retry.max_retries
+        } else {
+            0
+        };
+
+        let mut attempt = 0;
+        loop {
+            let result = tokio::select! {
+                _ = cancel.cancelled() => return Err(DurianError::Cancelled),
+                result = self.transport.execute(request.clone()) => result,
+            };
+            if attempt >= retries || !RetryPolicy::should_retry(&result) {
+                return result;
+            }
+
+            tokio::select! {
+                _ = cancel.cancelled() => return Err(DurianError::Cancelled),
+                _ = tokio::time::sleep(self.retry.backoff(attempt)) => {}
+            }
+            attempt += 1;
+        }
+    }
+
+    /// 取消所有进行中的请求（包括正在等待重试的请求）
+    ///
+    /// 之后发起的请求不受影响
+    pub fn cancel_pending(&self) {
+        let mut cancel = self.cancel.lock().unwrap_or_else(|e| e.into_inner());
+        cancel.cancel();
+        *cancel = CancellationToken::new();
+    }
+
+    /// 共享传输层和重试策略、取消状态独立的客户端
+    ///
+    /// 后台同步和队列回放使用，[`Self::cancel_pending`] 不会取消其请求
+    pub fn detached(&self) -> Self {
+        Self::with_transport(self.transport.clone(), self.retry)
+    }
+
+    /// 当前的取消令牌
+    fn cancel_token(&self) -> CancellationToken {
+        self.cancel.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// 获取服务器证书的公钥指纹
+    ///
+    /// 用于首次信任：以首次信任模式连接服务器，返回证书链不受信任时记录的指纹；
+    /// 证书已受信任（可正常连接）时返回 `None`
+    ///
+    /// # Arguments
+    /// * `profile` - 服务器配置（其中固定的指纹会被忽略）
+    pub async fn probe_fingerprint(profile: &ServerProfile) -> DurianResult<Option<String>> {
+        let mut probe = profile.clone();
+        probe.tls.pinned_spki_sha256.clear();
+        probe.tls.trust_on_first_use = true;
+        let client = Self::new(&probe)?;
+        match client.send(client.get(&probe.api_base_url)).await {
+            Ok(_) => Ok(None),
+            Err(DurianError::UntrustedCertificate { fingerprint }) => Ok(Some(fingerprint)),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// 默认 User-Agent，例如 `durian-web/0.1.2 (linux)`
+pub fn default_user_agent() -> String {
+    format!(
+        "{}/{} ({})",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS
+    )
+}
+
+// ============================================
+// API 客户端接口
+// ============================================
+
+/// API 调用返回的 Future
+pub type ApiFuture<'a, T> = Pin<Box<dyn Future<Output = DurianResult<T>> + Send + 'a>>;
+
+/// 后端 API
+///
+/// 由 [`HttpApi`] 通过 HTTP 访问服务器实现；测试中可以替换为内存中的模拟服务器。
+/// 密码参数均为明文，由实现按 KDF 参数哈希，各方法的语义与同名的 `api_*` 函数一致
+pub trait ApiClient: Send + Sync {
+    /// 登录前协商 KDF 参数
+    fn prelogin<'a>(&'a self, username: &'a str) -> ApiFuture<'a, KdfSuite>;
+
+    /// 用户登录
+    fn login<'a>(
+        &'a self,
+        username: &'a str,
+        password: &'a str,
+        core_password: &'a str,
+        kdf: &'a KdfSuite,
+    ) -> ApiFuture<'a, ApiResponse<LoginTokenData>>;
+
+    /// 用户注册
+    fn register<'a>(
+        &'a self,
+        username: &'a str,
+        password: &'a str,
+        core_password: &'a str,
+        kdf: &'a KdfSuite,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>>;
+
+    /// 升级账户的 KDF 参数
+    fn upgrade_kdf<'a>(
+        &'a self,
+        token: &'a str,
+        password: &'a str,
+        core_password: &'a str,
+        kdf: &'a KdfSuite,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>>;
+
+    /// 修改核心密码
+    #[allow(clippy::too_many_arguments)]
+    fn change_core_password<'a>(
+        &'a self,
+        token: &'a str,
+        old_core_password: &'a str,
+        new_core_password: &'a str,
+        old_kdf: &'a KdfParams,
+        new_kdf: &'a KdfParams,
+        wrapped_key: Option<&'a WrappedKey>,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>>;
+
+    /// 获取用户所有包装后的数据密钥和保险库设置，服务器不支持信封加密时返回 `None`
+    fn get_vault_keys<'a>(
+        &'a self,
+        token: &'a str,
+    ) -> ApiFuture<'a, Option<VaultKeysResponseData>>;
+
+    /// 上传包装后的数据密钥
+    fn create_vault_key<'a>(
+        &'a self,
+        token: &'a str,
+        key: &'a WrappedKey,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>>;
+
+    /// 开启或关闭全字段加密（保存在服务器上，所有设备共用）
+    fn set_full_field_encryption<'a>(
+        &'a self,
+        token: &'a str,
+        enabled: bool,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>>;
+
+    /// 验证 Token 有效性
+    fn verify<'a>(&'a self, token: &'a str) -> ApiFuture<'a, bool>;
+
+    /// 查询账户列表
+    fn query_accounts<'a>(
+        &'a self,
+        token: &'a str,
+        update_time: i64,
+    ) -> ApiFuture<'a, ApiResponse<QueryResponseData>>;
+
+    /// 插入新账户（忽略 `item` 的记录 ID 和修订号）
+    ///
+    /// `op_id` 为客户端生成的操作 ID：服务器已处理过相同 ID 的插入时直接返回原记录 ID，
+    /// 响应丢失后重新发送不会产生重复记录
+    fn insert_account<'a>(
+        &'a self,
+        token: &'a str,
+        item: &'a AccountItem,
+        op_id: Option<&'a str>,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>>;
+
+    /// 更新账户信息
+    ///
+    /// `item.revision` 为本地所基于的修订号（不能为 0），
+    /// 冲突时返回错误码 409 和服务器上的当前记录
+    fn update_account<'a>(
+        &'a self,
+        token: &'a str,
+        item: &'a AccountItem,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>>;
+
+    /// 批量更新账户
+    fn update_accounts_batch<'a>(
+        &'a self,
+        token: &'a str,
+        accounts: &'a [AccountItem],
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>>;
+
+    /// 删除账户
+    fn delete_account<'a>(
+        &'a self,
+        token: &'a str,
+        rid: i64,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>>;
+
+    /// 发送缓存的分桶摘要，返回与服务器不一致的分桶
+    ///
+    /// 摘要的计算方式见 [`crate::verify`]，分桶数为 `digests` 的长度
+    fn verify_accounts<'a>(
+        &'a self,
+        token: &'a str,
+        digests: &'a [String],
+    ) -> ApiFuture<'a, ApiResponse<VerifyResponseData>>;
+
+    /// 查询指定分桶中的全部账户
+    fn query_account_buckets<'a>(
+        &'a self,
+        token: &'a str,
+        bucket_count: usize,
+        buckets: &'a [usize],
+    ) -> ApiFuture<'a, ApiResponse<BucketQueryResponseData>>;
+
+    /// 取消所有进行中的请求
+    fn cancel_pending(&self) {}
+
+    /// 取消状态独立的客户端，[`Self::cancel_pending`] 不影响其请求
+    ///
+    /// 不支持取消的客户端返回 `None`，此时直接使用原客户端
+    fn detached(&self) -> Option<Arc<dyn ApiClient>> {
+        None
+    }
+}
+
+/// 通过 HTTP 访问服务器的 API 客户端
+#[derive(Clone)]
+pub struct HttpApi {
+    http: HttpClient,
+    api_base_url: String,
+}
+
+impl HttpApi {
+    /// 创建 API 客户端
+    ///
+    /// # Arguments
+    /// * `http` - HTTP 客户端
+    /// * `api_base_url` - API 基础 URL
+    pub fn new(http: HttpClient, api_base_url: &str) -> Self {
+        Self {
+            http,
+            api_base_url: api_base_url.to_string(),
+        }
+    }
+}
+
+impl ApiClient for HttpApi {
+    fn prelogin<'a>(&'a self, username: &'a str) -> ApiFuture<'a, KdfSuite> {
+        Box::pin(api_prelogin(&self.http, &self.api_base_url, username))
+    }
+
+    fn login<'a>(
+        &'a self,
+        username: &'a str,
+        password: &'a str,
+        core_password: &'a str,
+        kdf: &'a KdfSuite,
+    ) -> ApiFuture<'a, ApiResponse<LoginTokenData>> {
+        Box::pin(api_login(
+            &self.http,
+            &self.api_base_url,
+            username,
+            password,
+            core_password,
+            kdf,
+        ))
+    }
+
+    fn register<'a>(
+        &'a self,
+        username: &'a str,
+        password: &'a str,
+        core_password: &'a str,
+        kdf: &'a KdfSuite,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>> {
+        Box::pin(api_register(
+            &self.http,
+            &self.api_base_url,
+            username,
+            password,
+            core_password,
+            kdf,
+        ))
+    }
+
+    fn upgrade_kdf<'a>(
+        &'a self,
+        token: &'a str,
+        password: &'a str,
+        core_password: &'a str,
+        kdf: &'a KdfSuite,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>> {
+        Box::pin(api_upgrade_kdf(
+            &self.http,
+            &self.api_base_url,
+            token,
+            password,
+            core_password,
+            kdf,
+        ))
+    }
+
+    fn change_core_password<'a>(
+        &'a self,
+        token: &'a str,
+        old_core_password: &'a str,
+        new_core_password: &'a str,
+        old_kdf: &'a KdfParams,
+        new_kdf: &'a KdfParams,
+        wrapped_key: Option<&'a WrappedKey>,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>> {
+        Box::pin(api_change_core_password(
+            &self.http,
+            &self.api_base_url,
+            token,
+            old_core_password,
+            new_core_password,
+            old_kdf,
+            new_kdf,
+            wrapped_key,
+        ))
+    }
+
+    fn get_vault_keys<'a>(
+        &'a self,
+        token: &'a str,
+    ) -> ApiFuture<'a, Option<VaultKeysResponseData>> {
+        Box::pin(api_get_vault_keys(&self.http, &self.api_base_url, token))
+    }
+
+    fn create_vault_key<'a>(
+        &'a self,
+        token: &'a str,
+        key: &'a WrappedKey,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>> {
+        Box::pin(api_create_vault_key(&self.http, &self.api_base_url, token, key))
+    }
+
+    fn set_full_field_encryption<'a>(
+        &'a self,
+        token: &'a str,
+        enabled: bool,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>> {
+        Box::pin(api_set_full_field_encryption(
+            &self.http,
+            &self.api_base_url,
+            token,
+            enabled,
+        ))
+    }
+
+    fn verify<'a>(&'a self, token: &'a str) -> ApiFuture<'a, bool> {
+        Box::pin(api_verify(&self.http, &self.api_base_url, token))
+    }
+
+    fn query_accounts<'a>(
+        &'a self,
+        token: &'a str,
+        update_time: i64,
+    ) -> ApiFuture<'a, ApiResponse<QueryResponseData>> {
+        Box::pin(api_query_accounts(
+            &self.http,
+            &self.api_base_url,
+            token,
+            update_time,
+        ))
+    }
+
+    fn insert_account<'a>(
+        &'a self,
+        token: &'a str,
+        item: &'a AccountItem,
+        op_id: Option<&'a str>,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>> {
+        Box::pin(api_insert_account(
+            &self.http,
+            &self.api_base_url,
+            token,
+            item,
+            op_id,
+        ))
+    }
+
+    fn update_account<'a>(
+        &'a self,
+        token: &'a str,
+        item: &'a AccountItem,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>> {
+        Box::pin(api_update_account(&self.http, &self.api_base_url, token, item))
+    }
+
+    fn update_accounts_batch<'a>(
+        &'a self,
+        token: &'a str,
+        accounts: &'a [AccountItem],
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>> {
+        Box::pin(api_update_accounts_batch(
+            &self.http,
+            &self.api_base_url,
+            token,
+            accounts,
+        ))
+    }
+
+    fn delete_account<'a>(
+        &'a self,
+        token: &'a str,
+        rid: i64,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>> {
+        Box::pin(api_delete_account(&self.http, &self.api_base_url, token, rid))
+    }
+
+    fn verify_accounts<'a>(
+        &'a self,
+        token: &'a str,
+        digests: &'a [String],
+    ) -> ApiFuture<'a, ApiResponse<VerifyResponseData>> {
+        Box::pin(api_verify_accounts(
+            &self.http,
+            &self.api_base_url,
+            token,
+            digests,
+        ))
+    }
+
+    fn query_account_buckets<'a>(
+        &'a self,
+        token: &'a str,
+        bucket_count: usize,
+        buckets: &'a [usize],
+    ) -> ApiFuture<'a, ApiResponse<BucketQueryResponseData>> {
+        Box::pin(api_query_account_buckets(
+            &self.http,
+            &self.api_base_url,
+            token,
+            bucket_count,
+            buckets,
+        ))
+    }
+
+    fn cancel_pending(&self) {
+        self.http.cancel_pending();
+    }
+
+    fn detached(&self) -> Option<Arc<dyn ApiClient>> {
+        Some(Arc::new(HttpApi::new(self.http.detached(), &self.api_base_url)))
+    }
+}
 
 // ============================================
 // 认证相关 API
 // ============================================
 
+/// 登录前协商 KDF 参数
+///
+/// 服务器对未升级的账户（以及不存在的用户）返回空参数，此时使用旧版参数；
+/// 非 2xx 状态或业务错误视为请求失败，不回退到旧版参数，以免降级到弱哈希
+///
+/// # Arguments
+/// * `client` - HTTP 客户端
+/// * `api_base_url` - API 基础 URL
+/// * `username` - 用户名
+///
+/// # Returns
+/// 该用户的 KDF 参数组合
+pub async fn api_prelogin(
+    client: &HttpClient,
+    api_base_url: &str,
+    username: &str,
+) -> DurianResult<KdfSuite> {
+    let url = format!("{}/v1/login/params", api_base_url);
+
+    let body = serde_json::json!({
+        "username": username
+    });
+
+    let request = client
+        .post(&url)
+        .header("Content-Type", "application/json")
+        .json(&body);
+    let response = client.send(request).await?;
+    let status = response.status();
+    let response = response.api_json::<PreloginResponseData>()?;
+
+    if !status.is_success() || !response.is_success() {
+        return Err(DurianError::from_response(response));
+    }
+    KdfSuite::from_prelogin(response.data.unwrap_or_default())
+}
+
 /// 用户登录请求
 ///
 /// # Arguments
+/// * `client` - HTTP 客户端
 /// * `api_base_url` - API 基础 URL
 /// * `username` - 用户名
 /// * `password` - 密码（明文，函数内部会进行哈希）
 /// * `core_password` - 核心密码（明文，函数内部会进行哈希）
+/// * `kdf` - 协商得到的 KDF 参数
 ///
 /// # Returns
 /// 登录响应，包含 token
-pub fn api_login(
+pub async fn api_login(
+    client: &HttpClient,
     api_base_url: &str,
     username: &str,
     password: &str,
     core_password: &str,
-) -> DurianResult<ApiResponse<LoginResponseData>> {
+    kdf: &KdfSuite,
+) -> DurianResult<ApiResponse<LoginTokenData>> {
     let url = format!("{}/v1/login", api_base_url);
 
     let body = serde_json::json!({
         "username": username,
-        "password": hash_login_password(password),
-        "core_password": hash_core_password(core_password)
+        "password": kdf.login.hash_password(password)?,
+        "core_password": kdf.core.hash_password(core_password)?
     });
 
-    let response = HTTP_CLIENT
+    let request = client
         .post(&url)
         .header("Content-Type", "application/json")
-        .json(&body)
-        .send()?;
+        .json(&body);
+    let response = client.send(request).await?;
 
-    response
-        .json::<ApiResponse<LoginResponseData>>()
-        .map_err(|e| DurianError::network(format!("解析响应失败: {}", e)))
+    response.api_json::<LoginTokenData>()
 }
 
 /// 用户注册请求
 ///
+/// KDF 参数随请求一起提交，由服务器保存并在登录前协商时返回
+///
 /// # Arguments
+/// * `client` - HTTP 客户端
 /// * `api_base_url` - API 基础 URL
 /// * `username` - 用户名
 /// * `password` - 密码（明文，函数内部会进行哈希）
 /// * `core_password` - 核心密码（明文，函数内部会进行哈希）
+/// * `kdf` - 新生成的 KDF 参数
 ///
 /// # Returns
 /// 注册响应
-pub fn api_register(
+pub async fn api_register(
+    client: &HttpClient,
     api_base_url: &str,
     username: &str,
     password: &str,
     core_password: &str,
+    kdf: &KdfSuite,
 ) -> DurianResult<ApiResponse<serde_json::Value>> {
     let url = format!("{}/v1/register", api_base_url);
 
     let body = serde_json::json!({
         "username": username,
-        "password": hash_login_password(password),
-        "core_password": hash_core_password(core_password)
+        "password": kdf.login.hash_password(password)?,
+        "core_password": kdf.core.hash_password(core_password)?,
+        "login_kdf": kdf.login,
+        "core_kdf": kdf.core,
+        "vault_kdf": kdf.vault
+    });
+
+    let request = client
+        .post(&url)
+        .header("Content-Type", "application/json")
+        .json(&body);
+    let response = client.send(request).await?;
+
+    response.api_json::<serde_json::Value>()
+}
+
+/// 升级账户的 KDF 参数
+///
+/// 在登录成功后调用，使用新参数重新计算两个密码的哈希并替换服务器上的旧哈希。
+/// 保险库密钥参数不随升级改变，不包含在请求中
+///
+/// # Arguments
+/// * `client` - HTTP 客户端
+/// * `api_base_url` - API 基础 URL
+/// * `token` - 认证令牌
+/// * `password` - 密码（明文，函数内部会进行哈希）
+/// * `core_password` - 核心密码（明文，函数内部会进行哈希）
+/// * `kdf` - 新的 KDF 参数
+///
+/// # Returns
+/// 升级响应
+pub async fn api_upgrade_kdf(
+    client: &HttpClient,
+    api_base_url: &str,
+    token: &str,
+    password: &str,
+    core_password: &str,
+    kdf: &KdfSuite,
+) -> DurianResult<ApiResponse<serde_json::Value>> {
+    let url = format!("{}/v1/auth/kdf", api_base_url);
+
+    let body = serde_json::json!({
+        "password": kdf.login.hash_password(password)?,
+        "core_password": kdf.core.hash_password(core_password)?,
+        "login_kdf": kdf.login,
+        "core_kdf": kdf.core
+    });
+
+    let request = client
+        .put(&url)
+        .header("Content-Type", "application/json")
+        .header("Authorization", token)
+        .json(&body);
+    let response = client.send(request).await?;
+
+    response.api_json::<serde_json::Value>()
+}
+
+/// 修改核心密码
+///
+/// 服务器校验原核心密码哈希后，替换为新哈希和新 KDF 参数。
+/// 启用信封加密时同时原子替换核心密码对应的数据密钥包装
+///
+/// # Arguments
+/// * `client` - HTTP 客户端
+/// * `api_base_url` - API 基础 URL
+/// * `token` - 认证令牌
+/// * `old_core_password` - 原核心密码（明文，函数内部会进行哈希）
+/// * `new_core_password` - 新核心密码（明文，函数内部会进行哈希）
+/// * `old_kdf` - 原核心密码的 KDF 参数
+/// * `new_kdf` - 新核心密码的 KDF 参数
+/// * `wrapped_key` - 用新核心密码重新包装的数据密钥（未启用信封加密时为 `None`）
+///
+/// # Returns
+/// 修改响应
+#[allow(clippy::too_many_arguments)]
+pub async fn api_change_core_password(
+    client: &HttpClient,
+    api_base_url: &str,
+    token: &str,
+    old_core_password: &str,
+    new_core_password: &str,
+    old_kdf: &KdfParams,
+    new_kdf: &KdfParams,
+    wrapped_key: Option<&WrappedKey>,
+) -> DurianResult<ApiResponse<serde_json::Value>> {
+    let url = format!("{}/v1/auth/core_password", api_base_url);
+
+    let body = serde_json::json!({
+        "old_core_password": old_kdf.hash_password(old_core_password)?,
+        "core_password": new_kdf.hash_password(new_core_password)?,
+        "core_kdf": new_kdf,
+        "wrapped_key": wrapped_key
     });
 
-    let response = HTTP_CLIENT
+    let request = client
+        .put(&url)
+        .header("Content-Type", "application/json")
+        .header("Authorization", token)
+        .json(&body);
+    let response = client.send(request).await?;
+
+    response.api_json::<serde_json::Value>()
+}
+
+// ============================================
+// 保险库密钥相关 API
+// ============================================
+
+/// 获取用户所有包装后的数据密钥和保险库设置
+///
+/// # Arguments
+/// * `client` - HTTP 客户端
+/// * `api_base_url` - API 基础 URL
+/// * `token` - 认证令牌
+///
+/// # Returns
+/// 包装后的数据密钥列表和保险库设置；服务器不支持信封加密（非 2xx 状态）时返回 `None`
+pub async fn api_get_vault_keys(
+    client: &HttpClient,
+    api_base_url: &str,
+    token: &str,
+) -> DurianResult<Option<VaultKeysResponseData>> {
+    let url = format!("{}/v1/vault/keys", api_base_url);
+
+    let request = client
+        .get(&url)
+        .header("Authorization", token);
+    let response = client.send(request).await?;
+
+    if !response.status().is_success() {
+        return Ok(None);
+    }
+
+    let response = response.api_json::<VaultKeysResponseData>()?;
+
+    if !response.is_success() {
+        return Err(DurianError::from_response(response));
+    }
+    Ok(Some(response.data.unwrap_or_default()))
+}
+
+/// 上传包装后的数据密钥
+///
+/// 服务器仅在该 `key_id` 不存在时创建，已存在时返回业务错误，
+/// 避免两台设备同时生成不同的数据密钥
+///
+/// # Arguments
+/// * `client` - HTTP 客户端
+/// * `api_base_url` - API 基础 URL
+/// * `token` - 认证令牌
+/// * `key` - 包装后的数据密钥
+///
+/// # Returns
+/// 创建响应
+pub async fn api_create_vault_key(
+    client: &HttpClient,
+    api_base_url: &str,
+    token: &str,
+    key: &WrappedKey,
+) -> DurianResult<ApiResponse<serde_json::Value>> {
+    let url = format!("{}/v1/vault/keys", api_base_url);
+
+    let request = client
         .post(&url)
         .header("Content-Type", "application/json")
-        .json(&body)
-        .send()?;
+        .header("Authorization", token)
+        .json(key);
+    let response = client.send(request).await?;
+
+    response.api_json::<serde_json::Value>()
+}
+
+/// 开启或关闭全字段加密
+///
+/// # Arguments
+/// * `client` - HTTP 客户端
+/// * `api_base_url` - API 基础 URL
+/// * `token` - 认证令牌
+/// * `enabled` - 是否开启
+///
+/// # Returns
+/// 修改响应
+pub async fn api_set_full_field_encryption(
+    client: &HttpClient,
+    api_base_url: &str,
+    token: &str,
+    enabled: bool,
+) -> DurianResult<ApiResponse<serde_json::Value>> {
+    let url = format!("{}/v1/vault/settings", api_base_url);
+
+    let body = serde_json::json!({
+        "full_field_encryption": enabled
+    });
+
+    let request = client
+        .put(&url)
+        .header("Content-Type", "application/json")
+        .header("Authorization", token)
+        .json(&body);
+    let response = client.send(request).await?;
 
-    response
-        .json::<ApiResponse<serde_json::Value>>()
-        .map_err(|e| DurianError::network(format!("解析响应失败: {}", e)))
+    response.api_json::<serde_json::Value>()
 }
 
 /// 验证 Token 有效性
 ///
 /// # Arguments
+/// * `client` - HTTP 客户端
 /// * `api_base_url` - API 基础 URL
 /// * `token` - 认证令牌
 ///
 /// # Returns
 /// token 是否有效
-pub fn api_verify(api_base_url: &str, token: &str) -> DurianResult<bool> {
+pub async fn api_verify(client: &HttpClient, api_base_url: &str, token: &str) -> DurianResult<bool> {
     let url = format!("{}/v1/auth/verify", api_base_url);
 
-    let response = HTTP_CLIENT
+    let request = client
         .get(&url)
-        .header("Authorization", token)
-        .send()?;
+        .header("Authorization", token);
+    let response = client.send(request).await?;
 
     Ok(response.status().is_success())
 }
@@ -140,119 +1150,152 @@ pub fn api_verify(api_base_url: &str, token: &str) -> DurianResult<bool> {
 /// 查询账户列表
 ///
 /// # Arguments
+/// * `client` - HTTP 客户端
 /// * `api_base_url` - API 基础 URL
 /// * `token` - 认证令牌
 /// * `update_time` - 上次更新时间戳，用于增量查询
 ///
 /// # Returns
 /// 账户列表响应
-pub fn api_query_accounts(
+pub async fn api_query_accounts(
+    client: &HttpClient,
     api_base_url: &str,
     token: &str,
     update_time: i64,
 ) -> DurianResult<ApiResponse<QueryResponseData>> {
     let url = format!("{}/v1/account?update_time={}", api_base_url, update_time);
 
-    let response = HTTP_CLIENT
+    let request = client
         .get(&url)
         .header("Content-Type", "application/json")
-        .header("Authorization", token)
-        .send()?;
+        .header("Authorization", token);
+    let response = client.send(request).await?;
 
-    response
-        .json::<ApiResponse<QueryResponseData>>()
-        .map_err(|e| DurianError::network(format!("解析响应失败: {}", e)))
+    response.api_json::<QueryResponseData>()
 }
 
 /// 插入新账户
 ///
 /// # Arguments
+/// * `client` - HTTP 客户端
 /// * `api_base_url` - API 基础 URL
 /// * `token` - 认证令牌
-/// * `website` - 网站地址
-/// * `account` - 账号名
-/// * `password` - 加密后的密码
+/// * `item` - 要插入的账户（密码为加密后的密文）
+/// * `op_id` - 客户端操作 ID，服务器据此忽略重复的插入
 ///
 /// # Returns
 /// 插入响应
-pub fn api_insert_account(
+pub async fn api_insert_account(
+    client: &HttpClient,
     api_base_url: &str,
     token: &str,
-    website: &str,
-    account: &str,
-    password: &str,
+    item: &AccountItem,
+    op_id: Option<&str>,
 ) -> DurianResult<ApiResponse<serde_json::Value>> {
     let url = format!("{}/v1/account", api_base_url);
 
     let body = serde_json::json!({
-        "website": website,
-        "account": account,
-        "password": password
+        "website": item.website,
+        "account": item.account,
+        "password": item.password,
+        "fields_encrypted": item.fields_encrypted,
+        "op_id": op_id
     });
 
-    let response = HTTP_CLIENT
+    let request = client
         .post(&url)
         .header("Content-Type", "application/json")
         .header("Authorization", token)
-        .json(&body)
-        .send()?;
+        .json(&body);
+    let response = client.send(request).await?;
 
-    response
-        .json::<ApiResponse<serde_json::Value>>()
-        .map_err(|e| DurianError::network(format!("解析响应失败: {}", e)))
+    response.api_json::<serde_json::Value>()
 }
 
 /// 更新账户信息
 ///
 /// # Arguments
+/// * `client` - HTTP 客户端
 /// * `api_base_url` - API 基础 URL
 /// * `token` - 认证令牌
-/// * `rid` - 记录 ID
-/// * `website` - 网站地址
-/// * `account` - 账号名
-/// * `password` - 加密后的密码
+/// * `item` - 要更新的账户，`revision` 为本地所基于的修订号（不能为 0）
 ///
 /// # Returns
-/// 更新响应
-pub fn api_update_account(
+/// 更新响应，成功时 `data` 为新的修订号；修订号冲突时错误码为 409，
+/// `data` 为服务器上的当前记录
+pub async fn api_update_account(
+    client: &HttpClient,
     api_base_url: &str,
     token: &str,
-    rid: i64,
-    website: &str,
-    account: &str,
-    password: &str,
+    item: &AccountItem,
 ) -> DurianResult<ApiResponse<serde_json::Value>> {
     let url = format!("{}/v1/account", api_base_url);
 
     let body = serde_json::json!({
-        "rid": rid,
-        "website": website,
-        "account": account,
-        "password": password
+        "rid": item.rid,
+        "website": item.website,
+        "account": item.account,
+        "password": item.password,
+        "revision": item.revision,
+        "fields_encrypted": item.fields_encrypted
+    });
+
+    let request = client
+        .put(&url)
+        .header("Content-Type", "application/json")
+        .header("Authorization", token)
+        .json(&body);
+    let response = client.send(request).await?;
+
+    response.api_json::<serde_json::Value>()
+}
+
+/// 批量更新账户
+///
+/// 服务器在单个事务中应用整批更新，要么全部成功，要么全部失败
+///
+/// # Arguments
+/// * `client` - HTTP 客户端
+/// * `api_base_url` - API 基础 URL
+/// * `token` - 认证令牌
+/// * `accounts` - 要更新的账户（密码为加密后的密文）
+///
+/// # Returns
+/// 更新响应，成功时 `data` 为各记录的新修订号
+pub async fn api_update_accounts_batch(
+    client: &HttpClient,
+    api_base_url: &str,
+    token: &str,
+    accounts: &[AccountItem],
+) -> DurianResult<ApiResponse<serde_json::Value>> {
+    let url = format!("{}/v1/account/batch", api_base_url);
+
+    let body = serde_json::json!({
+        "accounts": accounts
     });
 
-    let response = HTTP_CLIENT
+    let request = client
         .put(&url)
         .header("Content-Type", "application/json")
         .header("Authorization", token)
-        .json(&body)
-        .send()?;
+        .json(&body);
+    let response = client.send(request).await?;
 
-    response
-        .json::<ApiResponse<serde_json::Value>>()
-        .map_err(|e| DurianError::network(format!("解析响应失败: {}", e)))
+    response.api_json::<serde_json::Value>()
 }
 
 /// 删除账户
 ///
 /// # Arguments
+/// * `client` - HTTP 客户端
 /// * `api_base_url` - API 基础 URL
 /// * `token` - 认证令牌
 /// * `rid` - 记录 ID
 ///
 /// # Returns
 /// 删除响应
-pub fn api_delete_account(
+pub async fn api_delete_account(
+    client: &HttpClient,
     api_base_url: &str,
     token: &str,
     rid: i64,
@@ -263,14 +1306,449 @@ pub fn api_delete_account(
         "rid": rid
     });
 
-    let response = HTTP_CLIENT
+    let request = client
         .delete(&url)
         .header("Content-Type", "application/json")
         .header("Authorization", token)
-        .json(&body)
-        .send()?;
+        .json(&body);
+    let response = client.send(request).await?;
+
+    response.api_json::<serde_json::Value>()
+}
+
+// ============================================
+// 缓存校验 API
+// ============================================
+
+/// 发送缓存的分桶摘要
+///
+/// # Arguments
+/// * `client` - HTTP 客户端
+/// * `api_base_url` - API 基础 URL
+/// * `token` - 认证令牌
+/// * `digests` - 每个分桶的摘要
+///
+/// # Returns
+/// 校验响应，成功时 `data` 中为摘要不一致的分桶
+pub async fn api_verify_accounts(
+    client: &HttpClient,
+    api_base_url: &str,
+    token: &str,
+    digests: &[String],
+) -> DurianResult<ApiResponse<VerifyResponseData>> {
+    let url = format!("{}/v1/account/verify", api_base_url);
+
+    let body = serde_json::json!({
+        "digests": digests
+    });
+
+    let request = client
+        .post(&url)
+        .header("Content-Type", "application/json")
+        .header("Authorization", token)
+        .json(&body);
+    let response = client.send(request).await?;
+
+    response.api_json::<VerifyResponseData>()
+}
+
+/// 查询指定分桶中的全部账户
+///
+/// # Arguments
+/// * `client` - HTTP 客户端
+/// * `api_base_url` - API 基础 URL
+/// * `token` - 认证令牌
+/// * `bucket_count` - 分桶数
+/// * `buckets` - 要查询的分桶
+///
+/// # Returns
+/// 账户列表响应
+pub async fn api_query_account_buckets(
+    client: &HttpClient,
+    api_base_url: &str,
+    token: &str,
+    bucket_count: usize,
+    buckets: &[usize],
+) -> DurianResult<ApiResponse<BucketQueryResponseData>> {
+    let url = format!("{}/v1/account/buckets", api_base_url);
+
+    let body = serde_json::json!({
+        "bucket_count": bucket_count,
+        "buckets": buckets
+    });
+
+    let request = client
+        .post(&url)
+        .header("Content-Type", "application/json")
+        .header("Authorization", token)
+        .json(&body);
+    let response = client.send(request).await?;
+
+    response.api_json::<BucketQueryResponseData>()
+}
+
+// ============================================
+// 单元测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use crate::models::PullMode;
+    use std::collections::VecDeque;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc;
+
+    /// 按顺序返回预设结果的模拟传输层，结果用完后请求一直挂起
+    struct MockTransport {
+        responses: Mutex<VecDeque<DurianResult<HttpResponse>>>,
+        calls: AtomicUsize,
+    }
+
+    impl MockTransport {
+        fn new(responses: Vec<DurianResult<HttpResponse>>) -> Arc<Self> {
+            Arc::new(Self {
+                responses: Mutex::new(responses.into()),
+                calls: AtomicUsize::new(0),
+            })
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    impl Transport for MockTransport {
+        fn execute(&self, _request: HttpRequest) -> TransportFuture<'_> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let next = self.responses.lock().unwrap().pop_front();
+            Box::pin(async move {
+                match next {
+                    Some(result) => result,
+                    None => std::future::pending().await,
+                }
+            })
+        }
+    }
+
+    fn status(code: u16) -> DurianResult<HttpResponse> {
+        Ok(HttpResponse {
+            status: StatusCode::from_u16(code).unwrap(),
+            body: br#"{"code":0,"msg":"ok","data":null}"#.to_vec(),
+        })
+    }
+
+    fn mock_client(transport: Arc<MockTransport>) -> HttpClient {
+        let retry = RetryPolicy {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(2),
+        };
+        HttpClient::with_transport(transport, retry)
+    }
+
+    /// 启动只处理一个请求的本地 HTTP 代理，返回代理 URL 和收到的请求头
+    fn start_proxy() -> (String, mpsc::Receiver<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut lines = Vec::new();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                    break;
+                }
+                lines.push(line.trim_end().to_string());
+            }
+            let mut stream = stream;
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            sender.send(lines).unwrap();
+        });
+        (url, receiver)
+    }
+
+    #[tokio::test]
+    async fn test_proxy_and_user_agent() {
+        let (proxy_url, requests) = start_proxy();
+        let profile = ServerProfile {
+            proxy_url: Some(proxy_url),
+            user_agent: Some("durian-test/1.0".to_string()),
+            ..ServerProfile::unsaved("http://vault.example.invalid/api")
+        };
+        let client = HttpClient::new(&profile).unwrap();
+        assert!(api_verify(&client, &profile.api_base_url, "token")
+            .await
+            .unwrap());
+
+        let lines = requests.recv().unwrap();
+        assert_eq!(
+            lines[0],
+            "GET http://vault.example.invalid/api/v1/auth/verify HTTP/1.1"
+        );
+        assert!(lines
+            .iter()
+            .any(|line| line.eq_ignore_ascii_case("user-agent: durian-test/1.0")));
+    }
+
+    #[tokio::test]
+    async fn test_retry_idempotent_requests() {
+        let transport = MockTransport::new(vec![
+            Err(DurianError::network("连接失败")),
+            status(503),
+            status(200),
+        ]);
+        let client = mock_client(transport.clone());
+        assert!(api_verify(&client, "http://localhost", "token").await.unwrap());
+        assert_eq!(transport.calls(), 3);
+
+        // 重试次数用完后返回最后一次的结果
+        let transport = MockTransport::new(vec![status(503), status(503), status(503)]);
+        let client = mock_client(transport.clone());
+        assert!(!api_verify(&client, "http://localhost", "token").await.unwrap());
+        assert_eq!(transport.calls(), 3);
+
+        // 按 rid 删除同样可以重试
+        let transport = MockTransport::new(vec![status(502), status(200)]);
+        let client = mock_client(transport.clone());
+        let response = api_delete_account(&client, "http://localhost", "token", 1)
+            .await
+            .unwrap();
+        assert!(response.is_success());
+        assert_eq!(transport.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_for_writes_and_certificate_errors() {
+        let transport = MockTransport::new(vec![status(503), status(200)]);
+        let client = mock_client(transport.clone());
+        let item = AccountItem::new(0, "site".into(), "user".into(), "pw".into());
+        let response =
+            api_insert_account(&client, "http://localhost", "token", &item, None).await;
+        assert!(response.is_ok());
+        assert_eq!(transport.calls(), 1);
+
+        let transport = MockTransport::new(vec![Err(DurianError::UntrustedCertificate {
+            fingerprint: "abc=".to_string(),
+        })]);
+        let client = mock_client(transport.clone());
+        assert!(matches!(
+            api_verify(&client, "http://localhost", "token").await,
+            Err(DurianError::UntrustedCertificate { .. })
+        ));
+        assert_eq!(transport.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_pending() {
+        let transport = MockTransport::new(Vec::new());
+        let client = mock_client(transport.clone());
+
+        let pending = tokio::spawn({
+            let client = client.clone();
+            async move { api_verify(&client, "http://localhost", "token").await }
+        });
+        while transport.calls() == 0 {
+            tokio::task::yield_now().await;
+        }
+        client.cancel_pending();
+        assert!(matches!(pending.await.unwrap(), Err(DurianError::Cancelled)));
+
+        // 取消之后发起的请求不受影响
+        transport.responses.lock().unwrap().push_back(status(200));
+        assert!(api_verify(&client, "http://localhost", "token").await.unwrap());
+
+        // 独立取消状态的客户端不受原客户端取消的影响
+        let detached = client.detached();
+        let mut pending =
+            tokio::spawn(async move { api_verify(&detached, "http://localhost", "token").await });
+        while transport.calls() < 3 {
+            tokio::task::yield_now().await;
+        }
+        client.cancel_pending();
+        let still_running = tokio::time::timeout(Duration::from_millis(50), &mut pending).await;
+        assert!(still_running.is_err());
+        pending.abort();
+    }
+
+    #[tokio::test]
+    async fn test_prelogin_only_falls_back_on_empty_params() {
+        let empty = br#"{"code":0,"msg":"ok","data":{"login_kdf":null,"core_kdf":null}}"#;
+        let transport = MockTransport::new(vec![Ok(HttpResponse {
+            status: StatusCode::OK,
+            body: empty.to_vec(),
+        })]);
+        let client = mock_client(transport);
+        let kdf = api_prelogin(&client, "http://localhost", "alice").await;
+        assert_eq!(kdf.unwrap(), KdfSuite::legacy());
+
+        // 不支持该接口、服务器错误和业务错误都不回退到旧版参数
+        let transport = MockTransport::new(vec![
+            status(404),
+            status(500),
+            Ok(HttpResponse {
+                status: StatusCode::OK,
+                body: br#"{"code":1,"msg":"failed","data":null}"#.to_vec(),
+            }),
+        ]);
+        let client = mock_client(transport);
+        for _ in 0..3 {
+            assert!(api_prelogin(&client, "http://localhost", "alice")
+                .await
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn test_api_json_keeps_status() {
+        let response = HttpResponse {
+            status: StatusCode::UNAUTHORIZED,
+            body: br#"{"code":401,"msg":"invalid token"}"#.to_vec(),
+        };
+        let response = response.api_json::<serde_json::Value>().unwrap();
+        assert_eq!(response.status, Some(401));
+        let err = DurianError::from_response(response);
+        assert_eq!(err.kind(), ErrorKind::Unauthorized);
+
+        // 代理返回的错误页面
+        let response = HttpResponse {
+            status: StatusCode::BAD_GATEWAY,
+            body: b"<html>Bad Gateway</html>".to_vec(),
+        };
+        let err = response.api_json::<serde_json::Value>().unwrap_err();
+        assert!(matches!(err, DurianError::HttpError { status: 502, .. }));
+        assert!(err.is_retryable());
+
+        let response = HttpResponse {
+            status: StatusCode::OK,
+            body: b"not json".to_vec(),
+        };
+        let err = response.api_json::<serde_json::Value>().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Serialization);
+        assert!(!err.is_retryable());
+    }
+
+    #[test]
+    fn test_backoff() {
+        let retry = RetryPolicy::default();
+        for attempt in 0..3 {
+            let full = retry.initial_backoff * 2u32.pow(attempt);
+            let backoff = retry.backoff(attempt);
+            assert!(backoff <= full && backoff >= full / 2);
+        }
+        assert!(retry.backoff(20) <= retry.max_backoff);
+    }
+
+    #[test]
+    fn test_default_user_agent() {
+        let profile = ServerProfile::unsaved("http://localhost");
+        assert!(HttpClient::new(&profile).is_ok());
+        assert!(default_user_agent().starts_with("durian-web/"));
+    }
+
+    /// 对参考同步服务器运行完整流程：注册 → 登录 → 查询 → 插入 → 增量同步
+    #[tokio::test]
+    async fn test_reference_server() {
+        let store = durian_server::Store::in_memory().unwrap();
+        let addr = durian_server::spawn("127.0.0.1:0", store).await.unwrap();
+        let api_base_url = format!("http://{}", addr);
+        let http = HttpClient::new(&ServerProfile::unsaved(&api_base_url)).unwrap();
+        let api = HttpApi::new(http, &api_base_url);
+
+        // 使用最低成本的参数，避免测试过慢
+        let kdf = KdfSuite {
+            login: KdfParams::argon2id(8 * 1024, 1, 1).unwrap(),
+            core: KdfParams::argon2id(8 * 1024, 1, 1).unwrap(),
+            vault: Some(KdfParams::argon2id(8 * 1024, 1, 1).unwrap()),
+        };
+        let response = api
+            .register("alice", "password", "core_password", &kdf)
+            .await
+            .unwrap();
+        assert!(response.is_success());
+        assert_eq!(api.prelogin("alice").await.unwrap(), kdf);
+        assert_eq!(api.prelogin("nobody").await.unwrap(), KdfSuite::legacy());
+
+        let response = api.login("alice", "password", "wrong", &kdf).await.unwrap();
+        assert!(!response.is_success());
+        let token = api
+            .login("alice", "password", "core_password", &kdf)
+            .await
+            .unwrap()
+            .into_result()
+            .unwrap()
+            .token;
+        assert!(api.verify(&token).await.unwrap());
+        assert!(!api.verify("invalid").await.unwrap());
+
+        let data = api
+            .query_accounts(&token, 0)
+            .await
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(data.pull_mode, PullMode::PullAll);
+        assert!(data.accounts.is_empty());
+
+        let item = AccountItem::new(0, "example.com".into(), "alice".into(), "ciphertext".into());
+        let response = api.insert_account(&token, &item, Some("op-1")).await.unwrap();
+        assert!(response.is_success());
+        // 重新发送同一操作返回原记录 ID，不产生重复记录
+        let retried = api.insert_account(&token, &item, Some("op-1")).await.unwrap();
+        assert_eq!(retried.data, response.data);
+        let data = api
+            .query_accounts(&token, data.update_time)
+            .await
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(data.pull_mode, PullMode::PullUpdated);
+        assert_eq!(data.accounts.len(), 1);
+        assert_eq!(data.accounts[0].website, "example.com");
+        let item = data.accounts[0].clone();
+
+        let data = api
+            .query_accounts(&token, data.update_time)
+            .await
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(data.pull_mode, PullMode::PullNothing);
+
+        // 客户端与服务器的分桶摘要算法一致
+        let revisions = [(item.rid, item.revision)];
+        let buckets = crate::verify::bucket_count(revisions.len());
+        let digests = crate::verify::bucket_digests(&revisions, buckets);
+        let data = api
+            .verify_accounts(&token, &digests)
+            .await
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert!(data.buckets.is_empty());
 
-    response
-        .json::<ApiResponse<serde_json::Value>>()
-        .map_err(|e| DurianError::network(format!("解析响应失败: {}", e)))
+        let data = api
+            .verify_accounts(&token, &crate::verify::bucket_digests(&[], buckets))
+            .await
+            .unwrap()
+            .into_result()
+            .unwrap();
+        let bucket = crate::verify::bucket_of(item.rid, buckets);
+        assert_eq!(data.buckets, vec![bucket]);
+        let data = api
+            .query_account_buckets(&token, buckets, &data.buckets)
+            .await
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(data.accounts.len(), 1);
+        assert_eq!(data.accounts[0].rid, item.rid);
+    }
 }
@@ -3,16 +3,77 @@
 //! 定义所有供前端调用的 Tauri 命令
 //!
 //! # 错误处理
-//! 所有命令都返回 `Result<T, String>`，错误信息会自动转换为可读的中文提示
+//! 所有命令都返回 `Result<T, DurianError>`，错误序列化为结构化的
+//! `{kind, code, message, retryable, details}`（见 [`crate::error::ErrorPayload`]）。
+//! 前端按 `kind`（API 错误再按服务器返回的 `code`）区分未登录、令牌失效、密码错误、
+//! 网络错误等情况，不应解析 `message`：它由消息目录（见 [`crate::i18n`]）按生成错误时的
+//! 界面语言生成，只用于向用户展示。`retryable` 表示稍后重试是否可能成功
+//!
+//! # 参数与返回值
+//! 命令直接接收和返回带类型的结构体，由 Tauri 序列化，不在命令中手动编解码 JSON。
+//! 这些结构体派生了 `ts_rs::TS`，`cargo test` 时生成对应的 TypeScript 类型
+//! （`src/types/bindings`），前端类型与 Rust 保持一致
 //!
 //! # 输入验证
 //! 所有命令都会对输入参数进行验证
+//!
+//! # 本地化
+//! 验证错误和返回的状态消息来自消息目录（见 [`crate::i18n`]），使用当前界面语言。
+//! 使用 `get_locale` 和 `set_locale` 查看和修改界面语言
+//!
+//! # 敏感参数
+//! 密码和令牌参数直接反序列化为 `SecretString`，使用后自动清零
+//!
+//! # 会话状态
+//! 会话状态由 Tauri 托管（[`AppState`]），通过 `State` 参数注入到命令中。
+//! 同时登录多个用户时，命令作用于当前会话
+//!
+//! # 异步命令
+//! 访问服务器的命令都是异步的，等待响应期间不持有状态锁（见 [`crate::state`]）。
+//! 前端离开页面时调用 `cancel_requests` 取消当前会话由命令发起的请求；
+//! 离线写入队列的回放和后台同步不受影响
+//!
+//! # 离线写入
+//! 插入、修改和删除账户先写入离线队列并应用到本地缓存，再尝试发送到服务器
+//! （见 [`crate::sync`]）。服务器不可达时命令仍然成功，
+//! 使用 `get_sync_status` 查看等待同步和被拒绝的操作
+//!
+//! # 后台同步
+//! 登录、`init_state` 或解锁后为会话启动后台同步任务，按设定的间隔同步并发送
+//! `sync-started`、`sync-finished`、`sync-failed` 事件（见 [`crate::sync_worker`]）；
+//! 锁定或登出时停止。使用 `get_sync_settings` 和 `set_sync_settings` 查看和修改同步间隔
+//!
+//! # 缓存校验
+//! `verify_cache` 与服务器比较缓存的分桶摘要，只重新拉取不一致的分桶，
+//! 修复缓存与服务器的偏差而无需清除缓存后全量拉取（见 [`crate::verify`]）
+//!
+//! # 修改冲突
+//! 修改账户时提交本地缓存的修订号，记录已被其他设备修改时服务器拒绝修改，
+//! 本地版本和服务器版本都保存在冲突记录中。使用 `list_conflicts` 查看，
+//! `resolve_conflict` 选择保留的版本
+
+use std::path::PathBuf;
+use std::sync::Arc;
 
-use crate::api_client;
+use crate::api_client::{ApiClient, HttpApi, HttpClient};
 use crate::crypto::{decrypt_message, encrypt_message};
-use crate::error::DurianError;
-use crate::models::{AccountRecord, CacheData, TempAccountRecord};
-use crate::state::{self, DurianState};
+use crate::database;
+use crate::error::{DurianError, DurianResult, ErrorPayload, CODE_FAILED};
+use crate::i18n::{self, tr, Field, Locale, Message, WriteAction};
+use crate::kdf::{KdfParams, KdfSuite};
+use crate::lock::LockSettings;
+use crate::models::{
+    AccountConflict, AccountItem, AccountRecord, CacheData, ConflictChoice, FlushReport,
+    LoginResponseData, PendingOpKind, PullMode, SyncStatus, VerifyReport,
+};
+use crate::rekey;
+use crate::secret::SecretString;
+use crate::settings::{ServerProfile, SettingsStore};
+use crate::state::{default_db_path, session_id, AppState, Connection, DurianState, SessionInfo};
+use crate::sync;
+use crate::sync_worker::{self, SyncSettings};
+use crate::verify;
+use tauri::{AppHandle, Runtime, State};
 
 // ============================================
 // 认证相关命令
@@ -20,298 +81,931 @@ use crate::state::{self, DurianState};
 
 /// 初始化应用状态
 ///
-/// 在登录成功后调用，设置用户状态
+/// 在登录成功后调用，设置用户状态并派生本次会话的保险库密钥，然后启动后台同步。
+/// `profile` 为服务器名称，未指定时使用默认服务器
 #[tauri::command]
-pub fn init_state(
+pub async fn init_state<R: Runtime>(
+    app: AppHandle<R>,
+    app_state: State<'_, AppState>,
+    settings: State<'_, SettingsStore>,
+    profile: Option<String>,
     username: String,
-    core_password: String,
-    token: String,
-    api_base_url: String,
-) -> Result<(), String> {
+    core_password: SecretString,
+    token: SecretString,
+) -> DurianResult<()> {
     // 输入验证
-    validate_not_empty(&username, "用户名")?;
-    validate_not_empty(&core_password, "核心密码")?;
-    validate_not_empty(&token, "认证令牌")?;
-    validate_not_empty(&api_base_url, "API URL")?;
-
-    let durian_state = DurianState::new(username, core_password, token, api_base_url)
-        .map_err(|e| e.to_string())?;
-    state::set_global_state(durian_state);
+    validate_not_empty(&username, Field::Username)?;
+    validate_not_empty(core_password.expose(), Field::CorePassword)?;
+    validate_not_empty(token.expose(), Field::Token)?;
+
+    let profile = settings.load()?.resolve_profile(profile.as_deref())?;
+    let api = api_client(&profile)?;
+    let api_base_url = profile.api_base_url;
+    let db_path = default_db_path()?;
+    let id = session_id(&api_base_url, &username);
+    let vault_kdf = api.prelogin(&username).await?.vault_params(&username);
+    start_session(
+        &app_state,
+        api,
+        db_path,
+        username,
+        core_password,
+        &vault_kdf,
+        token,
+        api_base_url,
+    )
+    .await?;
+    start_sync_worker(&app, &app_state, &id)
+}
+
+/// 创建会话状态并设为当前会话
+#[allow(clippy::too_many_arguments)]
+async fn start_session(
+    app_state: &AppState,
+    api: Arc<dyn ApiClient>,
+    db_path: PathBuf,
+    username: String,
+    core_password: SecretString,
+    vault_kdf: &KdfParams,
+    token: SecretString,
+    api_base_url: String,
+) -> DurianResult<()> {
+    let durian_state = DurianState::new(
+        db_path,
+        username,
+        core_password.expose(),
+        vault_kdf,
+        token,
+        api_base_url,
+        api,
+    )
+    .await?;
+    app_state.set(durian_state)
+}
+
+/// 按会话的同步设置（重新）启动后台同步，原有的任务随之停止
+fn start_sync_worker<R: Runtime>(
+    app: &AppHandle<R>,
+    app_state: &AppState,
+    session_id: &str,
+) -> DurianResult<()> {
+    let mut state = app_state.session_mut(session_id)?;
+    let worker = sync_worker::spawn(
+        app_state.clone(),
+        session_id.to_string(),
+        state.username.clone(),
+        state.sync_settings(),
+        app.clone(),
+    );
+    state.set_sync_worker(worker);
     Ok(())
 }
 
 /// 用户登录
 ///
-/// 执行登录请求并在成功后初始化状态
+/// 先与服务器协商 KDF 参数，执行登录请求并在成功后初始化状态。
+/// 若账户仍在使用过时的 KDF 参数，登录成功后会透明升级。
+/// `profile` 为服务器名称，未指定时使用默认服务器。
+/// 返回登录的用户名；令牌只保存在后端会话中，不交给前端
 #[tauri::command]
-pub fn login(
-    api_base_url: String,
+pub async fn login<R: Runtime>(
+    app: AppHandle<R>,
+    app_state: State<'_, AppState>,
+    settings: State<'_, SettingsStore>,
+    profile: Option<String>,
     username: String,
-    password: String,
-    core_password: String,
-) -> Result<String, String> {
+    password: SecretString,
+    core_password: SecretString,
+) -> DurianResult<LoginResponseData> {
     // 输入验证
-    validate_not_empty(&api_base_url, "API URL")?;
-    validate_not_empty(&username, "用户名")?;
-    validate_not_empty(&password, "密码")?;
-    validate_not_empty(&core_password, "核心密码")?;
+    validate_not_empty(&username, Field::Username)?;
+    validate_not_empty(password.expose(), Field::Password)?;
+    validate_not_empty(core_password.expose(), Field::CorePassword)?;
+
+    let profile = settings.load()?.resolve_profile(profile.as_deref())?;
+    let api = api_client(&profile)?;
+    let api_base_url = profile.api_base_url;
+    let db_path = default_db_path()?;
+    let id = session_id(&api_base_url, &username);
+    let result = login_with(
+        &app_state,
+        api,
+        db_path,
+        api_base_url,
+        username,
+        password,
+        core_password,
+    )
+    .await?;
+    start_sync_worker(&app, &app_state, &id)?;
+    Ok(result)
+}
+
+/// 使用指定的 API 客户端和本地数据库登录（参数已验证）
+async fn login_with(
+    app_state: &AppState,
+    api: Arc<dyn ApiClient>,
+    db_path: PathBuf,
+    api_base_url: String,
+    username: String,
+    password: SecretString,
+    core_password: SecretString,
+) -> DurianResult<LoginResponseData> {
+    let kdf = api.prelogin(&username).await?;
+
+    // 已升级的账户不接受旧版参数，防止被降级到弱哈希
+    let id = session_id(&api_base_url, &username);
+    database::init_database(&db_path)?;
+    database::adopt_user_rows(&db_path, &username, &id)?;
+    let upgraded = database::is_kdf_upgraded(&db_path, &id)?;
+    if upgraded && kdf.uses_legacy() {
+        return Err(DurianError::crypto(tr(Message::KdfDowngrade)));
+    }
 
-    let response = api_client::api_login(&api_base_url, &username, &password, &core_password)
-        .map_err(|e| e.to_string())?;
+    let response = api
+        .login(&username, password.expose(), core_password.expose(), &kdf)
+        .await?;
 
     if response.code == 0 {
         if let Some(data) = response.data {
+            let token = SecretString::from(data.token);
+            if !kdf.uses_legacy() {
+                database::set_kdf_upgraded(&db_path, &id)?;
+            }
+
+            // 升级过时的 KDF 参数（失败不影响本次登录，下次登录时会重试）
+            let mut kdf_upgrade_error = None;
+            if kdf.needs_upgrade() {
+                match upgrade_kdf(api.as_ref(), &token, &password, &core_password).await {
+                    Ok(()) => database::set_kdf_upgraded(&db_path, &id)?,
+                    Err(e) => {
+                        kdf_upgrade_error =
+                            Some(login_warning(&e, |msg| tr(Message::KdfUpgradeFailed(msg))));
+                    }
+                }
+            }
+            let result = LoginResponseData {
+                username: username.clone(),
+                kdf_upgrade_error,
+                migration_error: None,
+            };
+
             // 初始化状态
-            init_state(
+            let vault_kdf = kdf.vault_params(&username);
+            start_session(
+                app_state,
+                api,
+                db_path,
                 username,
                 core_password,
-                data.token.clone(),
+                &vault_kdf,
+                token,
                 api_base_url,
-            )?;
-            return Ok(data.token);
+            )
+            .await?;
+
+            // 将旧格式的记录迁移到当前格式（失败不影响本次登录，下次登录时会重试）
+            let migration_error = rekey::migrate_legacy_entries(app_state, &id)
+                .await
+                .err()
+                .map(|e| login_warning(&e, |msg| tr(Message::MigrationFailed(msg))));
+            return Ok(LoginResponseData {
+                migration_error,
+                ..result
+            });
         }
     }
 
-    Err(format!("登录失败: {}", response.msg))
+    // 服务器以业务失败拒绝登录：用户名、密码或核心密码错误
+    if response.code == CODE_FAILED {
+        return Err(DurianError::invalid_credentials(response.msg));
+    }
+    Err(DurianError::from_response(response))
+}
+
+/// 登录后的维护步骤失败的原因，描述中注明失败的步骤
+fn login_warning(err: &DurianError, describe: impl FnOnce(&str) -> String) -> ErrorPayload {
+    let mut payload = err.to_payload();
+    payload.message = describe(&payload.message);
+    payload
+}
+
+/// 使用新生成的 Argon2id 参数替换服务器上的密码哈希
+async fn upgrade_kdf(
+    api: &dyn ApiClient,
+    token: &SecretString,
+    password: &SecretString,
+    core_password: &SecretString,
+) -> DurianResult<()> {
+    // 保险库密钥参数决定已有密文的密钥，不随升级改变（请求中不包含）
+    let upgraded = KdfSuite::generate()?;
+    let response = api
+        .upgrade_kdf(
+            token.expose(),
+            password.expose(),
+            core_password.expose(),
+            &upgraded,
+        )
+        .await?;
+    if response.is_success() {
+        Ok(())
+    } else {
+        Err(DurianError::from_response(response))
+    }
 }
 
 /// 用户注册
+///
+/// 为登录密码和核心密码各生成一组带随机盐值的 Argon2id 参数。
+/// `profile` 为服务器名称，未指定时使用默认服务器
 #[tauri::command]
-pub fn register(
-    api_base_url: String,
+pub async fn register(
+    settings: State<'_, SettingsStore>,
+    profile: Option<String>,
     username: String,
-    password: String,
-    core_password: String,
-) -> Result<String, String> {
+    password: SecretString,
+    core_password: SecretString,
+) -> DurianResult<String> {
     // 输入验证
-    validate_not_empty(&api_base_url, "API URL")?;
-    validate_not_empty(&username, "用户名")?;
-    validate_not_empty(&password, "密码")?;
-    validate_not_empty(&core_password, "核心密码")?;
-    validate_min_length(&password, 6, "密码")?;
-    validate_min_length(&core_password, 6, "核心密码")?;
+    validate_not_empty(&username, Field::Username)?;
+    validate_not_empty(password.expose(), Field::Password)?;
+    validate_not_empty(core_password.expose(), Field::CorePassword)?;
+    validate_min_length(password.expose(), 6, Field::Password)?;
+    validate_min_length(core_password.expose(), 6, Field::CorePassword)?;
+
+    let profile = settings.load()?.resolve_profile(profile.as_deref())?;
+    let api = api_client(&profile)?;
+    register_with(api.as_ref(), &username, &password, &core_password).await
+}
+
+/// 使用指定的 API 客户端注册（参数已验证）
+async fn register_with(
+    api: &dyn ApiClient,
+    username: &str,
+    password: &SecretString,
+    core_password: &SecretString,
+) -> DurianResult<String> {
+    let kdf = KdfSuite::generate()?;
 
-    let response = api_client::api_register(&api_base_url, &username, &password, &core_password)
-        .map_err(|e| e.to_string())?;
+    let response = api
+        .register(username, password.expose(), core_password.expose(), &kdf)
+        .await?;
 
     if response.code == 0 {
-        Ok("注册成功".to_string())
+        Ok(tr(Message::Registered))
     } else {
-        Err(format!("注册失败: {}", response.msg))
+        Err(DurianError::from_response(response))
     }
 }
 
 /// 验证登录状态
 #[tauri::command]
-pub fn verify() -> Result<bool, String> {
-    let state = state::get_state().map_err(|e| e.to_string())?;
-    api_client::api_verify(&state.api_base_url, &state.token).map_err(|e| e.to_string())
+pub async fn verify(app_state: State<'_, AppState>) -> DurianResult<bool> {
+    let connection = app_state.connection()?;
+    connection.api.verify(connection.token.expose()).await
+}
+
+/// 修改核心密码
+///
+/// 校验原核心密码后重新加密所有记录，并更新服务器上的核心密码哈希。
+/// 中断后使用相同参数再次调用即可继续
+#[tauri::command]
+pub async fn change_core_password(
+    app_state: State<'_, AppState>,
+    old_core_password: SecretString,
+    new_core_password: SecretString,
+) -> DurianResult<String> {
+    // 输入验证
+    validate_not_empty(old_core_password.expose(), Field::OldCorePassword)?;
+    validate_not_empty(new_core_password.expose(), Field::NewCorePassword)?;
+    validate_min_length(new_core_password.expose(), 6, Field::NewCorePassword)?;
+
+    // 重新加密期间不持有状态锁，只读命令不受影响；
+    // 该会话的写入命令和队列回放会等待修改完成（新密钥装入会话）后再加密和发送
+    let session_id = app_state.session_id()?;
+    rekey::change_core_password(
+        &app_state,
+        &session_id,
+        old_core_password.expose(),
+        new_core_password.expose(),
+    )
+    .await?;
+    Ok(tr(Message::CorePasswordChanged))
+}
+
+/// 检查是否存在未完成的核心密码修改
+#[tauri::command]
+pub fn has_pending_core_password_change(app_state: State<'_, AppState>) -> DurianResult<bool> {
+    let state = app_state.get()?;
+    state.has_pending_core_password_change()
+}
+
+/// 锁定保险库
+///
+/// 清除内存中的会话密钥，保留登录状态和本地缓存
+#[tauri::command]
+pub fn lock(app_state: State<'_, AppState>) -> DurianResult<()> {
+    let mut state = app_state.get_mut()?;
+    state.lock();
+    Ok(())
+}
+
+/// 使用核心密码解锁保险库
+///
+/// 解锁后重新启动锁定时停止的后台同步
+#[tauri::command]
+pub fn unlock<R: Runtime>(
+    app: AppHandle<R>,
+    app_state: State<'_, AppState>,
+    core_password: SecretString,
+) -> DurianResult<()> {
+    validate_not_empty(core_password.expose(), Field::CorePassword)?;
+
+    let (session_id, context) = {
+        let state = app_state.get()?;
+        if !state.is_locked() {
+            return Ok(());
+        }
+        (state.session_id.clone(), state.unlock_context())
+    };
+
+    // 派生密钥（Argon2）期间不持有状态锁，其他命令和自动锁定检查不会被阻塞
+    let keys = context.derive_keys(core_password.expose())?;
+    app_state.session_mut(&session_id)?.install_keys(keys);
+    start_sync_worker(&app, &app_state, &session_id)
+}
+
+/// 检查保险库是否已锁定
+#[tauri::command]
+pub fn is_locked(app_state: State<'_, AppState>) -> DurianResult<bool> {
+    let state = app_state.get()?;
+    Ok(state.is_locked())
+}
+
+/// 获取自动锁定设置
+#[tauri::command]
+pub fn get_lock_settings(app_state: State<'_, AppState>) -> DurianResult<LockSettings> {
+    let state = app_state.get()?;
+    Ok(state.lock_settings())
+}
+
+/// 修改自动锁定设置
+///
+/// `idle_timeout_secs` 为 0 表示不按空闲时间自动锁定
+#[tauri::command]
+pub fn set_lock_settings(
+    app_state: State<'_, AppState>,
+    idle_timeout_secs: u64,
+    lock_on_suspend: bool,
+) -> DurianResult<()> {
+    let mut state = app_state.get_mut()?;
+    state.set_lock_settings(LockSettings {
+        idle_timeout_secs,
+        lock_on_suspend,
+    })
+}
+
+/// 获取后台同步设置
+#[tauri::command]
+pub fn get_sync_settings(app_state: State<'_, AppState>) -> DurianResult<SyncSettings> {
+    let state = app_state.get()?;
+    Ok(state.sync_settings())
+}
+
+/// 修改后台同步设置并按新的间隔重新启动后台同步
+///
+/// `interval_secs` 为 0 表示不在后台同步
+#[tauri::command]
+pub fn set_sync_settings<R: Runtime>(
+    app: AppHandle<R>,
+    app_state: State<'_, AppState>,
+    interval_secs: u64,
+) -> DurianResult<()> {
+    let session_id = {
+        let mut state = app_state.get_mut()?;
+        state.set_sync_settings(SyncSettings { interval_secs })?;
+        if state.is_locked() {
+            return Ok(());
+        }
+        state.session_id.clone()
+    };
+    start_sync_worker(&app, &app_state, &session_id)
+}
+
+/// 开启或关闭全字段加密
+///
+/// 设置保存在服务器上，对账户的所有设备生效（其他设备下次解锁时读取）。
+/// 开启后网站和账户字段也在客户端加密后再发送到服务器，并立即加密已有记录；
+/// 关闭后只影响新写入的记录，已加密的字段仍可正常读取
+#[tauri::command]
+pub async fn set_full_field_encryption(
+    app_state: State<'_, AppState>,
+    enabled: bool,
+) -> DurianResult<String> {
+    let connection = app_state.connection()?;
+    let session_id = connection.session_id.clone();
+
+    let response = connection
+        .api
+        .set_full_field_encryption(connection.token.expose(), enabled)
+        .await?;
+    if !response.is_success() {
+        return Err(DurianError::from_response(response));
+    }
+    app_state
+        .session(&session_id)?
+        .set_full_field_encryption(enabled)?;
+
+    // 加密已有记录失败时设置仍然生效，再次开启即可继续
+    if enabled {
+        rekey::encrypt_account_fields(&app_state, &session_id).await?;
+    }
+
+    Ok(if enabled {
+        tr(Message::FullFieldEncryptionEnabled)
+    } else {
+        tr(Message::FullFieldEncryptionDisabled)
+    })
+}
+
+/// 检查是否开启了全字段加密
+#[tauri::command]
+pub fn get_full_field_encryption(app_state: State<'_, AppState>) -> DurianResult<bool> {
+    let state = app_state.get()?;
+    state.full_field_encryption()
 }
 
 /// 用户登出
+///
+/// 释放当前会话，令牌和保险库密钥随之清零；
+/// 存在其他已登录会话时，最近使用的会话成为当前会话
 #[tauri::command]
-pub fn logout() -> Result<(), String> {
-    state::clear_state();
+pub fn logout(app_state: State<'_, AppState>) -> DurianResult<()> {
+    app_state.logout();
     Ok(())
 }
 
+/// 列出所有已登录的会话
+#[tauri::command]
+pub fn list_sessions(app_state: State<'_, AppState>) -> DurianResult<Vec<SessionInfo>> {
+    app_state.sessions()
+}
+
+/// 切换当前会话
+///
+/// 无需重新登录；`id` 为 `list_sessions` 返回的会话标识。目标会话已锁定时需要使用其核心密码解锁
+#[tauri::command]
+pub fn switch_session(app_state: State<'_, AppState>, id: String) -> DurianResult<()> {
+    validate_not_empty(&id, Field::Session)?;
+    app_state.switch_session(&id)
+}
+
 // ============================================
-// 账户管理命令
+// 服务器配置命令
 // ============================================
 
-/// 查询账户列表
+/// 列出保存的服务器配置
+#[tauri::command]
+pub fn list_profiles(settings: State<'_, SettingsStore>) -> DurianResult<Vec<ServerProfile>> {
+    settings.load().map(|settings| settings.profiles)
+}
+
+/// 保存服务器配置
 ///
-/// 支持从缓存加载和强制刷新
+/// 同名配置会被替换；URL、代理和超时时间会先经过校验。
+/// 使用该服务器的已登录会话会按新配置重建 HTTP 客户端
 #[tauri::command]
-pub fn query_accounts(force_refresh: bool) -> Result<String, String> {
-    let state = state::get_state().map_err(|e| e.to_string())?;
+pub fn save_profile(
+    app_state: State<'_, AppState>,
+    settings: State<'_, SettingsStore>,
+    profile: ServerProfile,
+) -> DurianResult<()> {
+    let profile = settings.update(|settings| settings.save_profile(profile))?;
+    app_state.reconfigure_http(&profile)
+}
 
-    // 如果不是强制刷新，先尝试从缓存加载
-    if !force_refresh {
-        if let Some(cache_data) = state.load_cache_data().map_err(|e| e.to_string())? {
-            if !cache_data.accounts.is_empty() {
-                return serde_json::to_string(&cache_data)
-                    .map_err(|e| format!("序列化失败: {}", e));
-            }
-        }
-    }
+/// 删除服务器配置
+#[tauri::command]
+pub fn delete_profile(settings: State<'_, SettingsStore>, name: String) -> DurianResult<()> {
+    validate_not_empty(&name, Field::ServerName)?;
+    settings.update(|settings| settings.delete_profile(&name))
+}
+
+/// 获取服务器证书的公钥指纹
+///
+/// 证书不受信任时返回其公钥指纹，供用户核对后调用 `trust_server_certificate`；
+/// 证书已受信任时返回 `None`
+#[tauri::command]
+pub async fn probe_server_certificate(
+    settings: State<'_, SettingsStore>,
+    name: String,
+) -> DurianResult<Option<String>> {
+    let settings = settings.load()?;
+    let profile = settings
+        .profile(&name)
+        .ok_or_else(|| DurianError::validation(tr(Message::ProfileNotFound(&name))))?;
+    HttpClient::probe_fingerprint(profile).await
+}
+
+/// 信任服务器证书（首次信任）
+///
+/// 将公钥指纹加入服务器配置的固定指纹列表，之后只接受该公钥
+#[tauri::command]
+pub fn trust_server_certificate(
+    app_state: State<'_, AppState>,
+    settings: State<'_, SettingsStore>,
+    name: String,
+    fingerprint: String,
+) -> DurianResult<()> {
+    validate_not_empty(&fingerprint, Field::Fingerprint)?;
+    let profile = settings.update(|settings| settings.trust_fingerprint(&name, &fingerprint))?;
+    app_state.reconfigure_http(&profile)
+}
 
-    // 获取最后更新时间
-    let last_update_time = state.get_last_update_time().map_err(|e| e.to_string())?;
+/// 按服务器配置创建 API 客户端
+fn api_client(profile: &ServerProfile) -> DurianResult<Arc<dyn ApiClient>> {
+    let http = HttpClient::new(profile)?;
+    Ok(Arc::new(HttpApi::new(http, &profile.api_base_url)))
+}
 
-    // 从服务器查询
-    let response = api_client::api_query_accounts(&state.api_base_url, &state.token, last_update_time)
-        .map_err(|e| e.to_string())?;
+// ============================================
+// 界面语言命令
+// ============================================
 
-    if response.code == 0 {
-        if let Some(data) = response.data {
-            // 转换为 AccountRecord 格式
-            let accounts: Vec<AccountRecord> = data
-                .accounts
-                .iter()
-                .map(|item| AccountRecord::new(
-                    item.rid,
-                    state.username.clone(),
-                    item.website.clone(),
-                    item.account.clone(),
-                    item.password.clone(),
-                ))
-                .collect();
-
-            let cache_data = CacheData::new(
-                state.username.clone(),
-                data.update_time,
-                accounts,
-            );
+/// 获取当前界面语言
+#[tauri::command]
+pub fn get_locale() -> Locale {
+    i18n::current_locale()
+}
 
-            // 保存到缓存
-            state
-                .save_cache_data(&cache_data, &data.pull_mode)
-                .map_err(|e| e.to_string())?;
+/// 设置界面语言并保存到设置中，立即生效
+///
+/// `locale` 为 `None` 时跟随操作系统语言；返回生效的语言
+#[tauri::command]
+pub fn set_locale(
+    settings: State<'_, SettingsStore>,
+    locale: Option<Locale>,
+) -> DurianResult<Locale> {
+    let settings = settings.update(|settings| {
+        settings.locale = locale;
+        Ok(settings.clone())
+    })?;
+    let locale = settings.locale();
+    i18n::set_current_locale(locale);
+    Ok(locale)
+}
+
+// ============================================
+// 账户管理命令
+// ============================================
+
+/// 查询账户列表
+///
+/// 支持从缓存加载和强制刷新
+#[tauri::command]
+pub async fn query_accounts(
+    app_state: State<'_, AppState>,
+    force_refresh: bool,
+) -> DurianResult<CacheData> {
+    let connection = {
+        let state = app_state.get()?;
 
-            // 重新从缓存加载（确保数据一致性）
-            if let Some(loaded_data) = state.load_cache_data().map_err(|e| e.to_string())? {
-                return serde_json::to_string(&loaded_data)
-                    .map_err(|e| format!("序列化失败: {}", e));
+        // 如果不是强制刷新，先尝试从缓存加载
+        if !force_refresh {
+            if let Some(cache_data) = state.load_cache_data()? {
+                if !cache_data.accounts.is_empty() {
+                    return Ok(cache_data);
+                }
             }
         }
-    }
 
-    Err(format!("查询失败: {}", response.msg))
+        state.connection()
+    };
+
+    // 先回放离线写入再拉取更新；仍有写入未能发送（离线）时返回本地缓存，
+    // 避免服务器数据覆盖乐观修改
+    sync::pull_accounts(&app_state, &connection).await?;
+
+    let state = app_state.session(&connection.session_id)?;
+    let cache_data = state
+        .load_cache_data()?
+        .unwrap_or_else(|| CacheData::empty(&state.username));
+    Ok(cache_data)
 }
 
 /// 插入账户
 ///
 /// 自动加密密码后发送到服务器
 #[tauri::command]
-pub fn insert_account(
+pub async fn insert_account(
+    app_state: State<'_, AppState>,
     website: String,
     account: String,
-    password: String,
-) -> Result<String, String> {
+    password: SecretString,
+) -> DurianResult<String> {
     // 输入验证（账户可以为空）
-    validate_not_empty(&website, "网站")?;
-    validate_not_empty(&password, "密码")?;
-
-    let state = state::get_state().map_err(|e| e.to_string())?;
+    validate_not_empty(&website, Field::Website)?;
+    validate_not_empty(password.expose(), Field::Password)?;
 
-    // 加密密码
-    let encrypted_password = encrypt_message(&password, &state.core_password)
-        .map_err(|e| e.to_string())?;
-
-    let response = api_client::api_insert_account(
-        &state.api_base_url,
-        &state.token,
-        &website,
-        &account,
-        &encrypted_password,
-    ).map_err(|e| e.to_string())?;
-
-    if response.code == 0 {
-        Ok("插入成功".to_string())
-    } else {
-        Err(format!("插入失败: {}", response.msg))
-    }
+    let connection = app_state.connection()?;
+    queue_write(
+        &app_state,
+        &connection,
+        PendingOpKind::Insert,
+        WriteAction::Insert,
+        None,
+        |state| {
+            // 加密密码（全字段加密时同时加密网站和账户）
+            let item = seal_account(state, 0, &website, &account, &password)?;
+            let record = AccountRecord::new(
+                0,
+                state.username.clone(),
+                website,
+                account,
+                item.password.clone(),
+            );
+            Ok((item, Some(record)))
+        },
+    )
+    .await
 }
 
 /// 更新账户
 ///
 /// 自动加密密码后发送到服务器
 #[tauri::command]
-pub fn update_account(
+pub async fn update_account(
+    app_state: State<'_, AppState>,
     rid: i64,
     website: String,
     account: String,
-    password: String,
-) -> Result<String, String> {
-    // 输入验证
-    if rid <= 0 {
-        return Err(DurianError::validation("无效的记录 ID").to_string());
+    password: SecretString,
+) -> DurianResult<String> {
+    // 输入验证（尚未同步的记录使用负数的临时 ID）
+    if rid == 0 {
+        return Err(DurianError::validation(tr(Message::InvalidRecordId)));
     }
-    validate_not_empty(&website, "网站")?;
-    validate_not_empty(&account, "账户")?;
-    validate_not_empty(&password, "密码")?;
-
-    let state = state::get_state().map_err(|e| e.to_string())?;
+    validate_not_empty(&website, Field::Website)?;
+    validate_not_empty(&account, Field::Account)?;
+    validate_not_empty(password.expose(), Field::Password)?;
 
-    // 加密密码
-    let encrypted_password = encrypt_message(&password, &state.core_password)
-        .map_err(|e| e.to_string())?;
-
-    let response = api_client::api_update_account(
-        &state.api_base_url,
-        &state.token,
-        rid,
-        &website,
-        &account,
-        &encrypted_password,
-    ).map_err(|e| e.to_string())?;
+    let connection = app_state.connection()?;
+    queue_write(
+        &app_state,
+        &connection,
+        PendingOpKind::Update,
+        WriteAction::Update,
+        None,
+        |state| {
+            // 基于本地缓存的修订号修改，服务器上的记录已被其他设备修改时产生冲突
+            if state.get_conflict(rid)?.is_some() {
+                return Err(DurianError::conflict(tr(Message::UnresolvedConflict)));
+            }
+            // 尚未同步的插入没有修订号，发送前由插入的结果补上
+            let (revision, updated_at) = match state.get_account(rid)? {
+                Some(record) if rid < 0 || record.revision > 0 => {
+                    (record.revision, record.updated_at)
+                }
+                _ => return Err(DurianError::validation(tr(Message::RecordNotCached))),
+            };
 
-    if response.code == 0 {
-        Ok("更新成功".to_string())
-    } else {
-        Err(format!("更新失败: {}", response.msg))
-    }
+            // 加密密码（全字段加密时同时加密网站和账户）
+            let item = seal_account(state, rid, &website, &account, &password)?
+                .with_revision(revision, 0);
+            let record = AccountRecord::new(
+                rid,
+                state.username.clone(),
+                website,
+                account,
+                item.password.clone(),
+            )
+            .with_revision(revision, updated_at);
+            Ok((item, Some(record)))
+        },
+    )
+    .await
 }
 
 /// 删除账户
 #[tauri::command]
-pub fn delete_account(rid: i64) -> Result<String, String> {
-    // 输入验证
-    if rid <= 0 {
-        return Err(DurianError::validation("无效的记录 ID").to_string());
+pub async fn delete_account(app_state: State<'_, AppState>, rid: i64) -> DurianResult<String> {
+    // 输入验证（尚未同步的记录使用负数的临时 ID）
+    if rid == 0 {
+        return Err(DurianError::validation(tr(Message::InvalidRecordId)));
     }
 
-    let state = state::get_state().map_err(|e| e.to_string())?;
+    let connection = app_state.connection()?;
+    queue_write(
+        &app_state,
+        &connection,
+        PendingOpKind::Delete,
+        WriteAction::Delete,
+        None,
+        |_| {
+            let item = AccountItem::new(rid, String::new(), String::new(), String::new());
+            Ok((item, None))
+        },
+    )
+    .await
+}
 
-    let response = api_client::api_delete_account(&state.api_base_url, &state.token, rid)
-        .map_err(|e| e.to_string())?;
+/// 将写入操作加入离线队列并立即尝试回放
+///
+/// `prepare` 在持有写入队列锁时加密要写入的记录，修改核心密码期间会等待其完成，
+/// 不会使用即将失效的密钥加密。
+/// 服务器不可达时操作留在队列中，命令仍然成功；服务器拒绝该操作时返回错误。
+/// `resolves` 为该写入处理的冲突的记录 ID，操作加入队列后才移除该冲突
+async fn queue_write<F>(
+    app_state: &AppState,
+    connection: &Connection,
+    kind: PendingOpKind,
+    action: WriteAction,
+    resolves: Option<i64>,
+    prepare: F,
+) -> DurianResult<String>
+where
+    F: FnOnce(&DurianState) -> DurianResult<(AccountItem, Option<AccountRecord>)>,
+{
+    let _guard = connection.write_queue.lock().await;
 
-    if response.code == 0 {
-        Ok("删除成功".to_string())
-    } else {
-        Err(format!("删除失败: {}", response.msg))
+    // 状态锁不能跨越 await 持有
+    let op_id = {
+        let state = app_state.session(&connection.session_id)?;
+        let (item, record) = prepare(&state)?;
+        let op_id = state.enqueue_write(kind, &item, record.as_ref())?;
+        // 重新提交时再次冲突会记录新的冲突
+        if let Some(rid) = resolves {
+            state.remove_conflict(rid)?;
+        }
+        op_id
+    };
+
+    sync::flush_pending_ops_locked(app_state, connection).await?;
+
+    let state = app_state.session(&connection.session_id)?;
+    let status = state.sync_status()?;
+    match status.operations.iter().find(|op| op.id == op_id) {
+        None => Ok(tr(Message::WriteSucceeded(action))),
+        Some(op) if op.failed => {
+            let reason = op.last_error.as_deref().unwrap_or_default();
+            let message = tr(Message::WriteFailed(action, reason));
+            // 修改冲突时前端需要引导用户处理冲突，与其他拒绝区分
+            if state.get_conflict(op.rid)?.is_some() {
+                Err(DurianError::conflict(message))
+            } else {
+                Err(DurianError::api(CODE_FAILED, message))
+            }
+        }
+        Some(_) => Ok(tr(Message::WriteQueued(action))),
     }
 }
 
+/// 回放离线写入队列
+///
+/// 前端在网络恢复时调用
+#[tauri::command]
+pub async fn sync_pending_ops(app_state: State<'_, AppState>) -> DurianResult<FlushReport> {
+    let connection = app_state.connection()?;
+    sync::flush_pending_ops(&app_state, &connection).await
+}
+
+/// 获取离线写入队列的状态（等待同步和被服务器拒绝的操作）
+#[tauri::command]
+pub fn get_sync_status(app_state: State<'_, AppState>) -> DurianResult<SyncStatus> {
+    let state = app_state.get()?;
+    state.sync_status()
+}
+
+/// 清除被服务器拒绝的写入操作
+#[tauri::command]
+pub fn clear_sync_failures(app_state: State<'_, AppState>) -> DurianResult<()> {
+    let state = app_state.get()?;
+    state.clear_failed_ops()
+}
+
+/// 列出等待处理的修改冲突（本地版本和服务器版本）
+#[tauri::command]
+pub fn list_conflicts(app_state: State<'_, AppState>) -> DurianResult<Vec<AccountConflict>> {
+    let state = app_state.get()?;
+    state.list_conflicts()
+}
+
+/// 处理修改冲突
+///
+/// - `local`：基于服务器版本的修订号重新提交本地版本，覆盖服务器版本
+/// - `server`：放弃本地修改（本地缓存中已是服务器版本）
+/// - `keep_both`：服务器版本保留在原记录，本地版本作为新记录插入
+#[tauri::command]
+pub async fn resolve_conflict(
+    app_state: State<'_, AppState>,
+    rid: i64,
+    choice: ConflictChoice,
+) -> DurianResult<String> {
+    let connection = app_state.connection()?;
+    let (kind, action) = match choice {
+        ConflictChoice::Server => {
+            let state = app_state.session(&connection.session_id)?;
+            if !state.remove_conflict(rid)? {
+                return Err(DurianError::validation(tr(Message::NoConflict)));
+            }
+            return Ok(tr(Message::ServerVersionKept));
+        }
+        ConflictChoice::Local => (PendingOpKind::Update, WriteAction::KeepLocal),
+        ConflictChoice::KeepBoth => (PendingOpKind::Insert, WriteAction::KeepBoth),
+    };
+
+    queue_write(&app_state, &connection, kind, action, Some(rid), |state| {
+        let AccountConflict { local, server, .. } = state
+            .get_conflict(rid)?
+            .ok_or_else(|| DurianError::validation(tr(Message::NoConflict)))?;
+
+        if choice == ConflictChoice::Local {
+            let item =
+                seal_account_fields(state, rid, &local.website, &local.account, &local.password)?
+                    .with_revision(server.revision, 0);
+            let record = local.with_revision(server.revision, server.updated_at);
+            Ok((item, Some(record)))
+        } else {
+            let item =
+                seal_account_fields(state, 0, &local.website, &local.account, &local.password)?;
+            let record = AccountRecord::new(
+                0,
+                local.username,
+                local.website,
+                local.account,
+                local.password,
+            );
+            Ok((item, Some(record)))
+        }
+    })
+    .await
+}
+
+/// 校验本地缓存，重新拉取与服务器不一致的分桶
+///
+/// 仍有离线写入未能发送时返回错误
+#[tauri::command]
+pub async fn verify_cache(app_state: State<'_, AppState>) -> DurianResult<VerifyReport> {
+    let connection = app_state.connection()?;
+    verify::verify_cache(&app_state, &connection).await
+}
+
+/// 取消当前会话所有进行中的网络请求
+///
+/// 前端离开页面时调用，被取消的命令返回“请求已取消”错误。
+/// 离线写入队列的回放和后台同步使用独立的客户端（见 [`crate::state::Connection::background`]），
+/// 不会被取消，已加入队列的写入总会发送到服务器
+#[tauri::command]
+pub fn cancel_requests(app_state: State<'_, AppState>) -> DurianResult<()> {
+    let state = app_state.get()?;
+    state.api.cancel_pending();
+    Ok(())
+}
+
 // ============================================
 // 加密解密命令
 // ============================================
 
 /// 加密消息
 #[tauri::command]
-pub fn encrypt(message: String) -> Result<String, String> {
+pub fn encrypt(app_state: State<'_, AppState>, message: SecretString) -> DurianResult<String> {
     if message.is_empty() {
-        return Err(DurianError::validation("加密内容不能为空").to_string());
+        return Err(DurianError::validation(tr(Message::EmptyEncryptInput)));
     }
-    let state = state::get_state().map_err(|e| e.to_string())?;
-    encrypt_message(&message, &state.core_password).map_err(|e| e.to_string())
+    let state = app_state.get()?;
+    let vault_key = state.vault_key()?;
+    encrypt_message(message.expose(), vault_key)
 }
 
 /// 解密消息
 #[tauri::command]
-pub fn decrypt(message: String) -> Result<String, String> {
+pub fn decrypt(app_state: State<'_, AppState>, message: String) -> DurianResult<String> {
     if message.is_empty() {
-        return Err(DurianError::validation("解密内容不能为空").to_string());
+        return Err(DurianError::validation(tr(Message::EmptyDecryptInput)));
     }
-    let state = state::get_state().map_err(|e| e.to_string())?;
-    decrypt_message(&message, &state.core_password).map_err(|e| e.to_string())
+    let state = app_state.get()?;
+    let vault_key = state.vault_key()?;
+    decrypt_message(&message, vault_key)
 }
 
 /// 批量解密消息
 ///
-/// 提高多个密码解密的效率
+/// 所有消息共用会话保险库密钥，无需逐条派生密钥
 #[tauri::command]
-pub fn decrypt_batch(messages: Vec<String>) -> Result<Vec<String>, String> {
-    let state = state::get_state().map_err(|e| e.to_string())?;
-    
+pub fn decrypt_batch(
+    app_state: State<'_, AppState>,
+    messages: Vec<String>,
+) -> DurianResult<Vec<String>> {
+    let state = app_state.get()?;
+    let vault_key = state.vault_key()?;
+
     messages
         .iter()
         .map(|msg| {
             if msg.is_empty() {
                 Ok(String::new())
             } else {
-                decrypt_message(msg, &state.core_password).map_err(|e| e.to_string())
+                decrypt_message(msg, vault_key)
             }
         })
         .collect()
@@ -322,33 +1016,26 @@ pub fn decrypt_batch(messages: Vec<String>) -> Result<Vec<String>, String> {
 // ============================================
 
 /// 保存查询缓存
+///
+/// `accounts` 为服务器返回的账户（启用全字段加密时网站和账户为密文）
 #[tauri::command]
 pub fn save_query_cache(
-    pull_mode: String,
+    app_state: State<'_, AppState>,
+    pull_mode: PullMode,
     update_time: i64,
-    accounts_json: String,
-) -> Result<(), String> {
+    accounts: Vec<AccountItem>,
+) -> DurianResult<()> {
     // 输入验证
-    validate_not_empty(&pull_mode, "pull_mode")?;
     if update_time < 0 {
-        return Err(DurianError::validation("无效的更新时间").to_string());
+        return Err(DurianError::validation(tr(Message::InvalidUpdateTime)));
     }
 
-    let state = state::get_state().map_err(|e| e.to_string())?;
-
-    let temp_accounts: Vec<TempAccountRecord> = serde_json::from_str(&accounts_json)
-        .map_err(|e| format!("解析账户数据失败: {}", e))?;
+    let state = app_state.get()?;
 
-    let accounts: Vec<AccountRecord> = temp_accounts
+    let accounts: Vec<AccountRecord> = accounts
         .into_iter()
-        .map(|temp| AccountRecord::new(
-            temp.rid,
-            state.username.clone(),
-            temp.website,
-            temp.account,
-            temp.password,
-        ))
-        .collect();
+        .map(|item| sync::open_server_item(&state, &item))
+        .collect::<Result<_, _>>()?;
 
     let cache_data = CacheData::new(
         state.username.clone(),
@@ -356,37 +1043,32 @@ pub fn save_query_cache(
         accounts,
     );
 
-    state
-        .save_cache_data(&cache_data, &pull_mode)
-        .map_err(|e| e.to_string())?;
+    state.save_cache_data(&cache_data, pull_mode)?;
 
     Ok(())
 }
 
 /// 加载查询缓存
+///
+/// 没有缓存时返回 `None`
 #[tauri::command]
-pub fn load_query_cache() -> Result<String, String> {
-    let state = state::get_state().map_err(|e| e.to_string())?;
-
-    match state.load_cache_data().map_err(|e| e.to_string())? {
-        Some(cache_data) => serde_json::to_string(&cache_data)
-            .map_err(|e| format!("序列化失败: {}", e)),
-        None => Ok("{}".to_string()),
-    }
+pub fn load_query_cache(app_state: State<'_, AppState>) -> DurianResult<Option<CacheData>> {
+    let state = app_state.get()?;
+    state.load_cache_data()
 }
 
 /// 获取最后更新时间
 #[tauri::command]
-pub fn get_last_update_time() -> Result<i64, String> {
-    let state = state::get_state().map_err(|e| e.to_string())?;
-    state.get_last_update_time().map_err(|e| e.to_string())
+pub fn get_last_update_time(app_state: State<'_, AppState>) -> DurianResult<i64> {
+    let state = app_state.get()?;
+    state.get_last_update_time()
 }
 
 /// 清除用户缓存
 #[tauri::command]
-pub fn clear_cache() -> Result<(), String> {
-    let state = state::get_state().map_err(|e| e.to_string())?;
-    state.clear_cache().map_err(|e| e.to_string())
+pub fn clear_cache(app_state: State<'_, AppState>) -> DurianResult<()> {
+    let state = app_state.get()?;
+    state.clear_cache()
 }
 
 // ============================================
@@ -395,20 +1077,57 @@ pub fn clear_cache() -> Result<(), String> {
 
 /// 获取当前用户名
 #[tauri::command]
-pub fn get_username() -> Result<String, String> {
-    state::get_username().map_err(|e| e.to_string())
+pub fn get_username(app_state: State<'_, AppState>) -> DurianResult<String> {
+    app_state.username()
 }
 
-/// 获取认证令牌
+/// 检查状态是否已初始化
 #[tauri::command]
-pub fn get_token() -> Result<String, String> {
-    state::get_token().map_err(|e| e.to_string())
+pub fn is_logged_in(app_state: State<'_, AppState>) -> bool {
+    app_state.is_initialized()
 }
 
-/// 检查状态是否已初始化
-#[tauri::command]
-pub fn is_logged_in() -> bool {
-    state::is_state_initialized()
+// ============================================
+// 全字段加密辅助函数
+// ============================================
+
+/// 构建要写入服务器的账户项，全字段加密开启时加密网站和账户字段并在记录上标记
+fn seal_account_fields(
+    state: &DurianState,
+    rid: i64,
+    website: &str,
+    account: &str,
+    encrypted_password: &str,
+) -> DurianResult<AccountItem> {
+    if !state.full_field_encryption()? {
+        return Ok(AccountItem::new(
+            rid,
+            website.to_string(),
+            account.to_string(),
+            encrypted_password.to_string(),
+        ));
+    }
+
+    let vault_key = state.vault_key()?;
+    Ok(AccountItem::new(
+        rid,
+        encrypt_message(website, vault_key)?,
+        encrypt_message(account, vault_key)?,
+        encrypted_password.to_string(),
+    )
+    .with_fields_encrypted(true))
+}
+
+/// 加密要写入服务器的账户（密码，以及全字段加密时的网站和账户）
+fn seal_account(
+    state: &DurianState,
+    rid: i64,
+    website: &str,
+    account: &str,
+    password: &SecretString,
+) -> DurianResult<AccountItem> {
+    let encrypted_password = encrypt_message(password.expose(), state.vault_key()?)?;
+    seal_account_fields(state, rid, website, account, &encrypted_password)
 }
 
 // ============================================
@@ -416,19 +1135,1220 @@ pub fn is_logged_in() -> bool {
 // ============================================
 
 /// 验证字符串不为空
-fn validate_not_empty(value: &str, field_name: &str) -> Result<(), String> {
+fn validate_not_empty(value: &str, field: Field) -> DurianResult<()> {
     if value.trim().is_empty() {
-        Err(DurianError::validation(format!("{}不能为空", field_name)).to_string())
+        Err(DurianError::validation(tr(Message::Required(field))))
     } else {
         Ok(())
     }
 }
 
 /// 验证字符串最小长度
-fn validate_min_length(value: &str, min_len: usize, field_name: &str) -> Result<(), String> {
+fn validate_min_length(value: &str, min_len: usize, field: Field) -> DurianResult<()> {
     if value.len() < min_len {
-        Err(DurianError::validation(format!("{}长度不能少于{}个字符", field_name, min_len)).to_string())
+        let message = tr(Message::MinLength(field, min_len));
+        Err(DurianError::validation(message))
     } else {
         Ok(())
     }
 }
+
+// ============================================
+// 单元测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::VaultKey;
+    use crate::database;
+    use crate::error::ErrorKind;
+    use crate::fake_api::FakeServer;
+    use crate::models::SyncChanges;
+    use tauri::Manager;
+
+    /// 创建托管了独立 AppState 的测试应用
+    fn mock_app() -> tauri::App<tauri::test::MockRuntime> {
+        let app = tauri::test::mock_app();
+        app.manage(AppState::new());
+        app
+    }
+
+    /// 直接使用派生密钥登录测试用户（不访问网络）
+    fn sign_in(app: &tauri::App<tauri::test::MockRuntime>, file: &tempfile::NamedTempFile) {
+        database::init_database(file.path()).unwrap();
+        let vault_key =
+            VaultKey::derive("core_password", &KdfParams::legacy_vault("test_user")).unwrap();
+        let durian_state = DurianState::with_vault_key(
+            "test_user".to_string(),
+            vault_key,
+            SecretString::from("token"),
+            file.path().to_path_buf(),
+            "http://localhost".to_string(),
+            Arc::new(FakeServer::new()),
+        )
+        .unwrap();
+        app.state::<AppState>().set(durian_state).unwrap();
+    }
+
+    /// 通过模拟服务器注册并登录，返回登录令牌
+    async fn sign_in_with(
+        app_state: &AppState,
+        server: Arc<FakeServer>,
+        file: &tempfile::NamedTempFile,
+        username: &str,
+    ) -> String {
+        let password = SecretString::from("password");
+        let core_password = SecretString::from("core_password");
+        register_with(server.as_ref(), username, &password, &core_password)
+            .await
+            .unwrap();
+        log_in_with(app_state, server, file, username).await
+    }
+
+    /// 以已注册的用户登录（模拟另一台设备）
+    async fn log_in_with(
+        app_state: &AppState,
+        server: Arc<FakeServer>,
+        file: &tempfile::NamedTempFile,
+        username: &str,
+    ) -> String {
+        login_with(
+            app_state,
+            server,
+            file.path().to_path_buf(),
+            "http://fake".to_string(),
+            username.to_string(),
+            SecretString::from("password"),
+            SecretString::from("core_password"),
+        )
+        .await
+        .unwrap();
+        app_state.token().unwrap().expose().to_string()
+    }
+
+    /// 按网站排序的缓存记录（网站、账户、明文密码）
+    fn snapshot(
+        app_state: &State<'_, AppState>,
+        cache: &CacheData,
+    ) -> Vec<(String, String, String)> {
+        let mut records: Vec<_> = cache
+            .accounts
+            .iter()
+            .map(|record| {
+                let password = decrypt(app_state.clone(), record.password.clone()).unwrap();
+                (record.website.clone(), record.account.clone(), password)
+            })
+            .collect();
+        records.sort();
+        records
+    }
+
+    /// 强制从服务器同步并返回缓存
+    async fn sync(app_state: &State<'_, AppState>) -> CacheData {
+        query_accounts(app_state.clone(), true).await.unwrap()
+    }
+
+    /// 修改 a.com 记录的账户（密码与账户相同）
+    async fn edit_account(
+        app_state: &State<'_, AppState>,
+        rid: i64,
+        account: &str,
+    ) -> DurianResult<String> {
+        update_account(
+            app_state.clone(),
+            rid,
+            "a.com".to_string(),
+            account.to_string(),
+            SecretString::from(account),
+        )
+        .await
+    }
+
+    #[test]
+    fn test_commands_require_login() {
+        let app = mock_app();
+        let app_state = app.state::<AppState>();
+
+        assert!(!is_logged_in(app_state.clone()));
+        assert!(get_username(app_state.clone()).is_err());
+        assert!(lock(app_state.clone()).is_err());
+        assert!(encrypt(app_state, SecretString::from("secret")).is_err());
+    }
+
+    #[test]
+    fn test_encrypt_and_decrypt() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let app = mock_app();
+        sign_in(&app, &file);
+        let app_state = app.state::<AppState>();
+
+        assert_eq!(get_username(app_state.clone()).unwrap(), "test_user");
+        let encrypted = encrypt(app_state.clone(), SecretString::from("secret")).unwrap();
+        assert_eq!(decrypt(app_state.clone(), encrypted).unwrap(), "secret");
+
+        logout(app_state.clone()).unwrap();
+        assert!(!is_logged_in(app_state));
+    }
+
+    #[test]
+    fn test_query_cache_round_trip() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let app = mock_app();
+        sign_in(&app, &file);
+        let app_state = app.state::<AppState>();
+
+        assert!(load_query_cache(app_state.clone()).unwrap().is_none());
+
+        let password = encrypt(app_state.clone(), SecretString::from("secret")).unwrap();
+        let item =
+            AccountItem::new(1, "a.com".into(), "alice".into(), password).with_revision(3, 100);
+        save_query_cache(app_state.clone(), PullMode::PullAll, 1000, vec![item]).unwrap();
+
+        let cache = load_query_cache(app_state.clone()).unwrap().unwrap();
+        assert_eq!(cache.update_time, 1000);
+        assert_eq!(cache.accounts.len(), 1);
+        assert_eq!(cache.accounts[0].website, "a.com");
+        assert_eq!(cache.accounts[0].revision, 3);
+        assert_eq!(
+            snapshot(&app_state, &cache),
+            vec![(
+                "a.com".to_string(),
+                "alice".to_string(),
+                "secret".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_lock_commands() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let app = mock_app();
+        sign_in(&app, &file);
+        let app_state = app.state::<AppState>();
+        let handle = app.handle().clone();
+
+        lock(app_state.clone()).unwrap();
+        assert!(is_locked(app_state.clone()).unwrap());
+        assert!(encrypt(app_state.clone(), SecretString::from("secret")).is_err());
+
+        let wrong = SecretString::from("wrong_password");
+        let result = unlock(handle.clone(), app_state.clone(), wrong);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidCredentials);
+        unlock(
+            handle,
+            app_state.clone(),
+            SecretString::from("core_password"),
+        )
+        .unwrap();
+        assert!(!is_locked(app_state).unwrap());
+    }
+
+    #[test]
+    fn test_sync_worker_follows_lock_state() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let app = mock_app();
+        sign_in(&app, &file);
+        let app_state = app.state::<AppState>();
+        let handle = app.handle().clone();
+        let syncing = || app_state.get().unwrap().is_syncing_in_background();
+
+        // 解锁后启动，锁定时停止
+        lock(app_state.clone()).unwrap();
+        unlock(
+            handle.clone(),
+            app_state.clone(),
+            SecretString::from("core_password"),
+        )
+        .unwrap();
+        assert!(syncing());
+        lock(app_state.clone()).unwrap();
+        assert!(!syncing());
+        unlock(
+            handle.clone(),
+            app_state.clone(),
+            SecretString::from("core_password"),
+        )
+        .unwrap();
+
+        // 间隔为 0 时停止，设置会保存
+        assert!(set_sync_settings(handle.clone(), app_state.clone(), 1).is_err());
+        set_sync_settings(handle.clone(), app_state.clone(), 0).unwrap();
+        assert!(!syncing());
+        assert_eq!(
+            get_sync_settings(app_state.clone()).unwrap().interval_secs,
+            0
+        );
+        set_sync_settings(handle, app_state.clone(), 60).unwrap();
+        assert!(syncing());
+
+        logout(app_state.clone()).unwrap();
+        assert!(!is_logged_in(app_state.clone()));
+    }
+
+    #[test]
+    fn test_apps_are_independent() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let app = mock_app();
+        let other = mock_app();
+        sign_in(&app, &file);
+
+        assert!(is_logged_in(app.state::<AppState>()));
+        assert!(!is_logged_in(other.state::<AppState>()));
+    }
+
+    #[test]
+    fn test_switch_session() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let app = mock_app();
+        sign_in(&app, &file);
+        let app_state = app.state::<AppState>();
+
+        assert!(switch_session(app_state.clone(), "nobody".to_string()).is_err());
+        assert!(switch_session(app_state.clone(), "test_user".to_string()).is_err());
+
+        let sessions = list_sessions(app_state.clone()).unwrap();
+        switch_session(app_state.clone(), sessions[0].id.clone()).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert!(sessions[0].active);
+    }
+
+    #[test]
+    fn test_login_query_insert_and_incremental_sync() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let app = mock_app();
+        let server = Arc::new(FakeServer::new());
+        let app_state = app.state::<AppState>();
+
+        tauri::async_runtime::block_on(async {
+            let token = sign_in_with(&app_state, server.clone(), &file, "alice").await;
+            assert!(verify(app_state.clone()).await.unwrap());
+            assert_eq!(get_username(app_state.clone()).unwrap(), "alice");
+
+            // 首次同步：空保险库
+            let cache = sync(&app_state).await;
+            assert!(cache.accounts.is_empty());
+
+            insert_account(
+                app_state.clone(),
+                "example.com".to_string(),
+                "alice".to_string(),
+                SecretString::from("secret"),
+            )
+            .await
+            .unwrap();
+            let cache = sync(&app_state).await;
+            assert_eq!(cache.accounts.len(), 1);
+            let record = &cache.accounts[0];
+            assert_eq!(record.website, "example.com");
+            assert_eq!(
+                decrypt(app_state.clone(), record.password.clone()).unwrap(),
+                "secret"
+            );
+
+            // 其他设备写入的记录通过增量同步合并到缓存
+            let other_device = server.issue_token("alice").unwrap();
+            assert_ne!(other_device, token);
+            let password = encrypt(app_state.clone(), SecretString::from("other")).unwrap();
+            let item = AccountItem::new(0, "other.com".into(), "bob".into(), password);
+            server.insert_account(&other_device, &item, None).await.unwrap();
+            let cache = sync(&app_state).await;
+            assert_eq!(cache.accounts.len(), 2);
+
+            // 没有变化时缓存保持不变
+            let unchanged = sync(&app_state).await;
+            assert_eq!(unchanged.accounts.len(), 2);
+            assert!(unchanged.update_time >= cache.update_time);
+
+            // 未强制刷新时直接使用缓存
+            let cached = query_accounts(app_state.clone(), false).await.unwrap();
+            assert_eq!(cached.accounts.len(), 2);
+
+            // 更新和删除
+            let rid = cache
+                .accounts
+                .iter()
+                .find(|record| record.website == "other.com")
+                .unwrap()
+                .rid;
+            update_account(
+                app_state.clone(),
+                rid,
+                "other.com".to_string(),
+                "carol".to_string(),
+                SecretString::from("changed"),
+            )
+            .await
+            .unwrap();
+            let cache = sync(&app_state).await;
+            let record = cache
+                .accounts
+                .iter()
+                .find(|record| record.rid == rid)
+                .unwrap();
+            assert_eq!(record.account, "carol");
+
+            delete_account(app_state.clone(), rid).await.unwrap();
+            let cache = sync(&app_state).await;
+            assert_eq!(cache.accounts.len(), 1);
+            assert_eq!(cache.accounts[0].website, "example.com");
+        });
+    }
+
+    #[test]
+    fn test_two_devices_converge() {
+        let server = Arc::new(FakeServer::new());
+        let (file_a, file_b) = (
+            tempfile::NamedTempFile::new().unwrap(),
+            tempfile::NamedTempFile::new().unwrap(),
+        );
+        let (app_a, app_b) = (mock_app(), mock_app());
+        let (device_a, device_b) = (app_a.state::<AppState>(), app_b.state::<AppState>());
+
+        tauri::async_runtime::block_on(async {
+            sign_in_with(&device_a, server.clone(), &file_a, "alice").await;
+            log_in_with(&device_b, server.clone(), &file_b, "alice").await;
+
+            // A 插入两条，B 同步后看到两条
+            for website in ["a.com", "b.com"] {
+                insert_account(
+                    device_a.clone(),
+                    website.to_string(),
+                    "alice".to_string(),
+                    SecretString::from("secret"),
+                )
+                .await
+                .unwrap();
+            }
+            let cache_a = sync(&device_a).await;
+            let cache_b = sync(&device_b).await;
+            assert_eq!(cache_b.accounts.len(), 2);
+            let rid = |cache: &CacheData, website: &str| {
+                cache
+                    .accounts
+                    .iter()
+                    .find(|record| record.website == website)
+                    .unwrap()
+                    .rid
+            };
+            let (rid_a, rid_b) = (rid(&cache_b, "a.com"), rid(&cache_b, "b.com"));
+
+            // B 删除 a.com 并修改 b.com，A 同时插入 c.com
+            delete_account(device_b.clone(), rid_a).await.unwrap();
+            update_account(
+                device_b.clone(),
+                rid_b,
+                "b.com".to_string(),
+                "bob".to_string(),
+                SecretString::from("changed"),
+            )
+            .await
+            .unwrap();
+            insert_account(
+                device_a.clone(),
+                "c.com".to_string(),
+                "carol".to_string(),
+                SecretString::from("new"),
+            )
+            .await
+            .unwrap();
+
+            // A 的增量同步应用 B 的删除和修改
+            let synced_a = sync(&device_a).await;
+            assert!(synced_a.update_time > cache_a.update_time);
+            let expected = vec![
+                (
+                    "b.com".to_string(),
+                    "bob".to_string(),
+                    "changed".to_string(),
+                ),
+                ("c.com".to_string(), "carol".to_string(), "new".to_string()),
+            ];
+            assert_eq!(snapshot(&device_a, &synced_a), expected);
+
+            // A 删除 c.com 后 B 同步，两台设备收敛
+            delete_account(device_a.clone(), rid(&synced_a, "c.com"))
+                .await
+                .unwrap();
+            let synced_a = sync(&device_a).await;
+            let synced_b = sync(&device_b).await;
+            assert_eq!(snapshot(&device_a, &synced_a), expected[..1]);
+            assert_eq!(snapshot(&device_b, &synced_b), expected[..1]);
+
+            // 删除已被另一台设备删除的记录视为成功，不留下失败的操作
+            delete_account(device_a.clone(), rid_a).await.unwrap();
+            let status = get_sync_status(device_a.clone()).unwrap();
+            assert_eq!((status.pending, status.failed), (0, 0));
+            assert_eq!(sync(&device_a).await.accounts.len(), 1);
+
+            // 本地缓存与服务器全量数据一致
+            let cached = query_accounts(device_b.clone(), false).await.unwrap();
+            assert_eq!(snapshot(&device_b, &cached), expected[..1]);
+        });
+    }
+
+    #[test]
+    fn test_verify_cache_repairs_drift() {
+        let server = Arc::new(FakeServer::new());
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let app = mock_app();
+        let app_state = app.state::<AppState>();
+
+        tauri::async_runtime::block_on(async {
+            sign_in_with(&app_state, server.clone(), &file, "alice").await;
+            for website in ["a.com", "b.com", "c.com"] {
+                insert_account(
+                    app_state.clone(),
+                    website.to_string(),
+                    "alice".to_string(),
+                    SecretString::from("secret"),
+                )
+                .await
+                .unwrap();
+            }
+            let expected = snapshot(&app_state, &sync(&app_state).await);
+
+            let report = verify_cache(app_state.clone()).await.unwrap();
+            assert_eq!(report.mismatched, 0);
+            assert_eq!(report.changes, SyncChanges::default());
+
+            // 模拟缓存损坏：丢失一条记录、多出一条记录、修订号不一致
+            let cache = load_query_cache(app_state.clone()).unwrap().unwrap();
+            let conn = rusqlite::Connection::open(file.path()).unwrap();
+            conn.execute(
+                "DELETE FROM accounts WHERE rid = ?1",
+                [cache.accounts[0].rid],
+            )
+            .unwrap();
+            conn.execute(
+                "UPDATE accounts SET revision = 5 WHERE rid = ?1",
+                [cache.accounts[1].rid],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO accounts
+                 SELECT 999, username, website, account, password, website_index,
+                        encrypted, 1, 0
+                 FROM accounts WHERE rid = ?1",
+                [cache.accounts[2].rid],
+            )
+            .unwrap();
+
+            let report = verify_cache(app_state.clone()).await.unwrap();
+            assert!(report.mismatched > 0);
+            assert!(report.mismatched < report.buckets);
+            assert_eq!(
+                report.changes,
+                SyncChanges {
+                    added: 1,
+                    updated: 1,
+                    deleted: 1
+                }
+            );
+            let cache = load_query_cache(app_state.clone()).unwrap().unwrap();
+            assert_eq!(snapshot(&app_state, &cache), expected);
+
+            // 有离线写入未能发送时拒绝校验
+            server.set_offline(true);
+            edit_account(&app_state, cache.accounts[0].rid, "bob")
+                .await
+                .unwrap();
+            assert!(verify_cache(app_state.clone()).await.is_err());
+        });
+    }
+
+    #[test]
+    fn test_offline_writes_are_replayed() {
+        let server = Arc::new(FakeServer::new());
+        let (file_a, file_b) = (
+            tempfile::NamedTempFile::new().unwrap(),
+            tempfile::NamedTempFile::new().unwrap(),
+        );
+        let (app_a, app_b) = (mock_app(), mock_app());
+        let (device_a, device_b) = (app_a.state::<AppState>(), app_b.state::<AppState>());
+
+        tauri::async_runtime::block_on(async {
+            sign_in_with(&device_a, server.clone(), &file_a, "alice").await;
+            log_in_with(&device_b, server.clone(), &file_b, "alice").await;
+            insert_account(
+                device_a.clone(),
+                "a.com".to_string(),
+                "alice".to_string(),
+                SecretString::from("secret"),
+            )
+            .await
+            .unwrap();
+            let rid = sync(&device_a).await.accounts[0].rid;
+
+            // 离线时写入仍然成功，并立即反映在本地缓存中
+            server.set_offline(true);
+            let message = insert_account(
+                device_a.clone(),
+                "b.com".to_string(),
+                "bob".to_string(),
+                SecretString::from("offline"),
+            )
+            .await
+            .unwrap();
+            assert!(message.contains("网络恢复后同步"));
+            delete_account(device_a.clone(), rid).await.unwrap();
+
+            let cache = sync(&device_a).await;
+            assert_eq!(cache.accounts.len(), 1);
+            let temp_rid = cache.accounts[0].rid;
+            assert!(temp_rid < 0);
+
+            // 修改尚未同步的记录
+            update_account(
+                device_a.clone(),
+                temp_rid,
+                "b.com".to_string(),
+                "carol".to_string(),
+                SecretString::from("changed"),
+            )
+            .await
+            .unwrap();
+
+            let status = get_sync_status(device_a.clone()).unwrap();
+            assert_eq!(status.pending, 3);
+            assert_eq!(status.failed, 0);
+            assert!(status.operations[0].last_error.is_some());
+            let kinds: Vec<_> = status.operations.iter().map(|op| op.kind).collect();
+            assert_eq!(
+                kinds,
+                vec![
+                    PendingOpKind::Insert,
+                    PendingOpKind::Delete,
+                    PendingOpKind::Update
+                ]
+            );
+
+            // 网络恢复后按顺序回放
+            server.set_offline(false);
+            let report = sync_pending_ops(device_a.clone()).await.unwrap();
+            assert_eq!(
+                report,
+                FlushReport {
+                    synced: 3,
+                    rejected: 0,
+                    pending: 0,
+                    conflicts: 0
+                }
+            );
+            assert_eq!(get_sync_status(device_a.clone()).unwrap().pending, 0);
+
+            let expected = vec![(
+                "b.com".to_string(),
+                "carol".to_string(),
+                "changed".to_string(),
+            )];
+            let cache = sync(&device_a).await;
+            assert!(cache.accounts[0].rid > 0);
+            assert_eq!(snapshot(&device_a, &cache), expected);
+            let cache = sync(&device_b).await;
+            assert_eq!(snapshot(&device_b, &cache), expected);
+        });
+    }
+
+    #[test]
+    fn test_rejected_writes_are_reported() {
+        let server = Arc::new(FakeServer::new());
+        let (file_a, file_b) = (
+            tempfile::NamedTempFile::new().unwrap(),
+            tempfile::NamedTempFile::new().unwrap(),
+        );
+        let (app_a, app_b) = (mock_app(), mock_app());
+        let (device_a, device_b) = (app_a.state::<AppState>(), app_b.state::<AppState>());
+
+        tauri::async_runtime::block_on(async {
+            sign_in_with(&device_a, server.clone(), &file_a, "alice").await;
+            log_in_with(&device_b, server.clone(), &file_b, "alice").await;
+            insert_account(
+                device_a.clone(),
+                "a.com".to_string(),
+                "alice".to_string(),
+                SecretString::from("secret"),
+            )
+            .await
+            .unwrap();
+            let rid = sync(&device_a).await.accounts[0].rid;
+            sync(&device_b).await;
+
+            // A 离线修改记录期间 B 删除了它：回放时被服务器拒绝
+            server.set_offline(true);
+            update_account(
+                device_a.clone(),
+                rid,
+                "a.com".to_string(),
+                "changed".to_string(),
+                SecretString::from("secret"),
+            )
+            .await
+            .unwrap();
+            server.set_offline(false);
+            delete_account(device_b.clone(), rid).await.unwrap();
+
+            let report = sync_pending_ops(device_a.clone()).await.unwrap();
+            assert_eq!(report.rejected, 1);
+            let status = get_sync_status(device_a.clone()).unwrap();
+            assert_eq!((status.pending, status.failed), (0, 1));
+            assert_eq!(status.operations[0].rid, rid);
+            assert!(status.operations[0].last_error.is_some());
+
+            // 乐观修改被撤销：下次同步全量拉取服务器数据
+            assert!(sync(&device_a).await.accounts.is_empty());
+
+            // 本地缓存中没有的记录不能修改
+            let result = update_account(
+                device_a.clone(),
+                rid,
+                "a.com".to_string(),
+                "again".to_string(),
+                SecretString::from("secret"),
+            )
+            .await;
+            assert_eq!(result.unwrap_err().kind(), ErrorKind::Validation);
+
+            // 在线时被拒绝的写入直接返回错误
+            insert_account(
+                device_a.clone(),
+                "b.com".to_string(),
+                "alice".to_string(),
+                SecretString::from("secret"),
+            )
+            .await
+            .unwrap();
+            let rid = sync(&device_a).await.accounts[0].rid;
+            sync(&device_b).await;
+            delete_account(device_b.clone(), rid).await.unwrap();
+            let result = update_account(
+                device_a.clone(),
+                rid,
+                "b.com".to_string(),
+                "again".to_string(),
+                SecretString::from("secret"),
+            )
+            .await;
+            let err = result.unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::Rejected);
+            assert!(err.to_string().contains("更新失败"));
+            assert_eq!(get_sync_status(device_a.clone()).unwrap().failed, 2);
+
+            clear_sync_failures(device_a.clone()).unwrap();
+            assert!(get_sync_status(device_a.clone())
+                .unwrap()
+                .operations
+                .is_empty());
+        });
+    }
+
+    #[test]
+    fn test_pre_revision_cache_can_be_edited() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let app = mock_app();
+        let server = Arc::new(FakeServer::new());
+        let app_state = app.state::<AppState>();
+
+        tauri::async_runtime::block_on(async {
+            sign_in_with(&app_state, server.clone(), &file, "alice").await;
+            insert_account(
+                app_state.clone(),
+                "a.com".to_string(),
+                "alice".to_string(),
+                SecretString::from("secret"),
+            )
+            .await
+            .unwrap();
+            let rid = sync(&app_state).await.accounts[0].rid;
+            app_state.clear();
+
+            // 还原为 v3 之前的缓存：记录没有修订号，上次同步时间仍然有效
+            {
+                let conn = rusqlite::Connection::open(file.path()).unwrap();
+                conn.execute_batch(
+                    "ALTER TABLE accounts DROP COLUMN revision;
+                     ALTER TABLE accounts DROP COLUMN updated_at;
+                     ALTER TABLE pending_ops DROP COLUMN revision;
+                     ALTER TABLE pending_ops DROP COLUMN fields_encrypted;
+                     ALTER TABLE pending_ops DROP COLUMN op_id;
+                     ALTER TABLE vault_settings DROP COLUMN sync_interval_secs;
+                     ALTER TABLE vault_settings DROP COLUMN kdf_upgraded;
+                     PRAGMA user_version = 2;",
+                )
+                .unwrap();
+            }
+
+            // 升级后的首次同步全量拉取，补齐修订号后可以修改
+            log_in_with(&app_state, server.clone(), &file, "alice").await;
+            sync(&app_state).await;
+            edit_account(&app_state, rid, "changed").await.unwrap();
+            let cache = sync(&app_state).await;
+            assert_eq!(cache.accounts[0].account, "changed");
+        });
+    }
+
+    #[test]
+    fn test_concurrent_edits_conflict() {
+        let server = Arc::new(FakeServer::new());
+        let (file_a, file_b) = (
+            tempfile::NamedTempFile::new().unwrap(),
+            tempfile::NamedTempFile::new().unwrap(),
+        );
+        let (app_a, app_b) = (mock_app(), mock_app());
+        let (device_a, device_b) = (app_a.state::<AppState>(), app_b.state::<AppState>());
+        let record = |account: &str| {
+            (
+                "a.com".to_string(),
+                account.to_string(),
+                account.to_string(),
+            )
+        };
+
+        tauri::async_runtime::block_on(async {
+            sign_in_with(&device_a, server.clone(), &file_a, "alice").await;
+            log_in_with(&device_b, server.clone(), &file_b, "alice").await;
+            insert_account(
+                device_a.clone(),
+                "a.com".to_string(),
+                "alice".to_string(),
+                SecretString::from("secret"),
+            )
+            .await
+            .unwrap();
+            let rid = sync(&device_a).await.accounts[0].rid;
+            sync(&device_b).await;
+
+            // 两台设备基于同一修订号修改同一记录：后提交的修改产生冲突，两个版本都被保留
+            edit_account(&device_a, rid, "first_a").await.unwrap();
+            let result = edit_account(&device_b, rid, "first_b").await;
+            assert_eq!(result.unwrap_err().kind(), ErrorKind::Conflict);
+            let conflicts = list_conflicts(device_b.clone()).unwrap();
+            assert_eq!(conflicts.len(), 1);
+            assert_eq!(conflicts[0].local.account, "first_b");
+            assert_eq!(conflicts[0].server.account, "first_a");
+
+            // 本地缓存显示服务器版本；处理冲突前不能再次修改
+            let cached = query_accounts(device_b.clone(), false).await.unwrap();
+            assert_eq!(snapshot(&device_b, &cached), vec![record("first_a")]);
+            let result = edit_account(&device_b, rid, "other").await;
+            assert_eq!(result.unwrap_err().kind(), ErrorKind::Conflict);
+
+            // 保留本地版本：覆盖服务器版本
+            resolve_conflict(device_b.clone(), rid, ConflictChoice::Local)
+                .await
+                .unwrap();
+            assert!(list_conflicts(device_b.clone()).unwrap().is_empty());
+            let cache = sync(&device_a).await;
+            assert_eq!(snapshot(&device_a, &cache), vec![record("first_b")]);
+
+            // 采用服务器版本：放弃本地修改
+            edit_account(&device_a, rid, "second_a").await.unwrap();
+            assert!(edit_account(&device_b, rid, "second_b").await.is_err());
+            resolve_conflict(device_b.clone(), rid, ConflictChoice::Server)
+                .await
+                .unwrap();
+            assert!(get_sync_status(device_b.clone())
+                .unwrap()
+                .operations
+                .is_empty());
+            let cache = sync(&device_b).await;
+            assert_eq!(snapshot(&device_b, &cache), vec![record("second_a")]);
+
+            // 保留两个版本：本地版本作为新记录插入
+            edit_account(&device_a, rid, "third_a").await.unwrap();
+            assert!(edit_account(&device_b, rid, "third_b").await.is_err());
+            resolve_conflict(device_b.clone(), rid, ConflictChoice::KeepBoth)
+                .await
+                .unwrap();
+            let expected = vec![record("third_a"), record("third_b")];
+            let cache = sync(&device_a).await;
+            assert_eq!(snapshot(&device_a, &cache), expected);
+            let cache = sync(&device_b).await;
+            assert_eq!(snapshot(&device_b, &cache), expected);
+
+            let result = resolve_conflict(device_b.clone(), rid, ConflictChoice::Local).await;
+            assert!(result.is_err());
+        });
+    }
+
+    #[test]
+    fn test_login_failures() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let app = mock_app();
+        let server = Arc::new(FakeServer::new());
+        let app_state = app.state::<AppState>();
+
+        tauri::async_runtime::block_on(async {
+            let result = login_with(
+                &app_state,
+                server.clone(),
+                file.path().to_path_buf(),
+                "http://fake".to_string(),
+                "nobody".to_string(),
+                SecretString::from("password"),
+                SecretString::from("core_password"),
+            )
+            .await;
+            assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidCredentials);
+            assert!(!is_logged_in(app_state.clone()));
+
+            let password = SecretString::from("password");
+            register_with(server.as_ref(), "alice", &password, &password)
+                .await
+                .unwrap();
+            let result = register_with(server.as_ref(), "alice", &password, &password).await;
+            let err = result.unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::Rejected);
+            assert!(err.to_string().contains("用户已存在"));
+        });
+    }
+
+    #[test]
+    fn test_kdf_upgrade_and_downgrade() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let app = mock_app();
+        let app_state = app.state::<AppState>();
+
+        tauri::async_runtime::block_on(async {
+            let server = Arc::new(FakeServer::new());
+            server
+                .register("alice", "password", "core_password", &KdfSuite::legacy())
+                .await
+                .unwrap();
+
+            // 升级失败不影响登录，原因随登录结果返回
+            server.set_kdf_upgrade_rejected(true);
+            let result = login_with(
+                &app_state,
+                server.clone(),
+                file.path().to_path_buf(),
+                "http://fake".to_string(),
+                "alice".to_string(),
+                SecretString::from("password"),
+                SecretString::from("core_password"),
+            )
+            .await
+            .unwrap();
+            let warning = result.kdf_upgrade_error.unwrap();
+            assert_eq!(warning.kind, ErrorKind::Rejected);
+            assert!(warning.message.contains("暂不支持升级"));
+            assert_eq!(result.migration_error, None);
+            assert!(server.prelogin("alice").await.unwrap().uses_legacy());
+            server.set_kdf_upgrade_rejected(false);
+
+            // 旧版账户登录后透明升级
+            log_in_with(&app_state, server.clone(), &file, "alice").await;
+            assert!(!server.prelogin("alice").await.unwrap().uses_legacy());
+            let id = session_id("http://fake", "alice");
+            assert!(database::is_kdf_upgraded(file.path(), &id).unwrap());
+
+            // 升级不改变保险库密钥参数，旧账户继续使用旧版参数读取已有密文
+            assert_eq!(server.prelogin("alice").await.unwrap().vault, None);
+            let vault_kdf = app_state.session(&id).unwrap().vault_key().unwrap().kdf().clone();
+            assert_eq!(vault_kdf, KdfParams::legacy_vault("alice"));
+
+            // 服务器（或中间人）再返回旧版参数时拒绝登录
+            let downgraded = Arc::new(FakeServer::new());
+            downgraded
+                .register("alice", "password", "core_password", &KdfSuite::legacy())
+                .await
+                .unwrap();
+            let result = login_with(
+                &app_state,
+                downgraded,
+                file.path().to_path_buf(),
+                "http://fake".to_string(),
+                "alice".to_string(),
+                SecretString::from("password"),
+                SecretString::from("core_password"),
+            )
+            .await;
+            assert_eq!(result.unwrap_err().kind(), ErrorKind::Crypto);
+        });
+    }
+
+    #[test]
+    fn test_vault_key_uses_registered_params() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let app = mock_app();
+        let app_state = app.state::<AppState>();
+        let handle = app.handle().clone();
+
+        tauri::async_runtime::block_on(async {
+            let server = Arc::new(FakeServer::new());
+            sign_in_with(&app_state, server.clone(), &file, "alice").await;
+
+            // 新账户的保险库密钥使用注册时生成的随机盐值，而不是由用户名生成的盐值
+            let registered = server.prelogin("alice").await.unwrap().vault.unwrap();
+            let id = session_id("http://fake", "alice");
+            let vault_kdf = app_state.session(&id).unwrap().vault_key().unwrap().kdf().clone();
+            assert_eq!(vault_kdf, registered);
+            assert_ne!(vault_kdf.salt, KdfParams::legacy_vault("alice").salt);
+        });
+
+        // 锁定后使用同一参数重新解锁
+        lock(app_state.clone()).unwrap();
+        unlock(handle, app_state.clone(), SecretString::from("core_password")).unwrap();
+        assert!(!is_locked(app_state).unwrap());
+    }
+
+    #[test]
+    fn test_full_field_encryption_is_shared_and_marked() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let other_file = tempfile::NamedTempFile::new().unwrap();
+        let app = mock_app();
+        let other_app = mock_app();
+        let server = Arc::new(FakeServer::new());
+        let app_state = app.state::<AppState>();
+        let other_state = other_app.state::<AppState>();
+
+        tauri::async_runtime::block_on(async {
+            let token = sign_in_with(&app_state, server.clone(), &file, "alice").await;
+            insert_account(
+                app_state.clone(),
+                "a.com".to_string(),
+                "alice".to_string(),
+                SecretString::from("secret"),
+            )
+            .await
+            .unwrap();
+
+            // 开启后已有记录和新记录都带有加密标记
+            set_full_field_encryption(app_state.clone(), true).await.unwrap();
+            insert_account(
+                app_state.clone(),
+                "b.com".to_string(),
+                "bob".to_string(),
+                SecretString::from("secret"),
+            )
+            .await
+            .unwrap();
+            let accounts = server.query_accounts(&token, 0).await.unwrap().data.unwrap().accounts;
+            assert_eq!(accounts.len(), 2);
+            assert!(accounts.iter().all(|item| item.fields_encrypted));
+            assert!(accounts.iter().all(|item| item.website != "a.com" && item.website != "b.com"));
+
+            // 设置保存在服务器上，其他设备登录后同样生效
+            log_in_with(&other_state, server.clone(), &other_file, "alice").await;
+            assert!(get_full_field_encryption(other_state.clone()).unwrap());
+            let cache = sync(&other_state).await;
+            assert_eq!(snapshot(&other_state, &cache)[0].0, "a.com");
+
+            // 没有加密标记的字段即使形似密文也按明文显示
+            let lookalike = encrypt(app_state.clone(), SecretString::from("c.com")).unwrap();
+            let password = encrypt(app_state.clone(), SecretString::from("secret")).unwrap();
+            let item = AccountItem::new(0, lookalike.clone(), "carol".into(), password);
+            server.insert_account(&token, &item, None).await.unwrap();
+            let cache = sync(&app_state).await;
+            assert!(cache.accounts.iter().any(|record| record.website == lookalike));
+        });
+    }
+
+    #[test]
+    fn test_legacy_entries_are_migrated_on_login() {
+        use ciftl::crypter::{chacha20, StringCrypter, StringCrypterTrait};
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let app = mock_app();
+        let app_state = app.state::<AppState>();
+
+        tauri::async_runtime::block_on(async {
+            let server = Arc::new(FakeServer::new());
+            register_with(
+                server.as_ref(),
+                "alice",
+                &SecretString::from("password"),
+                &SecretString::from("core_password"),
+            )
+            .await
+            .unwrap();
+
+            // 旧版客户端写入的 ciftl 密文
+            let crypter = StringCrypter::<chacha20::ChaCha20CipherAlgorithm>::default();
+            let legacy = crypter.encrypt("legacy secret", "core_password").unwrap();
+            let token = server.issue_token("alice").unwrap();
+            let item = AccountItem::new(0, "a.com".into(), "alice".into(), legacy);
+            server.insert_account(&token, &item, None).await.unwrap();
+
+            log_in_with(&app_state, server.clone(), &file, "alice").await;
+
+            let accounts = server.query_accounts(&token, 0).await.unwrap().data.unwrap().accounts;
+            let password = &accounts[0].password;
+            assert!(crate::crypto::is_envelope(password));
+            assert_eq!(decrypt(app_state.clone(), password.clone()).unwrap(), "legacy secret");
+
+            // 无法迁移的记录不影响登录，原因随登录结果返回
+            let item = AccountItem::new(0, "b.com".into(), "bob".into(), "corrupted".into());
+            server.insert_account(&token, &item, None).await.unwrap();
+            let result = login_with(
+                &app_state,
+                server.clone(),
+                file.path().to_path_buf(),
+                "http://fake".to_string(),
+                "alice".to_string(),
+                SecretString::from("password"),
+                SecretString::from("core_password"),
+            )
+            .await
+            .unwrap();
+            assert_eq!(result.migration_error.unwrap().kind, ErrorKind::Crypto);
+            assert_eq!(result.kdf_upgrade_error, None);
+            assert!(is_logged_in(app_state.clone()));
+        });
+    }
+
+    #[test]
+    fn test_writes_wait_for_core_password_change() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let app = mock_app();
+        let app_state = app.state::<AppState>();
+
+        tauri::async_runtime::block_on(async {
+            // 未启用信封加密时修改核心密码需要重新加密每条记录
+            let server = Arc::new(FakeServer::new());
+            server.set_vault_keys_supported(false);
+            sign_in_with(&app_state, server.clone(), &file, "alice").await;
+            let insert = |website: &str| {
+                insert_account(
+                    app_state.clone(),
+                    website.to_string(),
+                    "alice".to_string(),
+                    SecretString::from(website),
+                )
+            };
+
+            // 还有未发送的写入时拒绝修改
+            server.set_offline(true);
+            insert("a.com").await.unwrap();
+            let result = change_core_password(
+                app_state.clone(),
+                SecretString::from("core_password"),
+                SecretString::from("new_core_password"),
+            )
+            .await;
+            assert_eq!(result.unwrap_err().kind(), ErrorKind::Conflict);
+            server.set_offline(false);
+
+            // 模拟正在进行的队列回放，使修改和插入都在等待写入队列锁
+            let connection = app_state.connection().unwrap();
+            let replay = connection.write_queue.lock().await;
+            let (changed, inserted, ()) = tokio::join!(
+                change_core_password(
+                    app_state.clone(),
+                    SecretString::from("core_password"),
+                    SecretString::from("new_core_password"),
+                ),
+                insert("b.com"),
+                async move {
+                    tokio::task::yield_now().await;
+                    drop(replay);
+                },
+            );
+            changed.unwrap();
+            inserted.unwrap();
+
+            // 修改期间插入的记录使用新密钥加密，所有记录都能解密
+            let token = server.issue_token("alice").unwrap();
+            let accounts = server.query_accounts(&token, 0).await.unwrap().data.unwrap().accounts;
+            assert_eq!(accounts.len(), 2);
+            for item in accounts {
+                let password = decrypt(app_state.clone(), item.password).unwrap();
+                assert_eq!(password, item.website);
+            }
+        });
+    }
+
+    #[test]
+    fn test_enabling_envelope_encryption_keeps_local_changes() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let app = mock_app();
+        let app_state = app.state::<AppState>();
+
+        tauri::async_runtime::block_on(async {
+            let server = Arc::new(FakeServer::new());
+            server.set_vault_keys_supported(false);
+            sign_in_with(&app_state, server.clone(), &file, "alice").await;
+            let insert = |website: &str| {
+                insert_account(
+                    app_state.clone(),
+                    website.to_string(),
+                    "alice".to_string(),
+                    SecretString::from(website),
+                )
+            };
+            insert("a.com").await.unwrap();
+            server.set_offline(true);
+            insert("b.com").await.unwrap();
+            server.set_offline(false);
+
+            // 服务器升级后重新登录时生成数据密钥，缓存和离线写入重新加密后保留
+            server.set_vault_keys_supported(true);
+            log_in_with(&app_state, server.clone(), &file, "alice").await;
+            assert!(app_state.get().unwrap().vault_key().unwrap().has_data_key());
+            assert_eq!(get_sync_status(app_state.clone()).unwrap().pending, 1);
+
+            let cache = sync(&app_state).await;
+            let expected = vec![
+                ("a.com".to_string(), "alice".to_string(), "a.com".to_string()),
+                ("b.com".to_string(), "alice".to_string(), "b.com".to_string()),
+            ];
+            assert_eq!(snapshot(&app_state, &cache), expected);
+        });
+    }
+
+    /// 提交的 TypeScript 类型与 Rust 类型一致
+    ///
+    /// 绑定在编译时读入，因此修改 Rust 类型后首次运行 `cargo test` 会失败；
+    /// 同一次运行已重新生成 `src/types/bindings`，提交生成的文件即可
+    #[test]
+    fn test_bindings_up_to_date() {
+        use crate::error::{ErrorDetails, ErrorKind, ErrorPayload};
+        use crate::i18n::Locale;
+        use crate::lock::LockSettings;
+        use crate::models::{PendingOpInfo, QueryResponseData};
+        use crate::settings::{ServerProfile, TimeoutOptions, TlsOptions};
+        use crate::state::SessionInfo;
+        use crate::sync_worker::{SyncEventPayload, SyncFinishedPayload, SyncSettings};
+        use ts_rs::TS;
+
+        macro_rules! assert_bindings {
+            ($($ty:ident),* $(,)?) => {$(
+                assert_eq!(
+                    include_str!(concat!("../../src/types/bindings/", stringify!($ty), ".ts")),
+                    $ty::export_to_string().unwrap(),
+                    "src/types/bindings/{}.ts 已过期，请提交重新生成的文件",
+                    stringify!($ty),
+                );
+            )*};
+        }
+
+        assert_bindings!(
+            AccountConflict,
+            AccountItem,
+            AccountRecord,
+            CacheData,
+            ConflictChoice,
+            ErrorDetails,
+            ErrorKind,
+            ErrorPayload,
+            FlushReport,
+            Locale,
+            LockSettings,
+            LoginResponseData,
+            PendingOpInfo,
+            PendingOpKind,
+            PullMode,
+            QueryResponseData,
+            ServerProfile,
+            SessionInfo,
+            SyncChanges,
+            SyncEventPayload,
+            SyncFinishedPayload,
+            SyncSettings,
+            SyncStatus,
+            TimeoutOptions,
+            TlsOptions,
+            VerifyReport,
+        );
+    }
+}
@@ -1,133 +1,771 @@
 //! 加密和密码哈希模块
 //!
-//! 提供密码哈希（PBKDF2）和消息加解密（ChaCha20）功能
+//! 提供旧版密码哈希（固定盐值 PBKDF2）、消息加解密和安全随机数功能
+//!
+//! 新的密码派生逻辑见 `kdf` 模块
+//!
+//! # 密文格式
+//! 新密文使用带认证的版本化信封（XChaCha20-Poly1305），以 `durian:` 为前缀：
+//!
+//! ```text
+//! durian:base64( version(1) | kdf_id(1) | salt_len(1) | salt | nonce(24) | ciphertext | tag(16) )
+//! ```
+//!
+//! 信封头部（version、kdf_id、salt）作为关联数据参与认证。
+//! 不带前缀的密文视为旧版 ciftl ChaCha20 格式，仍可解密，登录时由迁移流程重新加密为新格式
+//!
+//! # 会话密钥
+//! 所有加解密都使用 `VaultKey`：它在登录时由核心密码派生一次，
+//! 之后不再需要核心密码明文，且在释放时自动清零
+//!
+//! # 信封加密
+//! 记录使用随机生成的数据密钥加密，数据密钥再由核心密码派生的包装密钥加密（`WrappedKey`），
+//! 保存在服务器和本地缓存中。修改核心密码时只需重新包装数据密钥；
+//! 同一个数据密钥也可以由其他密钥（恢复密钥、其他设备）分别包装
+//!
+//! # 本地缓存加密
+//! 本地缓存中的网站和账户字段使用 `CacheCipher` 加密，密钥由会话保险库密钥经 HKDF 派生；
+//! 按网站查找时使用 HMAC-SHA256 盲索引，数据库中不出现任何明文
+
+use std::borrow::Cow;
 
-use ciftl::crypter::{chacha20, StringCrypter, StringCrypterTrait};
-use ring::pbkdf2;
-use std::num::NonZeroU32;
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use ciftl::crypter::chacha20::ChaCha20CipherAlgorithm;
+use ciftl::crypter::{CipherAlgorithmTrait, IVKeyNewTrait};
+use ciftl::encoding::base64::Base64Encoding;
+use ciftl::encoding::EncodingTrait;
+use ciftl::hash::crc::Crc32cHasher;
+use ciftl::hash::HasherTrait;
+use ring::digest;
+use ring::hkdf;
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use zeroize::Zeroizing;
 
 use crate::error::{DurianError, DurianResult};
+use crate::i18n::{tr, Field, Message};
+use crate::kdf::{KdfParams, KDF_OUTPUT_LEN};
+use crate::models::WrappedKey;
+use crate::secret::SecretBytes;
 
 // ============================================
-// 常量定义
+// 安全随机数
 // ============================================
 
-/// 登录密码哈希盐值
-const DURIAN_PASSWORD_SALT: &str = "durian.password";
-
-/// 核心密码哈希盐值
-const DURIAN_CORE_PASSWORD_SALT: &str = "durian.core.password";
-
-/// PBKDF2 迭代次数
-const PBKDF2_ITERATIONS: u32 = 100000;
+/// 生成指定长度的安全随机字节
+///
+/// # Returns
+/// 随机字节数组，或错误信息
+pub fn random_bytes<const N: usize>() -> DurianResult<[u8; N]> {
+    let mut bytes = [0u8; N];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| DurianError::crypto(tr(Message::RandomFailed)))?;
+    Ok(bytes)
+}
 
 // ============================================
-// 密码哈希功能
+// 旧版密码哈希功能
 // ============================================
 
-/// 使用 PBKDF2 进行密码哈希
-///
-/// # Arguments
-/// * `password` - 要哈希的密码
-/// * `salt` - 盐值
+/// 对登录密码进行旧版哈希（固定盐值 PBKDF2）
 ///
-/// # Returns
-/// 十六进制编码的哈希字符串
-fn hash_password_pbkdf2(password: &str, salt: &str) -> String {
-    let iterations = NonZeroU32::new(PBKDF2_ITERATIONS).unwrap();
-    let mut hash = [0u8; 32]; // SHA-256 输出 32 字节
-
-    pbkdf2::derive(
-        pbkdf2::PBKDF2_HMAC_SHA256,
-        iterations,
-        salt.as_bytes(),
-        password.as_bytes(),
-        &mut hash,
-    );
-
-    // 转换为十六进制字符串
-    hex::encode(hash)
-}
-
-/// 对登录密码进行哈希
+/// 仅用于兼容尚未升级 KDF 的账户
 ///
 /// # Arguments
 /// * `password` - 原始密码
 ///
 /// # Returns
 /// 哈希后的密码字符串
-pub fn hash_login_password(password: &str) -> String {
-    hash_password_pbkdf2(password, DURIAN_PASSWORD_SALT)
+pub fn hash_login_password(password: &str) -> DurianResult<String> {
+    KdfParams::legacy_login().hash_password(password)
 }
 
-/// 对核心密码进行哈希
+/// 对核心密码进行旧版哈希（固定盐值 PBKDF2）
+///
+/// 仅用于兼容尚未升级 KDF 的账户
 ///
 /// # Arguments
 /// * `password` - 原始核心密码
 ///
 /// # Returns
 /// 哈希后的核心密码字符串
-pub fn hash_core_password(password: &str) -> String {
-    hash_password_pbkdf2(password, DURIAN_CORE_PASSWORD_SALT)
+pub fn hash_core_password(password: &str) -> DurianResult<String> {
+    KdfParams::legacy_core().hash_password(password)
+}
+
+// ============================================
+// 会话保险库密钥
+// ============================================
+
+/// 会话保险库密钥
+///
+/// 在 `init_state` 时由核心密码派生一次，供本次会话的所有加解密使用。
+/// 密钥材料保存在 `SecretBytes` 中，释放时自动清零，且不实现 `Debug`/`Clone`，避免意外泄露或复制
+///
+/// 读取旧格式密文所需的旧版密钥只在登录时附加（[`VaultKey::with_legacy_key`]），
+/// 旧记录迁移完成后即被清除（[`VaultKey::forget_legacy_key`]），不会在整个会话中保留
+pub struct VaultKey {
+    /// 数据密钥（随机生成，由核心密码包装后保存），未启用信封加密时为 `None`
+    data_key: Option<SecretBytes<KDF_OUTPUT_LEN>>,
+    /// Argon2id 派生的密钥：未启用信封加密时用于加密新数据，否则仅用于读取迁移前的记录
+    derived_key: SecretBytes<KDF_OUTPUT_LEN>,
+    /// SHA-256(核心密码)，仅用于读取旧版 ciftl 密文和第一代信封，迁移完成后为 `None`
+    legacy_key: Option<SecretBytes<KDF_OUTPUT_LEN>>,
+    /// 派生 `derived_key` 使用的 KDF 参数（见 [`crate::kdf::KdfSuite::vault_params`]）
+    kdf: KdfParams,
+}
+
+impl VaultKey {
+    /// 从核心密码派生用户的保险库密钥
+    ///
+    /// # Arguments
+    /// * `core_password` - 核心密码
+    /// * `params` - 该账户的保险库密钥参数（见 [`crate::kdf::KdfSuite::vault_params`]）
+    pub fn derive(core_password: &str, params: &KdfParams) -> DurianResult<Self> {
+        if core_password.is_empty() {
+            return Err(DurianError::validation(tr(Message::Required(Field::CorePassword))));
+        }
+
+        let derived_key = SecretBytes::new(params.derive_key(core_password)?);
+
+        Ok(Self {
+            data_key: None,
+            derived_key,
+            legacy_key: None,
+            kdf: params.clone(),
+        })
+    }
+
+    /// 派生密钥使用的 KDF 参数
+    #[inline]
+    pub fn kdf(&self) -> &KdfParams {
+        &self.kdf
+    }
+
+    /// 附加读取旧格式密文所需的旧版密钥
+    ///
+    /// 只在需要迁移旧记录时调用（登录、修改核心密码），迁移后使用 [`VaultKey::forget_legacy_key`] 清除
+    ///
+    /// # Arguments
+    /// * `core_password` - 核心密码
+    pub fn with_legacy_key(mut self, core_password: &str) -> Self {
+        // 与 ciftl 相同的密钥生成方式：对密码做一次 SHA-256
+        let mut legacy_key = SecretBytes::zeroed();
+        let digest = digest::digest(&digest::SHA256, core_password.as_bytes());
+        legacy_key.expose_mut().copy_from_slice(digest.as_ref());
+        self.legacy_key = Some(legacy_key);
+        self
+    }
+
+    /// 清除旧版密钥（旧记录迁移完成后调用）
+    pub fn forget_legacy_key(&mut self) {
+        self.legacy_key = None;
+    }
+
+    /// 旧版密钥，已清除时返回错误
+    fn legacy_key(&self) -> DurianResult<&SecretBytes<KDF_OUTPUT_LEN>> {
+        self.legacy_key
+            .as_ref()
+            .ok_or_else(|| DurianError::crypto(tr(Message::LegacyKeyUnavailable)))
+    }
+
+    /// 启用信封加密：之后的新数据使用该数据密钥加密
+    pub fn with_data_key(mut self, data_key: SecretBytes<KDF_OUTPUT_LEN>) -> Self {
+        self.data_key = Some(data_key);
+        self
+    }
+
+    /// 是否已启用信封加密
+    #[inline]
+    pub fn has_data_key(&self) -> bool {
+        self.data_key.is_some()
+    }
+
+    /// 使用新核心密码和相同的 KDF 参数重新派生，保留当前的数据密钥
+    ///
+    /// # Arguments
+    /// * `core_password` - 新核心密码
+    pub fn with_core_password(&self, core_password: &str) -> DurianResult<Self> {
+        let key = Self::derive(core_password, &self.kdf)?;
+        Ok(match &self.data_key {
+            Some(data_key) => key.with_data_key(data_key.clone()),
+            None => key,
+        })
+    }
+
+    /// 用指定密码包装当前的数据密钥
+    ///
+    /// # Arguments
+    /// * `key_id` - 解包密钥标识（如 `core`）
+    /// * `password` - 包装密码
+    pub fn wrap_data_key(&self, key_id: &str, password: &str) -> DurianResult<WrappedKey> {
+        let data_key = self
+            .data_key
+            .as_ref()
+            .ok_or_else(|| DurianError::crypto(tr(Message::NoDataKeyToWrap)))?;
+        wrap_data_key(data_key.expose(), key_id, password)
+    }
+
+    /// 以常量时间比较两个保险库密钥是否由同一核心密码派生
+    ///
+    /// 用于在不保存核心密码的情况下校验用户输入的核心密码
+    pub fn same_core_password(&self, other: &VaultKey) -> bool {
+        self.derived_key
+            .expose()
+            .iter()
+            .zip(other.derived_key.expose().iter())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+    }
+
+    /// 新数据使用的信封类型
+    fn active_kdf(&self) -> EnvelopeKdf {
+        if self.data_key.is_some() {
+            EnvelopeKdf::DataKey
+        } else {
+            EnvelopeKdf::DerivedKey
+        }
+    }
+
+    /// 新数据使用的密钥
+    fn active_key(&self) -> &SecretBytes<KDF_OUTPUT_LEN> {
+        self.data_key.as_ref().unwrap_or(&self.derived_key)
+    }
+}
+
+// ============================================
+// 数据密钥包装
+// ============================================
+
+/// 生成随机数据密钥
+pub fn generate_data_key() -> DurianResult<SecretBytes<KDF_OUTPUT_LEN>> {
+    Ok(SecretBytes::new(random_bytes::<KDF_OUTPUT_LEN>()?))
+}
+
+/// 用密码包装数据密钥
+///
+/// 每次包装都使用新的随机盐值派生包装密钥
+///
+/// # Arguments
+/// * `data_key` - 数据密钥
+/// * `key_id` - 解包密钥标识
+/// * `password` - 包装密码
+pub fn wrap_data_key(
+    data_key: &[u8; KDF_OUTPUT_LEN],
+    key_id: &str,
+    password: &str,
+) -> DurianResult<WrappedKey> {
+    let kdf = KdfParams::recommended()?;
+    let wrapping_key = SecretBytes::new(kdf.derive_key(password)?);
+    let envelope =
+        Envelope::seal(EnvelopeKdf::KeyWrap, Vec::new(), wrapping_key.expose(), data_key)?;
+
+    Ok(WrappedKey {
+        key_id: key_id.to_string(),
+        kdf,
+        wrapped: envelope.encode(),
+    })
+}
+
+/// 用密码解包数据密钥
+///
+/// # Arguments
+/// * `wrapped` - 包装后的数据密钥
+/// * `password` - 包装密码
+pub fn unwrap_data_key(
+    wrapped: &WrappedKey,
+    password: &str,
+) -> DurianResult<SecretBytes<KDF_OUTPUT_LEN>> {
+    wrapped.kdf.validate()?;
+    let envelope = Envelope::decode(&wrapped.wrapped)?;
+    if envelope.kdf != EnvelopeKdf::KeyWrap {
+        return Err(DurianError::crypto(tr(Message::NotWrappedKey)));
+    }
+
+    let wrapping_key = SecretBytes::new(wrapped.kdf.derive_key(password)?);
+    let plaintext = envelope
+        .open(wrapping_key.expose())
+        .map_err(|_| DurianError::crypto(tr(Message::UnwrapKeyFailed)))?;
+    if plaintext.len() != KDF_OUTPUT_LEN {
+        return Err(DurianError::crypto(tr(Message::InvalidDataKeyLength)));
+    }
+
+    let mut data_key = SecretBytes::zeroed();
+    data_key.expose_mut().copy_from_slice(&plaintext);
+    Ok(data_key)
+}
+
+// ============================================
+// 本地缓存加密
+// ============================================
+
+/// 缓存字段加密密钥的 HKDF info
+const CACHE_FIELD_HKDF_INFO: &[u8] = b"durian.cache.field.v1";
+
+/// 缓存盲索引密钥的 HKDF info
+const CACHE_INDEX_HKDF_INFO: &[u8] = b"durian.cache.index.v1";
+
+/// 缓存密钥校验值的 HMAC 输入
+const CACHE_KEY_CHECK_INPUT: &[u8] = b"durian.cache.key_check";
+
+/// 本地缓存字段加密器
+///
+/// 由会话保险库密钥派生，保险库密钥变化（如未启用信封加密时修改核心密码）后
+/// 旧缓存需要重新加密，可通过 [`CacheCipher::key_check`] 检测
+pub struct CacheCipher {
+    field_key: SecretBytes<KDF_OUTPUT_LEN>,
+    index_key: SecretBytes<KDF_OUTPUT_LEN>,
+}
+
+impl CacheCipher {
+    /// 从会话保险库密钥派生缓存加密器
+    pub fn new(key: &VaultKey) -> DurianResult<Self> {
+        Self::from_key(key.active_key().expose())
+    }
+
+    /// 启用信封加密前（由派生密钥派生）的缓存加密器
+    ///
+    /// 用于首次启用信封加密时将已有缓存重新加密到数据密钥下；未启用信封加密时返回 `None`
+    pub fn without_data_key(key: &VaultKey) -> DurianResult<Option<Self>> {
+        if key.has_data_key() {
+            Self::from_key(key.derived_key.expose()).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// 从原始密钥派生缓存加密器
+    fn from_key(key: &[u8; KDF_OUTPUT_LEN]) -> DurianResult<Self> {
+        Ok(Self {
+            field_key: hkdf_expand(key, CACHE_FIELD_HKDF_INFO)?,
+            index_key: hkdf_expand(key, CACHE_INDEX_HKDF_INFO)?,
+        })
+    }
+
+    /// 加密缓存字段
+    pub fn encrypt_field(&self, value: &str) -> DurianResult<String> {
+        Envelope::seal(
+            EnvelopeKdf::CacheField,
+            Vec::new(),
+            self.field_key.expose(),
+            value.as_bytes(),
+        )
+        .map(|envelope| envelope.encode())
+    }
+
+    /// 解密缓存字段
+    pub fn decrypt_field(&self, value: &str) -> DurianResult<String> {
+        let envelope = Envelope::decode(value)?;
+        if envelope.kdf != EnvelopeKdf::CacheField {
+            return Err(DurianError::crypto(tr(Message::NotCacheField)));
+        }
+        let plaintext = envelope.open(self.field_key.expose())?;
+        String::from_utf8(plaintext.to_vec())
+            .map_err(|e| DurianError::crypto(tr(Message::InvalidPlaintext(&e.to_string()))))
+    }
+
+    /// 计算盲索引
+    ///
+    /// 查找时忽略首尾空白和大小写
+    pub fn blind_index(&self, value: &str) -> String {
+        let normalized = Zeroizing::new(value.trim().to_lowercase());
+        self.mac(normalized.as_bytes())
+    }
+
+    /// 密钥校验值，用于检测缓存是否由当前密钥加密
+    pub fn key_check(&self) -> String {
+        self.mac(CACHE_KEY_CHECK_INPUT)
+    }
+
+    fn mac(&self, data: &[u8]) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, self.index_key.expose());
+        hex::encode(hmac::sign(&key, data).as_ref())
+    }
+}
+
+/// HKDF-SHA256 派生子密钥（无盐值）
+fn hkdf_expand(
+    key: &[u8; KDF_OUTPUT_LEN],
+    info: &[u8],
+) -> DurianResult<SecretBytes<KDF_OUTPUT_LEN>> {
+    let mut output = SecretBytes::zeroed();
+    hkdf::Salt::new(hkdf::HKDF_SHA256, &[])
+        .extract(key)
+        .expand(&[info], hkdf::HKDF_SHA256)
+        .and_then(|okm| okm.fill(output.expose_mut()))
+        .map_err(|_| DurianError::crypto(tr(Message::KeyDerivationFailed)))?;
+    Ok(output)
+}
+
+// ============================================
+// 密文信封
+// ============================================
+
+/// 信封密文前缀，用于区分旧版 ciftl 密文（标准 Base64 不含 `:`）
+const ENVELOPE_PREFIX: &str = "durian:";
+
+/// 当前信封格式版本
+const ENVELOPE_VERSION: u8 = 1;
+
+/// XChaCha20 随机数长度
+const ENVELOPE_NONCE_LEN: usize = 24;
+
+/// Poly1305 认证标签长度
+const ENVELOPE_TAG_LEN: usize = 16;
+
+/// 消息密钥派生的 HKDF info
+const ENVELOPE_HKDF_INFO: &[u8] = b"durian.entry.v1";
+
+/// 旧版 ciftl 密文的随机数长度
+const LEGACY_IV_LEN: usize = 12;
+
+/// 旧版 ciftl 密文的 CRC32C 校验值长度
+const LEGACY_CHECKSUM_LEN: usize = 4;
+
+/// 信封中记录的消息密钥派生方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EnvelopeKdf {
+    /// HKDF-SHA256(SHA-256(核心密码), 消息盐值)
+    ///
+    /// 第一代信封格式，只读；登录时由迁移流程重新加密为当前格式（见 [`crate::rekey`]）
+    PasswordHkdf = 1,
+    /// 直接使用由核心密码派生的密钥（无盐值），依靠 24 字节随机数保证唯一性
+    ///
+    /// 未启用信封加密时的格式
+    DerivedKey = 2,
+    /// 直接使用随机数据密钥（无盐值）
+    DataKey = 3,
+    /// 数据密钥的包装，密钥由 `WrappedKey` 中的 KDF 参数派生
+    KeyWrap = 4,
+    /// 本地缓存字段，密钥由 `CacheCipher` 派生
+    CacheField = 5,
+}
+
+impl TryFrom<u8> for EnvelopeKdf {
+    type Error = DurianError;
+
+    fn try_from(id: u8) -> Result<Self, Self::Error> {
+        match id {
+            1 => Ok(EnvelopeKdf::PasswordHkdf),
+            2 => Ok(EnvelopeKdf::DerivedKey),
+            3 => Ok(EnvelopeKdf::DataKey),
+            4 => Ok(EnvelopeKdf::KeyWrap),
+            5 => Ok(EnvelopeKdf::CacheField),
+            _ => Err(DurianError::crypto(tr(Message::UnsupportedEnvelopeKdf(id)))),
+        }
+    }
+}
+
+/// 解析后的密文信封
+struct Envelope {
+    kdf: EnvelopeKdf,
+    salt: Vec<u8>,
+    nonce: [u8; ENVELOPE_NONCE_LEN],
+    /// 密文（末尾附带认证标签）
+    ciphertext: Vec<u8>,
+}
+
+impl Envelope {
+    /// 使用给定密钥封装数据
+    fn seal(
+        kdf: EnvelopeKdf,
+        salt: Vec<u8>,
+        key: &[u8; KDF_OUTPUT_LEN],
+        plaintext: &[u8],
+    ) -> DurianResult<Self> {
+        let mut envelope = Envelope {
+            kdf,
+            salt,
+            nonce: random_bytes::<ENVELOPE_NONCE_LEN>()?,
+            ciphertext: Vec::new(),
+        };
+
+        let cipher = XChaCha20Poly1305::new(key.into());
+        let header = envelope.header();
+        envelope.ciphertext = cipher
+            .encrypt(
+                XNonce::from_slice(&envelope.nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: &header,
+                },
+            )
+            .map_err(|_| DurianError::crypto(tr(Message::EncryptFailed)))?;
+
+        Ok(envelope)
+    }
+
+    /// 使用给定密钥解封并校验数据
+    fn open(&self, key: &[u8; KDF_OUTPUT_LEN]) -> DurianResult<Zeroizing<Vec<u8>>> {
+        let cipher = XChaCha20Poly1305::new(key.into());
+        let header = self.header();
+        cipher
+            .decrypt(
+                XNonce::from_slice(&self.nonce),
+                Payload {
+                    msg: &self.ciphertext,
+                    aad: &header,
+                },
+            )
+            .map(Zeroizing::new)
+            .map_err(|_| DurianError::crypto(tr(Message::CiphertextTampered)))
+    }
+
+    /// 信封头部，作为 AEAD 关联数据
+    fn header(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(3 + self.salt.len());
+        header.push(ENVELOPE_VERSION);
+        header.push(self.kdf as u8);
+        header.push(self.salt.len() as u8);
+        header.extend_from_slice(&self.salt);
+        header
+    }
+
+    /// 编码为带前缀的字符串
+    fn encode(&self) -> String {
+        let mut bytes = self.header();
+        bytes.extend_from_slice(&self.nonce);
+        bytes.extend_from_slice(&self.ciphertext);
+        format!("{}{}", ENVELOPE_PREFIX, STANDARD_NO_PAD.encode(bytes))
+    }
+
+    /// 从带前缀的字符串解码
+    fn decode(text: &str) -> DurianResult<Self> {
+        let body = text
+            .strip_prefix(ENVELOPE_PREFIX)
+            .ok_or_else(|| DurianError::crypto(tr(Message::NotEnvelope)))?;
+        let bytes = STANDARD_NO_PAD
+            .decode(body)
+            .map_err(|e| DurianError::crypto(tr(Message::InvalidCiphertextEncoding(&e.to_string()))))?;
+
+        if bytes.len() < 3 {
+            return Err(DurianError::crypto(tr(Message::CiphertextTooShort)));
+        }
+        if bytes[0] != ENVELOPE_VERSION {
+            return Err(DurianError::crypto(tr(Message::UnsupportedEnvelopeVersion(bytes[0]))));
+        }
+        let kdf = EnvelopeKdf::try_from(bytes[1])?;
+        let salt_len = bytes[2] as usize;
+
+        let rest = &bytes[3..];
+        if rest.len() < salt_len + ENVELOPE_NONCE_LEN + ENVELOPE_TAG_LEN {
+            return Err(DurianError::crypto(tr(Message::CiphertextTooShort)));
+        }
+        let (salt, rest) = rest.split_at(salt_len);
+        let (nonce, ciphertext) = rest.split_at(ENVELOPE_NONCE_LEN);
+
+        let mut nonce_bytes = [0u8; ENVELOPE_NONCE_LEN];
+        nonce_bytes.copy_from_slice(nonce);
+
+        Ok(Self {
+            kdf,
+            salt: salt.to_vec(),
+            nonce: nonce_bytes,
+            ciphertext: ciphertext.to_vec(),
+        })
+    }
+}
+
+/// 检查密文是否为新版信封格式
+#[inline]
+pub fn is_envelope(ciphertext: &str) -> bool {
+    ciphertext.starts_with(ENVELOPE_PREFIX)
+}
+
+/// 获取信封对应的消息密钥
+///
+/// 当前格式直接借用会话密钥，不复制密钥材料；只有第一代信封需要派生新的消息密钥
+fn message_key<'a>(
+    kdf: EnvelopeKdf,
+    key: &'a VaultKey,
+    salt: &[u8],
+) -> DurianResult<Cow<'a, SecretBytes<KDF_OUTPUT_LEN>>> {
+    match kdf {
+        EnvelopeKdf::PasswordHkdf => {
+            let mut message_key = SecretBytes::zeroed();
+            hkdf::Salt::new(hkdf::HKDF_SHA256, salt)
+                .extract(key.legacy_key()?.expose())
+                .expand(&[ENVELOPE_HKDF_INFO], hkdf::HKDF_SHA256)
+                .and_then(|okm| okm.fill(message_key.expose_mut()))
+                .map_err(|_| DurianError::crypto(tr(Message::KeyDerivationFailed)))?;
+            Ok(Cow::Owned(message_key))
+        }
+        EnvelopeKdf::DerivedKey => Ok(Cow::Borrowed(&key.derived_key)),
+        EnvelopeKdf::DataKey => key
+            .data_key
+            .as_ref()
+            .map(Cow::Borrowed)
+            .ok_or_else(|| DurianError::crypto(tr(Message::MissingDataKey))),
+        EnvelopeKdf::KeyWrap => Err(DurianError::crypto(tr(Message::NotRecordCiphertext))),
+        EnvelopeKdf::CacheField => Err(DurianError::crypto(tr(Message::NotRecordCiphertext))),
+    }
+}
+
+/// 使用指定派生方式封装消息
+///
+/// 只写入不带盐值的格式（[`VaultKey::active_kdf`]），第一代信封只读
+fn seal(kdf: EnvelopeKdf, message: &str, key: &VaultKey) -> DurianResult<String> {
+    let message_key = message_key(kdf, key, &[])?;
+    Envelope::seal(kdf, Vec::new(), message_key.expose(), message.as_bytes()).map(|e| e.encode())
 }
 
 // ============================================
 // 消息加解密功能
 // ============================================
 
-/// 使用 ChaCha20 加密消息
+/// 使用 XChaCha20-Poly1305 加密消息
+///
+/// 输出新版信封格式，每条消息使用独立的随机数
 ///
 /// # Arguments
 /// * `message` - 要加密的明文消息
-/// * `key` - 加密密钥（核心密码）
+/// * `key` - 会话保险库密钥
 ///
 /// # Returns
 /// 加密后的密文，或错误信息
-pub fn encrypt_message(message: &str, key: &str) -> DurianResult<String> {
+pub fn encrypt_message(message: &str, key: &VaultKey) -> DurianResult<String> {
     if message.is_empty() {
-        return Err(DurianError::validation("加密内容不能为空"));
-    }
-    if key.is_empty() {
-        return Err(DurianError::validation("加密密钥不能为空"));
+        return Err(DurianError::validation(tr(Message::EmptyEncryptInput)));
     }
-    
-    let crypter = StringCrypter::<chacha20::ChaCha20CipherAlgorithm>::default();
-    crypter
-        .encrypt(message, key)
-        .map_err(|e| DurianError::crypto(format!("加密失败: {:?}", e)))
+
+    seal(key.active_kdf(), message, key)
 }
 
-/// 使用 ChaCha20 解密消息
+/// 解密消息
+///
+/// 自动识别密文格式：新版信封会校验认证标签，被篡改的密文返回错误；
+/// 旧版 ciftl 密文按原 ChaCha20 方式解密
 ///
 /// # Arguments
 /// * `ciphertext` - 要解密的密文
-/// * `key` - 解密密钥（核心密码）
+/// * `key` - 会话保险库密钥
 ///
 /// # Returns
 /// 解密后的明文，或错误信息
-pub fn decrypt_message(ciphertext: &str, key: &str) -> DurianResult<String> {
+pub fn decrypt_message(ciphertext: &str, key: &VaultKey) -> DurianResult<String> {
     if ciphertext.is_empty() {
-        return Err(DurianError::validation("解密内容不能为空"));
+        return Err(DurianError::validation(tr(Message::EmptyDecryptInput)));
+    }
+
+    if !is_envelope(ciphertext) {
+        return decrypt_legacy_message(ciphertext, key);
+    }
+
+    let envelope = Envelope::decode(ciphertext)?;
+    let message_key = message_key(envelope.kdf, key, &envelope.salt)?;
+    let plaintext = envelope.open(message_key.expose())?;
+
+    String::from_utf8(plaintext.to_vec())
+        .map_err(|e| DurianError::crypto(tr(Message::InvalidPlaintext(&e.to_string()))))
+}
+
+/// 解密账户的网站或账户字段
+///
+/// 是否加密由记录上的标记（[`crate::models::AccountItem::fields_encrypted`]）决定，
+/// 不根据内容猜测，因此恰好形似密文的明文也会原样返回
+///
+/// # Arguments
+/// * `value` - 字段值
+/// * `encrypted` - 字段是否为密文
+/// * `key` - 会话保险库密钥
+pub fn decrypt_account_field(value: &str, encrypted: bool, key: &VaultKey) -> DurianResult<String> {
+    if encrypted {
+        decrypt_message(value, key)
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+/// 将密文重新加密到新的保险库密钥下
+///
+/// 已使用 `new_key` 当前格式封装的密文会被跳过，因此中断后重复执行是安全的。
+/// `old_key` 与 `new_key` 相同时即把旧格式记录迁移到当前格式
+///
+/// # Arguments
+/// * `ciphertext` - 原密文（任意受支持的格式）
+/// * `old_key` - 原保险库密钥
+/// * `new_key` - 新保险库密钥
+///
+/// # Returns
+/// 需要更新时返回新密文，已是新密钥下的密文则返回 `None`
+pub fn reencrypt_message(
+    ciphertext: &str,
+    old_key: &VaultKey,
+    new_key: &VaultKey,
+) -> DurianResult<Option<String>> {
+    if is_envelope(ciphertext) {
+        let envelope = Envelope::decode(ciphertext)?;
+        if envelope.kdf == new_key.active_kdf() && decrypt_message(ciphertext, new_key).is_ok() {
+            return Ok(None);
+        }
     }
-    if key.is_empty() {
-        return Err(DurianError::validation("解密密钥不能为空"));
+
+    let plaintext = Zeroizing::new(decrypt_message(ciphertext, old_key)?);
+    encrypt_message(&plaintext, new_key).map(Some)
+}
+
+/// 解密旧版 ciftl ChaCha20 密文
+///
+/// 格式为 `base64( iv(12) | 加密的 CRC32C(4) | 加密的明文 )`，密钥流先用于明文、再用于校验值。
+/// 使用登录时附加的旧版密钥，避免每次重新对核心密码做哈希
+fn decrypt_legacy_message(ciphertext: &str, key: &VaultKey) -> DurianResult<String> {
+    let data = Base64Encoding::default()
+        .decode(ciphertext)
+        .map_err(|e| DurianError::crypto(tr(Message::LegacyDecryptFailed(&format!("{:?}", e)))))?;
+    if data.len() < LEGACY_IV_LEN + LEGACY_CHECKSUM_LEN {
+        return Err(DurianError::crypto(tr(Message::CiphertextTooShort)));
     }
-    
-    let crypter = StringCrypter::<chacha20::ChaCha20CipherAlgorithm>::default();
-    crypter
-        .decrypt(ciphertext, key)
-        .map_err(|e| DurianError::crypto(format!("解密失败: {:?}", e)))
+
+    let (iv, rest) = data.split_at(LEGACY_IV_LEN);
+    let (checksum, body) = rest.split_at(LEGACY_CHECKSUM_LEN);
+
+    let mut cipher = ChaCha20CipherAlgorithm::new(iv, key.legacy_key()?.expose())
+        .map_err(|e| DurianError::crypto(tr(Message::LegacyDecryptFailed(&format!("{:?}", e)))))?;
+    let zeros = vec![0u8; body.len() + LEGACY_CHECKSUM_LEN];
+    let mut keystream = Zeroizing::new(vec![0u8; zeros.len()]);
+    cipher
+        .crypt(&zeros, &mut keystream)
+        .map_err(|e| DurianError::crypto(tr(Message::LegacyDecryptFailed(&format!("{:?}", e)))))?;
+
+    let plaintext: Vec<u8> = body.iter().zip(keystream.iter()).map(|(c, k)| c ^ k).collect();
+    let expected: Vec<u8> = checksum
+        .iter()
+        .zip(&keystream[body.len()..])
+        .map(|(c, k)| c ^ k)
+        .collect();
+
+    let mut hasher = Crc32cHasher::default();
+    hasher.update_bytes(&plaintext);
+    if hasher.finalize() != expected {
+        return Err(DurianError::crypto(tr(Message::LegacyChecksumMismatch)));
+    }
+
+    String::from_utf8(plaintext)
+        .map_err(|e| DurianError::crypto(tr(Message::InvalidPlaintext(&e.to_string()))))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ciftl::crypter::{StringCrypter, StringCrypterTrait};
+
+    /// 测试用的低成本保险库密钥（与登录时相同，附加旧版密钥）
+    fn test_key(core_password: &str) -> VaultKey {
+        let params = KdfParams::argon2id(8 * 1024, 1, 1).unwrap();
+        VaultKey::derive(core_password, &params)
+            .unwrap()
+            .with_legacy_key(core_password)
+    }
 
     #[test]
     fn test_hash_login_password() {
         let password = "test_password";
-        let hash1 = hash_login_password(password);
-        let hash2 = hash_login_password(password);
+        let hash1 = hash_login_password(password).unwrap();
+        let hash2 = hash_login_password(password).unwrap();
         
         // 相同密码应产生相同哈希
         assert_eq!(hash1, hash2);
@@ -138,8 +776,8 @@ mod tests {
     #[test]
     fn test_hash_core_password() {
         let password = "test_core_password";
-        let hash1 = hash_core_password(password);
-        let hash2 = hash_core_password(password);
+        let hash1 = hash_core_password(password).unwrap();
+        let hash2 = hash_core_password(password).unwrap();
         
         assert_eq!(hash1, hash2);
         assert_eq!(hash1.len(), 64);
@@ -148,8 +786,8 @@ mod tests {
     #[test]
     fn test_different_salts_produce_different_hashes() {
         let password = "same_password";
-        let login_hash = hash_login_password(password);
-        let core_hash = hash_core_password(password);
+        let login_hash = hash_login_password(password).unwrap();
+        let core_hash = hash_core_password(password).unwrap();
         
         // 不同盐值应产生不同哈希
         assert_ne!(login_hash, core_hash);
@@ -158,11 +796,193 @@ mod tests {
     #[test]
     fn test_encrypt_decrypt_roundtrip() {
         let message = "Hello, World!";
-        let key = "test_key_12345";
+        let key = test_key("test_key_12345");
         
-        let encrypted = encrypt_message(message, key).unwrap();
-        let decrypted = decrypt_message(&encrypted, key).unwrap();
+        let encrypted = encrypt_message(message, &key).unwrap();
+        let decrypted = decrypt_message(&encrypted, &key).unwrap();
         
         assert_eq!(message, decrypted);
     }
+
+    #[test]
+    fn test_encrypt_produces_envelope() {
+        let key = test_key("test_key_12345");
+        let encrypted = encrypt_message("Hello, World!", &key).unwrap();
+        assert!(is_envelope(&encrypted));
+
+        // 相同明文每次加密结果不同
+        let again = encrypt_message("Hello, World!", &key).unwrap();
+        assert_ne!(encrypted, again);
+    }
+
+    #[test]
+    fn test_decrypt_legacy_ciphertext() {
+        let crypter = StringCrypter::<ChaCha20CipherAlgorithm>::default();
+        let legacy = crypter.encrypt("legacy secret", "test_key_12345").unwrap();
+        assert!(!is_envelope(&legacy));
+
+        let key = test_key("test_key_12345");
+        let decrypted = decrypt_message(&legacy, &key).unwrap();
+        assert_eq!(decrypted, "legacy secret");
+
+        // 错误的核心密码应校验失败
+        assert!(decrypt_message(&legacy, &test_key("another_key")).is_err());
+
+        // 迁移完成、清除旧版密钥后不再能读取旧格式
+        let mut key = key;
+        key.forget_legacy_key();
+        assert!(decrypt_message(&legacy, &key).is_err());
+    }
+
+    /// 按第一代信封格式（带随机盐值的 HKDF）加密，客户端已不再写入该格式
+    fn seal_first_generation(message: &str, key: &VaultKey) -> String {
+        let salt = random_bytes::<16>().unwrap().to_vec();
+        let message_key = message_key(EnvelopeKdf::PasswordHkdf, key, &salt).unwrap();
+        Envelope::seal(EnvelopeKdf::PasswordHkdf, salt, message_key.expose(), message.as_bytes())
+            .unwrap()
+            .encode()
+    }
+
+    #[test]
+    fn test_decrypt_password_hkdf_envelope() {
+        let key = test_key("test_key_12345");
+        let encrypted = seal_first_generation("first generation", &key);
+
+        // 第一代信封只依赖核心密码，与主密钥参数无关
+        let params = KdfParams::argon2id(16 * 1024, 2, 1).unwrap();
+        let other_params = VaultKey::derive("test_key_12345", &params)
+            .unwrap()
+            .with_legacy_key("test_key_12345");
+        assert!(!other_params.same_core_password(&key));
+        assert_eq!(decrypt_message(&encrypted, &other_params).unwrap(), "first generation");
+
+        // 重新加密时迁移到当前格式
+        let migrated = reencrypt_message(&encrypted, &key, &key).unwrap().unwrap();
+        assert_eq!(Envelope::decode(&migrated).unwrap().kdf, EnvelopeKdf::DerivedKey);
+        assert_eq!(decrypt_message(&migrated, &key).unwrap(), "first generation");
+    }
+
+    #[test]
+    fn test_reencrypt_message_is_idempotent() {
+        let old_key = test_key("old_core_password");
+        let new_key = test_key("new_core_password");
+
+        let crypter = StringCrypter::<ChaCha20CipherAlgorithm>::default();
+        let legacy = crypter.encrypt("legacy secret", "old_core_password").unwrap();
+        let current = encrypt_message("current secret", &old_key).unwrap();
+
+        let migrated_legacy = reencrypt_message(&legacy, &old_key, &new_key).unwrap().unwrap();
+        let migrated_current = reencrypt_message(&current, &old_key, &new_key).unwrap().unwrap();
+        assert_eq!(decrypt_message(&migrated_legacy, &new_key).unwrap(), "legacy secret");
+        assert_eq!(decrypt_message(&migrated_current, &new_key).unwrap(), "current secret");
+
+        // 已迁移的密文再次执行时应被跳过
+        assert!(reencrypt_message(&migrated_current, &old_key, &new_key).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_same_core_password() {
+        let params = KdfParams::argon2id(8 * 1024, 1, 1).unwrap();
+        let a = VaultKey::derive("core_password", &params).unwrap();
+        let b = VaultKey::derive("core_password", &params).unwrap();
+        let c = VaultKey::derive("other_password", &params).unwrap();
+        assert!(a.same_core_password(&b));
+        assert!(!a.same_core_password(&c));
+    }
+
+    #[test]
+    fn test_data_key_wrap_and_rewrap() {
+        let data_key = generate_data_key().unwrap();
+        let key = test_key("old_core_password").with_data_key(data_key.clone());
+        let encrypted = encrypt_message("secret", &key).unwrap();
+
+        // 修改核心密码只需重新包装数据密钥
+        let wrapped = key.wrap_data_key("core", "new_core_password").unwrap();
+        assert_eq!(wrapped.key_id, "core");
+        assert!(unwrap_data_key(&wrapped, "old_core_password").is_err());
+
+        let unwrapped = unwrap_data_key(&wrapped, "new_core_password").unwrap();
+        let new_key = test_key("new_core_password").with_data_key(unwrapped);
+        assert_eq!(decrypt_message(&encrypted, &new_key).unwrap(), "secret");
+    }
+
+    #[test]
+    fn test_data_key_reads_and_migrates_derived_key_entries() {
+        let derived_only = test_key("core_password");
+        let old_entry = encrypt_message("before envelope encryption", &derived_only).unwrap();
+
+        let key = derived_only.with_data_key(generate_data_key().unwrap());
+        assert_eq!(decrypt_message(&old_entry, &key).unwrap(), "before envelope encryption");
+
+        // 同一密钥下重新加密即迁移到数据密钥
+        let migrated = reencrypt_message(&old_entry, &key, &key).unwrap().unwrap();
+        assert!(reencrypt_message(&migrated, &key, &key).unwrap().is_none());
+
+        // 没有数据密钥时无法读取数据密钥加密的记录
+        assert!(decrypt_message(&migrated, &test_key("core_password")).is_err());
+    }
+
+    #[test]
+    fn test_tampered_ciphertext_is_rejected() {
+        let key = test_key("test_key_12345");
+        let encrypted = encrypt_message("Hello, World!", &key).unwrap();
+        let mut bytes = STANDARD_NO_PAD
+            .decode(encrypted.strip_prefix(ENVELOPE_PREFIX).unwrap())
+            .unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0x01;
+        let tampered = format!("{}{}", ENVELOPE_PREFIX, STANDARD_NO_PAD.encode(&bytes));
+
+        assert!(decrypt_message(&tampered, &key).is_err());
+    }
+
+    #[test]
+    fn test_wrong_key_is_rejected() {
+        let encrypted = encrypt_message("Hello, World!", &test_key("test_key_12345")).unwrap();
+        assert!(decrypt_message(&encrypted, &test_key("another_key")).is_err());
+    }
+
+    #[test]
+    fn test_unknown_envelope_version_is_rejected() {
+        let key = test_key("test_key_12345");
+        let encrypted = encrypt_message("Hello, World!", &key).unwrap();
+        let mut bytes = STANDARD_NO_PAD
+            .decode(encrypted.strip_prefix(ENVELOPE_PREFIX).unwrap())
+            .unwrap();
+        bytes[0] = 0xFF;
+        let modified = format!("{}{}", ENVELOPE_PREFIX, STANDARD_NO_PAD.encode(&bytes));
+
+        assert!(decrypt_message(&modified, &key).is_err());
+    }
+
+    #[test]
+    fn test_cache_cipher() {
+        let key = test_key("core_password");
+        let cipher = CacheCipher::new(&key).unwrap();
+
+        let encrypted = cipher.encrypt_field("example.com").unwrap();
+        assert!(!encrypted.contains("example"));
+        assert_eq!(cipher.decrypt_field(&encrypted).unwrap(), "example.com");
+        // 缓存字段与记录密文互不通用
+        assert!(decrypt_message(&encrypted, &key).is_err());
+
+        assert_eq!(cipher.blind_index("Example.com "), cipher.blind_index("example.com"));
+        assert_ne!(cipher.blind_index("example.com"), cipher.blind_index("example.org"));
+
+        let other = CacheCipher::new(&test_key("other_password")).unwrap();
+        assert_ne!(cipher.key_check(), other.key_check());
+        assert!(other.decrypt_field(&encrypted).is_err());
+    }
+
+    #[test]
+    fn test_decrypt_account_field() {
+        let key = test_key("core_password");
+        assert_eq!(decrypt_account_field("example.com", false, &key).unwrap(), "example.com");
+
+        let encrypted = encrypt_message("example.com", &key).unwrap();
+        assert_eq!(decrypt_account_field(&encrypted, true, &key).unwrap(), "example.com");
+        assert!(decrypt_account_field(&encrypted, true, &test_key("other_password")).is_err());
+        // 未标记为密文的字段即使形似密文也原样返回
+        assert_eq!(decrypt_account_field(&encrypted, false, &key).unwrap(), encrypted);
+    }
 }
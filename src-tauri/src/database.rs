@@ -3,40 +3,43 @@
 //! 封装 SQLite 数据库的初始化和 CRUD 操作
 //!
 //! # 功能
-//! - 数据库表结构初始化
+//! - 数据库表结构初始化与版本迁移
 //! - 缓存数据的 CRUD 操作
 //! - 支持全量和增量数据同步
+//! - 离线写入队列（`pending_ops`）
+//! - 修改冲突（`conflicts`）：与其他设备的修改冲突时同时保留本地和服务器版本
+//!
+//! # 静态加密
+//! `accounts` 表中的网站和账户字段使用 `CacheCipher` 加密保存，
+//! 按网站查找使用 `website_index` 盲索引；密码字段本身即为保险库密文。
+//! 旧版明文缓存在登录后由 [`prepare_cache`] 原地加密
+//!
+//! # 会话
+//! 各表的数据按会话区分（见 [`crate::state::session_id`]）。列名沿用旧版的 `username`，
+//! 保存的却是会话标识；旧版以用户名保存的数据由 [`adopt_user_rows`] 归入会话
 
+use rusqlite::types::Value;
 use rusqlite::Connection;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
-use std::str::FromStr;
 
+use crate::crypto::CacheCipher;
 use crate::error::{DurianError, DurianResult};
-use crate::models::{AccountRecord, CacheData};
-
-/// 支持的数据拉取模式
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum PullMode {
-    /// 全量拉取：删除旧数据后重新写入
-    PullAll,
-    /// 增量拉取：只更新有变化的数据
-    PullUpdated,
-    /// 无更新：只更新时间戳
-    PullNothing,
-}
-
-impl FromStr for PullMode {
-    type Err = DurianError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "PULL_ALL" => Ok(PullMode::PullAll),
-            "PULL_UPDATED" => Ok(PullMode::PullUpdated),
-            "PULL_NOTHING" => Ok(PullMode::PullNothing),
-            _ => Err(DurianError::validation(format!("未知的 pull_mode: {}", s))),
-        }
-    }
-}
+use crate::i18n::{tr, Message};
+use crate::kdf::KdfParams;
+use crate::lock::LockSettings;
+use crate::models::{
+    AccountConflict, AccountItem, AccountRecord, CacheData, PendingOp, PendingOpInfo,
+    PendingOpKind, PullMode, SyncChanges, WrappedKey,
+};
+use crate::sync_worker::SyncSettings;
+use crate::verify;
+
+/// 当前数据库结构版本（保存在 `PRAGMA user_version` 中）
+const SCHEMA_VERSION: i64 = 7;
+
+/// 服务器上新插入记录的修订号
+const INITIAL_REVISION: i64 = 1;
 
 // ============================================
 // 数据库初始化
@@ -65,7 +68,7 @@ pub fn init_database(db_path: &Path) -> DurianResult<()> {
     // 创建缓存元数据表
     conn.execute(
         "CREATE TABLE IF NOT EXISTS cache_metadata (
-            username TEXT PRIMARY KEY,
+            username TEXT PRIMARY KEY, -- 会话标识
             last_update_time INTEGER NOT NULL
         )",
         [],
@@ -75,7 +78,7 @@ pub fn init_database(db_path: &Path) -> DurianResult<()> {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS accounts (
             rid INTEGER NOT NULL,
-            username TEXT NOT NULL,
+            username TEXT NOT NULL, -- 会话标识
             website TEXT NOT NULL,
             account TEXT NOT NULL,
             password TEXT NOT NULL,
@@ -90,11 +93,444 @@ pub fn init_database(db_path: &Path) -> DurianResult<()> {
         [],
     )?;
 
+    // 创建核心密码修改日志表（记录未完成的修改，用于中断后恢复）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS core_password_changes (
+            username TEXT PRIMARY KEY, -- 会话标识
+            started_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    // 创建保险库密钥表（缓存服务器上包装后的数据密钥，用于离线解锁）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS vault_keys (
+            username TEXT NOT NULL, -- 会话标识
+            key_id TEXT NOT NULL,
+            kdf TEXT NOT NULL,
+            wrapped TEXT NOT NULL,
+            PRIMARY KEY (username, key_id)
+        )",
+        [],
+    )?;
+
+    // 创建保险库设置表（全字段加密为服务器上设置的本地缓存）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS vault_settings (
+            username TEXT PRIMARY KEY, -- 会话标识
+            full_field_encryption INTEGER NOT NULL DEFAULT 0
+        )",
+        [],
+    )?;
+
+    // 创建离线写入队列表（网站和账户字段与 accounts 表一样加密保存）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS pending_ops (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL, -- 会话标识
+            kind TEXT NOT NULL,
+            rid INTEGER NOT NULL,
+            website TEXT NOT NULL,
+            account TEXT NOT NULL,
+            password TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            last_error TEXT,
+            failed INTEGER NOT NULL DEFAULT 0
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_pending_ops_username ON pending_ops(username, id)",
+        [],
+    )?;
+
+    // 创建修改冲突表（两个版本的网站和账户字段与 accounts 表一样加密保存）
     conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_accounts_website ON accounts(website)",
+        "CREATE TABLE IF NOT EXISTS conflicts (
+            username TEXT NOT NULL, -- 会话标识
+            rid INTEGER NOT NULL,
+            local_website TEXT NOT NULL,
+            local_account TEXT NOT NULL,
+            local_password TEXT NOT NULL,
+            local_revision INTEGER NOT NULL,
+            server_website TEXT NOT NULL,
+            server_account TEXT NOT NULL,
+            server_password TEXT NOT NULL,
+            server_revision INTEGER NOT NULL,
+            server_updated_at INTEGER NOT NULL,
+            detected_at INTEGER NOT NULL,
+            PRIMARY KEY (username, rid)
+        )",
         [],
     )?;
 
+    migrate_schema(&conn)?;
+
+    Ok(())
+}
+
+/// 将数据库结构迁移到当前版本
+///
+/// 每一步迁移和对应的 `PRAGMA user_version` 在同一个事务中提交，
+/// 升级中断后下次启动从未完成的一步继续
+fn migrate_schema(conn: &Connection) -> DurianResult<()> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version >= SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    if version < 1 {
+        // v1：网站和账户字段加密保存，新增盲索引、加密标记和缓存密钥校验表；
+        // 删除基于明文网站的索引
+        migrate_step(
+            conn,
+            1,
+            "ALTER TABLE accounts ADD COLUMN website_index TEXT NOT NULL DEFAULT '';
+             ALTER TABLE accounts ADD COLUMN encrypted INTEGER NOT NULL DEFAULT 0;
+             DROP INDEX IF EXISTS idx_accounts_website;
+             CREATE INDEX IF NOT EXISTS idx_accounts_website_index
+                 ON accounts(username, website_index);
+             CREATE TABLE IF NOT EXISTS cache_keys (
+                 username TEXT PRIMARY KEY, -- 会话标识
+                 key_check TEXT NOT NULL
+             );",
+        )?;
+    }
+
+    if version < 2 {
+        // v2：自动锁定设置
+        migrate_step(
+            conn,
+            2,
+            &format!(
+                "ALTER TABLE vault_settings ADD COLUMN idle_timeout_secs INTEGER NOT NULL DEFAULT {};
+                 ALTER TABLE vault_settings ADD COLUMN lock_on_suspend INTEGER NOT NULL DEFAULT 1;",
+                crate::lock::DEFAULT_IDLE_TIMEOUT_SECS
+            ),
+        )?;
+    }
+
+    if version < 3 {
+        // v3：记录的服务器修订号和修改时间，用于检测与其他设备的修改冲突
+        migrate_step(
+            conn,
+            3,
+            "ALTER TABLE accounts ADD COLUMN revision INTEGER NOT NULL DEFAULT 0;
+             ALTER TABLE accounts ADD COLUMN updated_at INTEGER NOT NULL DEFAULT 0;
+             ALTER TABLE pending_ops ADD COLUMN revision INTEGER NOT NULL DEFAULT 0;",
+        )?;
+    }
+
+    if version < 4 {
+        // v4：后台同步间隔
+        migrate_step(
+            conn,
+            4,
+            &format!(
+                "ALTER TABLE vault_settings ADD COLUMN sync_interval_secs INTEGER NOT NULL DEFAULT {};",
+                crate::sync_worker::DEFAULT_SYNC_INTERVAL_SECS
+            ),
+        )?;
+    }
+
+    if version < 5 {
+        // v5：账户是否已升级 KDF 参数，用于拒绝服务器降级到旧版参数
+        migrate_step(
+            conn,
+            5,
+            "ALTER TABLE vault_settings ADD COLUMN kdf_upgraded INTEGER NOT NULL DEFAULT 0;",
+        )?;
+    }
+
+    if version < 6 {
+        // v6：队列中的写入是否加密了网站和账户字段（全字段加密）
+        migrate_step(
+            conn,
+            6,
+            "ALTER TABLE pending_ops ADD COLUMN fields_encrypted INTEGER NOT NULL DEFAULT 0;",
+        )?;
+    }
+
+    if version < 7 {
+        // v7：操作 ID，重新发送插入时服务器据此去重；已在队列中的操作补充随机 ID
+        migrate_step(
+            conn,
+            7,
+            "ALTER TABLE pending_ops ADD COLUMN op_id TEXT NOT NULL DEFAULT '';
+             UPDATE pending_ops SET op_id = lower(hex(randomblob(16))) WHERE op_id = '';",
+        )?;
+    }
+
+    Ok(())
+}
+
+/// 在单个事务中执行一步迁移，并将结构版本记为 `version`
+fn migrate_step(conn: &Connection, version: i64, sql: &str) -> DurianResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute_batch(sql)?;
+    tx.execute_batch(&format!("PRAGMA user_version = {}", version))?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// 按会话区分本地数据的表（`username` 列保存会话标识）
+const SESSION_TABLES: &[&str] = &[
+    "cache_metadata",
+    "accounts",
+    "core_password_changes",
+    "vault_keys",
+    "vault_settings",
+    "pending_ops",
+    "conflicts",
+    "cache_keys",
+];
+
+/// 将旧版只按用户名保存的本地数据归入会话
+///
+/// 各表的 `username` 列保存会话标识（见 [`crate::state::session_id`]），
+/// 旧版本以用户名保存。会话还没有任何本地数据时，把该用户名的数据整体改为会话标识；
+/// 之后同名用户在其他服务器上的会话使用各自独立的数据
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `username` - 用户名
+/// * `session_id` - 会话标识
+pub fn adopt_user_rows(db_path: &Path, username: &str, session_id: &str) -> DurianResult<()> {
+    if username == session_id {
+        return Ok(());
+    }
+
+    let conn = Connection::open(db_path)?;
+    let tx = conn.unchecked_transaction()?;
+    for table in SESSION_TABLES {
+        let owned: bool = tx.query_row(
+            &format!("SELECT EXISTS(SELECT 1 FROM {} WHERE username = ?1)", table),
+            [session_id],
+            |row| row.get(0),
+        )?;
+        if owned {
+            return Ok(());
+        }
+    }
+    for table in SESSION_TABLES {
+        tx.execute(
+            &format!("UPDATE {} SET username = ?1 WHERE username = ?2", table),
+            [session_id, username],
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// 准备会话的加密缓存
+///
+/// - 缓存由 `previous` 加密（启用信封加密、修改核心密码）时，在单个事务中将缓存、
+///   离线写入和冲突记录重新加密到 `cipher` 下
+/// - 缓存由其他未知密钥加密时清除该会话缓存的记录，下次查询会全量拉取；
+///   仍有离线写入或冲突时拒绝，避免丢失未同步的修改
+/// - 将旧版明文记录原地加密，并整理数据库文件以清除残留的明文页
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+/// * `cipher` - 缓存加密器
+/// * `previous` - 更换密钥前的缓存加密器（已知时）
+pub fn prepare_cache(
+    db_path: &Path,
+    session_id: &str,
+    cipher: &CacheCipher,
+    previous: Option<&CacheCipher>,
+) -> DurianResult<()> {
+    let conn = Connection::open(db_path)?;
+    let key_check = cipher.key_check();
+
+    let stored: Option<String> = match conn.query_row(
+        "SELECT key_check FROM cache_keys WHERE username = ?1",
+        [session_id],
+        |row| row.get(0),
+    ) {
+        Ok(check) => Some(check),
+        Err(rusqlite::Error::QueryReturnedNoRows) => None,
+        Err(e) => return Err(e.into()),
+    };
+
+    if stored.as_deref() != Some(key_check.as_str()) {
+        let tx = conn.unchecked_transaction()?;
+        let resealable = previous.filter(|previous| stored == Some(previous.key_check()));
+        if let Some(previous) = resealable {
+            reseal_cache(&tx, session_id, previous, cipher)?;
+        } else if stored.is_some() {
+            // 使用未知密钥加密的离线写入和冲突无法解密，也不能丢弃
+            let unsynced: i64 = tx.query_row(
+                "SELECT (SELECT COUNT(*) FROM pending_ops WHERE username = ?1)
+                      + (SELECT COUNT(*) FROM conflicts WHERE username = ?1)",
+                [session_id],
+                |row| row.get(0),
+            )?;
+            if unsynced > 0 {
+                return Err(DurianError::conflict(tr(Message::UnsyncedWritesUnderUnknownKey)));
+            }
+            tx.execute(
+                "DELETE FROM accounts WHERE username = ?1 AND encrypted = 1",
+                [session_id],
+            )?;
+            tx.execute("DELETE FROM cache_metadata WHERE username = ?1", [session_id])?;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO cache_keys (username, key_check) VALUES (?1, ?2)",
+            [session_id, &key_check],
+        )?;
+        tx.commit()?;
+    }
+
+    let plaintext_rows = {
+        let mut stmt = conn.prepare(
+            "SELECT rid, website, account FROM accounts WHERE username = ?1 AND encrypted = 0",
+        )?;
+        let rows = stmt
+            .query_map([session_id], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+
+    if plaintext_rows.is_empty() {
+        return Ok(());
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    {
+        let mut stmt = tx.prepare_cached(
+            "UPDATE accounts SET website = ?1, account = ?2, website_index = ?3, encrypted = 1
+             WHERE rid = ?4 AND username = ?5",
+        )?;
+        for (rid, website, account) in &plaintext_rows {
+            stmt.execute(rusqlite::params![
+                cipher.encrypt_field(website)?,
+                cipher.encrypt_field(account)?,
+                cipher.blind_index(website),
+                rid,
+                session_id
+            ])?;
+        }
+    }
+    tx.commit()?;
+
+    // 明文仍可能残留在 WAL 和空闲页中
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE); VACUUM;")?;
+
+    Ok(())
+}
+
+/// 将会话缓存中由 `previous` 加密的字段重新加密到 `cipher` 下
+///
+/// 包括 `accounts` 表（及盲索引）、离线写入队列和冲突记录，由调用方提交事务
+fn reseal_cache(
+    conn: &Connection,
+    session_id: &str,
+    previous: &CacheCipher,
+    cipher: &CacheCipher,
+) -> DurianResult<()> {
+    let accounts = {
+        let mut stmt = conn.prepare(
+            "SELECT rid, website, account FROM accounts WHERE username = ?1 AND encrypted = 1",
+        )?;
+        let rows = stmt
+            .query_map([session_id], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    {
+        let mut stmt = conn.prepare_cached(
+            "UPDATE accounts SET website = ?1, account = ?2, website_index = ?3
+             WHERE rid = ?4 AND username = ?5",
+        )?;
+        for (rid, website, account) in &accounts {
+            let website = previous.decrypt_field(website)?;
+            let account = previous.decrypt_field(account)?;
+            stmt.execute(rusqlite::params![
+                cipher.encrypt_field(&website)?,
+                cipher.encrypt_field(&account)?,
+                cipher.blind_index(&website),
+                rid,
+                session_id
+            ])?;
+        }
+    }
+
+    reseal_columns(conn, "pending_ops", "id", &["website", "account"], session_id, previous, cipher)?;
+    reseal_columns(
+        conn,
+        "conflicts",
+        "rid",
+        &["local_website", "local_account", "server_website", "server_account"],
+        session_id,
+        previous,
+        cipher,
+    )
+}
+
+/// 重新加密表中按 `key` 区分的各行的缓存字段列
+fn reseal_columns(
+    conn: &Connection,
+    table: &str,
+    key: &str,
+    columns: &[&str],
+    session_id: &str,
+    previous: &CacheCipher,
+    cipher: &CacheCipher,
+) -> DurianResult<()> {
+    let rows = {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}, {} FROM {} WHERE username = ?1",
+            key,
+            columns.join(", "),
+            table
+        ))?;
+        let rows = stmt
+            .query_map([session_id], |row| {
+                let values = (1..=columns.len())
+                    .map(|i| row.get::<_, String>(i))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok((row.get::<_, i64>(0)?, values))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+
+    let assignments: Vec<String> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| format!("{} = ?{}", column, i + 1))
+        .collect();
+    let mut stmt = conn.prepare(&format!(
+        "UPDATE {} SET {} WHERE {} = ?{} AND username = ?{}",
+        table,
+        assignments.join(", "),
+        key,
+        columns.len() + 1,
+        columns.len() + 2
+    ))?;
+    for (id, values) in rows {
+        let mut params = values
+            .iter()
+            .map(|value| {
+                let value = previous.decrypt_field(value)?;
+                cipher.encrypt_field(&value).map(Value::Text)
+            })
+            .collect::<DurianResult<Vec<_>>>()?;
+        params.push(Value::Integer(id));
+        params.push(Value::Text(session_id.to_string()));
+        stmt.execute(rusqlite::params_from_iter(params))?;
+    }
     Ok(())
 }
 
@@ -109,9 +545,12 @@ pub fn init_database(db_path: &Path) -> DurianResult<()> {
 /// - PULL_UPDATED: 增量更新，使用 INSERT OR REPLACE
 /// - PULL_NOTHING: 只更新时间戳
 ///
+/// 不处理已删除的记录，同步服务器的增量结果使用 [`save_cache_data_with_mode`]
+///
 /// # Arguments
 /// * `db_path` - 数据库文件路径
-/// * `username` - 用户名
+/// * `session_id` - 会话标识
+/// * `cipher` - 缓存加密器
 /// * `data` - 要保存的缓存数据
 /// * `pull_mode` - 拉取模式字符串
 ///
@@ -119,110 +558,247 @@ pub fn init_database(db_path: &Path) -> DurianResult<()> {
 /// 保存结果
 pub fn save_cache_data(
     db_path: &Path,
-    username: &str,
+    session_id: &str,
+    cipher: &CacheCipher,
     data: &CacheData,
     pull_mode: &str,
 ) -> DurianResult<()> {
     let mode = pull_mode.parse::<PullMode>()?;
-    save_cache_data_with_mode(db_path, username, data, mode)
+    save_cache_data_with_mode(db_path, session_id, cipher, data, &[], mode).map(|_| ())
 }
 
 /// 使用类型安全的 PullMode 保存缓存数据
+///
+/// 增量拉取时在同一事务中删除服务器返回的墓碑记录（`deleted_rids`）；
+/// 墓碑在写入更新之后应用，同一记录先修改后删除时以删除为准。
+/// 全量拉取时忽略墓碑
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+/// * `cipher` - 缓存加密器
+/// * `data` - 要保存的缓存数据
+/// * `deleted_rids` - 已在服务器上删除的记录 ID
+/// * `pull_mode` - 拉取模式
+///
+/// # Returns
+/// 缓存中新增、修改和删除的记录数
 pub fn save_cache_data_with_mode(
     db_path: &Path,
-    username: &str,
+    session_id: &str,
+    cipher: &CacheCipher,
     data: &CacheData,
+    deleted_rids: &[i64],
     pull_mode: PullMode,
-) -> DurianResult<()> {
+) -> DurianResult<SyncChanges> {
     let conn = Connection::open(db_path)?;
     let tx = conn.unchecked_transaction()?;
+    let changes = count_changes(&tx, session_id, data, deleted_rids, pull_mode)?;
 
     // 更新或插入最后更新时间
     tx.execute(
         "INSERT OR REPLACE INTO cache_metadata (username, last_update_time) VALUES (?1, ?2)",
-        [username, &data.update_time.to_string()],
+        [session_id, &data.update_time.to_string()],
     )?;
 
     match pull_mode {
         PullMode::PullAll => {
             // 全量更新：先删除旧数据，再批量插入新数据
-            tx.execute("DELETE FROM accounts WHERE username = ?1", [username])?;
-            batch_insert_accounts(&tx, username, &data.accounts)?;
+            tx.execute("DELETE FROM accounts WHERE username = ?1", [session_id])?;
+            batch_insert_accounts(&tx, session_id, cipher, &data.accounts)?;
         }
         PullMode::PullUpdated => {
-            // 增量更新：使用 INSERT OR REPLACE
-            batch_upsert_accounts(&tx, username, &data.accounts)?;
+            // 增量更新：使用 INSERT OR REPLACE，再删除墓碑记录
+            batch_upsert_accounts(&tx, session_id, cipher, &data.accounts)?;
+            batch_delete_accounts(&tx, session_id, deleted_rids)?;
         }
         PullMode::PullNothing => {
             // 无更新：只更新时间戳（已在上面完成）
+            batch_delete_accounts(&tx, session_id, deleted_rids)?;
         }
     }
 
     tx.commit()?;
-    Ok(())
+    Ok(changes)
+}
+
+/// 统计同步结果写入后缓存的变化（在写入前调用）
+///
+/// 修订号未变的记录不计为修改；服务器不提供修订号（为 0）时，
+/// 返回的已缓存记录都计为修改
+fn count_changes(
+    conn: &Connection,
+    session_id: &str,
+    data: &CacheData,
+    deleted_rids: &[i64],
+    pull_mode: PullMode,
+) -> DurianResult<SyncChanges> {
+    let cached: HashMap<i64, i64> = {
+        let mut stmt = conn.prepare("SELECT rid, revision FROM accounts WHERE username = ?1")?;
+        let rows = stmt
+            .query_map([session_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+        rows
+    };
+    // 墓碑在更新之后应用，同一记录先修改后删除时只计为删除
+    let deleted: HashSet<i64> = match pull_mode {
+        PullMode::PullAll => {
+            let received: HashSet<i64> = data.accounts.iter().map(|a| a.rid).collect();
+            cached
+                .keys()
+                .filter(|rid| !received.contains(rid))
+                .copied()
+                .collect()
+        }
+        PullMode::PullUpdated | PullMode::PullNothing => deleted_rids.iter().copied().collect(),
+    };
+
+    let mut changes = SyncChanges {
+        deleted: deleted
+            .iter()
+            .filter(|rid| cached.contains_key(rid))
+            .count(),
+        ..SyncChanges::default()
+    };
+    if pull_mode == PullMode::PullNothing {
+        return Ok(changes);
+    }
+    for account in data.accounts.iter().filter(|a| !deleted.contains(&a.rid)) {
+        match cached.get(&account.rid) {
+            None => changes.added += 1,
+            Some(&revision) if account.revision == 0 || revision != account.revision => {
+                changes.updated += 1
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(changes)
 }
 
 /// 批量插入账户（用于全量更新）
 fn batch_insert_accounts(
     conn: &Connection,
-    username: &str,
+    session_id: &str,
+    cipher: &CacheCipher,
     accounts: &[AccountRecord],
 ) -> DurianResult<()> {
     let mut stmt = conn.prepare_cached(
-        "INSERT INTO accounts (rid, username, website, account, password) VALUES (?1, ?2, ?3, ?4, ?5)",
+        "INSERT INTO accounts
+             (rid, username, website, account, password, website_index, encrypted, revision, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, 1, ?7, ?8)",
     )?;
 
     for account in accounts {
-        stmt.execute([
-            &account.rid.to_string(),
-            username,
-            &account.website,
-            &account.account,
-            &account.password,
+        stmt.execute(rusqlite::params![
+            account.rid,
+            session_id,
+            cipher.encrypt_field(&account.website)?,
+            cipher.encrypt_field(&account.account)?,
+            account.password,
+            cipher.blind_index(&account.website),
+            account.revision,
+            account.updated_at
         ])?;
     }
 
     Ok(())
 }
 
+/// 批量删除账户（用于应用墓碑）
+fn batch_delete_accounts(conn: &Connection, session_id: &str, rids: &[i64]) -> DurianResult<()> {
+    let mut stmt = conn.prepare_cached("DELETE FROM accounts WHERE username = ?1 AND rid = ?2")?;
+
+    for rid in rids {
+        stmt.execute([session_id, &rid.to_string()])?;
+    }
+
+    Ok(())
+}
+
 /// 批量更新/插入账户（用于增量更新）
 fn batch_upsert_accounts(
     conn: &Connection,
-    username: &str,
+    session_id: &str,
+    cipher: &CacheCipher,
     accounts: &[AccountRecord],
 ) -> DurianResult<()> {
     let mut stmt = conn.prepare_cached(
-        "INSERT OR REPLACE INTO accounts (rid, username, website, account, password) VALUES (?1, ?2, ?3, ?4, ?5)",
+        "INSERT OR REPLACE INTO accounts
+             (rid, username, website, account, password, website_index, encrypted, revision, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, 1, ?7, ?8)",
     )?;
 
     for account in accounts {
-        stmt.execute([
-            &account.rid.to_string(),
-            username,
-            &account.website,
-            &account.account,
-            &account.password,
+        stmt.execute(rusqlite::params![
+            account.rid,
+            session_id,
+            cipher.encrypt_field(&account.website)?,
+            cipher.encrypt_field(&account.account)?,
+            account.password,
+            cipher.blind_index(&account.website),
+            account.revision,
+            account.updated_at
         ])?;
     }
 
     Ok(())
 }
 
+/// 查询账户记录的列，顺序与 [`account_row`] 一致
+const ACCOUNT_COLUMNS: &str = "rid, website, account, password, revision, updated_at";
+
+/// 未解密的一行账户记录
+type AccountRow = (i64, String, String, String, i64, i64);
+
+/// 读取 [`ACCOUNT_COLUMNS`] 查询的一行
+fn account_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<AccountRow> {
+    Ok((
+        row.get(0)?,
+        row.get(1)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+        row.get(5)?,
+    ))
+}
+
+/// 解密一行账户记录
+fn decrypt_account_row(
+    cipher: &CacheCipher,
+    session_id: &str,
+    (rid, website, account, password, revision, updated_at): AccountRow,
+) -> DurianResult<AccountRecord> {
+    Ok(AccountRecord {
+        rid,
+        username: session_id.to_string(),
+        website: cipher.decrypt_field(&website)?,
+        account: cipher.decrypt_field(&account)?,
+        password,
+        revision,
+        updated_at,
+    })
+}
+
 /// 从数据库加载缓存数据
 ///
 /// # Arguments
 /// * `db_path` - 数据库文件路径
-/// * `username` - 用户名
+/// * `session_id` - 会话标识
+/// * `cipher` - 缓存加密器
 ///
 /// # Returns
 /// 缓存数据（如果存在且有效）
-pub fn load_cache_data(db_path: &Path, username: &str) -> DurianResult<Option<CacheData>> {
+pub fn load_cache_data(
+    db_path: &Path,
+    session_id: &str,
+    cipher: &CacheCipher,
+) -> DurianResult<Option<CacheData>> {
     let conn = Connection::open(db_path)?;
 
     // 获取最后更新时间
     let update_time: i64 = match conn.query_row(
         "SELECT last_update_time FROM cache_metadata WHERE username = ?1",
-        [username],
+        [session_id],
         |row| row.get(0),
     ) {
         Ok(time) => time,
@@ -235,43 +811,112 @@ pub fn load_cache_data(db_path: &Path, username: &str) -> DurianResult<Option<Ca
         return Ok(None);
     }
 
-    // 查询账户数据
-    let mut stmt = conn.prepare_cached(
-        "SELECT rid, website, account, password FROM accounts WHERE username = ?1 ORDER BY website",
-    )?;
+    // 查询账户数据（字段为密文，解密后再按网站排序）
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {} FROM accounts WHERE username = ?1 ORDER BY rid",
+        ACCOUNT_COLUMNS
+    ))?;
 
-    let accounts: Vec<AccountRecord> = stmt
-        .query_map([username], |row| {
-            Ok(AccountRecord {
-                rid: row.get(0)?,
-                website: row.get(1)?,
-                account: row.get(2)?,
-                password: row.get(3)?,
-                username: username.to_string(),
-            })
-        })?
+    let rows = stmt
+        .query_map([session_id], account_row)?
         .collect::<Result<Vec<_>, _>>()?;
 
+    let mut accounts = rows
+        .into_iter()
+        .map(|row| decrypt_account_row(cipher, session_id, row))
+        .collect::<DurianResult<Vec<_>>>()?;
+    accounts.sort_by(|a, b| a.website.cmp(&b.website));
+
     Ok(Some(CacheData {
         update_time,
         accounts,
-        username: username.to_string(),
+        username: session_id.to_string(),
     }))
 }
 
+/// 按网站查找缓存中的账户
+///
+/// 通过盲索引匹配，忽略首尾空白和大小写
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+/// * `cipher` - 缓存加密器
+/// * `website` - 网站
+pub fn find_accounts_by_website(
+    db_path: &Path,
+    session_id: &str,
+    cipher: &CacheCipher,
+    website: &str,
+) -> DurianResult<Vec<AccountRecord>> {
+    let conn = Connection::open(db_path)?;
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {} FROM accounts
+         WHERE username = ?1 AND website_index = ?2 ORDER BY rid",
+        ACCOUNT_COLUMNS
+    ))?;
+
+    let rows = stmt
+        .query_map([session_id, &cipher.blind_index(website)], account_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    rows.into_iter()
+        .map(|row| decrypt_account_row(cipher, session_id, row))
+        .collect()
+}
+
+/// 按记录 ID 获取缓存中的账户
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+/// * `cipher` - 缓存加密器
+/// * `rid` - 记录 ID
+pub fn get_account(
+    db_path: &Path,
+    session_id: &str,
+    cipher: &CacheCipher,
+    rid: i64,
+) -> DurianResult<Option<AccountRecord>> {
+    let conn = Connection::open(db_path)?;
+    find_account_row(&conn, session_id, rid)?
+        .map(|row| decrypt_account_row(cipher, session_id, row))
+        .transpose()
+}
+
+/// 读取一行未解密的账户记录
+fn find_account_row(
+    conn: &Connection,
+    session_id: &str,
+    rid: i64,
+) -> DurianResult<Option<AccountRow>> {
+    match conn.query_row(
+        &format!(
+            "SELECT {} FROM accounts WHERE username = ?1 AND rid = ?2",
+            ACCOUNT_COLUMNS
+        ),
+        rusqlite::params![session_id, rid],
+        account_row,
+    ) {
+        Ok(row) => Ok(Some(row)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
 /// 获取最后更新时间
 ///
 /// # Arguments
 /// * `db_path` - 数据库文件路径
-/// * `username` - 用户名
+/// * `session_id` - 会话标识
 ///
 /// # Returns
-/// 最后更新时间戳（如果用户不存在则返回 0）
-pub fn get_last_update_time(db_path: &Path, username: &str) -> DurianResult<i64> {
+/// 最后更新时间戳（如果会话没有缓存则返回 0）
+pub fn get_last_update_time(db_path: &Path, session_id: &str) -> DurianResult<i64> {
     let conn = Connection::open(db_path)?;
     match conn.query_row(
         "SELECT last_update_time FROM cache_metadata WHERE username = ?1",
-        [username],
+        [session_id],
         |row| row.get(0),
     ) {
         Ok(time) => Ok(time),
@@ -280,17 +925,33 @@ pub fn get_last_update_time(db_path: &Path, username: &str) -> DurianResult<i64>
     }
 }
 
-/// 删除用户的所有缓存数据
+/// 检查缓存中是否有已同步但缺少修订号的记录
+///
+/// v3 之前缓存的记录没有修订号，增量同步不会重新发送未修改的记录，需要全量拉取补齐
 ///
 /// # Arguments
 /// * `db_path` - 数据库文件路径
-/// * `username` - 用户名
-pub fn clear_user_cache(db_path: &Path, username: &str) -> DurianResult<()> {
+/// * `session_id` - 会话标识
+pub fn has_unrevisioned_accounts(db_path: &Path, session_id: &str) -> DurianResult<bool> {
+    let conn = Connection::open(db_path)?;
+    Ok(conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM accounts WHERE username = ?1 AND rid > 0 AND revision = 0)",
+        [session_id],
+        |row| row.get(0),
+    )?)
+}
+
+/// 删除会话的所有缓存数据
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+pub fn clear_user_cache(db_path: &Path, session_id: &str) -> DurianResult<()> {
     let conn = Connection::open(db_path)?;
     let tx = conn.unchecked_transaction()?;
 
-    tx.execute("DELETE FROM accounts WHERE username = ?1", [username])?;
-    tx.execute("DELETE FROM cache_metadata WHERE username = ?1", [username])?;
+    tx.execute("DELETE FROM accounts WHERE username = ?1", [session_id])?;
+    tx.execute("DELETE FROM cache_metadata WHERE username = ?1", [session_id])?;
 
     tx.commit()?;
     Ok(())
@@ -300,65 +961,971 @@ pub fn clear_user_cache(db_path: &Path, username: &str) -> DurianResult<()> {
 ///
 /// # Arguments
 /// * `db_path` - 数据库文件路径
-/// * `username` - 用户名
-pub fn get_account_count(db_path: &Path, username: &str) -> DurianResult<i64> {
+/// * `session_id` - 会话标识
+pub fn get_account_count(db_path: &Path, session_id: &str) -> DurianResult<i64> {
     let conn = Connection::open(db_path)?;
     let count: i64 = conn.query_row(
         "SELECT COUNT(*) FROM accounts WHERE username = ?1",
-        [username],
+        [session_id],
         |row| row.get(0),
     )?;
     Ok(count)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::NamedTempFile;
-
-    fn create_test_db() -> NamedTempFile {
-        let file = NamedTempFile::new().unwrap();
-        init_database(file.path()).unwrap();
-        file
-    }
+/// 批量更新账户密码（用于核心密码修改后的重新加密）
+///
+/// 所有更新在同一事务中完成
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+/// * `updates` - `(rid, 新密文, 新修订号)` 列表，服务器未返回修订号时保留原值
+pub fn update_account_passwords(
+    db_path: &Path,
+    session_id: &str,
+    updates: &[(i64, String, Option<i64>)],
+) -> DurianResult<()> {
+    let conn = Connection::open(db_path)?;
+    let tx = conn.unchecked_transaction()?;
 
-    #[test]
-    fn test_init_database() {
-        let file = NamedTempFile::new().unwrap();
-        let result = init_database(file.path());
-        assert!(result.is_ok());
+    {
+        let mut stmt = tx.prepare_cached(
+            "UPDATE accounts SET password = ?1, revision = COALESCE(?2, revision)
+             WHERE rid = ?3 AND username = ?4",
+        )?;
+        for (rid, password, revision) in updates {
+            stmt.execute(rusqlite::params![password, revision, rid, session_id])?;
+        }
     }
 
-    #[test]
-    fn test_pull_mode_parsing() {
-        assert_eq!(PullMode::from_str("PULL_ALL").unwrap(), PullMode::PullAll);
-        assert_eq!(PullMode::from_str("PULL_UPDATED").unwrap(), PullMode::PullUpdated);
-        assert_eq!(PullMode::from_str("PULL_NOTHING").unwrap(), PullMode::PullNothing);
-        assert!(PullMode::from_str("INVALID").is_err());
-    }
+    tx.commit()?;
+    Ok(())
+}
 
-    #[test]
-    fn test_save_and_load_cache_data() {
-        let file = create_test_db();
-        let username = "test_user";
+/// 获取缓存中所有记录的 `(rid, revision)`（用于缓存校验）
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+pub fn get_account_revisions(db_path: &Path, session_id: &str) -> DurianResult<Vec<(i64, i64)>> {
+    let conn = Connection::open(db_path)?;
+    let mut stmt = conn.prepare("SELECT rid, revision FROM accounts WHERE username = ?1")?;
+    let revisions = stmt
+        .query_map([session_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_, _>>()?;
+    Ok(revisions)
+}
 
-        let cache_data = CacheData {
-            username: username.to_string(),
-            update_time: 1234567890,
+/// 用服务器上的记录替换缓存中指定分桶的记录（用于修复缓存校验发现的不一致）
+///
+/// 分桶中不在服务器记录里的缓存记录被删除，其余记录写入缓存。
+/// 仍在离线写入队列中的记录保持不变，不覆盖乐观修改
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+/// * `cipher` - 缓存加密器
+/// * `bucket_count` - 分桶数
+/// * `buckets` - 要替换的分桶
+/// * `records` - 服务器上这些分桶中的全部记录
+///
+/// # Returns
+/// 缓存中新增、修改和删除的记录数
+pub fn replace_buckets(
+    db_path: &Path,
+    session_id: &str,
+    cipher: &CacheCipher,
+    bucket_count: usize,
+    buckets: &[usize],
+    records: &[AccountRecord],
+) -> DurianResult<SyncChanges> {
+    let conn = Connection::open(db_path)?;
+    let tx = conn.unchecked_transaction()?;
+
+    let pending: HashSet<i64> = {
+        let mut stmt = tx.prepare("SELECT DISTINCT rid FROM pending_ops WHERE username = ?1")?;
+        let rows = stmt
+            .query_map([session_id], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        rows
+    };
+    let buckets: HashSet<usize> = buckets.iter().copied().collect();
+    let in_scope = |rid: i64| {
+        !pending.contains(&rid) && buckets.contains(&verify::bucket_of(rid, bucket_count))
+    };
+
+    let cached: HashMap<i64, i64> = get_account_revisions(db_path, session_id)?
+        .into_iter()
+        .filter(|(rid, _)| in_scope(*rid))
+        .collect();
+    let records: Vec<AccountRecord> = records
+        .iter()
+        .filter(|record| in_scope(record.rid))
+        .cloned()
+        .collect();
+    let received: HashSet<i64> = records.iter().map(|record| record.rid).collect();
+    let stale: Vec<i64> = cached
+        .keys()
+        .filter(|rid| !received.contains(rid))
+        .copied()
+        .collect();
+
+    let mut changes = SyncChanges {
+        deleted: stale.len(),
+        ..SyncChanges::default()
+    };
+    for record in &records {
+        match cached.get(&record.rid) {
+            None => changes.added += 1,
+            Some(&revision) if revision != record.revision => changes.updated += 1,
+            Some(_) => {}
+        }
+    }
+
+    batch_delete_accounts(&tx, session_id, &stale)?;
+    batch_upsert_accounts(&tx, session_id, cipher, &records)?;
+
+    tx.commit()?;
+    Ok(changes)
+}
+
+// ============================================
+// 离线写入队列
+// ============================================
+
+/// 将写入操作加入离线队列，并在同一事务中乐观地应用到本地缓存
+///
+/// 插入操作使用负数的临时记录 ID（操作 ID 取负），同步成功后由
+/// [`complete_pending_op`] 替换为服务器分配的 ID
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+/// * `cipher` - 缓存加密器
+/// * `kind` - 操作类型
+/// * `item` - 发送给服务器的内容（修改操作的 `revision` 为其所基于的修订号）
+/// * `record` - 写入本地缓存的记录（网站和账户为明文），删除操作为 `None`
+/// * `created_at` - 加入队列的时间戳
+///
+/// # Returns
+/// 操作 ID 和本地记录 ID
+pub fn enqueue_pending_op(
+    db_path: &Path,
+    session_id: &str,
+    cipher: &CacheCipher,
+    kind: PendingOpKind,
+    item: &AccountItem,
+    record: Option<&AccountRecord>,
+    created_at: i64,
+) -> DurianResult<(i64, i64)> {
+    let conn = Connection::open(db_path)?;
+    let tx = conn.unchecked_transaction()?;
+
+    tx.execute(
+        "INSERT INTO pending_ops
+             (username, kind, rid, website, account, password, revision, fields_encrypted,
+              created_at, op_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, lower(hex(randomblob(16))))",
+        rusqlite::params![
+            session_id,
+            kind.as_str(),
+            item.rid,
+            cipher.encrypt_field(&item.website)?,
+            cipher.encrypt_field(&item.account)?,
+            item.password,
+            item.revision,
+            item.fields_encrypted,
+            created_at
+        ],
+    )?;
+    let id = tx.last_insert_rowid();
+
+    let rid = if kind == PendingOpKind::Insert {
+        tx.execute(
+            "UPDATE pending_ops SET rid = ?1 WHERE id = ?2",
+            rusqlite::params![-id, id],
+        )?;
+        -id
+    } else {
+        item.rid
+    };
+
+    match (kind, record) {
+        (PendingOpKind::Delete, _) => batch_delete_accounts(&tx, session_id, &[rid])?,
+        (_, Some(record)) => {
+            let record = AccountRecord {
+                rid,
+                ..record.clone()
+            };
+            batch_upsert_accounts(&tx, session_id, cipher, &[record])?;
+        }
+        (_, None) => return Err(DurianError::validation(tr(Message::MissingCacheRecord))),
+    }
+
+    tx.commit()?;
+    Ok((id, rid))
+}
+
+/// 获取队列中最早的一个等待发送的操作（跳过被服务器拒绝的操作）
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+/// * `cipher` - 缓存加密器
+pub fn next_pending_op(
+    db_path: &Path,
+    session_id: &str,
+    cipher: &CacheCipher,
+) -> DurianResult<Option<PendingOp>> {
+    let conn = Connection::open(db_path)?;
+    let row = match conn.query_row(
+        "SELECT id, kind, rid, website, account, password, revision, fields_encrypted, op_id
+         FROM pending_ops
+         WHERE username = ?1 AND failed = 0 ORDER BY id LIMIT 1",
+        [session_id],
+        |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, String>(5)?,
+                row.get::<_, i64>(6)?,
+                row.get::<_, bool>(7)?,
+                row.get::<_, String>(8)?,
+            ))
+        },
+    ) {
+        Ok(row) => row,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let (id, kind, rid, website, account, password, revision, fields_encrypted, op_id) = row;
+    Ok(Some(PendingOp {
+        id,
+        kind: kind.parse()?,
+        item: AccountItem::new(
+            rid,
+            cipher.decrypt_field(&website)?,
+            cipher.decrypt_field(&account)?,
+            password,
+        )
+        .with_revision(revision, 0)
+        .with_fields_encrypted(fields_encrypted),
+        op_id,
+    }))
+}
+
+/// 标记操作已发送成功并将其移出队列
+///
+/// - 插入操作的临时记录 ID 在本地缓存和队列中其后的操作里替换为服务器分配的 ID；
+///   服务器没有返回 ID 时删除临时记录并重置同步时间，下次同步时全量拉取
+/// - 修改操作的新修订号写入本地缓存和队列中其后对同一记录的修改
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+/// * `op` - 已发送的操作
+/// * `server_value` - 服务器返回的数据：插入时为分配的记录 ID，修改时为新的修订号
+pub fn complete_pending_op(
+    db_path: &Path,
+    session_id: &str,
+    op: &PendingOp,
+    server_value: Option<i64>,
+) -> DurianResult<()> {
+    let conn = Connection::open(db_path)?;
+    let tx = conn.unchecked_transaction()?;
+
+    tx.execute("DELETE FROM pending_ops WHERE id = ?1", [op.id])?;
+    match (op.kind, server_value) {
+        (PendingOpKind::Insert, Some(rid)) => {
+            tx.execute(
+                "UPDATE accounts SET rid = ?1, revision = ?2 WHERE username = ?3 AND rid = ?4",
+                rusqlite::params![rid, INITIAL_REVISION, session_id, op.item.rid],
+            )?;
+            tx.execute(
+                "UPDATE pending_ops SET rid = ?1, revision = ?2 WHERE username = ?3 AND rid = ?4",
+                rusqlite::params![rid, INITIAL_REVISION, session_id, op.item.rid],
+            )?;
+        }
+        (PendingOpKind::Insert, None) => {
+            batch_delete_accounts(&tx, session_id, &[op.item.rid])?;
+            reset_update_time(&tx, session_id)?;
+        }
+        (PendingOpKind::Update, Some(revision)) => {
+            tx.execute(
+                "UPDATE accounts SET revision = ?1 WHERE username = ?2 AND rid = ?3",
+                rusqlite::params![revision, session_id, op.item.rid],
+            )?;
+            tx.execute(
+                "UPDATE pending_ops SET revision = ?1 WHERE username = ?2 AND rid = ?3",
+                rusqlite::params![revision, session_id, op.item.rid],
+            )?;
+        }
+        _ => {}
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
+/// 记录操作发送失败（网络错误等），操作留在队列中稍后重试
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+/// * `id` - 操作 ID
+/// * `error` - 错误信息
+pub fn record_pending_op_error(
+    db_path: &Path,
+    session_id: &str,
+    id: i64,
+    error: &str,
+) -> DurianResult<()> {
+    let conn = Connection::open(db_path)?;
+    conn.execute(
+        "UPDATE pending_ops SET attempts = attempts + 1, last_error = ?1
+         WHERE id = ?2 AND username = ?3",
+        rusqlite::params![error, id, session_id],
+    )?;
+    Ok(())
+}
+
+/// 标记操作被服务器拒绝，不再重试
+///
+/// 被拒绝的插入会删除本地的临时记录，依赖它的后续操作同样标记为失败；
+/// 修改和删除无法在本地撤销，因此重置同步时间，下次同步时全量拉取服务器数据
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+/// * `op` - 被拒绝的操作
+/// * `error` - 服务器返回的错误信息
+pub fn reject_pending_op(
+    db_path: &Path,
+    session_id: &str,
+    op: &PendingOp,
+    error: &str,
+) -> DurianResult<()> {
+    let conn = Connection::open(db_path)?;
+    let tx = conn.unchecked_transaction()?;
+
+    tx.execute(
+        "UPDATE pending_ops SET failed = 1, attempts = attempts + 1, last_error = ?1
+         WHERE id = ?2",
+        rusqlite::params![error, op.id],
+    )?;
+    if op.kind == PendingOpKind::Insert {
+        batch_delete_accounts(&tx, session_id, &[op.item.rid])?;
+        tx.execute(
+            "UPDATE pending_ops SET failed = 1, last_error = ?1
+             WHERE username = ?2 AND rid = ?3 AND failed = 0",
+            rusqlite::params![error, session_id, op.item.rid],
+        )?;
+    } else {
+        reset_update_time(&tx, session_id)?;
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
+/// 将同步时间重置为 0，使下次查询全量拉取
+fn reset_update_time(conn: &Connection, session_id: &str) -> DurianResult<()> {
+    conn.execute(
+        "UPDATE cache_metadata SET last_update_time = 0 WHERE username = ?1",
+        [session_id],
+    )?;
+    Ok(())
+}
+
+/// 列出队列中的所有操作（包括被拒绝的操作），按加入队列的顺序
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+pub fn list_pending_ops(db_path: &Path, session_id: &str) -> DurianResult<Vec<PendingOpInfo>> {
+    let conn = Connection::open(db_path)?;
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, kind, rid, created_at, attempts, last_error, failed FROM pending_ops
+         WHERE username = ?1 ORDER BY id",
+    )?;
+
+    let rows = stmt
+        .query_map([session_id], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, i64>(4)?,
+                row.get::<_, Option<String>>(5)?,
+                row.get::<_, bool>(6)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    rows.into_iter()
+        .map(
+            |(id, kind, rid, created_at, attempts, last_error, failed)| {
+                Ok(PendingOpInfo {
+                    id,
+                    kind: kind.parse()?,
+                    rid,
+                    created_at,
+                    attempts,
+                    last_error,
+                    failed,
+                })
+            },
+        )
+        .collect()
+}
+
+/// 删除被服务器拒绝的操作
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+pub fn clear_failed_ops(db_path: &Path, session_id: &str) -> DurianResult<()> {
+    let conn = Connection::open(db_path)?;
+    conn.execute(
+        "DELETE FROM pending_ops WHERE username = ?1 AND failed = 1",
+        [session_id],
+    )?;
+    Ok(())
+}
+
+// ============================================
+// 修改冲突
+// ============================================
+
+/// 记录修改操作与其他设备的修改冲突
+///
+/// 在同一事务中：
+/// - 将本地缓存中的当前内容（包括其后尚未发送的修改）作为本地版本，与服务器版本一起保存
+/// - 将该操作和其后对同一记录的修改标记为失败，等待用户处理
+/// - 用服务器版本替换本地缓存中的记录
+///
+/// 本地缓存中已没有该记录（其后被删除）时直接移除该操作，由其后的删除操作覆盖服务器版本
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+/// * `cipher` - 缓存加密器
+/// * `op` - 发生冲突的修改操作
+/// * `server` - 服务器上的当前版本（网站和账户为明文）
+/// * `detected_at` - 发现冲突的时间戳
+///
+/// # Returns
+/// 是否记录了冲突
+pub fn record_conflict(
+    db_path: &Path,
+    session_id: &str,
+    cipher: &CacheCipher,
+    op: &PendingOp,
+    server: &AccountRecord,
+    detected_at: i64,
+) -> DurianResult<bool> {
+    let conn = Connection::open(db_path)?;
+    let tx = conn.unchecked_transaction()?;
+    let rid = op.item.rid;
+
+    // 缓存中的字段已由同一密钥加密，直接复制
+    let Some((_, website, account, password, _, _)) = find_account_row(&tx, session_id, rid)? else {
+        tx.execute("DELETE FROM pending_ops WHERE id = ?1", [op.id])?;
+        tx.commit()?;
+        return Ok(false);
+    };
+
+    tx.execute(
+        "INSERT OR REPLACE INTO conflicts
+             (username, rid, local_website, local_account, local_password, local_revision,
+              server_website, server_account, server_password, server_revision,
+              server_updated_at, detected_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        rusqlite::params![
+            session_id,
+            rid,
+            website,
+            account,
+            password,
+            op.item.revision,
+            cipher.encrypt_field(&server.website)?,
+            cipher.encrypt_field(&server.account)?,
+            server.password,
+            server.revision,
+            server.updated_at,
+            detected_at
+        ],
+    )?;
+
+    // 与其他设备的修改冲突，错误信息记录在操作上
+    let conflict_error = tr(Message::SyncConflict);
+    tx.execute(
+        "UPDATE pending_ops SET failed = 1, attempts = attempts + 1, last_error = ?1
+         WHERE id = ?2",
+        rusqlite::params![conflict_error, op.id],
+    )?;
+    tx.execute(
+        "UPDATE pending_ops SET failed = 1, last_error = ?1
+         WHERE username = ?2 AND rid = ?3 AND kind = ?4 AND failed = 0",
+        rusqlite::params![
+            conflict_error,
+            session_id,
+            rid,
+            PendingOpKind::Update.as_str()
+        ],
+    )?;
+    let server = AccountRecord {
+        rid,
+        username: session_id.to_string(),
+        ..server.clone()
+    };
+    batch_upsert_accounts(&tx, session_id, cipher, &[server])?;
+
+    tx.commit()?;
+    Ok(true)
+}
+
+/// 列出等待处理的修改冲突，按记录 ID 排序
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+/// * `cipher` - 缓存加密器
+pub fn list_conflicts(
+    db_path: &Path,
+    session_id: &str,
+    cipher: &CacheCipher,
+) -> DurianResult<Vec<AccountConflict>> {
+    let conn = Connection::open(db_path)?;
+    let mut stmt = conn.prepare_cached(
+        "SELECT rid, local_website, local_account, local_password, local_revision,
+                server_website, server_account, server_password, server_revision,
+                server_updated_at, detected_at
+         FROM conflicts WHERE username = ?1 ORDER BY rid",
+    )?;
+
+    let rows = stmt
+        .query_map([session_id], |row| {
+            let rid: i64 = row.get(0)?;
+            let local: AccountRow = (rid, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, 0);
+            let server: AccountRow = (
+                rid,
+                row.get(5)?,
+                row.get(6)?,
+                row.get(7)?,
+                row.get(8)?,
+                row.get(9)?,
+            );
+            Ok((rid, local, server, row.get::<_, i64>(10)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    rows.into_iter()
+        .map(|(rid, local, server, detected_at)| {
+            Ok(AccountConflict {
+                rid,
+                local: decrypt_account_row(cipher, session_id, local)?,
+                server: decrypt_account_row(cipher, session_id, server)?,
+                detected_at,
+            })
+        })
+        .collect()
+}
+
+/// 获取记录的修改冲突
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+/// * `cipher` - 缓存加密器
+/// * `rid` - 记录 ID
+pub fn get_conflict(
+    db_path: &Path,
+    session_id: &str,
+    cipher: &CacheCipher,
+    rid: i64,
+) -> DurianResult<Option<AccountConflict>> {
+    Ok(list_conflicts(db_path, session_id, cipher)?
+        .into_iter()
+        .find(|conflict| conflict.rid == rid))
+}
+
+/// 移除记录的修改冲突及因其失败的修改操作
+///
+/// 本地缓存中已是服务器版本，保留本地版本时由调用方重新加入写入队列
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+/// * `rid` - 记录 ID
+///
+/// # Returns
+/// 该记录是否存在冲突
+pub fn remove_conflict(db_path: &Path, session_id: &str, rid: i64) -> DurianResult<bool> {
+    let conn = Connection::open(db_path)?;
+    let tx = conn.unchecked_transaction()?;
+
+    let removed = tx.execute(
+        "DELETE FROM conflicts WHERE username = ?1 AND rid = ?2",
+        rusqlite::params![session_id, rid],
+    )?;
+    tx.execute(
+        "DELETE FROM pending_ops WHERE username = ?1 AND rid = ?2 AND kind = ?3 AND failed = 1",
+        rusqlite::params![session_id, rid, PendingOpKind::Update.as_str()],
+    )?;
+
+    tx.commit()?;
+    Ok(removed > 0)
+}
+
+// ============================================
+// 核心密码修改日志
+// ============================================
+
+/// 记录一次开始的核心密码修改
+///
+/// 已存在未完成的记录时保留原开始时间
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+/// * `started_at` - 开始时间戳
+pub fn begin_core_password_change(
+    db_path: &Path,
+    session_id: &str,
+    started_at: i64,
+) -> DurianResult<()> {
+    let conn = Connection::open(db_path)?;
+    conn.execute(
+        "INSERT OR IGNORE INTO core_password_changes (username, started_at) VALUES (?1, ?2)",
+        rusqlite::params![session_id, started_at],
+    )?;
+    Ok(())
+}
+
+/// 检查是否存在未完成的核心密码修改
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+pub fn has_pending_core_password_change(db_path: &Path, session_id: &str) -> DurianResult<bool> {
+    let conn = Connection::open(db_path)?;
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM core_password_changes WHERE username = ?1",
+        [session_id],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// 标记核心密码修改已完成
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+pub fn finish_core_password_change(db_path: &Path, session_id: &str) -> DurianResult<()> {
+    let conn = Connection::open(db_path)?;
+    conn.execute(
+        "DELETE FROM core_password_changes WHERE username = ?1",
+        [session_id],
+    )?;
+    Ok(())
+}
+
+// ============================================
+// 保险库密钥缓存
+// ============================================
+
+/// 保存（或替换）包装后的数据密钥
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+/// * `key` - 包装后的数据密钥
+pub fn save_wrapped_key(db_path: &Path, session_id: &str, key: &WrappedKey) -> DurianResult<()> {
+    let conn = Connection::open(db_path)?;
+    conn.execute(
+        "INSERT OR REPLACE INTO vault_keys (username, key_id, kdf, wrapped) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![
+            session_id,
+            key.key_id,
+            serde_json::to_string(&key.kdf)?,
+            key.wrapped
+        ],
+    )?;
+    Ok(())
+}
+
+/// 加载会话缓存的所有包装后的数据密钥
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+pub fn load_wrapped_keys(db_path: &Path, session_id: &str) -> DurianResult<Vec<WrappedKey>> {
+    let conn = Connection::open(db_path)?;
+    let mut stmt = conn.prepare_cached(
+        "SELECT key_id, kdf, wrapped FROM vault_keys WHERE username = ?1 ORDER BY key_id",
+    )?;
+
+    let rows = stmt
+        .query_map([session_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    rows.into_iter()
+        .map(|(key_id, kdf, wrapped)| {
+            Ok(WrappedKey {
+                key_id,
+                kdf: serde_json::from_str::<KdfParams>(&kdf)?,
+                wrapped,
+            })
+        })
+        .collect()
+}
+
+// ============================================
+// 保险库设置
+// ============================================
+
+/// 是否启用了全字段加密（服务器上设置的本地缓存，见 [`crate::vault`]）
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+pub fn get_full_field_encryption(db_path: &Path, session_id: &str) -> DurianResult<bool> {
+    let conn = Connection::open(db_path)?;
+    match conn.query_row(
+        "SELECT full_field_encryption FROM vault_settings WHERE username = ?1",
+        [session_id],
+        |row| row.get::<_, bool>(0),
+    ) {
+        Ok(enabled) => Ok(enabled),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// 缓存服务器上的全字段加密设置
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+/// * `enabled` - 是否启用
+pub fn set_full_field_encryption(
+    db_path: &Path,
+    session_id: &str,
+    enabled: bool,
+) -> DurianResult<()> {
+    let conn = Connection::open(db_path)?;
+    conn.execute(
+        "INSERT INTO vault_settings (username, full_field_encryption) VALUES (?1, ?2)
+         ON CONFLICT(username) DO UPDATE SET full_field_encryption = excluded.full_field_encryption",
+        rusqlite::params![session_id, enabled],
+    )?;
+    Ok(())
+}
+
+/// 加载自动锁定设置
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+pub fn get_lock_settings(db_path: &Path, session_id: &str) -> DurianResult<LockSettings> {
+    let conn = Connection::open(db_path)?;
+    match conn.query_row(
+        "SELECT idle_timeout_secs, lock_on_suspend FROM vault_settings WHERE username = ?1",
+        [session_id],
+        |row| {
+            Ok(LockSettings {
+                idle_timeout_secs: row.get::<_, i64>(0)?.max(0) as u64,
+                lock_on_suspend: row.get(1)?,
+            })
+        },
+    ) {
+        Ok(settings) => Ok(settings),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(LockSettings::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// 保存自动锁定设置
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+/// * `settings` - 自动锁定设置
+pub fn set_lock_settings(
+    db_path: &Path,
+    session_id: &str,
+    settings: &LockSettings,
+) -> DurianResult<()> {
+    let conn = Connection::open(db_path)?;
+    conn.execute(
+        "INSERT INTO vault_settings (username, idle_timeout_secs, lock_on_suspend) VALUES (?1, ?2, ?3)
+         ON CONFLICT(username) DO UPDATE SET
+             idle_timeout_secs = excluded.idle_timeout_secs,
+             lock_on_suspend = excluded.lock_on_suspend",
+        rusqlite::params![session_id, settings.idle_timeout_secs as i64, settings.lock_on_suspend],
+    )?;
+    Ok(())
+}
+
+/// 加载后台同步设置
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+pub fn get_sync_settings(db_path: &Path, session_id: &str) -> DurianResult<SyncSettings> {
+    let conn = Connection::open(db_path)?;
+    match conn.query_row(
+        "SELECT sync_interval_secs FROM vault_settings WHERE username = ?1",
+        [session_id],
+        |row| {
+            Ok(SyncSettings {
+                interval_secs: row.get::<_, i64>(0)?.max(0) as u64,
+            })
+        },
+    ) {
+        Ok(settings) => Ok(settings),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(SyncSettings::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// 保存后台同步设置
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+/// * `settings` - 后台同步设置
+pub fn set_sync_settings(
+    db_path: &Path,
+    session_id: &str,
+    settings: &SyncSettings,
+) -> DurianResult<()> {
+    let conn = Connection::open(db_path)?;
+    conn.execute(
+        "INSERT INTO vault_settings (username, sync_interval_secs) VALUES (?1, ?2)
+         ON CONFLICT(username) DO UPDATE SET sync_interval_secs = excluded.sync_interval_secs",
+        rusqlite::params![session_id, settings.interval_secs as i64],
+    )?;
+    Ok(())
+}
+
+/// 该账户在本设备上是否已使用过升级后的 KDF 参数登录
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+pub fn is_kdf_upgraded(db_path: &Path, session_id: &str) -> DurianResult<bool> {
+    let conn = Connection::open(db_path)?;
+    match conn.query_row(
+        "SELECT kdf_upgraded FROM vault_settings WHERE username = ?1",
+        [session_id],
+        |row| row.get::<_, bool>(0),
+    ) {
+        Ok(upgraded) => Ok(upgraded),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// 记录该账户已升级 KDF 参数（不可撤销）
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+pub fn set_kdf_upgraded(db_path: &Path, session_id: &str) -> DurianResult<()> {
+    let conn = Connection::open(db_path)?;
+    conn.execute(
+        "INSERT INTO vault_settings (username, kdf_upgraded) VALUES (?1, 1)
+         ON CONFLICT(username) DO UPDATE SET kdf_upgraded = 1",
+        [session_id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::VaultKey;
+    use crate::error::ErrorKind;
+    use std::str::FromStr;
+    use tempfile::NamedTempFile;
+
+    fn create_test_db() -> NamedTempFile {
+        let file = NamedTempFile::new().unwrap();
+        init_database(file.path()).unwrap();
+        file
+    }
+
+    fn test_cipher(core_password: &str) -> CacheCipher {
+        let params = KdfParams::argon2id(8 * 1024, 1, 1).unwrap();
+        CacheCipher::new(&VaultKey::derive(core_password, &params).unwrap()).unwrap()
+    }
+
+    fn sample_account(rid: i64, username: &str, website: &str) -> AccountRecord {
+        AccountRecord {
+            rid,
+            username: username.to_string(),
+            website: website.to_string(),
+            account: format!("user{}@{}", rid, website),
+            password: format!("encrypted_password_{}", rid),
+            revision: 0,
+            updated_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_init_database() {
+        let file = NamedTempFile::new().unwrap();
+        let result = init_database(file.path());
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_pull_mode_parsing() {
+        assert_eq!(PullMode::from_str("PULL_ALL").unwrap(), PullMode::PullAll);
+        assert_eq!(PullMode::from_str("PULL_UPDATED").unwrap(), PullMode::PullUpdated);
+        assert_eq!(PullMode::from_str("PULL_NOTHING").unwrap(), PullMode::PullNothing);
+        assert!(PullMode::from_str("INVALID").is_err());
+    }
+
+    #[test]
+    fn test_save_and_load_cache_data() {
+        let file = create_test_db();
+        let username = "test_user";
+        let cipher = test_cipher("core_password");
+
+        let cache_data = CacheData {
+            username: username.to_string(),
+            update_time: 1234567890,
             accounts: vec![AccountRecord {
                 rid: 1,
                 username: username.to_string(),
                 website: "example.com".to_string(),
                 account: "user@example.com".to_string(),
                 password: "encrypted_password".to_string(),
+                revision: 0,
+                updated_at: 0,
             }],
         };
 
         // 保存数据
-        save_cache_data(file.path(), username, &cache_data, "PULL_ALL").unwrap();
+        save_cache_data(file.path(), username, &cipher, &cache_data, "PULL_ALL").unwrap();
 
         // 加载数据
-        let loaded = load_cache_data(file.path(), username).unwrap();
+        let loaded = load_cache_data(file.path(), username, &cipher).unwrap();
         assert!(loaded.is_some());
 
         let loaded = loaded.unwrap();
@@ -371,6 +1938,7 @@ mod tests {
     fn test_incremental_update() {
         let file = create_test_db();
         let username = "test_user";
+        let cipher = test_cipher("core_password");
 
         // 初始数据
         let cache_data = CacheData {
@@ -382,9 +1950,11 @@ mod tests {
                 website: "site1.com".to_string(),
                 account: "user1".to_string(),
                 password: "pass1".to_string(),
+                revision: 0,
+                updated_at: 0,
             }],
         };
-        save_cache_data(file.path(), username, &cache_data, "PULL_ALL").unwrap();
+        save_cache_data(file.path(), username, &cipher, &cache_data, "PULL_ALL").unwrap();
 
         // 增量更新
         let update_data = CacheData {
@@ -396,19 +1966,105 @@ mod tests {
                 website: "site2.com".to_string(),
                 account: "user2".to_string(),
                 password: "pass2".to_string(),
+                revision: 0,
+                updated_at: 0,
             }],
         };
-        save_cache_data(file.path(), username, &update_data, "PULL_UPDATED").unwrap();
+        save_cache_data(file.path(), username, &cipher, &update_data, "PULL_UPDATED").unwrap();
 
         // 验证：应该有两条记录
         let count = get_account_count(file.path(), username).unwrap();
         assert_eq!(count, 2);
     }
 
+    #[test]
+    fn test_incremental_update_with_tombstones() {
+        let file = create_test_db();
+        let username = "test_user";
+        let cipher = test_cipher("core_password");
+        let record = |rid: i64, password: &str| AccountRecord {
+            rid,
+            username: username.to_string(),
+            website: format!("site{}.com", rid),
+            account: "user".to_string(),
+            password: password.to_string(),
+            revision: 0,
+            updated_at: 0,
+        };
+
+        let cache_data = CacheData::new(
+            username.to_string(),
+            1000,
+            vec![record(1, "pass1"), record(2, "pass2"), record(3, "pass3")],
+        );
+        save_cache_data(file.path(), username, &cipher, &cache_data, "PULL_ALL").unwrap();
+
+        // 同一批次中：更新 2，新增 4，删除 1；3 先被更新后被删除，以删除为准
+        let update_data = CacheData::new(
+            username.to_string(),
+            2000,
+            vec![record(2, "changed"), record(3, "changed"), record(4, "pass4")],
+        );
+        let changes = save_cache_data_with_mode(
+            file.path(),
+            username,
+            &cipher,
+            &update_data,
+            &[1, 3, 99],
+            PullMode::PullUpdated,
+        )
+        .unwrap();
+        assert_eq!(
+            changes,
+            SyncChanges {
+                added: 1,
+                updated: 1,
+                deleted: 2
+            }
+        );
+
+        let loaded = load_cache_data(file.path(), username, &cipher).unwrap().unwrap();
+        let mut rids: Vec<i64> = loaded.accounts.iter().map(|a| a.rid).collect();
+        rids.sort();
+        assert_eq!(rids, vec![2, 4]);
+        let updated = loaded.accounts.iter().find(|a| a.rid == 2).unwrap();
+        assert_eq!(updated.password, "changed");
+        assert_eq!(loaded.update_time, 2000);
+
+        // 只有墓碑时也会应用
+        let nothing = CacheData::new(username.to_string(), 3000, Vec::new());
+        let changes = save_cache_data_with_mode(
+            file.path(),
+            username,
+            &cipher,
+            &nothing,
+            &[2],
+            PullMode::PullNothing,
+        )
+        .unwrap();
+        assert_eq!(changes.deleted, 1);
+        assert_eq!(get_account_count(file.path(), username).unwrap(), 1);
+
+        // 墓碑只作用于本用户
+        save_cache_data(file.path(), "other", &cipher, &cache_data, "PULL_ALL").unwrap();
+        save_cache_data_with_mode(
+            file.path(),
+            username,
+            &cipher,
+            &nothing,
+            &[4, 1, 2, 3],
+            PullMode::PullUpdated,
+        )
+        .unwrap();
+        assert_eq!(get_account_count(file.path(), username).unwrap(), 0);
+        assert_eq!(get_account_count(file.path(), "other").unwrap(), 3);
+    }
+
     #[test]
     fn test_get_last_update_time() {
         let file = create_test_db();
         let username = "test_user";
+        let cipher = test_cipher("core_password");
 
         // 初始状态应返回 0
         let time = get_last_update_time(file.path(), username).unwrap();
@@ -420,7 +2076,7 @@ mod tests {
             update_time: 9999999,
             accounts: vec![],
         };
-        save_cache_data(file.path(), username, &cache_data, "PULL_ALL").unwrap();
+        save_cache_data(file.path(), username, &cipher, &cache_data, "PULL_ALL").unwrap();
 
         let time = get_last_update_time(file.path(), username).unwrap();
         assert_eq!(time, 9999999);
@@ -430,6 +2086,7 @@ mod tests {
     fn test_clear_user_cache() {
         let file = create_test_db();
         let username = "test_user";
+        let cipher = test_cipher("core_password");
 
         // 保存一些数据
         let cache_data = CacheData {
@@ -441,15 +2098,732 @@ mod tests {
                 website: "example.com".to_string(),
                 account: "user@example.com".to_string(),
                 password: "encrypted_password".to_string(),
+                revision: 0,
+                updated_at: 0,
             }],
         };
-        save_cache_data(file.path(), username, &cache_data, "PULL_ALL").unwrap();
+        save_cache_data(file.path(), username, &cipher, &cache_data, "PULL_ALL").unwrap();
 
         // 清除缓存
         clear_user_cache(file.path(), username).unwrap();
 
         // 验证缓存已清除
-        let loaded = load_cache_data(file.path(), username).unwrap();
+        let loaded = load_cache_data(file.path(), username, &cipher).unwrap();
         assert!(loaded.is_none());
     }
+
+    #[test]
+    fn test_update_account_passwords() {
+        let file = create_test_db();
+        let username = "test_user";
+        let cipher = test_cipher("core_password");
+
+        let record = |rid: i64, password: &str| {
+            AccountRecord::new(
+                rid,
+                username.to_string(),
+                format!("site{}.com", rid),
+                format!("user{}", rid),
+                password.to_string(),
+            )
+        };
+        let cache_data = CacheData {
+            username: username.to_string(),
+            update_time: 1000,
+            accounts: vec![record(1, "old1"), record(2, "old2")],
+        };
+        save_cache_data(file.path(), username, &cipher, &cache_data, "PULL_ALL").unwrap();
+
+        update_account_passwords(file.path(), username, &[(2, "new2".to_string(), Some(3))])
+            .unwrap();
+
+        let loaded = load_cache_data(file.path(), username, &cipher).unwrap().unwrap();
+        assert_eq!(loaded.accounts[0].password, "old1");
+        assert_eq!(loaded.accounts[1].password, "new2");
+        assert_eq!(loaded.accounts[1].revision, 3);
+    }
+
+    #[test]
+    fn test_core_password_change_journal() {
+        let file = create_test_db();
+        let username = "test_user";
+
+        assert!(!has_pending_core_password_change(file.path(), username).unwrap());
+
+        begin_core_password_change(file.path(), username, 1000).unwrap();
+        // 重复开始（恢复）不应报错
+        begin_core_password_change(file.path(), username, 2000).unwrap();
+        assert!(has_pending_core_password_change(file.path(), username).unwrap());
+        assert!(!has_pending_core_password_change(file.path(), "other_user").unwrap());
+
+        finish_core_password_change(file.path(), username).unwrap();
+        assert!(!has_pending_core_password_change(file.path(), username).unwrap());
+    }
+
+    #[test]
+    fn test_save_and_load_wrapped_keys() {
+        let file = create_test_db();
+        let username = "test_user";
+        assert!(load_wrapped_keys(file.path(), username).unwrap().is_empty());
+
+        let mut key = WrappedKey {
+            key_id: "core".to_string(),
+            kdf: KdfParams::legacy_vault(username),
+            wrapped: "durian:first".to_string(),
+        };
+        save_wrapped_key(file.path(), username, &key).unwrap();
+
+        // 同一 key_id 重新包装时替换
+        key.wrapped = "durian:second".to_string();
+        save_wrapped_key(file.path(), username, &key).unwrap();
+
+        let keys = load_wrapped_keys(file.path(), username).unwrap();
+        assert_eq!(keys, vec![key]);
+        assert!(load_wrapped_keys(file.path(), "other_user").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cache_fields_encrypted_at_rest() {
+        let file = create_test_db();
+        let username = "test_user";
+        let cipher = test_cipher("core_password");
+        prepare_cache(file.path(), username, &cipher, None).unwrap();
+
+        let cache_data = CacheData::new(
+            username.to_string(),
+            1000,
+            vec![
+                sample_account(1, username, "example.com"),
+                sample_account(2, username, "other.org"),
+            ],
+        );
+        save_cache_data(file.path(), username, &cipher, &cache_data, "PULL_ALL").unwrap();
+
+        // 数据库中不应出现网站和账户明文
+        let conn = Connection::open(file.path()).unwrap();
+        let stored: Vec<(String, String)> = conn
+            .prepare("SELECT website, account FROM accounts")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(stored.len(), 2);
+        for (website, account) in &stored {
+            assert!(!website.contains("example") && !website.contains("other"));
+            assert!(!account.contains('@'));
+        }
+
+        let found =
+            find_accounts_by_website(file.path(), username, &cipher, "Example.com").unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].rid, 1);
+        assert_eq!(found[0].account, "user1@example.com");
+    }
+
+    #[test]
+    fn test_migrate_plaintext_cache() {
+        let file = NamedTempFile::new().unwrap();
+        let username = "test_user";
+
+        // 模拟旧版（v0）明文缓存
+        {
+            let conn = Connection::open(file.path()).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE cache_metadata (
+                     username TEXT PRIMARY KEY,
+                     last_update_time INTEGER NOT NULL
+                 );
+                 CREATE TABLE accounts (
+                     rid INTEGER NOT NULL,
+                     username TEXT NOT NULL,
+                     website TEXT NOT NULL,
+                     account TEXT NOT NULL,
+                     password TEXT NOT NULL,
+                     PRIMARY KEY (rid, username)
+                 );
+                 CREATE INDEX idx_accounts_website ON accounts(website);
+                 INSERT INTO cache_metadata VALUES ('test_user', 1000);
+                 INSERT INTO accounts VALUES (1, 'test_user', 'example.com', 'alice', 'ct1');",
+            )
+            .unwrap();
+        }
+
+        init_database(file.path()).unwrap();
+        let cipher = test_cipher("core_password");
+        prepare_cache(file.path(), username, &cipher, None).unwrap();
+
+        let conn = Connection::open(file.path()).unwrap();
+        let website: String = conn
+            .query_row("SELECT website FROM accounts WHERE rid = 1", [], |row| row.get(0))
+            .unwrap();
+        assert_ne!(website, "example.com");
+
+        let loaded = load_cache_data(file.path(), username, &cipher).unwrap().unwrap();
+        assert_eq!(loaded.accounts[0].website, "example.com");
+        assert_eq!(loaded.accounts[0].account, "alice");
+        assert_eq!(loaded.accounts[0].password, "ct1");
+        let found =
+            find_accounts_by_website(file.path(), username, &cipher, "example.com").unwrap();
+        assert_eq!(found.len(), 1);
+
+        // 重复初始化和准备是幂等的
+        init_database(file.path()).unwrap();
+        prepare_cache(file.path(), username, &cipher, None).unwrap();
+        assert_eq!(get_account_count(file.path(), username).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_interrupted_migration_resumes() {
+        let file = NamedTempFile::new().unwrap();
+        let user_version = |conn: &Connection| -> i64 {
+            conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap()
+        };
+
+        // 旧版缓存缺少离线写入队列表，v3 迁移失败
+        {
+            let conn = Connection::open(file.path()).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE cache_metadata (
+                     username TEXT PRIMARY KEY,
+                     last_update_time INTEGER NOT NULL
+                 );
+                 CREATE TABLE accounts (
+                     rid INTEGER NOT NULL,
+                     username TEXT NOT NULL,
+                     website TEXT NOT NULL,
+                     account TEXT NOT NULL,
+                     password TEXT NOT NULL,
+                     PRIMARY KEY (rid, username)
+                 );
+                 CREATE TABLE vault_settings (
+                     username TEXT PRIMARY KEY,
+                     full_field_encryption INTEGER NOT NULL DEFAULT 0
+                 );",
+            )
+            .unwrap();
+            assert!(migrate_schema(&conn).is_err());
+
+            // 已完成的步骤连同版本号一起提交，失败的一步整体回滚
+            assert_eq!(user_version(&conn), 2);
+            let has_revision: bool = conn
+                .query_row(
+                    "SELECT EXISTS(SELECT 1 FROM pragma_table_info('accounts') WHERE name = 'revision')",
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert!(!has_revision);
+        }
+
+        // 再次启动时从 v3 继续，不会重复添加已有的列
+        init_database(file.path()).unwrap();
+        let conn = Connection::open(file.path()).unwrap();
+        assert_eq!(user_version(&conn), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_cache_cleared_when_key_changes() {
+        let file = create_test_db();
+        let username = "test_user";
+        let cipher = test_cipher("core_password");
+        prepare_cache(file.path(), username, &cipher, None).unwrap();
+
+        let cache_data = CacheData::new(
+            username.to_string(),
+            1000,
+            vec![sample_account(1, username, "example.com")],
+        );
+        save_cache_data(file.path(), username, &cipher, &cache_data, "PULL_ALL").unwrap();
+
+        let new_cipher = test_cipher("new_core_password");
+        prepare_cache(file.path(), username, &new_cipher, None).unwrap();
+        assert_eq!(get_account_count(file.path(), username).unwrap(), 0);
+        assert_eq!(get_last_update_time(file.path(), username).unwrap(), 0);
+        assert!(load_cache_data(file.path(), username, &new_cipher).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_unknown_key_keeps_unsynced_writes() {
+        let file = create_test_db();
+        let username = "test_user";
+        let cipher = test_cipher("core_password");
+        prepare_cache(file.path(), username, &cipher, None).unwrap();
+        let item = AccountItem::new(0, "b.com".into(), "bob".into(), "secret".into());
+        enqueue_pending_op(
+            file.path(),
+            username,
+            &cipher,
+            PendingOpKind::Insert,
+            &item,
+            Some(&sample_account(0, username, "b.com")),
+            0,
+        )
+        .unwrap();
+
+        // 未知密钥无法解密离线写入，拒绝打开缓存，写入和缓存都不受影响
+        let new_cipher = test_cipher("new_core_password");
+        let result = prepare_cache(file.path(), username, &new_cipher, None);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::Conflict);
+        assert_eq!(get_account_count(file.path(), username).unwrap(), 1);
+        let op = next_pending_op(file.path(), username, &cipher).unwrap().unwrap();
+        assert_eq!(op.item.website, "b.com");
+
+        // 原密钥仍可正常打开
+        prepare_cache(file.path(), username, &cipher, None).unwrap();
+    }
+
+    #[test]
+    fn test_cache_resealed_when_key_replaced() {
+        let file = create_test_db();
+        let username = "test_user";
+        let cipher = test_cipher("core_password");
+        prepare_cache(file.path(), username, &cipher, None).unwrap();
+
+        let cache_data = CacheData::new(
+            username.to_string(),
+            1000,
+            vec![sample_account(1, username, "example.com")],
+        );
+        save_cache_data(file.path(), username, &cipher, &cache_data, "PULL_ALL").unwrap();
+        let item = AccountItem::new(0, "b.com".into(), "bob".into(), "secret".into());
+        enqueue_pending_op(
+            file.path(),
+            username,
+            &cipher,
+            PendingOpKind::Insert,
+            &item,
+            Some(&sample_account(0, username, "b.com")),
+            0,
+        )
+        .unwrap();
+
+        // 已知原密钥时缓存和离线写入重新加密到新密钥下，不会丢失
+        let new_cipher = test_cipher("new_core_password");
+        prepare_cache(file.path(), username, &new_cipher, Some(&cipher)).unwrap();
+        assert_eq!(get_last_update_time(file.path(), username).unwrap(), 1000);
+        let loaded = load_cache_data(file.path(), username, &new_cipher).unwrap().unwrap();
+        assert_eq!(loaded.accounts.len(), 2);
+        let found =
+            find_accounts_by_website(file.path(), username, &new_cipher, "example.com").unwrap();
+        assert_eq!(found.len(), 1);
+        let op = next_pending_op(file.path(), username, &new_cipher).unwrap().unwrap();
+        assert_eq!(op.item.website, "b.com");
+        assert_eq!(op.item.account, "bob");
+        assert!(next_pending_op(file.path(), username, &cipher).is_err());
+    }
+
+    #[test]
+    fn test_full_field_encryption_setting() {
+        let file = create_test_db();
+        let username = "test_user";
+
+        assert!(!get_full_field_encryption(file.path(), username).unwrap());
+        set_full_field_encryption(file.path(), username, true).unwrap();
+        assert!(get_full_field_encryption(file.path(), username).unwrap());
+        assert!(!get_full_field_encryption(file.path(), "other_user").unwrap());
+        set_full_field_encryption(file.path(), username, false).unwrap();
+        assert!(!get_full_field_encryption(file.path(), username).unwrap());
+    }
+
+    #[test]
+    fn test_lock_settings() {
+        let file = create_test_db();
+        let username = "test_user";
+        assert_eq!(get_lock_settings(file.path(), username).unwrap(), LockSettings::default());
+
+        // 与全字段加密设置共用一行，互不覆盖
+        set_full_field_encryption(file.path(), username, true).unwrap();
+        let settings = LockSettings {
+            idle_timeout_secs: 60,
+            lock_on_suspend: false,
+        };
+        set_lock_settings(file.path(), username, &settings).unwrap();
+        assert_eq!(get_lock_settings(file.path(), username).unwrap(), settings);
+        assert!(get_full_field_encryption(file.path(), username).unwrap());
+
+        assert_eq!(get_sync_settings(file.path(), username).unwrap(), SyncSettings::default());
+        let sync_settings = SyncSettings { interval_secs: 0 };
+        set_sync_settings(file.path(), username, &sync_settings).unwrap();
+        assert_eq!(get_sync_settings(file.path(), username).unwrap(), sync_settings);
+        assert_eq!(get_lock_settings(file.path(), username).unwrap(), settings);
+    }
+
+    #[test]
+    fn test_full_sync_counts_changes_by_revision() {
+        let file = create_test_db();
+        let username = "test_user";
+        let cipher = test_cipher("core_password");
+        let record = |rid: i64, revision: i64| {
+            AccountRecord::new(
+                rid,
+                username.to_string(),
+                "a.com".into(),
+                "u".into(),
+                "p".into(),
+            )
+            .with_revision(revision, 0)
+        };
+
+        let cache_data =
+            CacheData::new(username.to_string(), 1000, vec![record(1, 1), record(2, 1)]);
+        let changes = save_cache_data_with_mode(
+            file.path(),
+            username,
+            &cipher,
+            &cache_data,
+            &[],
+            PullMode::PullAll,
+        )
+        .unwrap();
+        assert_eq!(changes.added, 2);
+
+        save_cache_data(
+            file.path(),
+            username,
+            &cipher,
+            &CacheData::new(username.to_string(), 1500, vec![record(4, 1)]),
+            "PULL_UPDATED",
+        )
+        .unwrap();
+        // 1 未变，2 被修改，3 新增；缺少的记录视为已删除
+        let cache_data = CacheData::new(
+            username.to_string(),
+            2000,
+            vec![record(1, 1), record(2, 2), record(3, 1)],
+        );
+        let changes = save_cache_data_with_mode(
+            file.path(),
+            username,
+            &cipher,
+            &cache_data,
+            &[],
+            PullMode::PullAll,
+        )
+        .unwrap();
+        assert_eq!(
+            changes,
+            SyncChanges {
+                added: 1,
+                updated: 1,
+                deleted: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_replace_buckets() {
+        let file = create_test_db();
+        let username = "test_user";
+        let cipher = test_cipher("core_password");
+        let record = |rid: i64, revision: i64, account: &str| {
+            AccountRecord::new(
+                rid,
+                username.to_string(),
+                "a.com".into(),
+                account.into(),
+                "p".into(),
+            )
+            .with_revision(revision, 0)
+        };
+
+        // 16 个分桶：1 和 17 在分桶 1，2 在分桶 2
+        let cache_data = CacheData::new(
+            username.to_string(),
+            1000,
+            vec![record(1, 1, "u"), record(17, 1, "u"), record(2, 1, "u")],
+        );
+        save_cache_data(file.path(), username, &cipher, &cache_data, "PULL_ALL").unwrap();
+
+        // 服务器上 1 已修改，17 已删除，33 为新记录；分桶 2 中的记录不受影响
+        let server = vec![record(1, 2, "v"), record(33, 1, "w"), record(4, 1, "x")];
+        let changes = replace_buckets(file.path(), username, &cipher, 16, &[1], &server).unwrap();
+        assert_eq!(
+            changes,
+            SyncChanges {
+                added: 1,
+                updated: 1,
+                deleted: 1
+            }
+        );
+        let mut revisions = get_account_revisions(file.path(), username).unwrap();
+        revisions.sort_unstable();
+        assert_eq!(revisions, vec![(1, 2), (2, 1), (33, 1)]);
+        let account = get_account(file.path(), username, &cipher, 1)
+            .unwrap()
+            .unwrap();
+        assert_eq!(account.account, "v");
+
+        // 仍在离线队列中的记录不被覆盖或删除
+        let edited = AccountItem::new(1, "a.com".into(), "local".into(), "p".into());
+        enqueue_pending_op(
+            file.path(),
+            username,
+            &cipher,
+            PendingOpKind::Update,
+            &edited,
+            Some(&record(1, 2, "local")),
+            0,
+        )
+        .unwrap();
+        let changes = replace_buckets(file.path(), username, &cipher, 16, &[1], &[]).unwrap();
+        assert_eq!(changes.deleted, 1);
+        let account = get_account(file.path(), username, &cipher, 1)
+            .unwrap()
+            .unwrap();
+        assert_eq!(account.account, "local");
+        assert!(get_account(file.path(), username, &cipher, 33)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_pending_ops_queue() {
+        let file = create_test_db();
+        let username = "test_user";
+        let cipher = test_cipher("core_password");
+        let cache_data = CacheData::new(
+            username.to_string(),
+            1000,
+            vec![sample_account(1, username, "a.com")],
+        );
+        save_cache_data(file.path(), username, &cipher, &cache_data, "PULL_ALL").unwrap();
+
+        let item = |rid: i64, website: &str| {
+            AccountItem::new(rid, website.to_string(), "user".to_string(), "secret".to_string())
+        };
+        let record = |website: &str| sample_account(0, username, website);
+        let enqueue = |kind, item: &AccountItem, record: Option<&AccountRecord>| {
+            enqueue_pending_op(file.path(), username, &cipher, kind, item, record, 0).unwrap()
+        };
+
+        // 离线插入、修改刚插入的记录、删除已有记录：立即反映在缓存中
+        let (insert_id, temp_rid) =
+            enqueue(PendingOpKind::Insert, &item(0, "b.com"), Some(&record("b.com")));
+        assert!(temp_rid < 0);
+        enqueue(
+            PendingOpKind::Update,
+            &item(temp_rid, "c.com"),
+            Some(&sample_account(temp_rid, username, "c.com")),
+        );
+        enqueue(PendingOpKind::Delete, &item(1, ""), None);
+
+        let loaded = load_cache_data(file.path(), username, &cipher).unwrap().unwrap();
+        assert_eq!(loaded.accounts.len(), 1);
+        assert_eq!(loaded.accounts[0].rid, temp_rid);
+        assert_eq!(loaded.accounts[0].website, "c.com");
+
+        let ops = list_pending_ops(file.path(), username).unwrap();
+        let kinds: Vec<_> = ops.iter().map(|op| op.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![PendingOpKind::Insert, PendingOpKind::Update, PendingOpKind::Delete]
+        );
+
+        // 队列中的网站和账户字段加密保存
+        let conn = Connection::open(file.path()).unwrap();
+        let website: String = conn
+            .query_row("SELECT website FROM pending_ops WHERE id = ?1", [insert_id], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_ne!(website, "b.com");
+
+        // 按顺序回放：插入成功后临时 ID 在缓存和后续操作中替换为服务器 ID
+        let op = next_pending_op(file.path(), username, &cipher).unwrap().unwrap();
+        assert_eq!(op.id, insert_id);
+        assert_eq!(op.item.website, "b.com");
+        record_pending_op_error(file.path(), username, op.id, "网络请求失败").unwrap();
+        let info = &list_pending_ops(file.path(), username).unwrap()[0];
+        assert_eq!(info.attempts, 1);
+        assert_eq!(info.last_error.as_deref(), Some("网络请求失败"));
+        assert!(!info.failed);
+
+        complete_pending_op(file.path(), username, &op, Some(42)).unwrap();
+        let op = next_pending_op(file.path(), username, &cipher).unwrap().unwrap();
+        assert_eq!(op.kind, PendingOpKind::Update);
+        assert_eq!(op.item.rid, 42);
+        assert_eq!(op.item.revision, INITIAL_REVISION);
+        let loaded = load_cache_data(file.path(), username, &cipher).unwrap().unwrap();
+        assert_eq!(loaded.accounts[0].rid, 42);
+
+        complete_pending_op(file.path(), username, &op, None).unwrap();
+        let op = next_pending_op(file.path(), username, &cipher).unwrap().unwrap();
+        complete_pending_op(file.path(), username, &op, None).unwrap();
+        assert!(next_pending_op(file.path(), username, &cipher).unwrap().is_none());
+        assert!(list_pending_ops(file.path(), username).unwrap().is_empty());
+        assert_eq!(get_last_update_time(file.path(), username).unwrap(), 1000);
+    }
+
+    #[test]
+    fn test_rejected_pending_ops() {
+        let file = create_test_db();
+        let username = "test_user";
+        let cipher = test_cipher("core_password");
+        let cache_data = CacheData::new(
+            username.to_string(),
+            1000,
+            vec![sample_account(1, username, "a.com")],
+        );
+        save_cache_data(file.path(), username, &cipher, &cache_data, "PULL_ALL").unwrap();
+
+        let item = |rid: i64| {
+            AccountItem::new(rid, "b.com".to_string(), "user".to_string(), "secret".to_string())
+        };
+        let record = sample_account(0, username, "b.com");
+
+        // 被拒绝的插入删除临时记录，依赖它的修改同样失败
+        let (_, temp_rid) = enqueue_pending_op(
+            file.path(),
+            username,
+            &cipher,
+            PendingOpKind::Insert,
+            &item(0),
+            Some(&record),
+            0,
+        )
+        .unwrap();
+        enqueue_pending_op(
+            file.path(),
+            username,
+            &cipher,
+            PendingOpKind::Update,
+            &item(temp_rid),
+            Some(&AccountRecord {
+                rid: temp_rid,
+                ..record.clone()
+            }),
+            0,
+        )
+        .unwrap();
+        let op = next_pending_op(file.path(), username, &cipher).unwrap().unwrap();
+        reject_pending_op(file.path(), username, &op, "网站不能为空").unwrap();
+
+        assert!(next_pending_op(file.path(), username, &cipher).unwrap().is_none());
+        let ops = list_pending_ops(file.path(), username).unwrap();
+        assert!(ops.iter().all(|op| op.failed));
+        assert_eq!(ops[1].last_error.as_deref(), Some("网站不能为空"));
+        assert_eq!(get_account_count(file.path(), username).unwrap(), 1);
+        assert_eq!(get_last_update_time(file.path(), username).unwrap(), 1000);
+
+        // 被拒绝的删除无法在本地撤销：重置同步时间以便全量拉取
+        enqueue_pending_op(
+            file.path(),
+            username,
+            &cipher,
+            PendingOpKind::Delete,
+            &item(1),
+            None,
+            0,
+        )
+        .unwrap();
+        assert_eq!(get_account_count(file.path(), username).unwrap(), 0);
+        let op = next_pending_op(file.path(), username, &cipher).unwrap().unwrap();
+        reject_pending_op(file.path(), username, &op, "记录不存在").unwrap();
+        assert_eq!(get_last_update_time(file.path(), username).unwrap(), 0);
+
+        clear_failed_ops(file.path(), username).unwrap();
+        assert!(list_pending_ops(file.path(), username).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_conflict_store() {
+        let file = create_test_db();
+        let username = "test_user";
+        let cipher = test_cipher("core_password");
+        let cache_data = CacheData::new(
+            username.to_string(),
+            1000,
+            vec![sample_account(1, username, "a.com").with_revision(3, 100)],
+        );
+        save_cache_data(file.path(), username, &cipher, &cache_data, "PULL_ALL").unwrap();
+
+        let update = |website: &str| {
+            let item = AccountItem::new(
+                1,
+                website.to_string(),
+                "user".to_string(),
+                "secret".to_string(),
+            )
+            .with_revision(3, 0);
+            let record = sample_account(1, username, website).with_revision(3, 100);
+            enqueue_pending_op(
+                file.path(),
+                username,
+                &cipher,
+                PendingOpKind::Update,
+                &item,
+                Some(&record),
+                0,
+            )
+            .unwrap();
+        };
+
+        // 修改成功后新的修订号写入缓存和其后的修改
+        update("b.com");
+        update("c.com");
+        let op = next_pending_op(file.path(), username, &cipher)
+            .unwrap()
+            .unwrap();
+        assert_eq!(op.item.revision, 3);
+        complete_pending_op(file.path(), username, &op, Some(4)).unwrap();
+        let op = next_pending_op(file.path(), username, &cipher)
+            .unwrap()
+            .unwrap();
+        assert_eq!(op.item.revision, 4);
+        let cached = get_account(file.path(), username, &cipher, 1)
+            .unwrap()
+            .unwrap();
+        assert_eq!(cached.revision, 4);
+
+        // 冲突：本地最新内容与服务器版本一起保存，缓存替换为服务器版本
+        update("d.com");
+        let server = sample_account(1, username, "server.com").with_revision(5, 200);
+        assert!(record_conflict(file.path(), username, &cipher, &op, &server, 50).unwrap());
+
+        assert!(next_pending_op(file.path(), username, &cipher)
+            .unwrap()
+            .is_none());
+        let ops = list_pending_ops(file.path(), username).unwrap();
+        assert_eq!(ops.len(), 2);
+        assert!(ops
+            .iter()
+            .all(|op| op.failed && op.last_error == Some(tr(Message::SyncConflict))));
+        let cached = get_account(file.path(), username, &cipher, 1)
+            .unwrap()
+            .unwrap();
+        assert_eq!(cached.website, "server.com");
+        assert_eq!(cached.revision, 5);
+
+        let conflicts = list_conflicts(file.path(), username, &cipher).unwrap();
+        assert_eq!(conflicts.len(), 1);
+        let conflict = &conflicts[0];
+        assert_eq!(conflict.local.website, "d.com");
+        assert_eq!(conflict.local.revision, 4);
+        assert_eq!(conflict.server.website, "server.com");
+        assert_eq!(conflict.server.revision, 5);
+        assert_eq!(conflict.server.updated_at, 200);
+        assert_eq!(conflict.detected_at, 50);
+        assert!(get_conflict(file.path(), username, &cipher, 2)
+            .unwrap()
+            .is_none());
+
+        // 冲突记录中的网站和账户字段加密保存
+        let conn = Connection::open(file.path()).unwrap();
+        let website: String = conn
+            .query_row(
+                "SELECT local_website FROM conflicts WHERE rid = 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_ne!(website, "d.com");
+
+        assert!(remove_conflict(file.path(), username, 1).unwrap());
+        assert!(!remove_conflict(file.path(), username, 1).unwrap());
+        assert!(list_conflicts(file.path(), username, &cipher)
+            .unwrap()
+            .is_empty());
+        assert!(list_pending_ops(file.path(), username).unwrap().is_empty());
+    }
 }
@@ -1,8 +1,24 @@
 //! 统一错误处理模块
 //!
 //! 定义应用中所有可能的错误类型，提供结构化的错误处理
+//!
+//! # 命令边界
+//! Tauri 命令直接返回 [`DurianError`]，序列化为 [`ErrorPayload`]：
+//! `{kind, code, message, retryable, details}`。前端按 `kind` 区分错误，
+//! 不依赖错误消息的文本；`message` 为可直接展示的错误描述
+//!
+//! API 错误按 `ApiResponse.code` 和 HTTP 状态码映射为稳定的 [`ErrorKind`]，
+//! 原始错误码保留在 `code` 中，HTTP 状态码保留在 `details.status` 中
+//!
+//! # 本地化
+//! 错误描述由消息目录（见 [`crate::i18n`]）按当前语言生成
 
+use serde::{Deserialize, Serialize, Serializer};
 use std::fmt;
+use ts_rs::TS;
+
+use crate::i18n::{current_locale, tr, Locale, Message};
+use crate::models::ApiResponse;
 
 // ============================================
 // 错误类型定义
@@ -17,10 +33,30 @@ pub enum DurianError {
     StateNotInitialized,
     /// 状态锁定失败
     StateLockError,
+    /// 保险库已锁定，需要使用核心密码解锁
+    Locked,
+    /// 指定用户没有已登录的会话
+    SessionNotFound(String),
+    /// 服务器证书公钥与固定的指纹不匹配
+    CertificatePinMismatch { fingerprint: String },
+    /// 服务器证书不受信任，需要用户确认是否信任该指纹
+    UntrustedCertificate { fingerprint: String },
     /// 网络请求错误
     NetworkError(String),
-    /// API 响应错误
-    ApiError { code: i32, message: String },
+    /// 请求已被取消
+    Cancelled,
+    /// API 响应错误（`status` 为响应的 HTTP 状态码）
+    ApiError {
+        code: i32,
+        message: String,
+        status: Option<u16>,
+    },
+    /// HTTP 错误（响应体不是 API 响应，例如代理返回的错误页面）
+    HttpError { status: u16, message: String },
+    /// 用户名、密码或核心密码错误
+    InvalidCredentials(String),
+    /// 记录存在未处理的修改冲突
+    Conflict(String),
     /// 数据库错误
     DatabaseError(String),
     /// 加密/解密错误
@@ -38,46 +74,45 @@ pub enum DurianError {
 }
 
 // ============================================
-// Display 实现
+// 错误描述
 // ============================================
 
-impl fmt::Display for DurianError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            DurianError::StateNotInitialized => {
-                write!(f, "应用状态未初始化，请先登录")
-            }
-            DurianError::StateLockError => {
-                write!(f, "状态锁定失败")
-            }
-            DurianError::NetworkError(msg) => {
-                write!(f, "网络请求失败: {}", msg)
-            }
-            DurianError::ApiError { code, message } => {
-                write!(f, "API 错误 [{}]: {}", code, message)
-            }
-            DurianError::DatabaseError(msg) => {
-                write!(f, "数据库错误: {}", msg)
-            }
-            DurianError::CryptoError(msg) => {
-                write!(f, "加密/解密错误: {}", msg)
-            }
-            DurianError::SerializationError(msg) => {
-                write!(f, "数据序列化错误: {}", msg)
-            }
-            DurianError::IoError(msg) => {
-                write!(f, "IO 错误: {}", msg)
-            }
-            DurianError::ConfigError(msg) => {
-                write!(f, "配置错误: {}", msg)
-            }
-            DurianError::ValidationError(msg) => {
-                write!(f, "输入验证错误: {}", msg)
+impl DurianError {
+    /// 指定语言的错误描述
+    pub fn message(&self, locale: Locale) -> String {
+        let message = match self {
+            DurianError::StateNotInitialized => Message::StateNotInitialized,
+            DurianError::StateLockError => Message::StateLockError,
+            DurianError::Locked => Message::Locked,
+            DurianError::SessionNotFound(username) => Message::SessionNotFound(username),
+            DurianError::CertificatePinMismatch { fingerprint } => {
+                Message::CertificatePinMismatch(fingerprint)
             }
-            DurianError::Unknown(msg) => {
-                write!(f, "未知错误: {}", msg)
+            DurianError::UntrustedCertificate { fingerprint } => {
+                Message::UntrustedCertificate(fingerprint)
             }
-        }
+            DurianError::NetworkError(msg) => Message::NetworkError(msg),
+            DurianError::Cancelled => Message::Cancelled,
+            DurianError::ApiError { code, message, .. } => Message::ApiError(*code, message),
+            DurianError::HttpError { status, message } => Message::HttpError(*status, message),
+            DurianError::InvalidCredentials(msg) => Message::InvalidCredentials(msg),
+            DurianError::Conflict(msg) => Message::Conflict(msg),
+            DurianError::DatabaseError(msg) => Message::DatabaseError(msg),
+            DurianError::CryptoError(msg) => Message::CryptoError(msg),
+            DurianError::SerializationError(msg) => Message::SerializationError(msg),
+            DurianError::IoError(msg) => Message::IoError(msg),
+            DurianError::ConfigError(msg) => Message::ConfigError(msg),
+            DurianError::ValidationError(msg) => Message::ValidationError(msg),
+            DurianError::Unknown(msg) => Message::Unknown(msg),
+        };
+        message.text(locale)
+    }
+}
+
+/// 使用当前语言（见 [`crate::i18n`]）
+impl fmt::Display for DurianError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message(current_locale()))
     }
 }
 
@@ -108,9 +143,9 @@ impl From<rusqlite::Error> for DurianError {
 impl From<reqwest::Error> for DurianError {
     fn from(err: reqwest::Error) -> Self {
         if err.is_timeout() {
-            DurianError::NetworkError("请求超时".to_string())
+            DurianError::NetworkError(tr(Message::RequestTimeout))
         } else if err.is_connect() {
-            DurianError::NetworkError("连接失败，请检查网络".to_string())
+            DurianError::NetworkError(tr(Message::ConnectFailed))
         } else {
             DurianError::NetworkError(err.to_string())
         }
@@ -124,9 +159,237 @@ impl From<Box<dyn std::error::Error>> for DurianError {
 }
 
 // ============================================
-// 转换为 String（用于 Tauri 命令）
+// 错误分类与序列化（用于 Tauri 命令）
 // ============================================
 
+/// 业务失败（服务器拒绝请求）时服务器返回的错误码
+pub const CODE_FAILED: i32 = 1;
+
+/// 令牌失效时服务器返回的错误码
+pub const CODE_UNAUTHORIZED: i32 = 401;
+
+/// 要删除的记录不存在时服务器返回的错误码
+pub const CODE_NOT_FOUND: i32 = 404;
+
+/// 修订号冲突时服务器返回的错误码
+pub const CODE_CONFLICT: i32 = 409;
+
+/// 服务器内部错误的错误码
+pub const CODE_INTERNAL: i32 = 500;
+
+/// 稳定的错误类别
+///
+/// 前端按类别决定如何处理错误（重新登录、解锁、重试等），新增类别不改变已有类别的含义
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum ErrorKind {
+    /// 未登录或会话不存在
+    NotLoggedIn,
+    /// 令牌失效，需要重新登录
+    Unauthorized,
+    /// 用户名、密码或核心密码错误
+    InvalidCredentials,
+    /// 保险库已锁定
+    Locked,
+    /// 服务器证书与固定的指纹不匹配
+    CertificatePinMismatch,
+    /// 服务器证书不受信任，等待用户确认
+    UntrustedCertificate,
+    /// 网络不可达、超时或响应无法解析
+    Network,
+    /// 请求已被取消
+    Cancelled,
+    /// 记录已被其他设备修改
+    Conflict,
+    /// 请求过于频繁
+    RateLimited,
+    /// 服务器内部错误或网关错误
+    Server,
+    /// 服务器拒绝了请求
+    Rejected,
+    /// 输入验证失败
+    Validation,
+    /// 本地数据库错误
+    Database,
+    /// 加密/解密错误
+    Crypto,
+    /// 数据序列化错误
+    Serialization,
+    /// 文件读写错误
+    Io,
+    /// 配置错误
+    Config,
+    /// 应用内部错误
+    Internal,
+    /// 未知错误
+    Unknown,
+}
+
+impl ErrorKind {
+    /// 所有错误类别
+    pub const ALL: [ErrorKind; 20] = [
+        ErrorKind::NotLoggedIn,
+        ErrorKind::Unauthorized,
+        ErrorKind::InvalidCredentials,
+        ErrorKind::Locked,
+        ErrorKind::CertificatePinMismatch,
+        ErrorKind::UntrustedCertificate,
+        ErrorKind::Network,
+        ErrorKind::Cancelled,
+        ErrorKind::Conflict,
+        ErrorKind::RateLimited,
+        ErrorKind::Server,
+        ErrorKind::Rejected,
+        ErrorKind::Validation,
+        ErrorKind::Database,
+        ErrorKind::Crypto,
+        ErrorKind::Serialization,
+        ErrorKind::Io,
+        ErrorKind::Config,
+        ErrorKind::Internal,
+        ErrorKind::Unknown,
+    ];
+}
+
+/// 错误的附加信息
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ErrorDetails {
+    /// HTTP 状态码
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub status: Option<u16>,
+    /// 服务器证书公钥指纹
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub fingerprint: Option<String>,
+    /// 相关的用户名
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub username: Option<String>,
+}
+
+/// 命令返回给前端的错误
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ErrorPayload {
+    /// 错误类别
+    pub kind: ErrorKind,
+    /// 服务器返回的 API 错误码（仅 API 错误）
+    pub code: Option<i32>,
+    /// 错误描述
+    pub message: String,
+    /// 稍后重试是否可能成功
+    pub retryable: bool,
+    /// 附加信息
+    pub details: ErrorDetails,
+}
+
+/// 网关错误和限流时重试可能成功（与 HTTP 客户端的重试策略一致）
+fn is_retryable_status(status: u16) -> bool {
+    matches!(status, 429 | 502 | 503 | 504)
+}
+
+/// 按 HTTP 状态码分类
+fn kind_of_status(status: u16) -> Option<ErrorKind> {
+    match status {
+        401 => Some(ErrorKind::Unauthorized),
+        409 => Some(ErrorKind::Conflict),
+        429 => Some(ErrorKind::RateLimited),
+        500..=599 => Some(ErrorKind::Server),
+        _ => None,
+    }
+}
+
+impl DurianError {
+    /// 错误类别
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            DurianError::StateNotInitialized | DurianError::SessionNotFound(_) => {
+                ErrorKind::NotLoggedIn
+            }
+            DurianError::StateLockError => ErrorKind::Internal,
+            DurianError::Locked => ErrorKind::Locked,
+            DurianError::CertificatePinMismatch { .. } => ErrorKind::CertificatePinMismatch,
+            DurianError::UntrustedCertificate { .. } => ErrorKind::UntrustedCertificate,
+            DurianError::NetworkError(_) => ErrorKind::Network,
+            DurianError::Cancelled => ErrorKind::Cancelled,
+            DurianError::ApiError { code, status, .. } => match *code {
+                CODE_UNAUTHORIZED => ErrorKind::Unauthorized,
+                CODE_CONFLICT => ErrorKind::Conflict,
+                CODE_INTERNAL => ErrorKind::Server,
+                _ => status
+                    .and_then(kind_of_status)
+                    .unwrap_or(ErrorKind::Rejected),
+            },
+            DurianError::HttpError { status, .. } => {
+                kind_of_status(*status).unwrap_or(ErrorKind::Rejected)
+            }
+            DurianError::InvalidCredentials(_) => ErrorKind::InvalidCredentials,
+            DurianError::Conflict(_) => ErrorKind::Conflict,
+            DurianError::DatabaseError(_) => ErrorKind::Database,
+            DurianError::CryptoError(_) => ErrorKind::Crypto,
+            DurianError::SerializationError(_) => ErrorKind::Serialization,
+            DurianError::IoError(_) => ErrorKind::Io,
+            DurianError::ConfigError(_) => ErrorKind::Config,
+            DurianError::ValidationError(_) => ErrorKind::Validation,
+            DurianError::Unknown(_) => ErrorKind::Unknown,
+        }
+    }
+
+    /// 稍后重试是否可能成功
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            DurianError::NetworkError(_) => true,
+            DurianError::ApiError {
+                status: Some(status),
+                ..
+            }
+            | DurianError::HttpError { status, .. } => is_retryable_status(*status),
+            _ => false,
+        }
+    }
+
+    /// 转换为返回给前端的结构化错误
+    pub fn to_payload(&self) -> ErrorPayload {
+        let mut details = ErrorDetails::default();
+        let code = match self {
+            DurianError::ApiError { code, status, .. } => {
+                details.status = *status;
+                Some(*code)
+            }
+            DurianError::HttpError { status, .. } => {
+                details.status = Some(*status);
+                None
+            }
+            DurianError::CertificatePinMismatch { fingerprint }
+            | DurianError::UntrustedCertificate { fingerprint } => {
+                details.fingerprint = Some(fingerprint.clone());
+                None
+            }
+            DurianError::SessionNotFound(username) => {
+                details.username = Some(username.clone());
+                None
+            }
+            _ => None,
+        };
+        ErrorPayload {
+            kind: self.kind(),
+            code,
+            message: self.to_string(),
+            retryable: self.is_retryable(),
+            details,
+        }
+    }
+}
+
+impl Serialize for DurianError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_payload().serialize(serializer)
+    }
+}
+
 impl From<DurianError> for String {
     fn from(err: DurianError) -> Self {
         err.to_string()
@@ -155,9 +418,29 @@ impl DurianError {
         DurianError::ApiError {
             code,
             message: message.into(),
+            status: None,
         }
     }
 
+    /// 由失败的 API 响应创建 API 错误，保留 HTTP 状态码
+    pub fn from_response<T>(response: ApiResponse<T>) -> Self {
+        DurianError::ApiError {
+            code: response.code,
+            message: response.msg,
+            status: response.status,
+        }
+    }
+
+    /// 创建身份验证错误
+    pub fn invalid_credentials<S: Into<String>>(msg: S) -> Self {
+        DurianError::InvalidCredentials(msg.into())
+    }
+
+    /// 创建修改冲突错误
+    pub fn conflict<S: Into<String>>(msg: S) -> Self {
+        DurianError::Conflict(msg.into())
+    }
+
     /// 创建数据库错误
     pub fn database<S: Into<String>>(msg: S) -> Self {
         DurianError::DatabaseError(msg.into())
@@ -195,6 +478,188 @@ mod tests {
         let err = DurianError::api(401, "未授权");
         assert!(err.to_string().contains("401"));
         assert!(err.to_string().contains("未授权"));
+
+        let err = DurianError::Locked;
+        assert!(err.to_string().contains("已锁定"));
+
+        let err = DurianError::SessionNotFound("alice".to_string());
+        assert!(err.to_string().contains("alice"));
+
+        let err = DurianError::CertificatePinMismatch {
+            fingerprint: "abc=".to_string(),
+        };
+        assert!(err.to_string().contains("abc="));
+
+        let err = DurianError::Cancelled;
+        assert!(err.to_string().contains("已取消"));
+    }
+
+    #[test]
+    fn test_error_kind() {
+        assert_eq!(
+            DurianError::api(401, "令牌无效").kind(),
+            ErrorKind::Unauthorized
+        );
+        assert_eq!(DurianError::api(409, "冲突").kind(), ErrorKind::Conflict);
+        assert_eq!(DurianError::api(500, "内部错误").kind(), ErrorKind::Server);
+        assert_eq!(
+            DurianError::api(1, "记录不存在").kind(),
+            ErrorKind::Rejected
+        );
+        assert_eq!(DurianError::network("超时").kind(), ErrorKind::Network);
+        assert_eq!(DurianError::Locked.kind(), ErrorKind::Locked);
+        assert_eq!(
+            DurianError::invalid_credentials("核心密码错误").kind(),
+            ErrorKind::InvalidCredentials
+        );
+
+        // 业务错误码未知时按 HTTP 状态码分类
+        let err = DurianError::ApiError {
+            code: 1,
+            message: "请求过于频繁".to_string(),
+            status: Some(429),
+        };
+        assert_eq!(err.kind(), ErrorKind::RateLimited);
+        assert!(err.is_retryable());
+
+        let err = DurianError::HttpError {
+            status: 502,
+            message: "Bad Gateway".to_string(),
+        };
+        assert_eq!(err.kind(), ErrorKind::Server);
+        assert!(err.is_retryable());
+
+        assert!(DurianError::network("超时").is_retryable());
+        assert!(!DurianError::api(401, "令牌无效").is_retryable());
+        assert!(!DurianError::validation("用户名不能为空").is_retryable());
+    }
+
+    #[test]
+    fn test_error_serialize() {
+        let err = DurianError::ApiError {
+            code: 401,
+            message: "令牌无效".to_string(),
+            status: Some(401),
+        };
+        let value = serde_json::to_value(&err).unwrap();
+        assert_eq!(value["kind"], "unauthorized");
+        assert_eq!(value["code"], 401);
+        assert_eq!(value["message"], err.to_string());
+        assert_eq!(value["retryable"], false);
+        assert_eq!(value["details"], serde_json::json!({ "status": 401 }));
+
+        let err = DurianError::UntrustedCertificate {
+            fingerprint: "abc=".to_string(),
+        };
+        let value = serde_json::to_value(&err).unwrap();
+        assert_eq!(value["kind"], "untrusted_certificate");
+        assert!(value["code"].is_null());
+        assert_eq!(value["details"]["fingerprint"], "abc=");
+
+        let value = serde_json::to_value(DurianError::network("超时")).unwrap();
+        assert_eq!(value["kind"], "network");
+        assert_eq!(value["retryable"], true);
+        assert_eq!(value["details"], serde_json::json!({}));
+    }
+
+    /// 每种错误类别的示例错误（穷尽匹配，新增类别时必须补充）
+    fn sample(kind: ErrorKind) -> DurianError {
+        match kind {
+            ErrorKind::NotLoggedIn => DurianError::SessionNotFound("alice".to_string()),
+            ErrorKind::Unauthorized => DurianError::api(CODE_UNAUTHORIZED, "token"),
+            ErrorKind::InvalidCredentials => DurianError::invalid_credentials("password"),
+            ErrorKind::Locked => DurianError::Locked,
+            ErrorKind::CertificatePinMismatch => DurianError::CertificatePinMismatch {
+                fingerprint: "abc=".to_string(),
+            },
+            ErrorKind::UntrustedCertificate => DurianError::UntrustedCertificate {
+                fingerprint: "abc=".to_string(),
+            },
+            ErrorKind::Network => DurianError::network("timeout"),
+            ErrorKind::Cancelled => DurianError::Cancelled,
+            ErrorKind::Conflict => DurianError::conflict("revision"),
+            ErrorKind::RateLimited => DurianError::HttpError {
+                status: 429,
+                message: "Too Many Requests".to_string(),
+            },
+            ErrorKind::Server => DurianError::api(CODE_INTERNAL, "internal"),
+            ErrorKind::Rejected => DurianError::api(CODE_FAILED, "rejected"),
+            ErrorKind::Validation => DurianError::validation("input"),
+            ErrorKind::Database => DurianError::database("sqlite"),
+            ErrorKind::Crypto => DurianError::crypto("aead"),
+            ErrorKind::Serialization => DurianError::SerializationError("json".to_string()),
+            ErrorKind::Io => DurianError::IoError("disk".to_string()),
+            ErrorKind::Config => DurianError::config("settings"),
+            ErrorKind::Internal => DurianError::StateLockError,
+            ErrorKind::Unknown => DurianError::Unknown("other".to_string()),
+        }
+    }
+
+    fn has_cjk(text: &str) -> bool {
+        text.chars().any(|c| ('\u{4e00}'..='\u{9fff}').contains(&c))
+    }
+
+    #[test]
+    fn test_every_kind_is_translated() {
+        for kind in ErrorKind::ALL {
+            let err = sample(kind);
+            assert_eq!(err.kind(), kind);
+            for locale in Locale::ALL {
+                let message = err.message(locale);
+                assert!(!message.is_empty(), "{:?} {:?}", kind, locale);
+                // 示例错误的详情都是 ASCII，只有中文翻译包含汉字
+                assert_eq!(has_cjk(&message), locale == Locale::ZhCn, "{}", message);
+            }
+        }
+
+        // 不同语言的描述不同，序列化时使用当前语言
+        let err = DurianError::Locked;
+        assert_ne!(err.message(Locale::En), err.message(Locale::ZhCn));
+        assert_eq!(err.to_payload().message, err.message(current_locale()));
+    }
+
+    /// 切换到英语后，各模块生成的错误描述不含中文
+    #[test]
+    fn test_english_errors() {
+        use crate::crypto::{decrypt_message, VaultKey};
+        use crate::i18n::{tr, with_locale, Field, Message};
+        use crate::kdf::KdfParams;
+        use crate::lock::LockSettings;
+        use crate::sync_worker::SyncSettings;
+
+        let params = KdfParams::argon2id(8 * 1024, 1, 1).unwrap();
+        let key = VaultKey::derive("core_password", &params).unwrap();
+        let failures = || {
+            let mut weak = params.clone();
+            weak.memory_kib = 1024;
+            vec![
+                VaultKey::derive("", &params).err().unwrap(),
+                decrypt_message("durian:not-an-envelope", &key).unwrap_err(),
+                weak.validate().unwrap_err(),
+                crate::settings::validate_api_base_url("not a url").unwrap_err(),
+                crate::settings::validate_api_base_url("ftp://localhost").unwrap_err(),
+                SyncSettings { interval_secs: 1 }.validate().unwrap_err(),
+                LockSettings {
+                    idle_timeout_secs: u64::MAX,
+                    lock_on_suspend: false,
+                }
+                .validate()
+                .unwrap_err(),
+            ]
+        };
+
+        for err in with_locale(Locale::En, failures) {
+            let message = err.message(Locale::En);
+            assert!(!has_cjk(&message), "{}", message);
+        }
+        // 中文描述作为对照
+        for err in with_locale(Locale::ZhCn, failures) {
+            assert!(has_cjk(&err.message(Locale::ZhCn)));
+        }
+        assert_eq!(
+            with_locale(Locale::En, || tr(Message::IncorrectPassword(Field::CorePassword))),
+            "incorrect core password"
+        );
     }
 
     #[test]
@@ -0,0 +1,906 @@
+//! 内存中的模拟服务器（仅用于测试）
+//!
+//! 实现 [`ApiClient`]，按真实服务器的语义维护用户、令牌、数据密钥包装和账户记录，
+//! 用于在不访问网络的情况下测试登录、查询、插入和增量同步等完整流程
+//!
+//! # 同步语义
+//! 服务器维护一个单调递增的逻辑时钟，每次写入推进时钟并记录在记录上：
+//! - 客户端首次同步（`update_time` 为 0）时返回 `PULL_ALL`
+//! - 其后有记录被插入、修改或删除时返回 `PULL_UPDATED`、变化的记录和被删除记录的墓碑
+//! - 没有任何变化时返回 `PULL_NOTHING`
+//!
+//! # 修订号
+//! 新记录的修订号为 1，每次修改递增。修改时提交的修订号与当前修订号不一致（且不为 0）时
+//! 返回 [`CODE_CONFLICT`] 和服务器上的当前记录，不修改任何记录
+//!
+//! # 离线
+//! [`FakeServer::set_offline`] 模拟服务器不可达：账户相关的请求返回网络错误，不修改任何数据
+//!
+//! # 缓存校验
+//! 分桶摘要使用与客户端相同的 [`crate::verify::bucket_digests`] 计算
+
+use std::collections::{BTreeMap, HashMap};
+use std::future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::api_client::{ApiClient, ApiFuture};
+use crate::error::{
+    DurianError, DurianResult, CODE_CONFLICT, CODE_FAILED, CODE_NOT_FOUND, CODE_UNAUTHORIZED,
+};
+use crate::kdf::{KdfParams, KdfSuite};
+use crate::models::{
+    AccountItem, ApiResponse, BucketQueryResponseData, LoginTokenData, PullMode,
+    QueryResponseData, VaultKeysResponseData, VerifyResponseData, WrappedKey,
+};
+use crate::verify;
+
+/// 内存中的模拟服务器
+#[derive(Default)]
+pub struct FakeServer {
+    inner: Mutex<ServerData>,
+    /// 是否模拟服务器不可达
+    offline: AtomicBool,
+    /// 是否模拟不支持信封加密的旧服务器
+    without_vault_keys: AtomicBool,
+    /// 是否拒绝升级 KDF 参数
+    reject_kdf_upgrade: AtomicBool,
+}
+
+/// 服务器数据
+#[derive(Default)]
+struct ServerData {
+    /// 用户（按用户名）
+    users: HashMap<String, FakeUser>,
+    /// 令牌到用户名的映射
+    tokens: HashMap<String, String>,
+    /// 逻辑时钟
+    clock: i64,
+    /// 下一个记录 ID
+    next_rid: i64,
+    /// 已签发的令牌数
+    issued_tokens: u64,
+}
+
+/// 服务器上的用户
+struct FakeUser {
+    kdf: KdfSuite,
+    password_hash: String,
+    core_password_hash: String,
+    keys: Vec<WrappedKey>,
+    /// 是否开启了全字段加密
+    full_field_encryption: bool,
+    /// 账户记录（按记录 ID）及其最后修改时间
+    accounts: BTreeMap<i64, (AccountItem, i64)>,
+    /// 墓碑：被删除的记录 ID 及其删除时间
+    tombstones: BTreeMap<i64, i64>,
+    /// 已处理的插入操作 ID 及其记录 ID
+    insert_ops: HashMap<String, i64>,
+}
+
+impl FakeServer {
+    /// 创建没有任何用户的服务器
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 为用户签发新令牌（模拟其他设备登录），用户不存在时返回 `None`
+    pub fn issue_token(&self, username: &str) -> Option<String> {
+        let mut data = self.data();
+        data.users
+            .contains_key(username)
+            .then(|| data.issue_token(username))
+    }
+
+    /// 模拟服务器不可达或恢复连接
+    pub fn set_offline(&self, offline: bool) {
+        self.offline.store(offline, Ordering::SeqCst);
+    }
+
+    /// 模拟不支持信封加密的旧服务器（客户端直接使用派生密钥加密记录）或升级后的服务器
+    pub fn set_vault_keys_supported(&self, supported: bool) {
+        self.without_vault_keys.store(!supported, Ordering::SeqCst);
+    }
+
+    /// 模拟服务器拒绝升级 KDF 参数
+    pub fn set_kdf_upgrade_rejected(&self, rejected: bool) {
+        self.reject_kdf_upgrade.store(rejected, Ordering::SeqCst);
+    }
+
+    /// 服务器不可达时返回网络错误
+    fn reachable(&self) -> DurianResult<()> {
+        if self.offline.load(Ordering::SeqCst) {
+            Err(DurianError::network("连接失败，请检查网络"))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn data(&self) -> MutexGuard<'_, ServerData> {
+        // 测试中某个断言失败导致锁中毒时，其余测试仍可继续使用数据
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn prelogin_sync(&self, username: &str) -> KdfSuite {
+        match self.data().users.get(username) {
+            Some(user) => user.kdf.clone(),
+            None => KdfSuite::legacy(),
+        }
+    }
+
+    fn login_sync(
+        &self,
+        username: &str,
+        password: &str,
+        core_password: &str,
+        kdf: &KdfSuite,
+    ) -> DurianResult<ApiResponse<LoginTokenData>> {
+        let password_hash = kdf.login.hash_password(password)?;
+        let core_password_hash = kdf.core.hash_password(core_password)?;
+
+        let mut data = self.data();
+        let valid = data.users.get(username).is_some_and(|user| {
+            user.password_hash == password_hash && user.core_password_hash == core_password_hash
+        });
+        if !valid {
+            return Ok(failure(CODE_FAILED, "用户名或密码错误"));
+        }
+        let token = data.issue_token(username);
+        Ok(success(LoginTokenData { token }))
+    }
+
+    fn register_sync(
+        &self,
+        username: &str,
+        password: &str,
+        core_password: &str,
+        kdf: &KdfSuite,
+    ) -> DurianResult<ApiResponse<serde_json::Value>> {
+        let user = FakeUser {
+            kdf: kdf.clone(),
+            password_hash: kdf.login.hash_password(password)?,
+            core_password_hash: kdf.core.hash_password(core_password)?,
+            keys: Vec::new(),
+            full_field_encryption: false,
+            accounts: BTreeMap::new(),
+            tombstones: BTreeMap::new(),
+            insert_ops: HashMap::new(),
+        };
+
+        let mut data = self.data();
+        if data.users.contains_key(username) {
+            return Ok(failure(CODE_FAILED, "用户已存在"));
+        }
+        data.users.insert(username.to_string(), user);
+        Ok(success(serde_json::Value::Null))
+    }
+
+    fn upgrade_kdf_sync(
+        &self,
+        token: &str,
+        password: &str,
+        core_password: &str,
+        kdf: &KdfSuite,
+    ) -> DurianResult<ApiResponse<serde_json::Value>> {
+        if self.reject_kdf_upgrade.load(Ordering::SeqCst) {
+            return Ok(failure(CODE_FAILED, "暂不支持升级"));
+        }
+        let password_hash = kdf.login.hash_password(password)?;
+        let core_password_hash = kdf.core.hash_password(core_password)?;
+
+        // 与参考服务器一致：升级不改变保险库密钥参数
+        Ok(self.with_user(token, |user, _| {
+            user.kdf = KdfSuite {
+                vault: user.kdf.vault.take(),
+                ..kdf.clone()
+            };
+            user.password_hash = password_hash;
+            user.core_password_hash = core_password_hash;
+            success(serde_json::Value::Null)
+        }))
+    }
+
+    fn change_core_password_sync(
+        &self,
+        token: &str,
+        old_hash: String,
+        new_hash: String,
+        new_kdf: &KdfParams,
+        wrapped_key: Option<&WrappedKey>,
+    ) -> ApiResponse<serde_json::Value> {
+        self.with_user(token, |user, _| {
+            if user.core_password_hash != old_hash {
+                return failure(CODE_FAILED, "原核心密码错误");
+            }
+            user.kdf.core = new_kdf.clone();
+            user.core_password_hash = new_hash;
+            if let Some(wrapped_key) = wrapped_key {
+                user.keys.retain(|key| key.key_id != wrapped_key.key_id);
+                user.keys.push(wrapped_key.clone());
+            }
+            success(serde_json::Value::Null)
+        })
+    }
+
+    fn create_vault_key_sync(
+        &self,
+        token: &str,
+        key: &WrappedKey,
+    ) -> ApiResponse<serde_json::Value> {
+        self.with_user(token, |user, _| {
+            if user
+                .keys
+                .iter()
+                .any(|existing| existing.key_id == key.key_id)
+            {
+                return failure(CODE_FAILED, "数据密钥已存在");
+            }
+            user.keys.push(key.clone());
+            success(serde_json::Value::Null)
+        })
+    }
+
+    fn query_accounts_sync(&self, token: &str, update_time: i64) -> ApiResponse<QueryResponseData> {
+        self.with_user(token, |user, now| {
+            let (pull_mode, accounts, deleted_rids) = if update_time <= 0 {
+                (
+                    PullMode::PullAll,
+                    user.accounts
+                        .values()
+                        .map(|(item, _)| item.clone())
+                        .collect(),
+                    Vec::new(),
+                )
+            } else {
+                let changed: Vec<AccountItem> = user
+                    .accounts
+                    .values()
+                    .filter(|(_, modified)| *modified > update_time)
+                    .map(|(item, _)| item.clone())
+                    .collect();
+                let deleted: Vec<i64> = user
+                    .tombstones
+                    .iter()
+                    .filter(|(_, deleted_at)| **deleted_at > update_time)
+                    .map(|(rid, _)| *rid)
+                    .collect();
+                if changed.is_empty() && deleted.is_empty() {
+                    (PullMode::PullNothing, changed, deleted)
+                } else {
+                    (PullMode::PullUpdated, changed, deleted)
+                }
+            };
+            success(QueryResponseData {
+                pull_mode,
+                update_time: now,
+                accounts,
+                deleted_rids,
+            })
+        })
+    }
+
+    fn write_accounts(
+        &self,
+        token: &str,
+        items: &[AccountItem],
+        insert: bool,
+    ) -> ApiResponse<serde_json::Value> {
+        let mut data = self.data();
+        let Some(username) = data.tokens.get(token).cloned() else {
+            return failure(CODE_UNAUTHORIZED, "令牌无效");
+        };
+
+        let items: Vec<AccountItem> = if insert {
+            items
+                .iter()
+                .map(|item| {
+                    data.next_rid += 1;
+                    AccountItem::new(
+                        data.next_rid,
+                        item.website.clone(),
+                        item.account.clone(),
+                        item.password.clone(),
+                    )
+                    .with_fields_encrypted(item.fields_encrypted)
+                })
+                .collect()
+        } else {
+            items.to_vec()
+        };
+        data.clock += 1;
+        let now = data.clock;
+
+        let user = data.users.get_mut(&username).expect("令牌对应的用户不存在");
+        // 批量更新是原子的：任一记录不存在、缺少修订号或修订号冲突时不修改任何记录
+        if !insert && items.iter().any(|item| item.revision < 1) {
+            return failure(CODE_FAILED, "缺少修订号");
+        }
+        let mut written = Vec::with_capacity(items.len());
+        for item in items {
+            let revision = if insert {
+                1
+            } else {
+                let Some((current, _)) = user.accounts.get(&item.rid) else {
+                    return failure(CODE_FAILED, "记录不存在");
+                };
+                if item.revision != current.revision {
+                    return ApiResponse {
+                        code: CODE_CONFLICT,
+                        msg: "记录已被其他设备修改".to_string(),
+                        data: serde_json::to_value(current).ok(),
+                        status: Some(409),
+                    };
+                }
+                current.revision + 1
+            };
+            written.push(item.with_revision(revision, now));
+        }
+
+        // 插入时返回新记录的 ID，修改时返回新的修订号
+        let values: Vec<i64> = written
+            .iter()
+            .map(|item| if insert { item.rid } else { item.revision })
+            .collect();
+        for item in written {
+            user.accounts.insert(item.rid, (item, now));
+        }
+        success(serde_json::Value::from(values))
+    }
+
+    /// 插入账户，已处理过相同操作 ID 的插入时返回原记录 ID
+    fn insert_account_sync(
+        &self,
+        token: &str,
+        item: &AccountItem,
+        op_id: Option<&str>,
+    ) -> ApiResponse<serde_json::Value> {
+        let Some(op_id) = op_id else {
+            return single(self.write_accounts(token, std::slice::from_ref(item), true));
+        };
+        let seen = self.with_user(token, |user, _| {
+            success(user.insert_ops.get(op_id).copied())
+        });
+        if let Some(rid) = seen.data.flatten() {
+            return success(serde_json::Value::from(rid));
+        }
+
+        let response = single(self.write_accounts(token, std::slice::from_ref(item), true));
+        if let Some(rid) = response.data.as_ref().and_then(|data| data.as_i64()) {
+            self.with_user(token, |user, _| {
+                user.insert_ops.insert(op_id.to_string(), rid);
+                success(())
+            });
+        }
+        response
+    }
+
+    fn delete_account_sync(&self, token: &str, rid: i64) -> ApiResponse<serde_json::Value> {
+        self.with_user(token, |user, now| {
+            if user.accounts.remove(&rid).is_none() {
+                return failure(CODE_NOT_FOUND, "记录不存在");
+            }
+            user.tombstones.insert(rid, now);
+            success(serde_json::Value::Null)
+        })
+    }
+
+    fn verify_accounts_sync(
+        &self,
+        token: &str,
+        digests: &[String],
+    ) -> ApiResponse<VerifyResponseData> {
+        if digests.is_empty() {
+            return failure(CODE_FAILED, "分桶数无效");
+        }
+        self.with_user(token, |user, _| {
+            let revisions: Vec<(i64, i64)> = user
+                .accounts
+                .values()
+                .map(|(item, _)| (item.rid, item.revision))
+                .collect();
+            let expected = verify::bucket_digests(&revisions, digests.len());
+            let buckets = (0..digests.len())
+                .filter(|&bucket| expected[bucket] != digests[bucket])
+                .collect();
+            success(VerifyResponseData { buckets })
+        })
+    }
+
+    fn query_account_buckets_sync(
+        &self,
+        token: &str,
+        bucket_count: usize,
+        buckets: &[usize],
+    ) -> ApiResponse<BucketQueryResponseData> {
+        if bucket_count == 0 {
+            return failure(CODE_FAILED, "分桶数无效");
+        }
+        self.with_user(token, |user, _| {
+            let accounts = user
+                .accounts
+                .values()
+                .filter(|(item, _)| buckets.contains(&verify::bucket_of(item.rid, bucket_count)))
+                .map(|(item, _)| item.clone())
+                .collect();
+            success(BucketQueryResponseData { accounts })
+        })
+    }
+
+    /// 以令牌对应的用户处理请求，`f` 的第二个参数为推进后的逻辑时间
+    fn with_user<T>(
+        &self,
+        token: &str,
+        f: impl FnOnce(&mut FakeUser, i64) -> ApiResponse<T>,
+    ) -> ApiResponse<T> {
+        let mut data = self.data();
+        let Some(username) = data.tokens.get(token).cloned() else {
+            return failure(CODE_UNAUTHORIZED, "令牌无效");
+        };
+        data.clock += 1;
+        let now = data.clock;
+        let user = data.users.get_mut(&username).expect("令牌对应的用户不存在");
+        f(user, now)
+    }
+}
+
+impl ServerData {
+    fn issue_token(&mut self, username: &str) -> String {
+        self.issued_tokens += 1;
+        let token = format!("token-{}", self.issued_tokens);
+        self.tokens.insert(token.clone(), username.to_string());
+        token
+    }
+}
+
+impl ApiClient for FakeServer {
+    fn prelogin<'a>(&'a self, username: &'a str) -> ApiFuture<'a, KdfSuite> {
+        ready(Ok(self.prelogin_sync(username)))
+    }
+
+    fn login<'a>(
+        &'a self,
+        username: &'a str,
+        password: &'a str,
+        core_password: &'a str,
+        kdf: &'a KdfSuite,
+    ) -> ApiFuture<'a, ApiResponse<LoginTokenData>> {
+        ready(self.login_sync(username, password, core_password, kdf))
+    }
+
+    fn register<'a>(
+        &'a self,
+        username: &'a str,
+        password: &'a str,
+        core_password: &'a str,
+        kdf: &'a KdfSuite,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>> {
+        ready(self.register_sync(username, password, core_password, kdf))
+    }
+
+    fn upgrade_kdf<'a>(
+        &'a self,
+        token: &'a str,
+        password: &'a str,
+        core_password: &'a str,
+        kdf: &'a KdfSuite,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>> {
+        ready(self.upgrade_kdf_sync(token, password, core_password, kdf))
+    }
+
+    fn change_core_password<'a>(
+        &'a self,
+        token: &'a str,
+        old_core_password: &'a str,
+        new_core_password: &'a str,
+        old_kdf: &'a KdfParams,
+        new_kdf: &'a KdfParams,
+        wrapped_key: Option<&'a WrappedKey>,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>> {
+        let hashes = old_kdf
+            .hash_password(old_core_password)
+            .and_then(|old| Ok((old, new_kdf.hash_password(new_core_password)?)));
+        ready(hashes.map(|(old_hash, new_hash)| {
+            self.change_core_password_sync(token, old_hash, new_hash, new_kdf, wrapped_key)
+        }))
+    }
+
+    fn get_vault_keys<'a>(
+        &'a self,
+        token: &'a str,
+    ) -> ApiFuture<'a, Option<VaultKeysResponseData>> {
+        if self.without_vault_keys.load(Ordering::SeqCst) {
+            return ready(Ok(None));
+        }
+        let data = self.data();
+        let keys = match data.tokens.get(token) {
+            Some(username) => {
+                let user = &data.users[username];
+                Ok(Some(VaultKeysResponseData {
+                    keys: user.keys.clone(),
+                    full_field_encryption: user.full_field_encryption,
+                }))
+            }
+            None => Err(DurianError::api(CODE_UNAUTHORIZED, "令牌无效".to_string())),
+        };
+        ready(keys)
+    }
+
+    fn create_vault_key<'a>(
+        &'a self,
+        token: &'a str,
+        key: &'a WrappedKey,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>> {
+        ready(Ok(self.create_vault_key_sync(token, key)))
+    }
+
+    fn set_full_field_encryption<'a>(
+        &'a self,
+        token: &'a str,
+        enabled: bool,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>> {
+        ready(self.reachable().map(|_| {
+            self.with_user(token, |user, _| {
+                user.full_field_encryption = enabled;
+                success(serde_json::Value::Null)
+            })
+        }))
+    }
+
+    fn verify<'a>(&'a self, token: &'a str) -> ApiFuture<'a, bool> {
+        ready(Ok(self.data().tokens.contains_key(token)))
+    }
+
+    fn query_accounts<'a>(
+        &'a self,
+        token: &'a str,
+        update_time: i64,
+    ) -> ApiFuture<'a, ApiResponse<QueryResponseData>> {
+        ready(
+            self.reachable()
+                .map(|_| self.query_accounts_sync(token, update_time)),
+        )
+    }
+
+    fn insert_account<'a>(
+        &'a self,
+        token: &'a str,
+        item: &'a AccountItem,
+        op_id: Option<&'a str>,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>> {
+        ready(
+            self.reachable()
+                .map(|_| self.insert_account_sync(token, item, op_id)),
+        )
+    }
+
+    fn update_account<'a>(
+        &'a self,
+        token: &'a str,
+        item: &'a AccountItem,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>> {
+        ready(
+            self.reachable()
+                .map(|_| single(self.write_accounts(token, std::slice::from_ref(item), false))),
+        )
+    }
+
+    fn update_accounts_batch<'a>(
+        &'a self,
+        token: &'a str,
+        accounts: &'a [AccountItem],
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>> {
+        ready(
+            self.reachable()
+                .map(|_| self.write_accounts(token, accounts, false)),
+        )
+    }
+
+    fn delete_account<'a>(
+        &'a self,
+        token: &'a str,
+        rid: i64,
+    ) -> ApiFuture<'a, ApiResponse<serde_json::Value>> {
+        ready(
+            self.reachable()
+                .map(|_| self.delete_account_sync(token, rid)),
+        )
+    }
+
+    fn verify_accounts<'a>(
+        &'a self,
+        token: &'a str,
+        digests: &'a [String],
+    ) -> ApiFuture<'a, ApiResponse<VerifyResponseData>> {
+        ready(
+            self.reachable()
+                .map(|_| self.verify_accounts_sync(token, digests)),
+        )
+    }
+
+    fn query_account_buckets<'a>(
+        &'a self,
+        token: &'a str,
+        bucket_count: usize,
+        buckets: &'a [usize],
+    ) -> ApiFuture<'a, ApiResponse<BucketQueryResponseData>> {
+        ready(
+            self.reachable()
+                .map(|_| self.query_account_buckets_sync(token, bucket_count, buckets)),
+        )
+    }
+}
+
+/// 单条写入的响应只返回第一个值（新记录的 ID 或新的修订号）
+fn single(mut response: ApiResponse<serde_json::Value>) -> ApiResponse<serde_json::Value> {
+    if response.is_success() {
+        response.data = response.data.and_then(|data| data.get(0).cloned());
+    }
+    response
+}
+
+/// 包装已完成的结果
+fn ready<'a, T: Send + 'a>(result: DurianResult<T>) -> ApiFuture<'a, T> {
+    Box::pin(future::ready(result))
+}
+
+fn success<T>(data: T) -> ApiResponse<T> {
+    ApiResponse {
+        code: 0,
+        msg: "success".to_string(),
+        data: Some(data),
+        status: Some(200),
+    }
+}
+
+/// 与参考服务器一致：令牌失效和冲突使用对应的 HTTP 状态码，其他业务失败为 200
+fn failure<T>(code: i32, msg: &str) -> ApiResponse<T> {
+    let status = match code {
+        CODE_UNAUTHORIZED | CODE_NOT_FOUND | CODE_CONFLICT => code as u16,
+        _ => 200,
+    };
+    ApiResponse {
+        code,
+        msg: msg.to_string(),
+        data: None,
+        status: Some(status),
+    }
+}
+
+// ============================================
+// 单元测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 使用最低成本的 KDF 参数注册用户，返回登录令牌
+    async fn register_user(server: &FakeServer, username: &str) -> String {
+        let kdf = KdfSuite {
+            login: KdfParams::argon2id(8 * 1024, 1, 1).unwrap(),
+            core: KdfParams::argon2id(8 * 1024, 1, 1).unwrap(),
+            vault: None,
+        };
+        let response = server
+            .register(username, "password", "core_password", &kdf)
+            .await
+            .unwrap();
+        assert!(response.is_success());
+
+        let kdf = server.prelogin(username).await.unwrap();
+        let response = server
+            .login(username, "password", "core_password", &kdf)
+            .await
+            .unwrap();
+        response.into_result().unwrap().token
+    }
+
+    /// 账户项（修订号为 0）
+    fn item(rid: i64, website: &str, password: &str) -> AccountItem {
+        AccountItem::new(rid, website.to_string(), "alice".to_string(), password.to_string())
+    }
+
+    async fn query(server: &FakeServer, token: &str, update_time: i64) -> QueryResponseData {
+        server
+            .query_accounts(token, update_time)
+            .await
+            .unwrap()
+            .into_result()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_login() {
+        let server = FakeServer::new();
+        let token = register_user(&server, "alice").await;
+        assert!(server.verify(&token).await.unwrap());
+        assert!(!server.verify("invalid").await.unwrap());
+
+        let kdf = server.prelogin("alice").await.unwrap();
+        let response = server
+            .login("alice", "wrong", "core_password", &kdf)
+            .await
+            .unwrap();
+        assert_eq!(response.code, CODE_FAILED);
+
+        // 未知用户使用旧版参数
+        assert_eq!(server.prelogin("nobody").await.unwrap(), KdfSuite::legacy());
+    }
+
+    #[tokio::test]
+    async fn test_pull_modes() {
+        let server = FakeServer::new();
+        let token = register_user(&server, "alice").await;
+
+        server
+            .insert_account(&token, &item(0, "a.com", "p1"), None)
+            .await
+            .unwrap();
+        let data = query(&server, &token, 0).await;
+        assert_eq!(data.pull_mode, PullMode::PullAll);
+        assert_eq!(data.accounts.len(), 1);
+
+        // 没有变化
+        let data = query(&server, &token, data.update_time).await;
+        assert_eq!(data.pull_mode, PullMode::PullNothing);
+        assert!(data.accounts.is_empty());
+
+        // 只返回变化的记录
+        let since = data.update_time;
+        server
+            .insert_account(&token, &item(0, "b.com", "p2"), None)
+            .await
+            .unwrap();
+        let data = query(&server, &token, since).await;
+        assert_eq!(data.pull_mode, PullMode::PullUpdated);
+        assert_eq!(data.accounts.len(), 1);
+        assert_eq!(data.accounts[0].website, "b.com");
+
+        // 删除通过墓碑增量同步
+        let since = data.update_time;
+        let rid = data.accounts[0].rid;
+        assert!(server
+            .delete_account(&token, rid)
+            .await
+            .unwrap()
+            .is_success());
+        let data = query(&server, &token, since).await;
+        assert_eq!(data.pull_mode, PullMode::PullUpdated);
+        assert!(data.accounts.is_empty());
+        assert_eq!(data.deleted_rids, vec![rid]);
+
+        let data = query(&server, &token, 0).await;
+        assert_eq!(data.pull_mode, PullMode::PullAll);
+        assert_eq!(data.accounts.len(), 1);
+        assert_eq!(data.accounts[0].website, "a.com");
+        assert!(data.deleted_rids.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_users_are_isolated() {
+        let server = FakeServer::new();
+        let alice = register_user(&server, "alice").await;
+        let bob = register_user(&server, "bob").await;
+
+        server
+            .insert_account(&alice, &item(0, "a.com", "p1"), None)
+            .await
+            .unwrap();
+        assert!(query(&server, &bob, 0).await.accounts.is_empty());
+
+        let rid = query(&server, &alice, 0).await.accounts[0].rid;
+        // 其他用户的记录视为不存在
+        let response = server.delete_account(&bob, rid).await.unwrap();
+        assert_eq!(response.code, CODE_NOT_FOUND);
+
+        let response = server.query_accounts("invalid", 0).await.unwrap();
+        assert_eq!(response.code, CODE_UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_insert_with_op_id_is_idempotent() {
+        let server = FakeServer::new();
+        let token = register_user(&server, "alice").await;
+
+        let first = server
+            .insert_account(&token, &item(0, "a.com", "p1"), Some("op-1"))
+            .await
+            .unwrap();
+        let retried = server
+            .insert_account(&token, &item(0, "a.com", "p1"), Some("op-1"))
+            .await
+            .unwrap();
+        assert!(retried.is_success());
+        assert_eq!(retried.data, first.data);
+        assert_eq!(query(&server, &token, 0).await.accounts.len(), 1);
+
+        server
+            .insert_account(&token, &item(0, "a.com", "p1"), Some("op-2"))
+            .await
+            .unwrap();
+        assert_eq!(query(&server, &token, 0).await.accounts.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_batch_update_is_atomic() {
+        let server = FakeServer::new();
+        let token = register_user(&server, "alice").await;
+        server
+            .insert_account(&token, &item(0, "a.com", "p1"), None)
+            .await
+            .unwrap();
+        let item = query(&server, &token, 0).await.accounts.remove(0);
+
+        let updated = AccountItem::new(
+            item.rid,
+            item.website.clone(),
+            item.account.clone(),
+            "p2".to_string(),
+        )
+        .with_revision(item.revision, 0);
+        let missing = AccountItem::new(
+            item.rid + 100,
+            "x".to_string(),
+            "x".to_string(),
+            "x".to_string(),
+        )
+        .with_revision(1, 0);
+        let response = server
+            .update_accounts_batch(&token, &[updated.clone(), missing])
+            .await
+            .unwrap();
+        assert_eq!(response.code, CODE_FAILED);
+        assert_eq!(query(&server, &token, 0).await.accounts[0].password, "p1");
+
+        let response = server
+            .update_accounts_batch(&token, &[updated])
+            .await
+            .unwrap();
+        assert!(response.is_success());
+        assert_eq!(query(&server, &token, 0).await.accounts[0].password, "p2");
+    }
+
+    #[tokio::test]
+    async fn test_update_revision_conflict() {
+        let server = FakeServer::new();
+        let token = register_user(&server, "alice").await;
+        let response = server
+            .insert_account(&token, &item(0, "a.com", "p1"), None)
+            .await
+            .unwrap();
+        let rid = response.data.unwrap().as_i64().unwrap();
+        assert_eq!(query(&server, &token, 0).await.accounts[0].revision, 1);
+
+        let response = server
+            .update_account(&token, &item(rid, "a.com", "p2").with_revision(1, 0))
+            .await
+            .unwrap();
+        assert_eq!(response.data, Some(serde_json::Value::from(2)));
+
+        // 基于旧修订号的修改返回服务器上的当前记录
+        let response = server
+            .update_account(&token, &item(rid, "a.com", "p3").with_revision(1, 0))
+            .await
+            .unwrap();
+        assert_eq!(response.code, CODE_CONFLICT);
+        let current: AccountItem = serde_json::from_value(response.data.unwrap()).unwrap();
+        assert_eq!((current.password.as_str(), current.revision), ("p2", 2));
+
+        // 不携带修订号的修改被拒绝
+        let response = server
+            .update_account(&token, &item(rid, "a.com", "p3"))
+            .await
+            .unwrap();
+        assert_eq!(response.code, CODE_FAILED);
+
+        let response = server
+            .update_account(&token, &item(rid, "a.com", "p3").with_revision(2, 0))
+            .await
+            .unwrap();
+        assert!(response.is_success());
+        let item = query(&server, &token, 0).await.accounts.remove(0);
+        assert_eq!((item.password.as_str(), item.revision), ("p3", 3));
+    }
+}
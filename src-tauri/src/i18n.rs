@@ -0,0 +1,739 @@
+//! 本地化消息模块
+//!
+//! 错误描述（见 [`crate::error`]）和命令返回的验证、状态消息集中定义在消息目录中，
+//! 每种语言一个翻译函数。翻译函数对 [`Message`] 穷尽匹配，新增消息时
+//! 缺少任何一种语言的翻译都无法编译
+//!
+//! # 语言选择
+//! 当前语言保存在进程全局状态中，启动时从设置读取（见 [`crate::settings::Settings::locale`]），
+//! 未设置时跟随操作系统语言；`set_locale` 命令修改后立即生效。
+//! 在设置生效前和测试中使用 [`Locale::default`]（简体中文）
+//!
+//! 错误在生成 `message` 时使用当前语言，已生成的错误描述不会随语言切换而改变
+
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+// ============================================
+// 语言
+// ============================================
+
+/// 支持的界面语言
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum Locale {
+    /// 简体中文
+    #[default]
+    #[serde(rename = "zh-CN")]
+    ZhCn,
+    /// 英语
+    #[serde(rename = "en")]
+    En,
+}
+
+impl Locale {
+    /// 所有支持的语言
+    pub const ALL: [Locale; 2] = [Locale::ZhCn, Locale::En];
+
+    /// 按 BCP 47 语言标签（如 `zh-CN`、`en_US.UTF-8`）选择语言
+    ///
+    /// 中文的各个变体都使用简体中文，其他语言使用英语；
+    /// 空标签（语言未知）与 [`Locale::system`] 一样使用默认语言
+    pub fn from_tag(tag: &str) -> Self {
+        let language = tag
+            .split(['-', '_', '.'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match language.as_str() {
+            "" => Locale::default(),
+            "zh" => Locale::ZhCn,
+            _ => Locale::En,
+        }
+    }
+
+    /// 操作系统的界面语言，无法获取时使用默认语言
+    pub fn system() -> Self {
+        sys_locale::get_locale()
+            .map(|tag| Locale::from_tag(&tag))
+            .unwrap_or_default()
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            Locale::ZhCn => 0,
+            Locale::En => 1,
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Locale::En,
+            _ => Locale::ZhCn,
+        }
+    }
+}
+
+/// 当前语言
+static CURRENT_LOCALE: AtomicU8 = AtomicU8::new(0);
+
+#[cfg(test)]
+thread_local! {
+    /// 测试中覆盖当前语言，只影响本线程，不干扰并行运行的其他测试
+    static TEST_LOCALE: std::cell::Cell<Option<Locale>> = const { std::cell::Cell::new(None) };
+}
+
+/// 获取当前语言
+pub fn current_locale() -> Locale {
+    #[cfg(test)]
+    if let Some(locale) = TEST_LOCALE.with(std::cell::Cell::get) {
+        return locale;
+    }
+    Locale::from_u8(CURRENT_LOCALE.load(Ordering::Relaxed))
+}
+
+/// 设置当前语言
+pub fn set_current_locale(locale: Locale) {
+    CURRENT_LOCALE.store(locale.to_u8(), Ordering::Relaxed);
+}
+
+/// 在本线程中以指定语言执行 `f`（仅用于测试）
+#[cfg(test)]
+pub(crate) fn with_locale<R>(locale: Locale, f: impl FnOnce() -> R) -> R {
+    let previous = TEST_LOCALE.with(|cell| cell.replace(Some(locale)));
+    let result = f();
+    TEST_LOCALE.with(|cell| cell.set(previous));
+    result
+}
+
+// ============================================
+// 消息目录
+// ============================================
+
+/// 输入验证中提到的字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    ApiUrl,
+    Username,
+    Password,
+    CorePassword,
+    OldCorePassword,
+    NewCorePassword,
+    Token,
+    ServerName,
+    Fingerprint,
+    Website,
+    Account,
+    Session,
+    UserAgent,
+    RequestTimeout,
+    ConnectTimeout,
+    SyncInterval,
+    IdleTimeout,
+}
+
+/// 写入账户的操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteAction {
+    Insert,
+    Update,
+    Delete,
+    /// 处理冲突时保留本地版本
+    KeepLocal,
+    /// 处理冲突时将本地版本另存为新记录
+    KeepBoth,
+}
+
+/// 可本地化的消息
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message<'a> {
+    // 错误描述（与 `DurianError` 的变体一一对应）
+    StateNotInitialized,
+    StateLockError,
+    Locked,
+    SessionNotFound(&'a str),
+    CertificatePinMismatch(&'a str),
+    UntrustedCertificate(&'a str),
+    NetworkError(&'a str),
+    Cancelled,
+    ApiError(i32, &'a str),
+    HttpError(u16, &'a str),
+    InvalidCredentials(&'a str),
+    Conflict(&'a str),
+    DatabaseError(&'a str),
+    CryptoError(&'a str),
+    SerializationError(&'a str),
+    IoError(&'a str),
+    ConfigError(&'a str),
+    ValidationError(&'a str),
+    Unknown(&'a str),
+
+    // 网络错误的原因
+    RequestTimeout,
+    ConnectFailed,
+
+    // 登录和密钥
+    KdfDowngrade,
+    KdfUpgradeFailed(&'a str),
+    MigrationFailed(&'a str),
+    SettingsUnavailable(&'a str),
+    AutoLockUnavailable(&'a str),
+    UnsyncedWritesBeforeRekey,
+    UnsyncedWritesUnderUnknownKey,
+    IncorrectPassword(Field),
+    SameCorePassword,
+
+    // 密钥派生和加解密
+    KdfIterationsTooLow,
+    KdfSaltTooShort,
+    KdfMemoryOutOfRange,
+    KdfTimeCostOutOfRange,
+    KdfParallelismOutOfRange,
+    InvalidKdfSalt(&'a str),
+    InvalidKdfParams(&'a str),
+    KdfFailed(&'a str),
+    RandomFailed,
+    KeyDerivationFailed,
+    LegacyKeyUnavailable,
+    NoDataKeyToWrap,
+    NotWrappedKey,
+    UnwrapKeyFailed,
+    InvalidDataKeyLength,
+    MissingDataKey,
+    NotCacheField,
+    NotRecordCiphertext,
+    NotEnvelope,
+    InvalidCiphertextEncoding(&'a str),
+    CiphertextTooShort,
+    UnsupportedEnvelopeVersion(u8),
+    UnsupportedEnvelopeKdf(u8),
+    EncryptFailed,
+    CiphertextTampered,
+    InvalidPlaintext(&'a str),
+    LegacyDecryptFailed(&'a str),
+    LegacyChecksumMismatch,
+
+    // 同步和本地缓存
+    ServerUnreachable,
+    VerifyNeedsNetwork,
+    QueryFailed(&'a str),
+    RecordNotSynced,
+    SyncConflict,
+    MissingCacheRecord,
+    UnknownPullMode(&'a str),
+    UnknownWriteKind(&'a str),
+
+    // 设置和 TLS
+    NoAppDataDir,
+    SettingsMalformed,
+    SettingsTooNew(u64, u32),
+    InvalidProxy(&'a str),
+    HttpClientFailed(&'a str),
+    InvalidServerCertificate(&'a str),
+    InvalidCaCertificate(&'a str),
+    CertificateVerifierFailed(&'a str),
+    TlsConfigFailed(&'a str),
+    CaFileUnreadable(&'a str, &'a str),
+    CaFileInvalid(&'a str, &'a str),
+    CaFileEmpty(&'a str),
+
+    // 输入验证
+    Required(Field),
+    MinLength(Field, usize),
+    MaxLength(Field, usize),
+    SecondsOutOfRange(Field, u64, u64),
+    InvalidUrl(&'a str),
+    UnsupportedUrlScheme,
+    UrlMissingHost,
+    UrlHasQuery,
+    UrlHasUserInfo,
+    InvalidProxyUrl(&'a str),
+    UnsupportedProxyScheme,
+    ProxyMissingHost,
+    NonAsciiUserAgent,
+    InvalidFingerprint(&'a str),
+    InvalidRecordId,
+    RecordNotCached,
+    InvalidUpdateTime,
+    ProfileNotFound(&'a str),
+    NoServerProfile,
+    UnresolvedConflict,
+    NoConflict,
+    EmptyEncryptInput,
+    EmptyDecryptInput,
+
+    // 命令返回的状态
+    Registered,
+    CorePasswordChanged,
+    FullFieldEncryptionEnabled,
+    FullFieldEncryptionDisabled,
+    WriteSucceeded(WriteAction),
+    WriteFailed(WriteAction, &'a str),
+    WriteQueued(WriteAction),
+    ServerVersionKept,
+}
+
+impl Message<'_> {
+    /// 指定语言的消息文本
+    pub fn text(&self, locale: Locale) -> String {
+        match locale {
+            Locale::ZhCn => zh_cn(self),
+            Locale::En => en(self),
+        }
+    }
+}
+
+impl fmt::Display for Message<'_> {
+    /// 使用当前语言
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text(current_locale()))
+    }
+}
+
+/// 使用当前语言的消息文本
+pub fn tr(message: Message<'_>) -> String {
+    message.text(current_locale())
+}
+
+// ============================================
+// 简体中文
+// ============================================
+
+fn zh_cn(message: &Message<'_>) -> String {
+    match *message {
+        Message::StateNotInitialized => "应用状态未初始化，请先登录".to_string(),
+        Message::StateLockError => "状态锁定失败".to_string(),
+        Message::Locked => "保险库已锁定，请输入核心密码解锁".to_string(),
+        Message::SessionNotFound(username) => format!("用户 {} 未登录", username),
+        Message::CertificatePinMismatch(fingerprint) => format!(
+            "服务器证书公钥与固定的指纹不匹配（实际指纹: {}），连接可能被劫持",
+            fingerprint
+        ),
+        Message::UntrustedCertificate(fingerprint) => {
+            format!("服务器证书不受信任，公钥指纹: {}", fingerprint)
+        }
+        Message::NetworkError(msg) => format!("网络请求失败: {}", msg),
+        Message::Cancelled => "请求已取消".to_string(),
+        Message::ApiError(code, msg) => format!("API 错误 [{}]: {}", code, msg),
+        Message::HttpError(status, msg) => format!("HTTP 错误 [{}]: {}", status, msg),
+        Message::InvalidCredentials(msg) => format!("身份验证失败: {}", msg),
+        Message::Conflict(msg) => format!("修改冲突: {}", msg),
+        Message::DatabaseError(msg) => format!("数据库错误: {}", msg),
+        Message::CryptoError(msg) => format!("加密/解密错误: {}", msg),
+        Message::SerializationError(msg) => format!("数据序列化错误: {}", msg),
+        Message::IoError(msg) => format!("IO 错误: {}", msg),
+        Message::ConfigError(msg) => format!("配置错误: {}", msg),
+        Message::ValidationError(msg) => format!("输入验证错误: {}", msg),
+        Message::Unknown(msg) => format!("未知错误: {}", msg),
+
+        Message::RequestTimeout => "请求超时".to_string(),
+        Message::ConnectFailed => "连接失败，请检查网络".to_string(),
+
+        Message::KdfDowngrade => {
+            "服务器返回了旧版 KDF 参数，但该账户已在本设备上升级，已拒绝登录".to_string()
+        }
+        Message::KdfUpgradeFailed(msg) => format!("升级 KDF 参数失败: {}", msg),
+        Message::MigrationFailed(msg) => format!("迁移旧格式记录失败: {}", msg),
+        Message::SettingsUnavailable(msg) => format!("无法打开设置文件: {}", msg),
+        Message::AutoLockUnavailable(msg) => format!("无法启动自动锁定: {}", msg),
+        Message::UnsyncedWritesBeforeRekey => {
+            "还有未同步的写入或未处理的冲突，请先同步并处理后再修改核心密码".to_string()
+        }
+        Message::UnsyncedWritesUnderUnknownKey => {
+            "本地缓存中有使用其他核心密码加密的未同步写入或未处理的冲突，为避免丢失已拒绝打开；\
+             请使用原核心密码解锁并完成同步"
+                .to_string()
+        }
+        Message::IncorrectPassword(field) => format!("{}错误", field_zh_cn(field)),
+        Message::SameCorePassword => "新核心密码不能与原核心密码相同".to_string(),
+
+        Message::KdfIterationsTooLow => "PBKDF2 迭代次数过低".to_string(),
+        Message::KdfSaltTooShort => "Argon2id 盐值过短".to_string(),
+        Message::KdfMemoryOutOfRange => "Argon2id 内存成本超出允许范围".to_string(),
+        Message::KdfTimeCostOutOfRange => "Argon2id 时间成本超出允许范围".to_string(),
+        Message::KdfParallelismOutOfRange => "Argon2id 并行度超出允许范围".to_string(),
+        Message::InvalidKdfSalt(msg) => format!("盐值格式错误: {}", msg),
+        Message::InvalidKdfParams(msg) => format!("Argon2id 参数无效: {}", msg),
+        Message::KdfFailed(msg) => format!("Argon2id 派生失败: {}", msg),
+        Message::RandomFailed => "生成随机数失败".to_string(),
+        Message::KeyDerivationFailed => "派生子密钥失败".to_string(),
+        Message::LegacyKeyUnavailable => "旧格式密文尚未迁移，请重新登录后再试".to_string(),
+        Message::NoDataKeyToWrap => "未启用信封加密，没有可包装的数据密钥".to_string(),
+        Message::NotWrappedKey => "不是包装后的数据密钥".to_string(),
+        Message::UnwrapKeyFailed => "解包数据密钥失败，密码错误或数据已被篡改".to_string(),
+        Message::InvalidDataKeyLength => "数据密钥长度错误".to_string(),
+        Message::MissingDataKey => "缺少数据密钥，无法解密".to_string(),
+        Message::NotCacheField => "不是缓存字段密文".to_string(),
+        Message::NotRecordCiphertext => "不是记录密文".to_string(),
+        Message::NotEnvelope => "不是信封格式的密文".to_string(),
+        Message::InvalidCiphertextEncoding(msg) => format!("密文编码错误: {}", msg),
+        Message::CiphertextTooShort => "密文长度不足".to_string(),
+        Message::UnsupportedEnvelopeVersion(version) => format!("不支持的密文版本: {}", version),
+        Message::UnsupportedEnvelopeKdf(id) => format!("不支持的密钥派生方式: {}", id),
+        Message::EncryptFailed => "加密失败".to_string(),
+        Message::CiphertextTampered => "密文校验失败，数据可能已被篡改或密钥错误".to_string(),
+        Message::InvalidPlaintext(msg) => format!("解密结果不是有效文本: {}", msg),
+        Message::LegacyDecryptFailed(msg) => format!("解密失败: {}", msg),
+        Message::LegacyChecksumMismatch => "解密失败: 校验值不匹配".to_string(),
+
+        Message::ServerUnreachable => "服务器不可达，离线写入等待同步".to_string(),
+        Message::VerifyNeedsNetwork => "仍有离线写入未能发送，请联网后再校验".to_string(),
+        Message::QueryFailed(msg) => format!("查询失败: {}", msg),
+        Message::RecordNotSynced => "记录尚未同步到服务器".to_string(),
+        Message::SyncConflict => "与其他设备的修改冲突".to_string(),
+        Message::MissingCacheRecord => "缺少要写入缓存的记录".to_string(),
+        Message::UnknownPullMode(mode) => format!("未知的 pull_mode: {}", mode),
+        Message::UnknownWriteKind(kind) => format!("未知的写入操作: {}", kind),
+
+        Message::NoAppDataDir => "无法获取 AppData 目录".to_string(),
+        Message::SettingsMalformed => "设置文件格式错误".to_string(),
+        Message::SettingsTooNew(version, supported) => format!(
+            "设置文件版本 {} 高于当前支持的版本 {}，请升级应用",
+            version, supported
+        ),
+        Message::InvalidProxy(msg) => format!("无效的代理配置: {}", msg),
+        Message::HttpClientFailed(msg) => format!("创建 HTTP 客户端失败: {}", msg),
+        Message::InvalidServerCertificate(msg) => format!("解析服务器证书失败: {}", msg),
+        Message::InvalidCaCertificate(msg) => format!("无效的 CA 证书: {}", msg),
+        Message::CertificateVerifierFailed(msg) => format!("创建证书校验器失败: {}", msg),
+        Message::TlsConfigFailed(msg) => format!("TLS 配置错误: {}", msg),
+        Message::CaFileUnreadable(path, msg) => format!("读取 CA 证书 {} 失败: {}", path, msg),
+        Message::CaFileInvalid(path, msg) => format!("解析 CA 证书 {} 失败: {}", path, msg),
+        Message::CaFileEmpty(path) => format!("CA 证书文件 {} 中没有证书", path),
+
+        Message::Required(field) => format!("{}不能为空", field_zh_cn(field)),
+        Message::MinLength(field, len) => {
+            format!("{}长度不能少于{}个字符", field_zh_cn(field), len)
+        }
+        Message::MaxLength(field, len) => {
+            format!("{}长度不能超过{}个字符", field_zh_cn(field), len)
+        }
+        Message::SecondsOutOfRange(field, min, max) => {
+            format!("{}必须在 {} 到 {} 秒之间", field_zh_cn(field), min, max)
+        }
+        Message::InvalidUrl(msg) => format!("无效的 URL: {}", msg),
+        Message::UnsupportedUrlScheme => "URL 只支持 http 或 https".to_string(),
+        Message::UrlMissingHost => "URL 缺少主机名".to_string(),
+        Message::UrlHasQuery => "URL 不能包含查询参数或片段".to_string(),
+        Message::UrlHasUserInfo => "URL 不能包含用户信息".to_string(),
+        Message::InvalidProxyUrl(msg) => format!("无效的代理 URL: {}", msg),
+        Message::UnsupportedProxyScheme => "代理只支持 http、https、socks5 或 socks5h".to_string(),
+        Message::ProxyMissingHost => "代理 URL 缺少主机名".to_string(),
+        Message::NonAsciiUserAgent => "User-Agent 只能包含 ASCII 可见字符".to_string(),
+        Message::InvalidFingerprint(value) => format!("无效的公钥指纹: {}", value),
+        Message::InvalidRecordId => "无效的记录 ID".to_string(),
+        Message::RecordNotCached => "本地缓存中没有该记录的当前版本，请同步后重试".to_string(),
+        Message::InvalidUpdateTime => "无效的更新时间".to_string(),
+        Message::ProfileNotFound(name) => format!("服务器 {} 不存在", name),
+        Message::NoServerProfile => "尚未配置服务器".to_string(),
+        Message::UnresolvedConflict => "该记录存在未处理的修改冲突".to_string(),
+        Message::NoConflict => "该记录没有修改冲突".to_string(),
+        Message::EmptyEncryptInput => "加密内容不能为空".to_string(),
+        Message::EmptyDecryptInput => "解密内容不能为空".to_string(),
+
+        Message::Registered => "注册成功".to_string(),
+        Message::CorePasswordChanged => "核心密码修改成功".to_string(),
+        Message::FullFieldEncryptionEnabled => "已开启全字段加密".to_string(),
+        Message::FullFieldEncryptionDisabled => "已关闭全字段加密".to_string(),
+        Message::WriteSucceeded(action) => format!("{}成功", action_zh_cn(action)),
+        Message::WriteFailed(action, reason) => {
+            format!("{}失败: {}", action_zh_cn(action), reason)
+        }
+        Message::WriteQueued(action) => {
+            format!("{}已保存，将在网络恢复后同步", action_zh_cn(action))
+        }
+        Message::ServerVersionKept => "已采用服务器版本".to_string(),
+    }
+}
+
+fn field_zh_cn(field: Field) -> &'static str {
+    match field {
+        Field::ApiUrl => "API URL",
+        Field::Username => "用户名",
+        Field::Password => "密码",
+        Field::CorePassword => "核心密码",
+        Field::OldCorePassword => "原核心密码",
+        Field::NewCorePassword => "新核心密码",
+        Field::Token => "认证令牌",
+        Field::ServerName => "服务器名称",
+        Field::Fingerprint => "公钥指纹",
+        Field::Website => "网站",
+        Field::Account => "账户",
+        Field::Session => "会话",
+        Field::UserAgent => "User-Agent",
+        Field::RequestTimeout => "请求超时时间",
+        Field::ConnectTimeout => "连接超时时间",
+        Field::SyncInterval => "同步间隔",
+        Field::IdleTimeout => "空闲超时时间",
+    }
+}
+
+fn action_zh_cn(action: WriteAction) -> &'static str {
+    match action {
+        WriteAction::Insert => "插入",
+        WriteAction::Update => "更新",
+        WriteAction::Delete => "删除",
+        WriteAction::KeepLocal => "保存本地版本",
+        WriteAction::KeepBoth => "另存本地版本",
+    }
+}
+
+// ============================================
+// 英语
+// ============================================
+
+fn en(message: &Message<'_>) -> String {
+    match *message {
+        Message::StateNotInitialized => "Not signed in. Please log in first".to_string(),
+        Message::StateLockError => "Failed to acquire the application state".to_string(),
+        Message::Locked => "The vault is locked. Enter your core password to unlock it".to_string(),
+        Message::SessionNotFound(username) => format!("User {} is not signed in", username),
+        Message::CertificatePinMismatch(fingerprint) => format!(
+            "The server certificate does not match the pinned fingerprint (actual: {}); \
+             the connection may have been intercepted",
+            fingerprint
+        ),
+        Message::UntrustedCertificate(fingerprint) => format!(
+            "The server certificate is not trusted (public key fingerprint: {})",
+            fingerprint
+        ),
+        Message::NetworkError(msg) => format!("Network request failed: {}", msg),
+        Message::Cancelled => "The request was cancelled".to_string(),
+        Message::ApiError(code, msg) => format!("API error [{}]: {}", code, msg),
+        Message::HttpError(status, msg) => format!("HTTP error [{}]: {}", status, msg),
+        Message::InvalidCredentials(msg) => format!("Authentication failed: {}", msg),
+        Message::Conflict(msg) => format!("Edit conflict: {}", msg),
+        Message::DatabaseError(msg) => format!("Database error: {}", msg),
+        Message::CryptoError(msg) => format!("Encryption error: {}", msg),
+        Message::SerializationError(msg) => format!("Serialization error: {}", msg),
+        Message::IoError(msg) => format!("I/O error: {}", msg),
+        Message::ConfigError(msg) => format!("Configuration error: {}", msg),
+        Message::ValidationError(msg) => format!("Invalid input: {}", msg),
+        Message::Unknown(msg) => format!("Unknown error: {}", msg),
+
+        Message::RequestTimeout => "the request timed out".to_string(),
+        Message::ConnectFailed => "could not connect, please check your network".to_string(),
+
+        Message::KdfDowngrade => "the server offered legacy KDF parameters for an account \
+                                  that was already upgraded on this device; login refused"
+            .to_string(),
+        Message::KdfUpgradeFailed(msg) => format!("failed to upgrade KDF parameters: {}", msg),
+        Message::MigrationFailed(msg) => format!("failed to migrate legacy entries: {}", msg),
+        Message::SettingsUnavailable(msg) => format!("cannot open the settings file: {}", msg),
+        Message::AutoLockUnavailable(msg) => format!("cannot start auto-lock: {}", msg),
+        Message::UnsyncedWritesBeforeRekey => "there are unsynced writes or unresolved conflicts; \
+                                               sync and resolve them before changing the core password"
+            .to_string(),
+        Message::UnsyncedWritesUnderUnknownKey => "the local cache holds unsynced writes or unresolved \
+                                                   conflicts encrypted with another core password; \
+                                                   unlock with the previous core password and sync first"
+            .to_string(),
+        Message::IncorrectPassword(field) => format!("incorrect {}", field_en(field)),
+        Message::SameCorePassword => {
+            "the new core password must differ from the current one".to_string()
+        }
+
+        Message::KdfIterationsTooLow => "too few PBKDF2 iterations".to_string(),
+        Message::KdfSaltTooShort => "the Argon2id salt is too short".to_string(),
+        Message::KdfMemoryOutOfRange => "the Argon2id memory cost is out of range".to_string(),
+        Message::KdfTimeCostOutOfRange => "the Argon2id time cost is out of range".to_string(),
+        Message::KdfParallelismOutOfRange => "the Argon2id parallelism is out of range".to_string(),
+        Message::InvalidKdfSalt(msg) => format!("malformed salt: {}", msg),
+        Message::InvalidKdfParams(msg) => format!("invalid Argon2id parameters: {}", msg),
+        Message::KdfFailed(msg) => format!("Argon2id key derivation failed: {}", msg),
+        Message::RandomFailed => "failed to generate random bytes".to_string(),
+        Message::KeyDerivationFailed => "failed to derive a subkey".to_string(),
+        Message::LegacyKeyUnavailable => {
+            "legacy ciphertext has not been migrated yet; log in again and retry".to_string()
+        }
+        Message::NoDataKeyToWrap => {
+            "envelope encryption is not enabled; there is no data key to wrap".to_string()
+        }
+        Message::NotWrappedKey => "not a wrapped data key".to_string(),
+        Message::UnwrapKeyFailed => "failed to unwrap the data key; \
+                                     the password is wrong or the data was tampered with"
+            .to_string(),
+        Message::InvalidDataKeyLength => "the data key has the wrong length".to_string(),
+        Message::MissingDataKey => "the data key is missing; cannot decrypt".to_string(),
+        Message::NotCacheField => "not a cache field ciphertext".to_string(),
+        Message::NotRecordCiphertext => "not a record ciphertext".to_string(),
+        Message::NotEnvelope => "not an envelope ciphertext".to_string(),
+        Message::InvalidCiphertextEncoding(msg) => format!("malformed ciphertext: {}", msg),
+        Message::CiphertextTooShort => "the ciphertext is too short".to_string(),
+        Message::UnsupportedEnvelopeVersion(version) => {
+            format!("unsupported ciphertext version: {}", version)
+        }
+        Message::UnsupportedEnvelopeKdf(id) => format!("unsupported key derivation: {}", id),
+        Message::EncryptFailed => "encryption failed".to_string(),
+        Message::CiphertextTampered => "ciphertext authentication failed; \
+                                        the data may have been tampered with or the key is wrong"
+            .to_string(),
+        Message::InvalidPlaintext(msg) => format!("the decrypted data is not valid text: {}", msg),
+        Message::LegacyDecryptFailed(msg) => format!("decryption failed: {}", msg),
+        Message::LegacyChecksumMismatch => "decryption failed: checksum mismatch".to_string(),
+
+        Message::ServerUnreachable => {
+            "the server is unreachable; offline writes are waiting to be synced".to_string()
+        }
+        Message::VerifyNeedsNetwork => "some offline writes could not be sent; \
+                                        connect to the network and verify again"
+            .to_string(),
+        Message::QueryFailed(msg) => format!("query failed: {}", msg),
+        Message::RecordNotSynced => "the record has not been synced to the server yet".to_string(),
+        Message::SyncConflict => "conflicts with a change from another device".to_string(),
+        Message::MissingCacheRecord => "no record to write to the cache".to_string(),
+        Message::UnknownPullMode(mode) => format!("unknown pull mode: {}", mode),
+        Message::UnknownWriteKind(kind) => format!("unknown write operation: {}", kind),
+
+        Message::NoAppDataDir => "cannot locate the application data directory".to_string(),
+        Message::SettingsMalformed => "the settings file is malformed".to_string(),
+        Message::SettingsTooNew(version, supported) => format!(
+            "settings file version {} is newer than the supported version {}; please update the app",
+            version, supported
+        ),
+        Message::InvalidProxy(msg) => format!("invalid proxy configuration: {}", msg),
+        Message::HttpClientFailed(msg) => format!("failed to create the HTTP client: {}", msg),
+        Message::InvalidServerCertificate(msg) => {
+            format!("failed to parse the server certificate: {}", msg)
+        }
+        Message::InvalidCaCertificate(msg) => format!("invalid CA certificate: {}", msg),
+        Message::CertificateVerifierFailed(msg) => {
+            format!("failed to create the certificate verifier: {}", msg)
+        }
+        Message::TlsConfigFailed(msg) => format!("invalid TLS configuration: {}", msg),
+        Message::CaFileUnreadable(path, msg) => {
+            format!("failed to read CA certificate {}: {}", path, msg)
+        }
+        Message::CaFileInvalid(path, msg) => {
+            format!("failed to parse CA certificate {}: {}", path, msg)
+        }
+        Message::CaFileEmpty(path) => format!("CA certificate file {} contains no certificates", path),
+
+        Message::Required(field) => format!("{} is required", field_en(field)),
+        Message::MinLength(field, len) => {
+            format!("{} must be at least {} characters", field_en(field), len)
+        }
+        Message::MaxLength(field, len) => {
+            format!("{} must be at most {} characters", field_en(field), len)
+        }
+        Message::SecondsOutOfRange(field, min, max) => {
+            format!("{} must be between {} and {} seconds", field_en(field), min, max)
+        }
+        Message::InvalidUrl(msg) => format!("invalid URL: {}", msg),
+        Message::UnsupportedUrlScheme => "only http and https URLs are supported".to_string(),
+        Message::UrlMissingHost => "the URL has no host name".to_string(),
+        Message::UrlHasQuery => "the URL must not contain a query or fragment".to_string(),
+        Message::UrlHasUserInfo => "the URL must not contain user credentials".to_string(),
+        Message::InvalidProxyUrl(msg) => format!("invalid proxy URL: {}", msg),
+        Message::UnsupportedProxyScheme => {
+            "only http, https, socks5 and socks5h proxies are supported".to_string()
+        }
+        Message::ProxyMissingHost => "the proxy URL has no host name".to_string(),
+        Message::NonAsciiUserAgent => {
+            "the User-Agent may only contain printable ASCII characters".to_string()
+        }
+        Message::InvalidFingerprint(value) => format!("invalid public key fingerprint: {}", value),
+        Message::InvalidRecordId => "invalid record ID".to_string(),
+        Message::RecordNotCached => {
+            "the current version of this record is not cached; sync and try again".to_string()
+        }
+        Message::InvalidUpdateTime => "invalid update time".to_string(),
+        Message::ProfileNotFound(name) => format!("server {} does not exist", name),
+        Message::NoServerProfile => "no server is configured".to_string(),
+        Message::UnresolvedConflict => "this record has an unresolved edit conflict".to_string(),
+        Message::NoConflict => "this record has no edit conflict".to_string(),
+        Message::EmptyEncryptInput => "nothing to encrypt".to_string(),
+        Message::EmptyDecryptInput => "nothing to decrypt".to_string(),
+
+        Message::Registered => "Registered successfully".to_string(),
+        Message::CorePasswordChanged => "Core password changed".to_string(),
+        Message::FullFieldEncryptionEnabled => "Full-field encryption enabled".to_string(),
+        Message::FullFieldEncryptionDisabled => "Full-field encryption disabled".to_string(),
+        Message::WriteSucceeded(action) => format!("{} succeeded", action_en(action)),
+        Message::WriteFailed(action, reason) => format!("{} failed: {}", action_en(action), reason),
+        Message::WriteQueued(action) => format!(
+            "{} saved; it will be synced when the network is back",
+            action_en(action)
+        ),
+        Message::ServerVersionKept => "Kept the server version".to_string(),
+    }
+}
+
+fn field_en(field: Field) -> &'static str {
+    match field {
+        Field::ApiUrl => "API URL",
+        Field::Username => "username",
+        Field::Password => "password",
+        Field::CorePassword => "core password",
+        Field::OldCorePassword => "current core password",
+        Field::NewCorePassword => "new core password",
+        Field::Token => "token",
+        Field::ServerName => "server name",
+        Field::Fingerprint => "public key fingerprint",
+        Field::Website => "website",
+        Field::Account => "account",
+        Field::Session => "session",
+        Field::UserAgent => "User-Agent",
+        Field::RequestTimeout => "request timeout",
+        Field::ConnectTimeout => "connect timeout",
+        Field::SyncInterval => "sync interval",
+        Field::IdleTimeout => "idle timeout",
+    }
+}
+
+fn action_en(action: WriteAction) -> &'static str {
+    match action {
+        WriteAction::Insert => "Insert",
+        WriteAction::Update => "Update",
+        WriteAction::Delete => "Delete",
+        WriteAction::KeepLocal => "Saving the local version",
+        WriteAction::KeepBoth => "Saving the local version as a new record",
+    }
+}
+
+// ============================================
+// 单元测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_from_tag() {
+        assert_eq!(Locale::from_tag("zh-CN"), Locale::ZhCn);
+        assert_eq!(Locale::from_tag("zh_TW.UTF-8"), Locale::ZhCn);
+        assert_eq!(Locale::from_tag("ZH"), Locale::ZhCn);
+        assert_eq!(Locale::from_tag("en-US"), Locale::En);
+        assert_eq!(Locale::from_tag("de_DE.UTF-8"), Locale::En);
+        assert_eq!(Locale::from_tag(""), Locale::default());
+    }
+
+    #[test]
+    fn test_locale_serialize() {
+        assert_eq!(serde_json::to_string(&Locale::ZhCn).unwrap(), r#""zh-CN""#);
+        assert_eq!(serde_json::to_string(&Locale::En).unwrap(), r#""en""#);
+        let locale: Locale = serde_json::from_str(r#""en""#).unwrap();
+        assert_eq!(locale, Locale::En);
+        for locale in Locale::ALL {
+            assert_eq!(Locale::from_u8(locale.to_u8()), locale);
+        }
+    }
+
+    #[test]
+    fn test_message_text() {
+        let message = Message::MinLength(Field::Password, 6);
+        assert_eq!(message.text(Locale::ZhCn), "密码长度不能少于6个字符");
+        assert_eq!(
+            message.text(Locale::En),
+            "password must be at least 6 characters"
+        );
+
+        let message = Message::WriteFailed(WriteAction::Delete, "记录不存在");
+        assert_eq!(message.text(Locale::ZhCn), "删除失败: 记录不存在");
+        assert_eq!(message.text(Locale::En), "Delete failed: 记录不存在");
+    }
+}
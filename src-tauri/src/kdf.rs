@@ -0,0 +1,486 @@
+//! 密钥派生（KDF）模块
+//!
+//! 提供版本化的密码派生算法：
+//! - `Pbkdf2Sha256` - 旧版算法（固定盐值），仅用于兼容尚未升级的账户
+//! - `Argon2id` - 当前默认算法，每个用户使用随机盐值，参数可调
+//!
+//! # 协商流程
+//! 1. 登录前通过 `/v1/login/params` 获取该用户的 KDF 参数，服务器返回空参数时使用旧版参数；
+//!    本设备上已升级过的账户拒绝旧版参数
+//! 2. 使用协商得到的参数计算登录密码和核心密码的哈希
+//! 3. 登录成功后，若参数已过时则生成新的 Argon2id 参数并上报服务器（透明升级）
+//!
+//! 保险库密钥参数在注册时随机生成，与登录/核心密码参数一起保存在服务器上；
+//! 它决定已有密文的密钥，因此升级和修改核心密码时保持不变
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use ring::pbkdf2;
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroU32;
+
+use crate::crypto::random_bytes;
+use crate::error::{DurianError, DurianResult};
+use crate::i18n::{tr, Message};
+use crate::models::PreloginResponseData;
+
+// ============================================
+// 常量定义
+// ============================================
+
+/// 派生密钥长度（字节）
+pub const KDF_OUTPUT_LEN: usize = 32;
+
+/// 随机盐值长度（字节）
+pub const KDF_SALT_LEN: usize = 16;
+
+/// Argon2id 默认内存成本（KiB，64 MiB）
+pub const ARGON2_DEFAULT_MEMORY_KIB: u32 = 64 * 1024;
+
+/// Argon2id 默认时间成本（迭代次数）
+pub const ARGON2_DEFAULT_ITERATIONS: u32 = 3;
+
+/// Argon2id 默认并行度
+pub const ARGON2_DEFAULT_PARALLELISM: u32 = 1;
+
+/// Argon2id 允许的最小内存成本（KiB），防止服务器下发过弱的参数
+const ARGON2_MIN_MEMORY_KIB: u32 = 8 * 1024;
+
+/// Argon2id 允许的最大内存成本（KiB），防止服务器下发导致内存耗尽的参数
+const ARGON2_MAX_MEMORY_KIB: u32 = 1024 * 1024;
+
+/// Argon2id 允许的最大时间成本
+const ARGON2_MAX_ITERATIONS: u32 = 16;
+
+/// Argon2id 允许的最大并行度
+const ARGON2_MAX_PARALLELISM: u32 = 16;
+
+/// 保险库密钥的 Argon2id 内存成本（KiB）
+///
+/// 已保存的保险库密钥参数不会再改变，修改此常量只影响新注册的账户
+const VAULT_MEMORY_KIB: u32 = 64 * 1024;
+
+/// 保险库密钥的 Argon2id 时间成本
+const VAULT_ITERATIONS: u32 = 3;
+
+/// 保险库密钥的 Argon2id 并行度
+const VAULT_PARALLELISM: u32 = 1;
+
+/// 旧版保险库密钥盐值的派生前缀
+const LEGACY_VAULT_SALT_DOMAIN: &str = "durian.vault.key:";
+
+/// 旧版 PBKDF2 迭代次数
+const LEGACY_PBKDF2_ITERATIONS: u32 = 100000;
+
+/// 旧版登录密码哈希盐值
+const LEGACY_LOGIN_SALT: &str = "durian.password";
+
+/// 旧版核心密码哈希盐值
+const LEGACY_CORE_SALT: &str = "durian.core.password";
+
+// ============================================
+// KDF 参数定义
+// ============================================
+
+/// 支持的 KDF 算法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KdfAlgorithm {
+    /// PBKDF2-HMAC-SHA256（旧版，版本 1）
+    Pbkdf2Sha256,
+    /// Argon2id（当前默认，版本 2）
+    Argon2id,
+}
+
+impl KdfAlgorithm {
+    /// 算法版本号，数值越大越新
+    #[inline]
+    pub fn version(self) -> u8 {
+        match self {
+            KdfAlgorithm::Pbkdf2Sha256 => 1,
+            KdfAlgorithm::Argon2id => 2,
+        }
+    }
+}
+
+/// KDF 参数
+///
+/// 与服务器交换时使用 JSON 格式，盐值为十六进制编码
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    /// 算法
+    pub algorithm: KdfAlgorithm,
+    /// 十六进制编码的盐值
+    pub salt: String,
+    /// 迭代次数（PBKDF2 为迭代轮数，Argon2id 为时间成本）
+    pub iterations: u32,
+    /// 内存成本（KiB，仅 Argon2id 使用）
+    #[serde(default)]
+    pub memory_kib: u32,
+    /// 并行度（仅 Argon2id 使用）
+    #[serde(default)]
+    pub parallelism: u32,
+}
+
+impl KdfParams {
+    /// 旧版登录密码参数（固定盐值 PBKDF2）
+    pub fn legacy_login() -> Self {
+        Self::legacy_pbkdf2(LEGACY_LOGIN_SALT)
+    }
+
+    /// 旧版核心密码参数（固定盐值 PBKDF2）
+    pub fn legacy_core() -> Self {
+        Self::legacy_pbkdf2(LEGACY_CORE_SALT)
+    }
+
+    fn legacy_pbkdf2(salt: &str) -> Self {
+        Self {
+            algorithm: KdfAlgorithm::Pbkdf2Sha256,
+            salt: hex::encode(salt),
+            iterations: LEGACY_PBKDF2_ITERATIONS,
+            memory_kib: 0,
+            parallelism: 0,
+        }
+    }
+
+    /// 使用随机盐值创建保险库密钥参数（Argon2id）
+    ///
+    /// 注册时生成并保存在服务器上，各设备在登录前协商时取得同一组参数。
+    /// 盐值不可预测，攻击者无法针对某个用户名提前计算核心密码的候选密钥
+    pub fn vault() -> DurianResult<Self> {
+        Self::argon2id(VAULT_MEMORY_KIB, VAULT_ITERATIONS, VAULT_PARALLELISM)
+    }
+
+    /// 旧版保险库密钥参数（Argon2id，盐值由用户名确定性生成）
+    ///
+    /// 盐值可以按用户名预先计算，仅用于服务器未保存保险库参数的旧账户，
+    /// 以便读取这些账户已有的密文
+    ///
+    /// # Arguments
+    /// * `username` - 用户名
+    pub fn legacy_vault(username: &str) -> Self {
+        let mut input = LEGACY_VAULT_SALT_DOMAIN.as_bytes().to_vec();
+        input.extend_from_slice(username.as_bytes());
+        let digest = ring::digest::digest(&ring::digest::SHA256, &input);
+        Self {
+            algorithm: KdfAlgorithm::Argon2id,
+            salt: hex::encode(&digest.as_ref()[..KDF_SALT_LEN]),
+            iterations: VAULT_ITERATIONS,
+            memory_kib: VAULT_MEMORY_KIB,
+            parallelism: VAULT_PARALLELISM,
+        }
+    }
+
+    /// 使用指定成本和随机盐值创建 Argon2id 参数
+    ///
+    /// # Arguments
+    /// * `memory_kib` - 内存成本（KiB）
+    /// * `iterations` - 时间成本
+    /// * `parallelism` - 并行度
+    pub fn argon2id(memory_kib: u32, iterations: u32, parallelism: u32) -> DurianResult<Self> {
+        let salt = random_bytes::<KDF_SALT_LEN>()?;
+        let params = Self {
+            algorithm: KdfAlgorithm::Argon2id,
+            salt: hex::encode(salt),
+            iterations,
+            memory_kib,
+            parallelism,
+        };
+        params.validate()?;
+        Ok(params)
+    }
+
+    /// 使用推荐成本和随机盐值创建 Argon2id 参数
+    pub fn recommended() -> DurianResult<Self> {
+        Self::argon2id(
+            ARGON2_DEFAULT_MEMORY_KIB,
+            ARGON2_DEFAULT_ITERATIONS,
+            ARGON2_DEFAULT_PARALLELISM,
+        )
+    }
+
+    /// 校验参数是否在允许范围内
+    ///
+    /// 参数可能来自服务器，需要防止过弱或过大的成本
+    pub fn validate(&self) -> DurianResult<()> {
+        let salt = self.salt_bytes()?;
+        match self.algorithm {
+            KdfAlgorithm::Pbkdf2Sha256 => {
+                if self.iterations < LEGACY_PBKDF2_ITERATIONS {
+                    return Err(DurianError::crypto(tr(Message::KdfIterationsTooLow)));
+                }
+            }
+            KdfAlgorithm::Argon2id => {
+                if salt.len() < KDF_SALT_LEN {
+                    return Err(DurianError::crypto(tr(Message::KdfSaltTooShort)));
+                }
+                if !(ARGON2_MIN_MEMORY_KIB..=ARGON2_MAX_MEMORY_KIB).contains(&self.memory_kib) {
+                    return Err(DurianError::crypto(tr(Message::KdfMemoryOutOfRange)));
+                }
+                if !(1..=ARGON2_MAX_ITERATIONS).contains(&self.iterations) {
+                    return Err(DurianError::crypto(tr(Message::KdfTimeCostOutOfRange)));
+                }
+                if !(1..=ARGON2_MAX_PARALLELISM).contains(&self.parallelism) {
+                    return Err(DurianError::crypto(tr(Message::KdfParallelismOutOfRange)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 检查参数是否低于当前推荐强度，需要升级
+    pub fn needs_upgrade(&self) -> bool {
+        match self.algorithm {
+            KdfAlgorithm::Pbkdf2Sha256 => true,
+            KdfAlgorithm::Argon2id => {
+                self.memory_kib < ARGON2_DEFAULT_MEMORY_KIB
+                    || self.iterations < ARGON2_DEFAULT_ITERATIONS
+            }
+        }
+    }
+
+    /// 解码盐值
+    fn salt_bytes(&self) -> DurianResult<Vec<u8>> {
+        hex::decode(&self.salt).map_err(|e| DurianError::crypto(tr(Message::InvalidKdfSalt(&e.to_string()))))
+    }
+
+    /// 从密码派生密钥
+    ///
+    /// # Arguments
+    /// * `password` - 原始密码
+    ///
+    /// # Returns
+    /// 32 字节的派生密钥
+    pub fn derive_key(&self, password: &str) -> DurianResult<[u8; KDF_OUTPUT_LEN]> {
+        let salt = self.salt_bytes()?;
+        let mut output = [0u8; KDF_OUTPUT_LEN];
+
+        match self.algorithm {
+            KdfAlgorithm::Pbkdf2Sha256 => {
+                let iterations = NonZeroU32::new(self.iterations)
+                    .ok_or_else(|| DurianError::crypto(tr(Message::KdfIterationsTooLow)))?;
+                pbkdf2::derive(
+                    pbkdf2::PBKDF2_HMAC_SHA256,
+                    iterations,
+                    &salt,
+                    password.as_bytes(),
+                    &mut output,
+                );
+            }
+            KdfAlgorithm::Argon2id => {
+                let params = Params::new(
+                    self.memory_kib,
+                    self.iterations,
+                    self.parallelism,
+                    Some(KDF_OUTPUT_LEN),
+                )
+                .map_err(|e| DurianError::crypto(tr(Message::InvalidKdfParams(&e.to_string()))))?;
+                Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password_into(password.as_bytes(), &salt, &mut output)
+                    .map_err(|e| DurianError::crypto(tr(Message::KdfFailed(&e.to_string()))))?;
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// 对密码进行哈希（用于提交给服务器）
+    ///
+    /// # Returns
+    /// 十六进制编码的哈希字符串
+    pub fn hash_password(&self, password: &str) -> DurianResult<String> {
+        self.derive_key(password).map(hex::encode)
+    }
+}
+
+// ============================================
+// 登录/核心密码参数组合
+// ============================================
+
+/// 一个用户的完整 KDF 参数组合
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KdfSuite {
+    /// 登录密码参数
+    pub login: KdfParams,
+    /// 核心密码参数
+    pub core: KdfParams,
+    /// 保险库密钥参数（旧账户没有，使用 [`KdfParams::legacy_vault`]）
+    pub vault: Option<KdfParams>,
+}
+
+impl KdfSuite {
+    /// 旧版参数组合（未升级账户或服务器不支持协商时使用）
+    pub fn legacy() -> Self {
+        Self {
+            login: KdfParams::legacy_login(),
+            core: KdfParams::legacy_core(),
+            vault: None,
+        }
+    }
+
+    /// 生成推荐参数组合（每组参数各自使用独立随机盐值）
+    pub fn generate() -> DurianResult<Self> {
+        Ok(Self {
+            login: KdfParams::recommended()?,
+            core: KdfParams::recommended()?,
+            vault: Some(KdfParams::vault()?),
+        })
+    }
+
+    /// 根据服务器的协商结果构造参数组合
+    ///
+    /// 服务器未返回的参数按旧版处理，返回的参数必须通过校验
+    pub fn from_prelogin(data: PreloginResponseData) -> DurianResult<Self> {
+        let login = data.login_kdf.unwrap_or_else(KdfParams::legacy_login);
+        let core = data.core_kdf.unwrap_or_else(KdfParams::legacy_core);
+        login.validate()?;
+        core.validate()?;
+        if let Some(vault) = &data.vault_kdf {
+            vault.validate()?;
+        }
+        Ok(Self {
+            login,
+            core,
+            vault: data.vault_kdf,
+        })
+    }
+
+    /// 派生保险库密钥使用的参数，旧账户使用由用户名生成的旧版参数
+    ///
+    /// # Arguments
+    /// * `username` - 用户名
+    pub fn vault_params(&self, username: &str) -> KdfParams {
+        self.vault
+            .clone()
+            .unwrap_or_else(|| KdfParams::legacy_vault(username))
+    }
+
+    /// 检查是否有任一参数需要升级
+    pub fn needs_upgrade(&self) -> bool {
+        self.login.needs_upgrade() || self.core.needs_upgrade()
+    }
+
+    /// 检查是否有任一参数是旧版 PBKDF2 参数
+    ///
+    /// 升级后的账户不会再使用旧版参数，服务器返回旧版参数时应视为降级
+    pub fn uses_legacy(&self) -> bool {
+        self.login.algorithm == KdfAlgorithm::Pbkdf2Sha256
+            || self.core.algorithm == KdfAlgorithm::Pbkdf2Sha256
+    }
+}
+
+// ============================================
+// 单元测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 测试用的低成本 Argon2id 参数
+    fn fast_argon2id() -> KdfParams {
+        KdfParams::argon2id(ARGON2_MIN_MEMORY_KIB, 1, 1).unwrap()
+    }
+
+    #[test]
+    fn test_legacy_params_match_fixed_salt_hash() {
+        let hash = KdfParams::legacy_login().hash_password("password").unwrap();
+        assert_eq!(hash, crate::crypto::hash_login_password("password").unwrap());
+
+        let hash = KdfParams::legacy_core().hash_password("password").unwrap();
+        assert_eq!(hash, crate::crypto::hash_core_password("password").unwrap());
+    }
+
+    #[test]
+    fn test_argon2id_random_salts() {
+        let a = fast_argon2id();
+        let b = fast_argon2id();
+        assert_ne!(a.salt, b.salt);
+
+        // 相同密码在不同盐值下应产生不同哈希
+        let hash_a = a.hash_password("same_password").unwrap();
+        let hash_b = b.hash_password("same_password").unwrap();
+        assert_ne!(hash_a, hash_b);
+        assert_eq!(hash_a.len(), 64);
+
+        // 相同参数应产生相同哈希
+        assert_eq!(hash_a, a.hash_password("same_password").unwrap());
+    }
+
+    #[test]
+    fn test_validate_rejects_weak_params() {
+        let mut params = fast_argon2id();
+        params.memory_kib = 1024;
+        assert!(params.validate().is_err());
+
+        let mut params = fast_argon2id();
+        params.salt = "abcd".to_string();
+        assert!(params.validate().is_err());
+
+        let mut params = KdfParams::legacy_login();
+        params.iterations = 1000;
+        assert!(params.validate().is_err());
+    }
+
+    #[test]
+    fn test_vault_params() {
+        let a = KdfParams::vault().unwrap();
+        let b = KdfParams::vault().unwrap();
+        assert_ne!(a.salt, b.salt);
+        assert_eq!((a.memory_kib, a.iterations), (b.memory_kib, b.iterations));
+
+        let legacy = KdfParams::legacy_vault("alice");
+        assert_eq!(legacy, KdfParams::legacy_vault("alice"));
+        assert_ne!(legacy.salt, KdfParams::legacy_vault("bob").salt);
+        assert!(legacy.validate().is_ok());
+
+        // 服务器保存了保险库参数时使用随机盐值，否则回退到旧版参数
+        let suite = KdfSuite::generate().unwrap();
+        assert_eq!(suite.vault_params("alice"), suite.vault.clone().unwrap());
+        assert_eq!(KdfSuite::legacy().vault_params("alice"), legacy);
+    }
+
+    #[test]
+    fn test_needs_upgrade() {
+        assert!(KdfSuite::legacy().needs_upgrade());
+        assert!(fast_argon2id().needs_upgrade());
+        assert!(!KdfParams::recommended().unwrap().needs_upgrade());
+    }
+
+    #[test]
+    fn test_from_prelogin_falls_back_to_legacy() {
+        let suite = KdfSuite::from_prelogin(PreloginResponseData::default()).unwrap();
+        assert_eq!(suite, KdfSuite::legacy());
+
+        let core = fast_argon2id();
+        let suite = KdfSuite::from_prelogin(PreloginResponseData {
+            login_kdf: None,
+            core_kdf: Some(core.clone()),
+            vault_kdf: None,
+        })
+        .unwrap();
+        assert_eq!(suite.login, KdfParams::legacy_login());
+        assert_eq!(suite.core, core);
+        assert!(suite.vault.is_none());
+        assert!(suite.uses_legacy());
+        assert!(!KdfSuite::generate().unwrap().uses_legacy());
+
+        // 服务器下发的保险库参数同样需要通过校验
+        let mut vault = fast_argon2id();
+        vault.memory_kib = 1024;
+        let result = KdfSuite::from_prelogin(PreloginResponseData {
+            login_kdf: None,
+            core_kdf: None,
+            vault_kdf: Some(vault),
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_params_json_roundtrip() {
+        let params = fast_argon2id();
+        let json = serde_json::to_string(&params).unwrap();
+        assert!(json.contains("\"argon2id\""));
+        let parsed: KdfParams = serde_json::from_str(&json).unwrap();
+        assert_eq!(params, parsed);
+    }
+}
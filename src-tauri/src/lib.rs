@@ -5,12 +5,24 @@
 //!
 //! # 模块结构
 //!
+//! - `i18n` - 本地化消息（简体中文、英语）
 //! - `models` - 数据模型定义
+//! - `secret` - 敏感数据封装（清零、防泄露）
+//! - `kdf` - 版本化的密钥派生（Argon2id / 旧版 PBKDF2）
 //! - `crypto` - 加密和密码哈希功能
+//! - `tls` - TLS 证书校验策略（自定义 CA、公钥固定）
 //! - `api_client` - HTTP API 客户端
 //! - `database` - SQLite 数据库操作
-//! - `state` - 应用状态管理
+//! - `vault` - 保险库解锁与数据密钥管理
+//! - `settings` - 应用设置（服务器配置）
+//! - `state` - 应用状态与多会话管理（由 Tauri 托管）
+//! - `lock` - 保险库自动锁定
+//! - `rekey` - 核心密码修改与保险库重新加密
+//! - `sync` - 离线写入队列
+//! - `sync_worker` - 后台定期同步
+//! - `verify` - 缓存完整性校验
 //! - `commands` - Tauri 命令定义
+//! - `fake_api` - 内存中的模拟服务器（仅测试）
 
 // ============================================
 // 模块声明
@@ -19,24 +31,61 @@
 /// 统一错误处理
 pub mod error;
 
+/// 本地化消息
+pub mod i18n;
+
 /// 数据模型定义
 pub mod models;
 
+/// 敏感数据封装
+pub mod secret;
+
+/// 版本化的密钥派生
+pub mod kdf;
+
 /// 加密和密码哈希功能
 pub mod crypto;
 
+/// TLS 证书校验策略
+pub mod tls;
+
 /// HTTP API 客户端
 pub mod api_client;
 
 /// SQLite 数据库操作
 pub mod database;
 
+/// 保险库解锁与数据密钥管理
+pub mod vault;
+
+/// 应用设置
+pub mod settings;
+
 /// 应用状态管理
 pub mod state;
 
+/// 保险库自动锁定
+pub mod lock;
+
+/// 核心密码修改与保险库重新加密
+pub mod rekey;
+
+/// 离线写入队列
+pub mod sync;
+
+/// 后台定期同步
+pub mod sync_worker;
+
+/// 缓存完整性校验
+pub mod verify;
+
 /// Tauri 命令定义
 pub mod commands;
 
+/// 内存中的模拟服务器
+#[cfg(test)]
+mod fake_api;
+
 // ============================================
 // 应用入口
 // ============================================
@@ -46,8 +95,27 @@ pub mod commands;
 /// 配置并启动 Tauri 应用，注册所有可用的命令处理器
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let app_state = state::AppState::new();
+    // 设置文件无法打开时仍然启动，读写设置的命令向前端返回错误
+    let settings = settings::SettingsStore::open_default();
+
+    // 界面语言：设置文件无法读取时跟随操作系统
+    let locale = settings
+        .load()
+        .map(|settings| settings.locale())
+        .unwrap_or_else(|_| i18n::Locale::system());
+    i18n::set_current_locale(locale);
+    let watched = app_state.clone();
+
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .setup(move |_| {
+            // 后台检查空闲超时和系统休眠，按设置自动锁定保险库
+            lock::start_watcher(watched)?;
+            Ok(())
+        })
+        .manage(app_state)
+        .manage(settings)
         .invoke_handler(tauri::generate_handler![
             // 认证相关
             commands::init_state,
@@ -55,12 +123,43 @@ pub fn run() {
             commands::register,
             commands::verify,
             commands::logout,
+            commands::list_sessions,
+            commands::switch_session,
             commands::is_logged_in,
+            commands::change_core_password,
+            commands::has_pending_core_password_change,
+            commands::lock,
+            commands::unlock,
+            commands::is_locked,
+            commands::get_lock_settings,
+            commands::set_lock_settings,
+            commands::set_full_field_encryption,
+            commands::get_full_field_encryption,
+            // 服务器配置
+            commands::list_profiles,
+            commands::save_profile,
+            commands::delete_profile,
+            commands::probe_server_certificate,
+            commands::trust_server_certificate,
+            // 界面语言
+            commands::get_locale,
+            commands::set_locale,
             // 账户管理
             commands::query_accounts,
             commands::insert_account,
             commands::update_account,
             commands::delete_account,
+            commands::cancel_requests,
+            // 离线写入队列
+            commands::sync_pending_ops,
+            commands::get_sync_status,
+            commands::clear_sync_failures,
+            // 后台同步
+            commands::get_sync_settings,
+            commands::set_sync_settings,
+            // 修改冲突
+            commands::list_conflicts,
+            commands::resolve_conflict,
             // 加密解密
             commands::encrypt,
             commands::decrypt,
@@ -70,9 +169,9 @@ pub fn run() {
             commands::load_query_cache,
             commands::get_last_update_time,
             commands::clear_cache,
+            commands::verify_cache,
             // 状态获取
-            commands::get_username,
-            commands::get_token
+            commands::get_username
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,184 @@
+//! 保险库自动锁定模块
+//!
+//! 锁定时清除内存中的保险库密钥和缓存加密器，但保留认证令牌和本地缓存，
+//! 之后使用核心密码即可在本地解锁，无需重新登录
+//!
+//! # 触发条件
+//! - 空闲超时：超过设定时间没有使用保险库密钥
+//! - 系统休眠：后台线程发现两次检查之间的时间间隔异常（休眠期间单调时钟暂停
+//!   或线程未被调度），视为系统曾经休眠
+
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::error::{DurianError, DurianResult};
+use crate::i18n::{tr, Field, Message};
+use crate::state::AppState;
+
+// ============================================
+// 配置常量
+// ============================================
+
+/// 默认空闲超时时间（秒）
+pub const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 300;
+
+/// 空闲超时时间上限（秒）
+pub const MAX_IDLE_TIMEOUT_SECS: u64 = 24 * 60 * 60;
+
+/// 后台检查间隔
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 判定为系统休眠的时间偏差
+const SUSPEND_THRESHOLD: Duration = Duration::from_secs(30);
+
+// ============================================
+// 锁定设置
+// ============================================
+
+/// 自动锁定设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct LockSettings {
+    /// 空闲超时时间（秒），0 表示不自动锁定
+    #[ts(type = "number")]
+    pub idle_timeout_secs: u64,
+    /// 系统休眠后是否锁定
+    pub lock_on_suspend: bool,
+}
+
+impl Default for LockSettings {
+    fn default() -> Self {
+        Self {
+            idle_timeout_secs: DEFAULT_IDLE_TIMEOUT_SECS,
+            lock_on_suspend: true,
+        }
+    }
+}
+
+impl LockSettings {
+    /// 空闲超时时间，不自动锁定时为 `None`
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        (self.idle_timeout_secs > 0).then(|| Duration::from_secs(self.idle_timeout_secs))
+    }
+
+    /// 校验设置是否有效
+    pub fn validate(&self) -> DurianResult<()> {
+        if self.idle_timeout_secs > MAX_IDLE_TIMEOUT_SECS {
+            return Err(DurianError::validation(tr(Message::SecondsOutOfRange(
+                Field::IdleTimeout,
+                0,
+                MAX_IDLE_TIMEOUT_SECS,
+            ))));
+        }
+        Ok(())
+    }
+}
+
+// ============================================
+// 休眠检测
+// ============================================
+
+/// 通过比较墙上时钟和单调时钟的流逝检测系统休眠
+pub struct SuspendDetector {
+    last_wall: SystemTime,
+    last_mono: Instant,
+}
+
+impl Default for SuspendDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SuspendDetector {
+    /// 以当前时间为起点创建检测器
+    pub fn new() -> Self {
+        Self {
+            last_wall: SystemTime::now(),
+            last_mono: Instant::now(),
+        }
+    }
+
+    /// 检查自上次调用以来系统是否曾经休眠
+    pub fn check(&mut self) -> bool {
+        let now_wall = SystemTime::now();
+        let now_mono = Instant::now();
+        // 墙上时钟被回拨时按 0 处理
+        let wall = now_wall.duration_since(self.last_wall).unwrap_or_default();
+        let mono = now_mono.duration_since(self.last_mono);
+        self.last_wall = now_wall;
+        self.last_mono = now_mono;
+        was_suspended(wall, mono)
+    }
+}
+
+/// 根据一次检查间隔内两种时钟的流逝判断是否发生休眠
+///
+/// - 单调时钟在休眠期间暂停（Linux、macOS），墙上时钟明显快于单调时钟
+/// - 单调时钟包含休眠时间的平台上，间隔本身远超检查周期
+fn was_suspended(wall: Duration, mono: Duration) -> bool {
+    wall.saturating_sub(mono) > SUSPEND_THRESHOLD || mono > WATCH_INTERVAL + SUSPEND_THRESHOLD
+}
+
+// ============================================
+// 后台检查
+// ============================================
+
+/// 启动自动锁定后台线程
+///
+/// 线程定期检查空闲超时和系统休眠，未登录或已锁定时不做任何事
+///
+/// # Arguments
+/// * `state` - 应用状态（与 Tauri 托管的实例共享）
+pub fn start_watcher(state: AppState) -> DurianResult<()> {
+    std::thread::Builder::new()
+        .name("durian-auto-lock".to_string())
+        .spawn(move || {
+            let mut detector = SuspendDetector::new();
+            loop {
+                std::thread::sleep(WATCH_INTERVAL);
+                let suspended = detector.check();
+                state.auto_lock(suspended);
+            }
+        })
+        .map(|_| ())
+        .map_err(|e| DurianError::IoError(tr(Message::AutoLockUnavailable(&e.to_string()))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_settings() {
+        let settings = LockSettings::default();
+        assert_eq!(settings.idle_timeout(), Some(Duration::from_secs(300)));
+        assert!(settings.validate().is_ok());
+
+        let never = LockSettings {
+            idle_timeout_secs: 0,
+            lock_on_suspend: false,
+        };
+        assert_eq!(never.idle_timeout(), None);
+
+        let too_long = LockSettings {
+            idle_timeout_secs: MAX_IDLE_TIMEOUT_SECS + 1,
+            lock_on_suspend: true,
+        };
+        assert!(too_long.validate().is_err());
+    }
+
+    #[test]
+    fn test_was_suspended() {
+        let tick = WATCH_INTERVAL;
+        assert!(!was_suspended(tick, tick));
+        // 单调时钟暂停，墙上时钟前进了一小时
+        assert!(was_suspended(Duration::from_secs(3600), tick));
+        // 单调时钟包含休眠时间
+        assert!(was_suspended(Duration::from_secs(3600), Duration::from_secs(3600)));
+        // 墙上时钟被回拨
+        assert!(!was_suspended(Duration::ZERO, tick));
+    }
+}
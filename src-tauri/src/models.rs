@@ -4,6 +4,12 @@
 
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::str::FromStr;
+use ts_rs::TS;
+
+use crate::error::{DurianError, ErrorPayload};
+use crate::i18n::{tr, Message};
+use crate::kdf::KdfParams;
 
 // ============================================
 // API 响应结构
@@ -11,11 +17,15 @@ use std::fmt;
 
 /// API 响应的通用结构
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound(deserialize = "T: Deserialize<'de>"))]
 pub struct ApiResponse<T> {
     pub code: i32,
     pub msg: String,
     #[serde(default)]
     pub data: Option<T>,
+    /// 响应的 HTTP 状态码（不属于响应体）
+    #[serde(skip)]
+    pub status: Option<u16>,
 }
 
 impl<T> ApiResponse<T> {
@@ -41,22 +51,123 @@ impl<T: fmt::Debug> fmt::Display for ApiResponse<T> {
     }
 }
 
-/// 登录响应数据
+/// 服务器登录接口返回的数据
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct LoginResponseData {
+pub struct LoginTokenData {
     #[serde(default)]
     pub token: String,
 }
 
-/// 查询响应数据
+/// `login` 命令的返回值（令牌只保存在后端会话中，不返回前端）
+///
+/// 登录后的维护步骤失败不影响登录，原因随结果返回，下次登录时重试
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct LoginResponseData {
+    /// 登录的用户名
+    pub username: String,
+    /// 升级过时的 KDF 参数失败的原因
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub kdf_upgrade_error: Option<ErrorPayload>,
+    /// 迁移旧格式记录失败的原因（记录仍可读取）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub migration_error: Option<ErrorPayload>,
+}
+
+/// 登录前 KDF 参数协商响应数据
+///
+/// 未升级的账户不返回对应参数，客户端按旧版参数处理
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct QueryResponseData {
+pub struct PreloginResponseData {
+    #[serde(default)]
+    pub login_kdf: Option<KdfParams>,
     #[serde(default)]
-    pub pull_mode: String,
+    pub core_kdf: Option<KdfParams>,
+    #[serde(default)]
+    pub vault_kdf: Option<KdfParams>,
+}
+
+/// 包装后的数据密钥
+///
+/// 数据密钥由 `kdf` 派生的包装密钥加密，同一数据密钥可以有多个不同 `key_id` 的包装
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct WrappedKey {
+    /// 解包密钥标识（核心密码为 `core`）
+    pub key_id: String,
+    /// 包装密钥的 KDF 参数
+    pub kdf: KdfParams,
+    /// 包装后的数据密钥（信封格式）
+    pub wrapped: String,
+}
+
+/// 保险库密钥响应数据
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct VaultKeysResponseData {
+    #[serde(default)]
+    pub keys: Vec<WrappedKey>,
+    /// 是否开启了全字段加密（保存在服务器上，所有设备共用）
+    #[serde(default)]
+    pub full_field_encryption: bool,
+}
+
+/// 数据拉取模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[ts(export)]
+pub enum PullMode {
+    /// 全量拉取：删除旧数据后重新写入
+    PullAll,
+    /// 增量拉取：只更新有变化的数据
+    PullUpdated,
+    /// 无更新：只更新时间戳
+    PullNothing,
+}
+
+impl FromStr for PullMode {
+    type Err = DurianError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "PULL_ALL" => Ok(PullMode::PullAll),
+            "PULL_UPDATED" => Ok(PullMode::PullUpdated),
+            "PULL_NOTHING" => Ok(PullMode::PullNothing),
+            _ => Err(DurianError::validation(tr(Message::UnknownPullMode(s)))),
+        }
+    }
+}
+
+/// 查询响应数据
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct QueryResponseData {
+    pub pull_mode: PullMode,
     #[serde(default)]
+    #[ts(type = "number")]
     pub update_time: i64,
     #[serde(default)]
     pub accounts: Vec<AccountItem>,
+    /// 自 `update_time` 以来被删除的记录 ID（墓碑），增量拉取时使用
+    #[serde(default)]
+    #[ts(type = "Array<number>")]
+    pub deleted_rids: Vec<i64>,
+}
+
+/// 缓存校验的响应数据
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct VerifyResponseData {
+    /// 摘要与服务器不一致的分桶
+    #[serde(default)]
+    pub buckets: Vec<usize>,
+}
+
+/// 按分桶查询账户的响应数据
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct BucketQueryResponseData {
+    /// 所查询分桶中的全部记录
+    #[serde(default)]
+    pub accounts: Vec<AccountItem>,
 }
 
 // ============================================
@@ -64,9 +175,11 @@ pub struct QueryResponseData {
 // ============================================
 
 /// 账户项（API返回的格式）
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, TS)]
+#[ts(export)]
 pub struct AccountItem {
     #[serde(default)]
+    #[ts(type = "number")]
     pub rid: i64,
     #[serde(default)]
     pub website: String,
@@ -74,12 +187,44 @@ pub struct AccountItem {
     pub account: String,
     #[serde(default)]
     pub password: String,
+    /// 服务器上的修订号，更新时必须提交以检测冲突（服务器拒绝为 0 的更新）
+    #[serde(default)]
+    #[ts(type = "number")]
+    pub revision: i64,
+    /// 服务器上最后修改的时间（毫秒）
+    #[serde(default)]
+    #[ts(type = "number")]
+    pub updated_at: i64,
+    /// 网站和账户字段是否为加密后的密文（全字段加密）
+    #[serde(default)]
+    pub fields_encrypted: bool,
 }
 
 impl AccountItem {
     /// 创建新的账户项
     pub fn new(rid: i64, website: String, account: String, password: String) -> Self {
-        Self { rid, website, account, password }
+        Self {
+            rid,
+            website,
+            account,
+            password,
+            revision: 0,
+            updated_at: 0,
+            fields_encrypted: false,
+        }
+    }
+
+    /// 设置修订号和修改时间
+    pub fn with_revision(mut self, revision: i64, updated_at: i64) -> Self {
+        self.revision = revision;
+        self.updated_at = updated_at;
+        self
+    }
+
+    /// 标记网站和账户字段是否已加密
+    pub fn with_fields_encrypted(mut self, fields_encrypted: bool) -> Self {
+        self.fields_encrypted = fields_encrypted;
+        self
     }
 }
 
@@ -91,13 +236,23 @@ impl fmt::Display for AccountItem {
 }
 
 /// 账户记录（本地存储格式）
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
 pub struct AccountRecord {
+    #[ts(type = "number")]
     pub rid: i64,
     pub username: String,
     pub website: String,
     pub account: String,
     pub password: String,
+    /// 本地缓存所基于的服务器修订号
+    #[serde(default)]
+    #[ts(type = "number")]
+    pub revision: i64,
+    /// 服务器上最后修改的时间（毫秒）
+    #[serde(default)]
+    #[ts(type = "number")]
+    pub updated_at: i64,
 }
 
 impl AccountRecord {
@@ -109,7 +264,14 @@ impl AccountRecord {
         account: String,
         password: String,
     ) -> Self {
-        Self { rid, username, website, account, password }
+        Self { rid, username, website, account, password, revision: 0, updated_at: 0 }
+    }
+
+    /// 设置修订号和修改时间
+    pub fn with_revision(mut self, revision: i64, updated_at: i64) -> Self {
+        self.revision = revision;
+        self.updated_at = updated_at;
+        self
     }
 
     /// 从 AccountItem 转换
@@ -120,6 +282,8 @@ impl AccountRecord {
             website: item.website.clone(),
             account: item.account.clone(),
             password: item.password.clone(),
+            revision: item.revision,
+            updated_at: item.updated_at,
         }
     }
 }
@@ -132,9 +296,11 @@ impl fmt::Display for AccountRecord {
 }
 
 /// 缓存数据结构
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
 pub struct CacheData {
     pub username: String,
+    #[ts(type = "number")]
     pub update_time: i64,
     pub accounts: Vec<AccountRecord>,
 }
@@ -175,14 +341,168 @@ impl fmt::Display for CacheData {
 }
 
 // ============================================
-// 临时数据结构（用于 JSON 解析）
+// 离线写入队列
 // ============================================
 
-/// 临时账户记录（用于保存缓存时的 JSON 解析）
-#[derive(Debug, Clone, Deserialize)]
-pub struct TempAccountRecord {
+/// 待同步写入操作的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "lowercase")]
+#[ts(export)]
+pub enum PendingOpKind {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl PendingOpKind {
+    /// 数据库中保存的名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PendingOpKind::Insert => "insert",
+            PendingOpKind::Update => "update",
+            PendingOpKind::Delete => "delete",
+        }
+    }
+}
+
+impl FromStr for PendingOpKind {
+    type Err = DurianError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "insert" => Ok(PendingOpKind::Insert),
+            "update" => Ok(PendingOpKind::Update),
+            "delete" => Ok(PendingOpKind::Delete),
+            _ => Err(DurianError::validation(tr(Message::UnknownWriteKind(s)))),
+        }
+    }
+}
+
+/// 待同步的写入操作
+///
+/// `item` 为发送给服务器的内容（启用全字段加密时网站和账户为密文）；
+/// 尚未同步的插入使用负数的临时记录 ID
+#[derive(Debug, Clone)]
+pub struct PendingOp {
+    pub id: i64,
+    pub kind: PendingOpKind,
+    pub item: AccountItem,
+    /// 加入队列时生成的随机操作 ID，插入时随请求发送，服务器据此忽略重复的插入
+    pub op_id: String,
+}
+
+/// 待同步写入操作的摘要（不包含账户内容）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct PendingOpInfo {
+    #[ts(type = "number")]
+    pub id: i64,
+    pub kind: PendingOpKind,
+    #[ts(type = "number")]
     pub rid: i64,
-    pub website: String,
-    pub account: String,
-    pub password: String,
+    /// 加入队列的时间（Unix 秒）
+    #[ts(type = "number")]
+    pub created_at: i64,
+    /// 已尝试发送的次数
+    #[ts(type = "number")]
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    /// 是否已被服务器拒绝（不再重试）
+    pub failed: bool,
+}
+
+/// 离线写入队列的状态
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SyncStatus {
+    /// 等待发送的操作数
+    pub pending: usize,
+    /// 被服务器拒绝的操作数
+    pub failed: usize,
+    /// 所有操作，按加入队列的顺序
+    pub operations: Vec<PendingOpInfo>,
+}
+
+impl SyncStatus {
+    /// 由队列中的操作汇总状态
+    pub fn new(operations: Vec<PendingOpInfo>) -> Self {
+        let failed = operations.iter().filter(|op| op.failed).count();
+        Self {
+            pending: operations.len() - failed,
+            failed,
+            operations,
+        }
+    }
+}
+
+/// 一次回放离线写入队列的结果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct FlushReport {
+    /// 成功发送的操作数
+    pub synced: usize,
+    /// 被服务器拒绝的操作数
+    pub rejected: usize,
+    /// 因网络不可用仍在等待的操作数
+    pub pending: usize,
+    /// 与其他设备的修改冲突、等待用户处理的操作数
+    pub conflicts: usize,
+}
+
+/// 一次从服务器拉取更新后本地缓存的变化
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SyncChanges {
+    /// 新增的记录数
+    pub added: usize,
+    /// 被修改的记录数
+    pub updated: usize,
+    /// 被删除的记录数
+    pub deleted: usize,
+}
+
+/// 一次缓存校验的结果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct VerifyReport {
+    /// 分桶数
+    pub buckets: usize,
+    /// 与服务器不一致、重新拉取的分桶数
+    pub mismatched: usize,
+    /// 修复时缓存的变化
+    pub changes: SyncChanges,
+}
+
+// ============================================
+// 修改冲突
+// ============================================
+
+/// 处理修改冲突时保留的版本
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum ConflictChoice {
+    /// 用本地版本覆盖服务器
+    Local,
+    /// 放弃本地修改，采用服务器版本
+    Server,
+    /// 服务器版本保留在原记录，本地版本作为新记录插入
+    KeepBoth,
+}
+
+/// 本地修改与其他设备的修改冲突的记录
+///
+/// 两个版本的网站和账户均为明文，密码为密文
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct AccountConflict {
+    #[ts(type = "number")]
+    pub rid: i64,
+    /// 本地未能同步的版本（`revision` 为其所基于的修订号）
+    pub local: AccountRecord,
+    /// 服务器上的当前版本
+    pub server: AccountRecord,
+    /// 发现冲突的时间（Unix 秒）
+    #[ts(type = "number")]
+    pub detected_at: i64,
 }
@@ -0,0 +1,301 @@
+//! 核心密码修改模块
+//!
+//! 启用信封加密时只需用新核心密码重新包装数据密钥；
+//! 否则需要将保险库中的每条记录重新加密到新密钥下。
+//! 启用全字段加密时对已有记录的网站和账户字段加密也使用同样的分批改写流程
+//!
+//! # 流程
+//! 1. 校验原核心密码（与会话保险库密钥比较）
+//! 2. 在本地记录一条未完成的修改日志
+//! 3. 从服务器全量拉取记录，分批重新加密：每批在服务器端原子更新，
+//!    成功后在本地单个事务中更新 `accounts` 表。
+//!    启用信封加密时跳过这一步，记录仍由数据密钥加密
+//! 4. 更新服务器上的核心密码哈希（以及数据密钥包装）
+//! 5. 删除修改日志
+//!
+//! # 旧格式迁移
+//! [`migrate_legacy_entries`] 将旧版 ciftl 密文、第一代信封和启用信封加密前写入的记录
+//! 重新加密为当前格式，是独立于修改核心密码的步骤：登录成功后自动执行一次，
+//! 使用同样的分批改写流程
+//!
+//! # 并发写入
+//! 改写期间持有会话的写入队列锁（[`crate::state::Connection::write_queue`]）：
+//! 写入命令和队列回放都要等待改写完成，新的保险库密钥也在释放锁之前装入会话，
+//! 因此不会有记录使用即将失效的密钥加密后发送到服务器。
+//! 需要重新加密记录时先回放队列，仍有未发送的写入或未处理的冲突时拒绝修改
+//!
+//! # 中断恢复
+//! 已使用新密钥加密的记录会被跳过，因此中断后使用相同的原/新核心密码再次执行即可继续。
+//! 若中断发生在服务器哈希更新之后，用户会以新核心密码登录，此时同样允许恢复
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::crypto::{encrypt_message, reencrypt_message, VaultKey};
+use crate::error::{DurianError, DurianResult};
+use crate::i18n::{tr, Field, Message};
+use crate::kdf::KdfParams;
+use crate::models::AccountItem;
+use crate::state::{AppState, Connection, DurianState};
+use crate::sync;
+use crate::vault::CORE_KEY_ID;
+
+/// 每批重新加密的记录数
+pub const REKEY_BATCH_SIZE: usize = 100;
+
+/// 修改核心密码并重新加密整个保险库，完成后用新的保险库密钥替换会话中的旧密钥
+///
+/// 只在本地改写记录时短暂持有状态锁，等待服务器响应期间不阻塞只读命令；
+/// 写入命令会等待修改完成
+///
+/// # Arguments
+/// * `app_state` - 应用状态
+/// * `session_id` - 会话标识
+/// * `old_core_password` - 原核心密码
+/// * `new_core_password` - 新核心密码
+pub async fn change_core_password(
+    app_state: &AppState,
+    session_id: &str,
+    old_core_password: &str,
+    new_core_password: &str,
+) -> DurianResult<()> {
+    if old_core_password == new_core_password {
+        return Err(DurianError::validation(tr(Message::SameCorePassword)));
+    }
+
+    let (connection, has_data_key, vault_kdf) = {
+        let state = app_state.session(session_id)?;
+        let vault_key = state.vault_key()?;
+        (state.connection(), vault_key.has_data_key(), vault_key.kdf().clone())
+    };
+    let username = connection.username.as_str();
+
+    // 未启用信封加密时仍可能有未迁移的旧格式记录，需要旧版密钥读取
+    let old_key = VaultKey::derive(old_core_password, &vault_kdf)?.with_legacy_key(old_core_password);
+
+    let _write_guard = connection.write_queue.lock().await;
+
+    // 未启用信封加密时，队列中的写入和冲突的本地版本由原密钥加密，修改后无法再发送
+    if !has_data_key {
+        let report = sync::flush_pending_ops_locked(app_state, &connection).await?;
+        if report.pending > 0 || !app_state.session(session_id)?.list_conflicts()?.is_empty() {
+            return Err(DurianError::conflict(tr(Message::UnsyncedWritesBeforeRekey)));
+        }
+    }
+
+    let (new_key, server_updated) = {
+        let state = app_state.session(session_id)?;
+        let vault_key = state.vault_key()?;
+        let new_key = vault_key.with_core_password(new_core_password)?;
+
+        // 中断于服务器哈希更新之后时，用户已使用新核心密码登录
+        let resuming = state.has_pending_core_password_change()?;
+        let server_updated = resuming && new_key.same_core_password(vault_key);
+        if !old_key.same_core_password(vault_key) && !server_updated {
+            return Err(DurianError::invalid_credentials(tr(Message::IncorrectPassword(
+                Field::OldCorePassword,
+            ))));
+        }
+
+        state.begin_core_password_change(current_timestamp())?;
+        (new_key, server_updated)
+    };
+
+    let wrapped_key = if has_data_key {
+        // 数据密钥不变，只需重新包装
+        Some(new_key.wrap_data_key(CORE_KEY_ID, new_core_password)?)
+    } else {
+        rewrite_vault(app_state, &connection, |_, item| {
+            reencrypt_item(item, &old_key, &new_key)
+        })
+        .await?;
+        None
+    };
+
+    if !server_updated {
+        let kdf = connection.api.prelogin(username).await?;
+        let new_kdf = KdfParams::recommended()?;
+        let response = connection
+            .api
+            .change_core_password(
+                connection.token.expose(),
+                old_core_password,
+                new_core_password,
+                &kdf.core,
+                &new_kdf,
+                wrapped_key.as_ref(),
+            )
+            .await?;
+        if !response.is_success() {
+            return Err(DurianError::from_response(response));
+        }
+        if let Some(wrapped_key) = &wrapped_key {
+            app_state.session(session_id)?.save_wrapped_key(wrapped_key)?;
+        }
+    }
+
+    // 释放写入队列锁之前装入新密钥，之后的写入使用新密钥加密
+    let mut state = app_state.session_mut(session_id)?;
+    state.set_vault_key(new_key)?;
+    state.finish_core_password_change()
+}
+
+/// 将仍使用旧格式的记录重新加密为当前格式
+///
+/// 已是当前格式的记录会被跳过，可安全重复执行。全部迁移成功后清除会话中的旧版密钥
+///
+/// # Arguments
+/// * `app_state` - 应用状态
+/// * `session_id` - 会话标识
+pub async fn migrate_legacy_entries(app_state: &AppState, session_id: &str) -> DurianResult<()> {
+    let connection = app_state.session(session_id)?.connection();
+    let _write_guard = connection.write_queue.lock().await;
+    rewrite_vault(app_state, &connection, |state, item| {
+        let vault_key = state.vault_key()?;
+        reencrypt_item(item, vault_key, vault_key)
+    })
+    .await?;
+    app_state.session_mut(session_id)?.forget_legacy_key();
+    Ok(())
+}
+
+/// 加密所有记录中尚未加密的网站和账户字段
+///
+/// 用于开启全字段加密时迁移已有记录；已加密的字段会被跳过，可安全重复执行
+///
+/// # Arguments
+/// * `app_state` - 应用状态
+/// * `session_id` - 会话标识
+pub async fn encrypt_account_fields(app_state: &AppState, session_id: &str) -> DurianResult<()> {
+    let connection = app_state.session(session_id)?.connection();
+    let _write_guard = connection.write_queue.lock().await;
+    rewrite_vault(app_state, &connection, |state, item| {
+        if item.fields_encrypted {
+            return Ok(None);
+        }
+
+        let vault_key = state.vault_key()?;
+        Ok(Some(
+            AccountItem::new(
+                item.rid,
+                encrypt_message(&item.website, vault_key)?,
+                encrypt_message(&item.account, vault_key)?,
+                item.password.clone(),
+            )
+            .with_revision(item.revision, 0)
+            .with_fields_encrypted(true),
+        ))
+    })
+    .await
+}
+
+/// 重新加密单条记录
+///
+/// 密码字段和标记为已加密的网站、账户字段都会重新加密
+fn reencrypt_item(
+    item: &AccountItem,
+    old_key: &VaultKey,
+    new_key: &VaultKey,
+) -> DurianResult<Option<AccountItem>> {
+    let password = if item.password.is_empty() {
+        None
+    } else {
+        reencrypt_message(&item.password, old_key, new_key)?
+    };
+    // 明文的网站和账户字段无需处理
+    let (website, account) = if item.fields_encrypted {
+        (
+            reencrypt_message(&item.website, old_key, new_key)?,
+            reencrypt_message(&item.account, old_key, new_key)?,
+        )
+    } else {
+        (None, None)
+    };
+
+    if password.is_none() && website.is_none() && account.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(
+        AccountItem::new(
+            item.rid,
+            website.unwrap_or_else(|| item.website.clone()),
+            account.unwrap_or_else(|| item.account.clone()),
+            password.unwrap_or_else(|| item.password.clone()),
+        )
+        .with_revision(item.revision, 0)
+        .with_fields_encrypted(item.fields_encrypted),
+    ))
+}
+
+/// 全量拉取服务器上的记录，分批改写后写回服务器和本地缓存
+///
+/// `rewrite` 返回 `None` 表示该记录无需改写，改写后的记录需带有拉取到的修订号。所有记录先在本地改写完毕，
+/// 上传期间不持有状态锁。本地缓存只保存解密后的网站和账户，因此只需同步密码字段。
+/// 调用方需持有 [`Connection::write_queue`]，避免改写期间的写入被覆盖
+async fn rewrite_vault<F>(
+    app_state: &AppState,
+    connection: &Connection,
+    mut rewrite: F,
+) -> DurianResult<()>
+where
+    F: FnMut(&DurianState, &AccountItem) -> DurianResult<Option<AccountItem>>,
+{
+    let response = connection
+        .api
+        .query_accounts(connection.token.expose(), 0)
+        .await?;
+    if !response.is_success() {
+        return Err(DurianError::from_response(response));
+    }
+    let accounts = response.data.map(|data| data.accounts).unwrap_or_default();
+
+    let batches = {
+        let state = app_state.session(&connection.session_id)?;
+        let mut batches = Vec::new();
+        for chunk in accounts.chunks(REKEY_BATCH_SIZE) {
+            let mut batch = Vec::with_capacity(chunk.len());
+            for item in chunk {
+                if let Some(rewritten) = rewrite(&state, item)? {
+                    batch.push(rewritten);
+                }
+            }
+            if !batch.is_empty() {
+                batches.push(batch);
+            }
+        }
+        batches
+    };
+
+    for batch in batches {
+        let response = connection
+            .api
+            .update_accounts_batch(connection.token.expose(), &batch)
+            .await?;
+        if !response.is_success() {
+            return Err(DurianError::from_response(response));
+        }
+
+        // 服务器返回各记录的新修订号，写入缓存以免之后的修改被误判为冲突
+        let revisions: Vec<i64> = response
+            .data
+            .and_then(|data| serde_json::from_value(data).ok())
+            .unwrap_or_default();
+        let updates: Vec<(i64, String, Option<i64>)> = batch
+            .into_iter()
+            .enumerate()
+            .map(|(i, item)| (item.rid, item.password, revisions.get(i).copied()))
+            .collect();
+        app_state
+            .session(&connection.session_id)?
+            .update_account_passwords(&updates)?;
+    }
+
+    Ok(())
+}
+
+/// 当前 Unix 时间戳（秒）
+fn current_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
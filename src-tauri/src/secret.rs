@@ -0,0 +1,288 @@
+//! 敏感数据封装模块
+//!
+//! 提供保存密码、令牌和密钥的封装类型
+//!
+//! # 特性
+//! - 释放时自动清零内存
+//! - `Debug` 只输出占位符，且不实现 `Display`，避免写入日志
+//! - 启用 `mlock` 特性时（仅 Linux），尽力将内存锁定在物理内存中，防止被换出到磁盘。
+//!   锁定按内存页计数，同一页上的其他秘密释放时不会解除锁定。
+//!   锁定失败（如超出 `RLIMIT_MEMLOCK`）时静默忽略
+
+use std::fmt;
+
+use serde::{Deserialize, Deserializer};
+use zeroize::Zeroize;
+
+// ============================================
+// 敏感字符串
+// ============================================
+
+/// 敏感字符串（密码、令牌等）
+///
+/// 内容只能通过 [`SecretString::expose`] 显式读取
+pub struct SecretString {
+    value: String,
+    locked: bool,
+}
+
+impl SecretString {
+    /// 接管字符串（不会复制缓冲区）
+    pub fn new(value: String) -> Self {
+        let locked = lock_memory(value.as_ptr(), value.capacity());
+        Self { value, locked }
+    }
+
+    /// 读取明文内容
+    #[inline]
+    pub fn expose(&self) -> &str {
+        &self.value
+    }
+
+    /// 内容是否为空
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.value.is_empty()
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(value: &str) -> Self {
+        Self::new(value.to_string())
+    }
+}
+
+impl Clone for SecretString {
+    fn clone(&self) -> Self {
+        Self::new(self.value.clone())
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        let (ptr, capacity) = (self.value.as_ptr(), self.value.capacity());
+        self.value.zeroize();
+        if self.locked {
+            unlock_memory(ptr, capacity);
+        }
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString(***)")
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::new)
+    }
+}
+
+// ============================================
+// 敏感字节数组
+// ============================================
+
+/// 定长敏感字节数组（密钥材料）
+///
+/// 数据保存在堆上，移动时不会在栈上留下副本
+pub struct SecretBytes<const N: usize> {
+    bytes: Box<[u8; N]>,
+    locked: bool,
+}
+
+impl<const N: usize> SecretBytes<N> {
+    /// 创建全零数组，供调用方原地填充
+    pub fn zeroed() -> Self {
+        let bytes = Box::new([0u8; N]);
+        let locked = lock_memory(bytes.as_ptr(), N);
+        Self { bytes, locked }
+    }
+
+    /// 复制数组内容，并清零传入的副本
+    pub fn new(mut bytes: [u8; N]) -> Self {
+        let mut secret = Self::zeroed();
+        secret.bytes.copy_from_slice(&bytes);
+        bytes.zeroize();
+        secret
+    }
+
+    /// 读取密钥材料
+    #[inline]
+    pub fn expose(&self) -> &[u8; N] {
+        &self.bytes
+    }
+
+    /// 可写地访问密钥材料
+    #[inline]
+    pub fn expose_mut(&mut self) -> &mut [u8; N] {
+        &mut self.bytes
+    }
+}
+
+impl<const N: usize> Clone for SecretBytes<N> {
+    fn clone(&self) -> Self {
+        let mut secret = Self::zeroed();
+        secret.bytes.copy_from_slice(self.bytes.as_ref());
+        secret
+    }
+}
+
+impl<const N: usize> Drop for SecretBytes<N> {
+    fn drop(&mut self) {
+        self.bytes.zeroize();
+        if self.locked {
+            unlock_memory(self.bytes.as_ptr(), N);
+        }
+    }
+}
+
+impl<const N: usize> fmt::Debug for SecretBytes<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBytes<{}>(***)", N)
+    }
+}
+
+// ============================================
+// 内存锁定
+// ============================================
+
+/// 各内存页被锁定的次数
+///
+/// `mlock` 不计数：同一页上的多个秘密各自锁定后，任何一个释放时的 `munlock`
+/// 都会解除整页的锁定。因此按页计数，只在首次锁定时 `mlock`、最后一次释放时 `munlock`
+#[cfg(all(target_os = "linux", feature = "mlock"))]
+static LOCKED_PAGES: std::sync::Mutex<std::collections::BTreeMap<usize, usize>> =
+    std::sync::Mutex::new(std::collections::BTreeMap::new());
+
+/// 覆盖 ptr..ptr+len 的各内存页起始地址和页大小
+#[cfg(all(target_os = "linux", feature = "mlock"))]
+fn pages(ptr: *const u8, len: usize) -> (impl Iterator<Item = usize>, usize) {
+    // 安全：sysconf 没有前置条件
+    let page_size = match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        size if size > 0 => size as usize,
+        _ => 4096,
+    };
+    let start = ptr as usize & !(page_size - 1);
+    let end = ptr as usize + len;
+    ((start..end).step_by(page_size), page_size)
+}
+
+/// 尝试锁定内存，返回是否成功
+#[cfg(all(target_os = "linux", feature = "mlock"))]
+fn lock_memory(ptr: *const u8, len: usize) -> bool {
+    if len == 0 {
+        return false;
+    }
+    let mut locked_pages = LOCKED_PAGES.lock().unwrap_or_else(|e| e.into_inner());
+    let (pages, page_size) = pages(ptr, len);
+    let mut counted = Vec::new();
+    for page in pages {
+        let count = locked_pages.entry(page).or_insert(0);
+        // 安全：该页与当前持有的有效分配重叠，mlock 只影响驻留状态
+        if *count == 0 && unsafe { libc::mlock(page as *const libc::c_void, page_size) } != 0 {
+            locked_pages.remove(&page);
+            // 撤销本次已计数的页，整段视为未锁定
+            for page in counted {
+                release_page(&mut locked_pages, page, page_size);
+            }
+            return false;
+        }
+        *count += 1;
+        counted.push(page);
+    }
+    true
+}
+
+/// 解除内存锁定（其他秘密仍在使用的页保持锁定）
+#[cfg(all(target_os = "linux", feature = "mlock"))]
+fn unlock_memory(ptr: *const u8, len: usize) {
+    let mut locked_pages = LOCKED_PAGES.lock().unwrap_or_else(|e| e.into_inner());
+    let (pages, page_size) = pages(ptr, len);
+    for page in pages {
+        release_page(&mut locked_pages, page, page_size);
+    }
+}
+
+/// 减少一页的锁定次数，归零时解除锁定
+#[cfg(all(target_os = "linux", feature = "mlock"))]
+fn release_page(
+    locked_pages: &mut std::collections::BTreeMap<usize, usize>,
+    page: usize,
+    page_size: usize,
+) {
+    let Some(count) = locked_pages.get_mut(&page) else {
+        return;
+    };
+    *count -= 1;
+    if *count == 0 {
+        locked_pages.remove(&page);
+        // 安全：munlock 只影响驻留状态
+        unsafe {
+            libc::munlock(page as *const libc::c_void, page_size);
+        }
+    }
+}
+
+#[cfg(not(all(target_os = "linux", feature = "mlock")))]
+fn lock_memory(_ptr: *const u8, _len: usize) -> bool {
+    false
+}
+
+#[cfg(not(all(target_os = "linux", feature = "mlock")))]
+fn unlock_memory(_ptr: *const u8, _len: usize) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_does_not_leak() {
+        let secret = SecretString::from("hunter2");
+        assert_eq!(secret.expose(), "hunter2");
+        assert!(!format!("{:?}", secret).contains("hunter2"));
+
+        let key = SecretBytes::new([0xAB; 4]);
+        assert_eq!(key.expose(), &[0xAB; 4]);
+        assert!(!format!("{:?}", key).to_lowercase().contains("ab"));
+    }
+
+    #[cfg(all(target_os = "linux", feature = "mlock"))]
+    #[test]
+    fn test_shared_page_stays_locked() {
+        // 页首的缓冲区，保证两段都落在同一页上
+        let buffer = vec![0u8; 1 << 17];
+        let (mut buffer_pages, page_size) = pages(buffer.as_ptr(), 1);
+        let offset = buffer_pages.next().unwrap() + page_size - buffer.as_ptr() as usize;
+        let first = buffer[offset..].as_ptr();
+        let second = buffer[offset + 32..].as_ptr();
+        let count = || LOCKED_PAGES.lock().unwrap().get(&(first as usize)).copied();
+
+        // 受 RLIMIT_MEMLOCK 限制时无法锁定，此时跳过
+        if !lock_memory(first, 8) {
+            return;
+        }
+        assert!(lock_memory(second, 8));
+        assert_eq!(count(), Some(2));
+        unlock_memory(first, 8);
+        assert_eq!(count(), Some(1));
+        unlock_memory(second, 8);
+        assert_eq!(count(), None);
+    }
+
+    #[test]
+    fn test_deserialize_and_clone() {
+        let secret: SecretString = serde_json::from_str("\"token-value\"").unwrap();
+        let cloned = secret.clone();
+        drop(secret);
+        assert_eq!(cloned.expose(), "token-value");
+        assert!(!cloned.is_empty());
+    }
+}
@@ -0,0 +1,737 @@
+//! 应用设置模块
+//!
+//! 设置保存在应用数据目录下的 `settings.json` 中，目前包括服务器配置：
+//! 名称、API 基础 URL、TLS 选项（自定义 CA、公钥固定）、超时时间以及默认服务器；
+//! 以及界面语言（未设置时跟随操作系统语言）
+//!
+//! # 版本
+//! 设置文件带有 `version` 字段。读取旧版本文件时按顺序迁移到当前版本，
+//! 遇到比当前程序更新的版本时拒绝读取，避免覆盖新版本写入的字段
+//!
+//! # 写入
+//! 先写入临时文件再重命名，避免写入中断时损坏原文件
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::error::{DurianError, DurianResult};
+use crate::i18n::{tr, Field, Locale, Message};
+use crate::tls;
+
+// ============================================
+// 配置常量
+// ============================================
+
+/// 当前设置文件版本
+pub const SETTINGS_VERSION: u32 = 2;
+
+/// 设置文件名
+const SETTINGS_FILE_NAME: &str = "settings.json";
+
+/// 服务器名称最大长度
+const MAX_PROFILE_NAME_LEN: usize = 64;
+
+/// 超时时间上限（秒）
+const MAX_TIMEOUT_SECS: u64 = 300;
+
+/// 默认请求超时时间（秒）
+pub const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 30;
+
+/// 默认连接超时时间（秒）
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 10;
+
+/// User-Agent 最大长度
+const MAX_USER_AGENT_LEN: usize = 256;
+
+/// 应用数据目录
+pub fn app_data_dir() -> DurianResult<PathBuf> {
+    Ok(dirs::data_dir()
+        .ok_or_else(|| DurianError::config(tr(Message::NoAppDataDir)))?
+        .join("durian-web"))
+}
+
+// ============================================
+// 设置结构
+// ============================================
+
+/// 应用设置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Settings {
+    /// 设置文件版本
+    pub version: u32,
+    /// 服务器配置
+    #[serde(default)]
+    pub profiles: Vec<ServerProfile>,
+    /// 界面语言，`None` 表示跟随操作系统
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<Locale>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            profiles: Vec::new(),
+            locale: None,
+        }
+    }
+}
+
+impl Settings {
+    /// 生效的界面语言：未设置时使用操作系统语言
+    pub fn locale(&self) -> Locale {
+        self.locale.unwrap_or_else(Locale::system)
+    }
+}
+
+/// 服务器配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ServerProfile {
+    /// 名称（唯一）
+    pub name: String,
+    /// API 基础 URL
+    pub api_base_url: String,
+    /// TLS 选项
+    #[serde(default)]
+    pub tls: TlsOptions,
+    /// 超时时间
+    #[serde(default)]
+    pub timeouts: TimeoutOptions,
+    /// 代理服务器 URL（http、https、socks5 或 socks5h），为空时使用系统代理环境变量
+    #[serde(default)]
+    pub proxy_url: Option<String>,
+    /// 自定义 User-Agent，为空时使用默认值
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// 是否为默认服务器
+    #[serde(default)]
+    pub is_default: bool,
+}
+
+/// TLS 选项
+///
+/// 默认使用内置根证书完整校验服务器证书，校验规则见 [`crate::tls`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct TlsOptions {
+    /// 自定义 CA 证书文件路径（PEM）
+    #[serde(default)]
+    pub ca_cert_path: Option<String>,
+    /// 固定的服务器公钥指纹（SPKI 的 SHA-256，Base64 编码）
+    #[serde(default)]
+    pub pinned_spki_sha256: Vec<String>,
+    /// 证书不受信任时是否提示用户首次信任
+    #[serde(default)]
+    pub trust_on_first_use: bool,
+}
+
+/// 超时时间
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct TimeoutOptions {
+    /// 请求超时时间（秒）
+    #[ts(type = "number")]
+    pub request_timeout_secs: u64,
+    /// 连接超时时间（秒）
+    #[ts(type = "number")]
+    pub connect_timeout_secs: u64,
+}
+
+impl Default for TimeoutOptions {
+    fn default() -> Self {
+        Self {
+            request_timeout_secs: DEFAULT_REQUEST_TIMEOUT_SECS,
+            connect_timeout_secs: DEFAULT_CONNECT_TIMEOUT_SECS,
+        }
+    }
+}
+
+impl ServerProfile {
+    /// 未保存的服务器配置（全部使用默认选项）
+    ///
+    /// 用于连接没有保存配置的服务器
+    pub fn unsaved(api_base_url: &str) -> Self {
+        Self {
+            name: String::new(),
+            api_base_url: api_base_url.trim().trim_end_matches('/').to_string(),
+            tls: TlsOptions::default(),
+            timeouts: TimeoutOptions::default(),
+            proxy_url: None,
+            user_agent: None,
+            is_default: false,
+        }
+    }
+
+    /// 校验并规范化服务器配置
+    ///
+    /// 去除名称首尾空白和 URL 末尾的 `/`
+    pub fn normalize(mut self) -> DurianResult<Self> {
+        self.name = self.name.trim().to_string();
+        if self.name.is_empty() {
+            return Err(DurianError::validation(tr(Message::Required(Field::ServerName))));
+        }
+        if self.name.chars().count() > MAX_PROFILE_NAME_LEN {
+            return Err(DurianError::validation(tr(Message::MaxLength(
+                Field::ServerName,
+                MAX_PROFILE_NAME_LEN,
+            ))));
+        }
+
+        self.api_base_url = validate_api_base_url(&self.api_base_url)?;
+        self.timeouts.validate()?;
+        if let Some(path) = &self.tls.ca_cert_path {
+            if path.trim().is_empty() {
+                self.tls.ca_cert_path = None;
+            }
+        }
+        let mut pins = Vec::with_capacity(self.tls.pinned_spki_sha256.len());
+        for pin in &self.tls.pinned_spki_sha256 {
+            let pin = tls::normalize_fingerprint(pin)?;
+            if !pins.contains(&pin) {
+                pins.push(pin);
+            }
+        }
+        self.tls.pinned_spki_sha256 = pins;
+
+        self.proxy_url = match self.proxy_url.as_deref().map(str::trim) {
+            Some("") | None => None,
+            Some(proxy_url) => Some(validate_proxy_url(proxy_url)?),
+        };
+        self.user_agent = match self.user_agent.as_deref().map(str::trim) {
+            Some("") | None => None,
+            Some(user_agent) => Some(validate_user_agent(user_agent)?),
+        };
+        Ok(self)
+    }
+}
+
+impl TimeoutOptions {
+    /// 校验超时时间
+    pub fn validate(&self) -> DurianResult<()> {
+        for (value, field) in [
+            (self.request_timeout_secs, Field::RequestTimeout),
+            (self.connect_timeout_secs, Field::ConnectTimeout),
+        ] {
+            if value == 0 || value > MAX_TIMEOUT_SECS {
+                return Err(DurianError::validation(tr(Message::SecondsOutOfRange(
+                    field,
+                    1,
+                    MAX_TIMEOUT_SECS,
+                ))));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 校验 API 基础 URL，返回去除末尾 `/` 的 URL
+///
+/// 只接受带主机名的 http/https URL，且不能包含查询参数、片段或用户信息
+pub fn validate_api_base_url(value: &str) -> DurianResult<String> {
+    let value = value.trim();
+    let url = Url::parse(value)
+        .map_err(|e| DurianError::validation(tr(Message::InvalidUrl(&e.to_string()))))?;
+
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(DurianError::validation(tr(Message::UnsupportedUrlScheme)));
+    }
+    if url.host_str().map_or(true, str::is_empty) {
+        return Err(DurianError::validation(tr(Message::UrlMissingHost)));
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err(DurianError::validation(tr(Message::UrlHasQuery)));
+    }
+    if !url.username().is_empty() || url.password().is_some() {
+        return Err(DurianError::validation(tr(Message::UrlHasUserInfo)));
+    }
+
+    Ok(value.trim_end_matches('/').to_string())
+}
+
+/// 校验代理服务器 URL
+///
+/// 支持 http、https、socks5 和 socks5h（由代理解析域名）代理，可以包含用户名和密码
+pub fn validate_proxy_url(value: &str) -> DurianResult<String> {
+    let url = Url::parse(value)
+        .map_err(|e| DurianError::validation(tr(Message::InvalidProxyUrl(&e.to_string()))))?;
+    if !matches!(url.scheme(), "http" | "https" | "socks5" | "socks5h") {
+        return Err(DurianError::validation(tr(Message::UnsupportedProxyScheme)));
+    }
+    if url.host_str().map_or(true, str::is_empty) {
+        return Err(DurianError::validation(tr(Message::ProxyMissingHost)));
+    }
+    Ok(value.to_string())
+}
+
+/// 校验 User-Agent（只能包含可见 ASCII 字符和空格）
+pub fn validate_user_agent(value: &str) -> DurianResult<String> {
+    if value.len() > MAX_USER_AGENT_LEN {
+        return Err(DurianError::validation(tr(Message::MaxLength(
+            Field::UserAgent,
+            MAX_USER_AGENT_LEN,
+        ))));
+    }
+    if !value.chars().all(|c| c == ' ' || c.is_ascii_graphic()) {
+        return Err(DurianError::validation(tr(Message::NonAsciiUserAgent)));
+    }
+    Ok(value.to_string())
+}
+
+// ============================================
+// 服务器配置管理
+// ============================================
+
+impl Settings {
+    /// 默认服务器
+    pub fn default_profile(&self) -> Option<&ServerProfile> {
+        self.profiles.iter().find(|profile| profile.is_default)
+    }
+
+    /// 按名称查找服务器
+    pub fn profile(&self, name: &str) -> Option<&ServerProfile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+
+    /// 按名称查找服务器，未指定名称时使用默认服务器
+    ///
+    /// # Arguments
+    /// * `name` - 服务器名称
+    pub fn resolve_profile(&self, name: Option<&str>) -> DurianResult<ServerProfile> {
+        let profile = match name {
+            Some(name) => self
+                .profile(name)
+                .ok_or_else(|| DurianError::validation(tr(Message::ProfileNotFound(name))))?,
+            None => self
+                .default_profile()
+                .ok_or_else(|| DurianError::config(tr(Message::NoServerProfile)))?,
+        };
+        Ok(profile.clone())
+    }
+
+    /// 按 API 基础 URL 查找服务器配置，没有保存的配置时使用默认选项
+    pub fn profile_for_url(&self, api_base_url: &str) -> ServerProfile {
+        let unsaved = ServerProfile::unsaved(api_base_url);
+        self.profiles
+            .iter()
+            .find(|profile| profile.api_base_url == unsaved.api_base_url)
+            .cloned()
+            .unwrap_or(unsaved)
+    }
+
+    /// 信任服务器证书：将公钥指纹加入服务器的固定指纹列表
+    ///
+    /// # Arguments
+    /// * `name` - 服务器名称
+    /// * `fingerprint` - 公钥指纹
+    ///
+    /// # Returns
+    /// 更新后的服务器配置
+    pub fn trust_fingerprint(
+        &mut self,
+        name: &str,
+        fingerprint: &str,
+    ) -> DurianResult<ServerProfile> {
+        let fingerprint = tls::normalize_fingerprint(fingerprint)?;
+        let profile = self
+            .profiles
+            .iter_mut()
+            .find(|profile| profile.name == name)
+            .ok_or_else(|| DurianError::validation(tr(Message::ProfileNotFound(name))))?;
+        if !profile.tls.pinned_spki_sha256.contains(&fingerprint) {
+            profile.tls.pinned_spki_sha256.push(fingerprint);
+        }
+        Ok(profile.clone())
+    }
+
+    /// 添加或替换同名服务器
+    ///
+    /// 设为默认时取消其他服务器的默认标记；第一个服务器自动成为默认服务器
+    ///
+    /// # Returns
+    /// 规范化后的服务器配置
+    pub fn save_profile(&mut self, profile: ServerProfile) -> DurianResult<ServerProfile> {
+        let mut profile = profile.normalize()?;
+
+        if profile.is_default {
+            for other in &mut self.profiles {
+                other.is_default = false;
+            }
+        }
+
+        let index = match self.profiles.iter().position(|p| p.name == profile.name) {
+            Some(index) => {
+                self.profiles[index] = profile;
+                index
+            }
+            None => {
+                if self.profiles.is_empty() {
+                    profile.is_default = true;
+                }
+                self.profiles.push(profile);
+                self.profiles.len() - 1
+            }
+        };
+
+        self.ensure_default();
+        Ok(self.profiles[index].clone())
+    }
+
+    /// 删除服务器
+    ///
+    /// 删除默认服务器时，第一个剩余的服务器成为默认服务器
+    pub fn delete_profile(&mut self, name: &str) -> DurianResult<()> {
+        let index = self
+            .profiles
+            .iter()
+            .position(|profile| profile.name == name)
+            .ok_or_else(|| DurianError::validation(tr(Message::ProfileNotFound(name))))?;
+        self.profiles.remove(index);
+        self.ensure_default();
+        Ok(())
+    }
+
+    /// 保证存在服务器时恰有一个默认服务器
+    fn ensure_default(&mut self) {
+        if self.default_profile().is_none() {
+            if let Some(first) = self.profiles.first_mut() {
+                first.is_default = true;
+            }
+        }
+    }
+}
+
+// ============================================
+// 文件读写
+// ============================================
+
+/// 读取设置文件，文件不存在时返回默认设置
+///
+/// # Arguments
+/// * `path` - 设置文件路径
+pub fn load_settings(path: &Path) -> DurianResult<Settings> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Settings::default()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let value: serde_json::Value = serde_json::from_str(&content)?;
+    let value = migrate_settings(value)?;
+    Ok(serde_json::from_value(value)?)
+}
+
+/// 保存设置文件
+///
+/// # Arguments
+/// * `path` - 设置文件路径
+/// * `settings` - 设置
+pub fn save_settings(path: &Path, settings: &Settings) -> DurianResult<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, serde_json::to_vec_pretty(settings)?)?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// 将旧版本的设置迁移到当前版本
+///
+/// - 版本 0：没有 `version` 字段的早期文件，结构与版本 1 相同
+/// - 版本 1 → 2：移除跳过证书验证的 `accept_invalid_certs`，
+///   原先开启该选项的服务器改为首次信任模式
+fn migrate_settings(mut value: serde_json::Value) -> DurianResult<serde_json::Value> {
+    let object = value
+        .as_object_mut()
+        .ok_or_else(|| DurianError::config(tr(Message::SettingsMalformed)))?;
+    let version = object
+        .get("version")
+        .and_then(serde_json::Value::as_u64)
+        .unwrap_or(0);
+
+    if version > u64::from(SETTINGS_VERSION) {
+        return Err(DurianError::config(tr(Message::SettingsTooNew(
+            version,
+            SETTINGS_VERSION,
+        ))));
+    }
+
+    if version < 2 {
+        let profiles = object
+            .get_mut("profiles")
+            .and_then(serde_json::Value::as_array_mut);
+        for profile in profiles.into_iter().flatten() {
+            let Some(tls) = profile.get_mut("tls").and_then(serde_json::Value::as_object_mut)
+            else {
+                continue;
+            };
+            if let Some(serde_json::Value::Bool(true)) = tls.remove("accept_invalid_certs") {
+                tls.insert("trust_on_first_use".to_string(), true.into());
+            }
+        }
+    }
+
+    object.insert("version".to_string(), SETTINGS_VERSION.into());
+    Ok(value)
+}
+
+// ============================================
+// 托管的设置存储
+// ============================================
+
+/// 由 Tauri 托管的设置存储
+///
+/// 每次修改都重新读取文件并在写锁内完成“读取-修改-写入”，避免并发修改相互覆盖
+pub struct SettingsStore {
+    /// 设置文件路径，无法获取应用数据目录时为 `None`
+    path: Option<PathBuf>,
+    lock: Mutex<()>,
+}
+
+impl SettingsStore {
+    /// 使用指定的设置文件路径
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path: Some(path),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// 使用应用数据目录下的设置文件
+    ///
+    /// 无法获取应用数据目录时应用仍可启动，读写设置的命令返回错误
+    pub fn open_default() -> Self {
+        Self {
+            path: app_data_dir().ok().map(|dir| dir.join(SETTINGS_FILE_NAME)),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// 设置文件路径
+    fn path(&self) -> DurianResult<&Path> {
+        self.path.as_deref().ok_or_else(|| {
+            DurianError::config(tr(Message::SettingsUnavailable(&tr(Message::NoAppDataDir))))
+        })
+    }
+
+    /// 读取当前设置
+    pub fn load(&self) -> DurianResult<Settings> {
+        let _guard = self.lock.lock().map_err(|_| DurianError::StateLockError)?;
+        load_settings(self.path()?)
+    }
+
+    /// 修改并保存设置
+    ///
+    /// `update` 返回错误时不写入文件
+    pub fn update<T, F>(&self, update: F) -> DurianResult<T>
+    where
+        F: FnOnce(&mut Settings) -> DurianResult<T>,
+    {
+        let _guard = self.lock.lock().map_err(|_| DurianError::StateLockError)?;
+        let path = self.path()?;
+        let mut settings = load_settings(path)?;
+        let result = update(&mut settings)?;
+        save_settings(path, &settings)?;
+        Ok(result)
+    }
+}
+
+// ============================================
+// 单元测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+
+    fn profile(name: &str, url: &str) -> ServerProfile {
+        ServerProfile {
+            name: name.to_string(),
+            api_base_url: url.to_string(),
+            ..ServerProfile::unsaved(url)
+        }
+    }
+
+    #[test]
+    fn test_validate_api_base_url() {
+        assert_eq!(
+            validate_api_base_url(" https://vault.example.com/api/ ").unwrap(),
+            "https://vault.example.com/api"
+        );
+        assert!(validate_api_base_url("http://localhost:7224/api").is_ok());
+
+        assert!(validate_api_base_url("").is_err());
+        assert!(validate_api_base_url("vault.example.com").is_err());
+        assert!(validate_api_base_url("ftp://vault.example.com").is_err());
+        assert!(validate_api_base_url("https://vault.example.com/?a=1").is_err());
+        assert!(validate_api_base_url("https://user:pw@vault.example.com").is_err());
+    }
+
+    #[test]
+    fn test_default_profile() {
+        let mut settings = Settings::default();
+        settings.save_profile(profile("personal", "https://a.example.com")).unwrap();
+        assert_eq!(settings.default_profile().unwrap().name, "personal");
+
+        let mut work = profile("work", "https://b.example.com/");
+        work.is_default = true;
+        settings.save_profile(work).unwrap();
+        assert_eq!(settings.default_profile().unwrap().name, "work");
+        assert_eq!(settings.profile("work").unwrap().api_base_url, "https://b.example.com");
+        assert_eq!(settings.profiles.iter().filter(|p| p.is_default).count(), 1);
+
+        // 同名保存替换原配置
+        settings.save_profile(profile("personal", "https://c.example.com")).unwrap();
+        assert_eq!(settings.profiles.len(), 2);
+        assert_eq!(settings.profile("personal").unwrap().api_base_url, "https://c.example.com");
+
+        settings.delete_profile("work").unwrap();
+        assert_eq!(settings.default_profile().unwrap().name, "personal");
+        assert!(settings.delete_profile("work").is_err());
+    }
+
+    #[test]
+    fn test_resolve_profile() {
+        let mut settings = Settings::default();
+        assert!(settings.resolve_profile(None).is_err());
+
+        settings.save_profile(profile("personal", "https://a.example.com")).unwrap();
+        settings.save_profile(profile("work", "https://b.example.com")).unwrap();
+        assert_eq!(settings.resolve_profile(None).unwrap().name, "personal");
+        assert_eq!(settings.resolve_profile(Some("work")).unwrap().api_base_url, "https://b.example.com");
+        assert!(settings.resolve_profile(Some("other")).is_err());
+    }
+
+    #[test]
+    fn test_invalid_profile() {
+        let mut settings = Settings::default();
+        assert!(settings.save_profile(profile(" ", "https://a.example.com")).is_err());
+        assert!(settings.save_profile(profile("bad", "not a url")).is_err());
+
+        let mut slow = profile("slow", "https://a.example.com");
+        slow.timeouts.request_timeout_secs = 0;
+        assert!(settings.save_profile(slow).is_err());
+
+        let mut proxied = profile("proxied", "https://a.example.com");
+        proxied.proxy_url = Some("ftp://proxy.example.com".to_string());
+        assert!(settings.save_profile(proxied).is_err());
+
+        let mut agent = profile("agent", "https://a.example.com");
+        agent.user_agent = Some("榴莲".to_string());
+        assert!(settings.save_profile(agent).is_err());
+        assert!(settings.profiles.is_empty());
+    }
+
+    #[test]
+    fn test_proxy_and_user_agent() {
+        let mut settings = Settings::default();
+        let mut work = profile("work", "https://vault.corp.example.com");
+        work.proxy_url = Some(" socks5h://user:pw@proxy.corp.example.com:1080 ".to_string());
+        work.user_agent = Some("  ".to_string());
+        let saved = settings.save_profile(work).unwrap();
+        assert_eq!(
+            saved.proxy_url.as_deref(),
+            Some("socks5h://user:pw@proxy.corp.example.com:1080")
+        );
+        assert_eq!(saved.user_agent, None);
+
+        let found = settings.profile_for_url("https://vault.corp.example.com/");
+        assert_eq!(found, saved);
+        let unsaved = settings.profile_for_url("https://other.example.com/");
+        assert_eq!(unsaved.proxy_url, None);
+        assert_eq!(unsaved.api_base_url, "https://other.example.com");
+    }
+
+    #[test]
+    fn test_save_and_load_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SettingsStore::new(dir.path().join("settings.json"));
+        assert_eq!(store.load().unwrap(), Settings::default());
+
+        store
+            .update(|settings| settings.save_profile(profile("personal", "https://a.example.com")))
+            .unwrap();
+        let settings = store.load().unwrap();
+        assert_eq!(settings.version, SETTINGS_VERSION);
+        assert_eq!(settings.profiles.len(), 1);
+
+        // 修改失败时不写入文件
+        assert!(store.update(|settings| settings.delete_profile("missing")).is_err());
+
+        // 无法确定设置文件位置时，读写设置返回配置错误
+        let unavailable = SettingsStore {
+            path: None,
+            lock: Mutex::new(()),
+        };
+        assert_eq!(unavailable.load().unwrap_err().kind(), ErrorKind::Config);
+        assert_eq!(
+            unavailable.update(|_| Ok(())).unwrap_err().kind(),
+            ErrorKind::Config
+        );
+        assert_eq!(store.load().unwrap(), settings);
+
+        // 界面语言
+        assert_eq!(settings.locale, None);
+        store
+            .update(|settings| {
+                settings.locale = Some(Locale::En);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(store.load().unwrap().locale(), Locale::En);
+    }
+
+    #[test]
+    fn test_migrate_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+
+        // 没有版本号的早期文件
+        std::fs::write(
+            &path,
+            r#"{"profiles":[{"name":"old","api_base_url":"https://a.example.com"}]}"#,
+        )
+        .unwrap();
+        let settings = load_settings(&path).unwrap();
+        assert_eq!(settings.version, SETTINGS_VERSION);
+        assert_eq!(settings.profiles[0].timeouts, TimeoutOptions::default());
+
+        // 版本 1 中跳过证书验证的服务器改为首次信任
+        std::fs::write(
+            &path,
+            r#"{"version":1,"profiles":[{"name":"lan","api_base_url":"https://10.0.0.2",
+                "tls":{"accept_invalid_certs":true}}]}"#,
+        )
+        .unwrap();
+        let settings = load_settings(&path).unwrap();
+        assert!(settings.profiles[0].tls.trust_on_first_use);
+
+        // 更新版本写入的文件
+        std::fs::write(&path, r#"{"version":99,"profiles":[]}"#).unwrap();
+        assert!(matches!(load_settings(&path), Err(DurianError::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_trust_fingerprint() {
+        let fingerprint = "2dI73OFVn4drcOBKmONbsPfG3KVQo3TLPJ38goDY7wo=";
+        let mut settings = Settings::default();
+        settings.save_profile(profile("lan", "https://10.0.0.2/")).unwrap();
+        assert_eq!(settings.profile_for_url("https://10.0.0.2").tls, TlsOptions::default());
+
+        assert!(settings.trust_fingerprint("lan", "not-a-fingerprint").is_err());
+        assert!(settings.trust_fingerprint("missing", fingerprint).is_err());
+        settings.trust_fingerprint("lan", fingerprint).unwrap();
+        settings.trust_fingerprint("lan", fingerprint).unwrap();
+        assert_eq!(
+            settings.profile_for_url("https://10.0.0.2/").tls.pinned_spki_sha256,
+            vec![fingerprint.to_string()]
+        );
+    }
+}
@@ -1,19 +1,49 @@
 //! 应用状态管理模块
 //!
-//! 管理应用的全局状态，包括用户信息、认证令牌和数据库路径
+//! 管理应用状态，包括用户信息、认证令牌和数据库路径
+//!
+//! # 多会话
+//! 可以同时登录多个用户（例如个人和工作保险库），每个会话拥有独立的令牌、
+//! API 基础 URL、保险库密钥和锁定状态。命令总是作用于当前会话，
+//! 使用 [`AppState::switch_session`] 切换当前会话。
+//! 会话和本地缓存都按会话标识（服务器地址和用户名，见 [`session_id`]）区分，
+//! 同一服务器上的同一用户名只保留一个会话，不同服务器上的同名用户互不影响
 //!
 //! # 线程安全
-//! 使用 `LazyLock<RwLock<Option<DurianState>>>` 实现：
-//! - LazyLock: 首次访问时懒加载初始化
+//! `AppState` 由 Tauri 托管（`tauri::State`），内部为 `Arc<RwLock<Vec<DurianState>>>`：
+//! - Arc: 可在 Tauri 托管的实例和后台线程（如自动锁定）之间共享
 //! - RwLock: 允许多个读者或单个写者，优化读取性能
-//! - Option: 允许状态被设置和清除
-
+//! - Vec: 已登录的会话，按最近使用排序，第一个为当前会话
+//!
+//! 锁中毒时返回 `DurianError::StateLockError` 而不是 panic；
+//! 每个 `AppState` 实例相互独立，测试中可以并行创建多个实例
+//!
+//! # 异步命令
+//! 状态锁不能跨越 `.await` 持有。异步命令先取出会话的 [`Connection`]，
+//! 等待网络请求期间不持有锁，之后使用 [`AppState::session`] 按会话标识重新获取同一会话，
+//! 避免期间切换会话后写入其他用户的数据
 use std::path::PathBuf;
-use std::sync::{LazyLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use ts_rs::TS;
 
+use crate::api_client::{ApiClient, HttpApi, HttpClient};
+use crate::crypto::{CacheCipher, VaultKey};
 use crate::database;
 use crate::error::{DurianError, DurianResult};
-use crate::models::CacheData;
+use crate::i18n::{tr, Field, Message};
+use crate::kdf::KdfParams;
+use crate::lock::LockSettings;
+use crate::models::{
+    AccountConflict, AccountItem, AccountRecord, CacheData, PendingOp, PendingOpKind, PullMode,
+    SyncChanges, SyncStatus, WrappedKey,
+};
+use crate::secret::SecretString;
+use crate::settings::{self, ServerProfile};
+use crate::sync_worker::{SyncSettings, SyncWorker};
+use crate::vault;
 
 // ============================================
 // 状态结构定义
@@ -25,84 +55,418 @@ use crate::models::CacheData;
 pub struct DurianState {
     /// 当前登录用户名
     pub username: String,
-    /// 核心密码（用于本地加密）
-    pub core_password: String,
-    /// 认证令牌
-    pub token: String,
+    /// 会话标识（见 [`session_id`]），本地缓存按其区分
+    pub session_id: String,
+    /// 会话密钥，保险库锁定时为 `None`
+    keys: Option<SessionKeys>,
+    /// 缓存密钥校验值，用于解锁时校验核心密码
+    key_check: String,
+    /// 保险库密钥参数，锁定后重新解锁时使用
+    vault_kdf: KdfParams,
+    /// 认证令牌（释放时清零）
+    pub token: SecretString,
     /// SQLite 数据库文件路径
     pub db_path: PathBuf,
     /// API 基础 URL
     pub api_base_url: String,
+    /// API 客户端（按服务器的 TLS 选项构建）
+    pub api: Arc<dyn ApiClient>,
+    /// 自动锁定设置
+    lock_settings: LockSettings,
+    /// 最后一次使用会话密钥的时间
+    last_activity: Mutex<Instant>,
+    /// 离线写入队列的回放锁（见 [`crate::sync`]）
+    write_queue: Arc<tokio::sync::Mutex<()>>,
+    /// 后台同步设置
+    sync_settings: SyncSettings,
+    /// 后台同步任务，释放时停止（见 [`crate::sync_worker`]）
+    sync_worker: Option<SyncWorker>,
 }
 
-// ============================================
-// 全局状态变量
-// ============================================
+/// 会话密钥（锁定时整体清除）
+pub struct SessionKeys {
+    /// 会话保险库密钥（由核心密码解锁，核心密码本身不保存）
+    vault_key: VaultKey,
+    /// 本地缓存字段加密器（由会话保险库密钥派生）
+    cache_cipher: CacheCipher,
+}
+
+/// 本地解锁所需的会话信息
+///
+/// 从会话中克隆得到，派生密钥（Argon2）期间无需持有状态锁
+pub struct UnlockContext {
+    db_path: PathBuf,
+    session_id: String,
+    vault_kdf: KdfParams,
+    key_check: String,
+}
+
+impl UnlockContext {
+    /// 使用核心密码派生会话密钥，并与缓存密钥校验值比较
+    ///
+    /// # Arguments
+    /// * `core_password` - 核心密码
+    pub fn derive_keys(&self, core_password: &str) -> DurianResult<SessionKeys> {
+        let vault_key =
+            vault::unlock_cached(&self.db_path, &self.session_id, &self.vault_kdf, core_password)
+                .map_err(|_| {
+                    DurianError::invalid_credentials(tr(Message::IncorrectPassword(Field::CorePassword)))
+                })?;
+        let cache_cipher = CacheCipher::new(&vault_key)?;
+        if cache_cipher.key_check() != self.key_check {
+            return Err(DurianError::invalid_credentials(tr(Message::IncorrectPassword(
+                Field::CorePassword,
+            ))));
+        }
+        Ok(SessionKeys {
+            vault_key,
+            cache_cipher,
+        })
+    }
+}
 
-/// 全局状态变量
+/// 会话的服务器连接信息
 ///
-/// 使用 LazyLock + RwLock<Option<>> 实现线程安全的可变状态
-/// - LazyLock: 首次访问时懒加载初始化，线程安全
-/// - RwLock: 提供多读单写的并发访问
-/// - Option: 允许状态被设置（登录）和清除（登出）
-static DURIAN_STATE: LazyLock<RwLock<Option<DurianState>>> = LazyLock::new(|| RwLock::new(None));
+/// 从会话中克隆得到，异步命令等待网络请求期间无需持有状态锁
+#[derive(Clone)]
+pub struct Connection {
+    /// 用户名
+    pub username: String,
+    /// 会话标识
+    pub session_id: String,
+    /// API 客户端，前端的 `cancel_requests` 命令会取消其进行中的请求
+    pub api: Arc<dyn ApiClient>,
+    /// 取消状态独立的 API 客户端，供队列回放和后台同步使用
+    pub background: Arc<dyn ApiClient>,
+    /// 认证令牌
+    pub token: SecretString,
+    /// 离线写入队列的回放锁，同一会话同时只有一个回放
+    pub write_queue: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Connection {
+    /// 所有请求都通过 [`Self::background`] 发送的连接，不受 `cancel_requests` 影响
+    pub fn detached(&self) -> Connection {
+        Connection {
+            api: self.background.clone(),
+            ..self.clone()
+        }
+    }
+}
 
 // ============================================
 // DurianState 实现
 // ============================================
 
+/// 默认的本地缓存数据库路径（应用数据目录下的 `cache.db`）
+///
+/// 应用数据目录不存在时创建
+pub fn default_db_path() -> DurianResult<PathBuf> {
+    let app_data_dir = settings::app_data_dir()?;
+    std::fs::create_dir_all(&app_data_dir)?;
+    Ok(app_data_dir.join("cache.db"))
+}
+
+/// 会话标识：服务器地址和用户名
+///
+/// 同一用户名在不同服务器上的会话互相独立，本地缓存的各表也按此标识区分
+///
+/// # Arguments
+/// * `api_base_url` - API 基础 URL
+/// * `username` - 用户名
+pub fn session_id(api_base_url: &str, username: &str) -> String {
+    format!("{}|{}", api_base_url.trim_end_matches('/'), username)
+}
+
 impl DurianState {
     /// 创建新的应用状态
     ///
+    /// 核心密码仅用于解锁会话保险库密钥，不会保存在状态中
+    ///
     /// # Arguments
+    /// * `db_path` - SQLite 数据库文件路径，通常为 [`default_db_path`]
     /// * `username` - 用户名
     /// * `core_password` - 核心密码
+    /// * `vault_kdf` - 保险库密钥参数（见 [`crate::kdf::KdfSuite::vault_params`]）
     /// * `token` - 认证令牌
     /// * `api_base_url` - API 基础 URL
+    /// * `api` - API 客户端
     ///
     /// # Returns
     /// 新的 DurianState 实例，或错误
-    pub fn new(
+    pub async fn new(
+        db_path: PathBuf,
         username: String,
-        core_password: String,
-        token: String,
+        core_password: &str,
+        vault_kdf: &KdfParams,
+        token: SecretString,
         api_base_url: String,
+        api: Arc<dyn ApiClient>,
     ) -> DurianResult<DurianState> {
         // 输入验证
         if username.is_empty() {
-            return Err(DurianError::validation("用户名不能为空"));
+            return Err(DurianError::validation(tr(Message::Required(Field::Username))));
         }
         if core_password.is_empty() {
-            return Err(DurianError::validation("核心密码不能为空"));
+            return Err(DurianError::validation(tr(Message::Required(Field::CorePassword))));
         }
         if token.is_empty() {
-            return Err(DurianError::validation("认证令牌不能为空"));
+            return Err(DurianError::validation(tr(Message::Required(Field::Token))));
         }
         if api_base_url.is_empty() {
-            return Err(DurianError::validation("API URL 不能为空"));
+            return Err(DurianError::validation(tr(Message::Required(Field::ApiUrl))));
         }
 
-        // 获取应用数据目录
-        let app_data_dir = dirs::data_dir()
-            .ok_or_else(|| DurianError::config("无法获取 AppData 目录"))?
-            .join("durian-web");
+        // 初始化数据库，旧版只按用户名区分的缓存归入本会话
+        database::init_database(&db_path)?;
+        let id = session_id(&api_base_url, &username);
+        database::adopt_user_rows(&db_path, &username, &id)?;
+
+        // 解锁会话保险库密钥（每次会话只解锁一次），
+        // 附加的旧版密钥在旧记录迁移完成后清除（见 [`crate::rekey::migrate_legacy_entries`]）
+        let vault_key = vault::unlock_vault(
+            api.as_ref(),
+            &db_path,
+            &id,
+            vault_kdf,
+            token.expose(),
+            core_password,
+        )
+        .await?
+        .with_legacy_key(core_password);
+
+        Self::with_vault_key(username, vault_key, token, db_path, api_base_url, api)
+    }
+
+    /// 使用已解锁的保险库密钥创建应用状态
+    ///
+    /// 数据库需已初始化
+    pub(crate) fn with_vault_key(
+        username: String,
+        vault_key: VaultKey,
+        token: SecretString,
+        db_path: PathBuf,
+        api_base_url: String,
+        api: Arc<dyn ApiClient>,
+    ) -> DurianResult<DurianState> {
+        // 准备加密缓存（迁移旧版明文缓存）；首次启用信封加密时，
+        // 由派生密钥加密的缓存、离线写入和冲突一次性重新加密到数据密钥下
+        let session_id = session_id(&api_base_url, &username);
+        let cache_cipher = CacheCipher::new(&vault_key)?;
+        let previous = CacheCipher::without_data_key(&vault_key)?;
+        database::prepare_cache(&db_path, &session_id, &cache_cipher, previous.as_ref())?;
 
-        // 确保目录存在
-        std::fs::create_dir_all(&app_data_dir)?;
-        let db_path = app_data_dir.join("cache.db");
+        let lock_settings = database::get_lock_settings(&db_path, &session_id)?;
+        let sync_settings = database::get_sync_settings(&db_path, &session_id)?;
 
-        let state = DurianState {
+        Ok(DurianState {
             username,
-            core_password,
+            session_id,
+            key_check: cache_cipher.key_check(),
+            vault_kdf: vault_key.kdf().clone(),
+            keys: Some(SessionKeys {
+                vault_key,
+                cache_cipher,
+            }),
             token,
             db_path,
             api_base_url,
-        };
+            api,
+            lock_settings,
+            last_activity: Mutex::new(Instant::now()),
+            write_queue: Arc::default(),
+            sync_settings,
+            sync_worker: None,
+        })
+    }
+
+    /// 替换会话保险库密钥
+    ///
+    /// 缓存加密器随之更新；若缓存密钥发生变化，缓存会重新加密到新密钥下
+    pub fn set_vault_key(&mut self, vault_key: VaultKey) -> DurianResult<()> {
+        let cache_cipher = CacheCipher::new(&vault_key)?;
+        let previous = self.keys.as_ref().map(|keys| &keys.cache_cipher);
+        database::prepare_cache(&self.db_path, &self.session_id, &cache_cipher, previous)?;
+        self.key_check = cache_cipher.key_check();
+        self.keys = Some(SessionKeys {
+            vault_key,
+            cache_cipher,
+        });
+        self.touch();
+        Ok(())
+    }
+
+    /// 清除会话保险库密钥中的旧版密钥
+    pub fn forget_legacy_key(&mut self) {
+        if let Some(keys) = self.keys.as_mut() {
+            keys.vault_key.forget_legacy_key();
+        }
+    }
+
+    /// 获取服务器连接信息
+    pub fn connection(&self) -> Connection {
+        Connection {
+            username: self.username.clone(),
+            session_id: self.session_id.clone(),
+            api: self.api.clone(),
+            background: self.api.detached().unwrap_or_else(|| self.api.clone()),
+            token: self.token.clone(),
+            write_queue: self.write_queue.clone(),
+        }
+    }
+
+    // ============================================
+    // 锁定与解锁
+    // ============================================
+
+    /// 获取会话保险库密钥
+    ///
+    /// 保险库锁定时返回 `DurianError::Locked`；每次获取都会刷新空闲计时
+    pub fn vault_key(&self) -> DurianResult<&VaultKey> {
+        self.session_keys().map(|keys| &keys.vault_key)
+    }
+
+    /// 获取会话保险库密钥，不刷新空闲计时
+    ///
+    /// 同步读写记录时使用（见 [`crate::sync`]），后台同步不算作用户活动，不会推迟自动锁定
+    pub fn sync_vault_key(&self) -> DurianResult<&VaultKey> {
+        self.current_keys().map(|keys| &keys.vault_key)
+    }
+
+    /// 获取本地缓存加密器
+    pub fn cache_cipher(&self) -> DurianResult<&CacheCipher> {
+        self.session_keys().map(|keys| &keys.cache_cipher)
+    }
+
+    fn session_keys(&self) -> DurianResult<&SessionKeys> {
+        let keys = self.current_keys()?;
+        self.touch();
+        Ok(keys)
+    }
+
+    /// 当前的会话密钥，不刷新空闲计时
+    fn current_keys(&self) -> DurianResult<&SessionKeys> {
+        self.keys.as_ref().ok_or(DurianError::Locked)
+    }
+
+    /// 保险库是否已锁定
+    pub fn is_locked(&self) -> bool {
+        self.keys.is_none()
+    }
+
+    /// 锁定保险库：清除会话密钥并停止后台同步，保留令牌和本地缓存
+    pub fn lock(&mut self) {
+        // SessionKeys 中的密钥材料在释放时清零
+        self.keys = None;
+        self.sync_worker = None;
+    }
+
+    /// 使用核心密码在本地解锁保险库
+    ///
+    /// 派生密钥期间持有会话的可变引用；命令中应先取出 [`DurianState::unlock_context`]，
+    /// 在释放状态锁后派生密钥，再用 [`DurianState::install_keys`] 装入
+    ///
+    /// # Arguments
+    /// * `core_password` - 核心密码
+    pub fn unlock(&mut self, core_password: &str) -> DurianResult<()> {
+        if !self.is_locked() {
+            return Ok(());
+        }
+        let keys = self.unlock_context().derive_keys(core_password)?;
+        self.install_keys(keys);
+        Ok(())
+    }
+
+    /// 获取本地解锁所需的会话信息
+    pub fn unlock_context(&self) -> UnlockContext {
+        UnlockContext {
+            db_path: self.db_path.clone(),
+            session_id: self.session_id.clone(),
+            vault_kdf: self.vault_kdf.clone(),
+            key_check: self.key_check.clone(),
+        }
+    }
+
+    /// 装入解锁得到的会话密钥
+    ///
+    /// 期间已被其他调用解锁时保留现有密钥
+    pub fn install_keys(&mut self, keys: SessionKeys) {
+        if self.is_locked() {
+            self.keys = Some(keys);
+        }
+        self.touch();
+    }
+
+    /// 刷新空闲计时
+    pub fn touch(&self) {
+        if let Ok(mut last_activity) = self.last_activity.lock() {
+            *last_activity = Instant::now();
+        }
+    }
+
+    /// 最后一次使用会话密钥的时间
+    pub fn last_activity(&self) -> Instant {
+        self.last_activity
+            .lock()
+            .map(|last_activity| *last_activity)
+            .unwrap_or_else(|_| Instant::now())
+    }
+
+    /// 根据自动锁定设置判断是否应当锁定
+    ///
+    /// # Arguments
+    /// * `suspended` - 系统是否刚从休眠中恢复
+    pub fn should_auto_lock(&self, suspended: bool) -> bool {
+        if self.is_locked() {
+            return false;
+        }
+        if suspended && self.lock_settings.lock_on_suspend {
+            return true;
+        }
+        match (self.lock_settings.idle_timeout(), self.last_activity.lock()) {
+            (Some(timeout), Ok(last_activity)) => last_activity.elapsed() >= timeout,
+            _ => false,
+        }
+    }
+
+    /// 获取自动锁定设置
+    pub fn lock_settings(&self) -> LockSettings {
+        self.lock_settings
+    }
+
+    /// 修改并保存自动锁定设置
+    pub fn set_lock_settings(&mut self, settings: LockSettings) -> DurianResult<()> {
+        settings.validate()?;
+        database::set_lock_settings(&self.db_path, &self.session_id, &settings)?;
+        self.lock_settings = settings;
+        Ok(())
+    }
+
+    // ============================================
+    // 后台同步
+    // ============================================
+
+    /// 获取后台同步设置
+    pub fn sync_settings(&self) -> SyncSettings {
+        self.sync_settings
+    }
 
-        // 初始化数据库
-        database::init_database(&state.db_path)?;
+    /// 修改并保存后台同步设置（需要重新启动后台同步任务才会生效）
+    pub fn set_sync_settings(&mut self, settings: SyncSettings) -> DurianResult<()> {
+        settings.validate()?;
+        database::set_sync_settings(&self.db_path, &self.session_id, &settings)?;
+        self.sync_settings = settings;
+        Ok(())
+    }
+
+    /// 替换后台同步任务，原任务随之停止
+    pub fn set_sync_worker(&mut self, worker: Option<SyncWorker>) {
+        self.sync_worker = worker;
+    }
 
-        Ok(state)
+    /// 后台同步任务是否在运行
+    pub fn is_syncing_in_background(&self) -> bool {
+        self.sync_worker.is_some()
     }
 
     // ============================================
@@ -110,149 +474,529 @@ impl DurianState {
     // ============================================
 
     /// 保存缓存数据
-    pub fn save_cache_data(&self, data: &CacheData, pull_mode: &str) -> DurianResult<()> {
-        database::save_cache_data(&self.db_path, &self.username, data, pull_mode)
+    pub fn save_cache_data(&self, data: &CacheData, pull_mode: PullMode) -> DurianResult<()> {
+        database::save_cache_data_with_mode(
+            &self.db_path,
+            &self.session_id,
+            self.cache_cipher()?,
+            data,
+            &[],
+            pull_mode,
+        )
+        .map(|_| ())
+    }
+
+    /// 保存从服务器同步的结果，增量拉取时在同一事务中删除墓碑记录
+    ///
+    /// 返回缓存中新增、修改和删除的记录数
+    pub fn apply_sync(
+        &self,
+        data: &CacheData,
+        deleted_rids: &[i64],
+        pull_mode: PullMode,
+    ) -> DurianResult<SyncChanges> {
+        database::save_cache_data_with_mode(
+            &self.db_path,
+            &self.session_id,
+            &self.current_keys()?.cache_cipher,
+            data,
+            deleted_rids,
+            pull_mode,
+        )
+    }
+
+    /// 获取缓存中所有记录的 `(rid, revision)`
+    pub fn account_revisions(&self) -> DurianResult<Vec<(i64, i64)>> {
+        database::get_account_revisions(&self.db_path, &self.session_id)
+    }
+
+    /// 用服务器上的记录替换缓存中指定分桶的记录
+    ///
+    /// 返回缓存中新增、修改和删除的记录数
+    pub fn replace_buckets(
+        &self,
+        bucket_count: usize,
+        buckets: &[usize],
+        records: &[AccountRecord],
+    ) -> DurianResult<SyncChanges> {
+        database::replace_buckets(
+            &self.db_path,
+            &self.session_id,
+            self.cache_cipher()?,
+            bucket_count,
+            buckets,
+            records,
+        )
     }
 
     /// 加载缓存数据
     pub fn load_cache_data(&self) -> DurianResult<Option<CacheData>> {
-        database::load_cache_data(&self.db_path, &self.username)
+        let data = database::load_cache_data(&self.db_path, &self.session_id, self.cache_cipher()?)?;
+        Ok(data.map(|mut data| {
+            data.username = self.username.clone();
+            data.accounts.iter_mut().for_each(|record| self.label(record));
+            data
+        }))
+    }
+
+    /// 按网站查找缓存中的账户
+    pub fn find_accounts_by_website(&self, website: &str) -> DurianResult<Vec<AccountRecord>> {
+        let mut records = database::find_accounts_by_website(
+            &self.db_path,
+            &self.session_id,
+            self.cache_cipher()?,
+            website,
+        )?;
+        records.iter_mut().for_each(|record| self.label(record));
+        Ok(records)
+    }
+
+    /// 按记录 ID 获取缓存中的账户
+    pub fn get_account(&self, rid: i64) -> DurianResult<Option<AccountRecord>> {
+        let record = database::get_account(&self.db_path, &self.session_id, self.cache_cipher()?, rid)?;
+        Ok(record.map(|mut record| {
+            self.label(&mut record);
+            record
+        }))
     }
 
     /// 获取最后更新时间
     pub fn get_last_update_time(&self) -> DurianResult<i64> {
-        database::get_last_update_time(&self.db_path, &self.username)
+        database::get_last_update_time(&self.db_path, &self.session_id)
+    }
+
+    /// 增量同步的起点：缓存中有缺少修订号的记录时为 0（全量拉取）
+    pub fn sync_cursor(&self) -> DurianResult<i64> {
+        if database::has_unrevisioned_accounts(&self.db_path, &self.session_id)? {
+            return Ok(0);
+        }
+        self.get_last_update_time()
     }
 
     /// 清除用户缓存
     pub fn clear_cache(&self) -> DurianResult<()> {
-        database::clear_user_cache(&self.db_path, &self.username)
+        database::clear_user_cache(&self.db_path, &self.session_id)
+    }
+
+    /// 批量更新缓存中的账户密码
+    pub fn update_account_passwords(
+        &self,
+        updates: &[(i64, String, Option<i64>)],
+    ) -> DurianResult<()> {
+        database::update_account_passwords(&self.db_path, &self.session_id, updates)
+    }
+
+    /// 记录开始修改核心密码
+    pub fn begin_core_password_change(&self, started_at: i64) -> DurianResult<()> {
+        database::begin_core_password_change(&self.db_path, &self.session_id, started_at)
+    }
+
+    /// 检查是否存在未完成的核心密码修改
+    pub fn has_pending_core_password_change(&self) -> DurianResult<bool> {
+        database::has_pending_core_password_change(&self.db_path, &self.session_id)
+    }
+
+    /// 标记核心密码修改已完成
+    pub fn finish_core_password_change(&self) -> DurianResult<()> {
+        database::finish_core_password_change(&self.db_path, &self.session_id)
+    }
+
+    /// 是否启用了全字段加密（服务器上设置的本地缓存）
+    pub fn full_field_encryption(&self) -> DurianResult<bool> {
+        database::get_full_field_encryption(&self.db_path, &self.session_id)
+    }
+
+    /// 缓存服务器上的全字段加密设置
+    pub fn set_full_field_encryption(&self, enabled: bool) -> DurianResult<()> {
+        database::set_full_field_encryption(&self.db_path, &self.session_id, enabled)
     }
+
+    /// 缓存包装后的数据密钥
+    pub fn save_wrapped_key(&self, key: &WrappedKey) -> DurianResult<()> {
+        database::save_wrapped_key(&self.db_path, &self.session_id, key)
+    }
+
+    // ============================================
+    // 离线写入队列代理方法
+    // ============================================
+
+    /// 将写入操作加入离线队列并应用到本地缓存，返回操作 ID
+    pub fn enqueue_write(
+        &self,
+        kind: PendingOpKind,
+        item: &AccountItem,
+        record: Option<&AccountRecord>,
+    ) -> DurianResult<i64> {
+        let (id, _) = database::enqueue_pending_op(
+            &self.db_path,
+            &self.session_id,
+            self.cache_cipher()?,
+            kind,
+            item,
+            record,
+            unix_timestamp(),
+        )?;
+        Ok(id)
+    }
+
+    /// 获取下一个等待发送的写入操作
+    pub fn next_pending_op(&self) -> DurianResult<Option<PendingOp>> {
+        let cipher = &self.current_keys()?.cache_cipher;
+        database::next_pending_op(&self.db_path, &self.session_id, cipher)
+    }
+
+    /// 标记写入操作已发送成功
+    pub fn complete_pending_op(
+        &self,
+        op: &PendingOp,
+        server_value: Option<i64>,
+    ) -> DurianResult<()> {
+        database::complete_pending_op(&self.db_path, &self.session_id, op, server_value)
+    }
+
+    /// 记录写入操作发送失败，稍后重试
+    pub fn record_pending_op_error(&self, id: i64, error: &str) -> DurianResult<()> {
+        database::record_pending_op_error(&self.db_path, &self.session_id, id, error)
+    }
+
+    /// 标记写入操作被服务器拒绝
+    pub fn reject_pending_op(&self, op: &PendingOp, error: &str) -> DurianResult<()> {
+        database::reject_pending_op(&self.db_path, &self.session_id, op, error)
+    }
+
+    /// 获取离线写入队列的状态
+    pub fn sync_status(&self) -> DurianResult<SyncStatus> {
+        database::list_pending_ops(&self.db_path, &self.session_id).map(SyncStatus::new)
+    }
+
+    /// 删除被服务器拒绝的写入操作
+    pub fn clear_failed_ops(&self) -> DurianResult<()> {
+        database::clear_failed_ops(&self.db_path, &self.session_id)
+    }
+
+    // ============================================
+    // 修改冲突代理方法
+    // ============================================
+
+    /// 记录修改操作与服务器版本的冲突，返回是否记录了冲突
+    pub fn record_conflict(&self, op: &PendingOp, server: &AccountRecord) -> DurianResult<bool> {
+        database::record_conflict(
+            &self.db_path,
+            &self.session_id,
+            &self.current_keys()?.cache_cipher,
+            op,
+            server,
+            unix_timestamp(),
+        )
+    }
+
+    /// 列出等待处理的修改冲突
+    pub fn list_conflicts(&self) -> DurianResult<Vec<AccountConflict>> {
+        let mut conflicts =
+            database::list_conflicts(&self.db_path, &self.session_id, self.cache_cipher()?)?;
+        conflicts.iter_mut().for_each(|conflict| self.label_conflict(conflict));
+        Ok(conflicts)
+    }
+
+    /// 获取记录的修改冲突
+    pub fn get_conflict(&self, rid: i64) -> DurianResult<Option<AccountConflict>> {
+        let conflict = database::get_conflict(&self.db_path, &self.session_id, self.cache_cipher()?, rid)?;
+        Ok(conflict.map(|mut conflict| {
+            self.label_conflict(&mut conflict);
+            conflict
+        }))
+    }
+
+    /// 移除记录的修改冲突，返回该记录是否存在冲突
+    pub fn remove_conflict(&self, rid: i64) -> DurianResult<bool> {
+        database::remove_conflict(&self.db_path, &self.session_id, rid)
+    }
+
+    /// 数据库中的记录按会话标识保存，返回前改为用户名
+    fn label(&self, record: &mut AccountRecord) {
+        record.username.clone_from(&self.username);
+    }
+
+    fn label_conflict(&self, conflict: &mut AccountConflict) {
+        self.label(&mut conflict.local);
+        self.label(&mut conflict.server);
+    }
+}
+
+/// 当前 Unix 时间戳（秒）
+fn unix_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
 }
 
 // ============================================
-// 全局状态管理函数
+// 托管状态
 // ============================================
 
-/// 初始化全局状态
+/// 由 Tauri 托管的应用状态
 ///
-/// # Arguments
-/// * `state` - DurianState 实例
-pub fn set_global_state(state: DurianState) {
-    let mut guard = DURIAN_STATE.write().expect("写锁定失败");
-    *guard = Some(state);
+/// 克隆得到的实例共享同一组会话
+#[derive(Clone, Default)]
+pub struct AppState {
+    inner: Arc<RwLock<Vec<DurianState>>>,
 }
 
-/// 获取全局状态的只读引用
-///
-/// 使用 RwLock 的读锁，允许多个读者同时访问
-///
-/// # Returns
-/// 状态的只读守卫，或错误信息
-pub fn get_state() -> DurianResult<StateReadGuard<'static>> {
-    let guard = DURIAN_STATE.read().map_err(|_| DurianError::StateLockError)?;
-    
-    if guard.is_none() {
-        return Err(DurianError::StateNotInitialized);
-    }
-    
-    Ok(StateReadGuard { guard })
+/// 已登录会话的摘要信息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, TS)]
+#[ts(export)]
+pub struct SessionInfo {
+    /// 会话标识，用于切换会话
+    pub id: String,
+    /// 用户名
+    pub username: String,
+    /// API 基础 URL
+    pub api_base_url: String,
+    /// 保险库是否已锁定
+    pub locked: bool,
+    /// 是否为当前会话
+    pub active: bool,
 }
 
-/// 获取全局状态的可写引用
-///
-/// 使用 RwLock 的写锁，独占访问
-///
-/// # Returns
-/// 状态的可写守卫，或错误信息
-#[allow(dead_code)]
-pub fn get_state_mut() -> DurianResult<StateWriteGuard<'static>> {
-    let guard = DURIAN_STATE.write().map_err(|_| DurianError::StateLockError)?;
-    
-    if guard.is_none() {
-        return Err(DurianError::StateNotInitialized);
+impl AppState {
+    /// 创建未登录的应用状态
+    pub fn new() -> Self {
+        Self::default()
     }
-    
-    Ok(StateWriteGuard { guard })
-}
 
-/// 检查全局状态是否已初始化
-pub fn is_state_initialized() -> bool {
-    DURIAN_STATE.read().map(|g| g.is_some()).unwrap_or(false)
-}
+    /// 添加会话并设为当前会话（登录）
+    ///
+    /// 同一服务器上的同一用户名已有会话时替换旧会话
+    ///
+    /// # Arguments
+    /// * `state` - DurianState 实例
+    pub fn set(&self, state: DurianState) -> DurianResult<()> {
+        let mut sessions = self.inner.write().map_err(|_| DurianError::StateLockError)?;
+        sessions.retain(|session| session.session_id != state.session_id);
+        sessions.insert(0, state);
+        Ok(())
+    }
+
+    /// 获取当前会话的只读引用
+    ///
+    /// 使用 RwLock 的读锁，允许多个读者同时访问
+    ///
+    /// # Returns
+    /// 状态的只读守卫，或错误信息
+    pub fn get(&self) -> DurianResult<StateReadGuard<'_>> {
+        let guard = self.inner.read().map_err(|_| DurianError::StateLockError)?;
+
+        if guard.is_empty() {
+            return Err(DurianError::StateNotInitialized);
+        }
+
+        Ok(StateReadGuard { guard, index: 0 })
+    }
+
+    /// 获取当前会话的可写引用
+    ///
+    /// 使用 RwLock 的写锁，独占访问
+    ///
+    /// # Returns
+    /// 状态的可写守卫，或错误信息
+    pub fn get_mut(&self) -> DurianResult<StateWriteGuard<'_>> {
+        let guard = self.inner.write().map_err(|_| DurianError::StateLockError)?;
+
+        if guard.is_empty() {
+            return Err(DurianError::StateNotInitialized);
+        }
+
+        Ok(StateWriteGuard { guard, index: 0 })
+    }
+
+    /// 按会话标识获取会话的只读引用（不要求是当前会话）
+    ///
+    /// 异步命令在等待网络请求后使用它重新获取发起请求的会话
+    ///
+    /// # Arguments
+    /// * `session_id` - 会话标识（见 [`session_id`]）
+    pub fn session(&self, session_id: &str) -> DurianResult<StateReadGuard<'_>> {
+        let guard = self.inner.read().map_err(|_| DurianError::StateLockError)?;
+        let index = Self::position(&guard, session_id)?;
+        Ok(StateReadGuard { guard, index })
+    }
+
+    /// 按会话标识获取会话的可写引用（不要求是当前会话）
+    ///
+    /// # Arguments
+    /// * `session_id` - 会话标识
+    pub fn session_mut(&self, session_id: &str) -> DurianResult<StateWriteGuard<'_>> {
+        let guard = self.inner.write().map_err(|_| DurianError::StateLockError)?;
+        let index = Self::position(&guard, session_id)?;
+        Ok(StateWriteGuard { guard, index })
+    }
+
+    /// 查找会话在列表中的位置
+    fn position(sessions: &[DurianState], session_id: &str) -> DurianResult<usize> {
+        sessions
+            .iter()
+            .position(|session| session.session_id == session_id)
+            .ok_or_else(|| DurianError::SessionNotFound(session_id.to_string()))
+    }
+
+    /// 检查是否已登录（存在当前会话）
+    pub fn is_initialized(&self) -> bool {
+        self.inner.read().map(|g| !g.is_empty()).unwrap_or(false)
+    }
+
+    /// 切换当前会话
+    ///
+    /// 会话各自保持锁定状态，切换到已锁定的会话后需要单独解锁
+    ///
+    /// # Arguments
+    /// * `session_id` - 目标会话的标识
+    pub fn switch_session(&self, session_id: &str) -> DurianResult<()> {
+        let mut sessions = self.inner.write().map_err(|_| DurianError::StateLockError)?;
+        let index = Self::position(&sessions, session_id)?;
+        let session = sessions.remove(index);
+        sessions.insert(0, session);
+        Ok(())
+    }
+
+    /// 列出所有已登录的会话，当前会话在最前
+    pub fn sessions(&self) -> DurianResult<Vec<SessionInfo>> {
+        let sessions = self.inner.read().map_err(|_| DurianError::StateLockError)?;
+        Ok(sessions
+            .iter()
+            .enumerate()
+            .map(|(index, session)| SessionInfo {
+                id: session.session_id.clone(),
+                username: session.username.clone(),
+                api_base_url: session.api_base_url.clone(),
+                locked: session.is_locked(),
+                active: index == 0,
+            })
+            .collect())
+    }
+
+    /// 登出当前会话
+    ///
+    /// 最近使用的其他会话成为当前会话；没有其他会话时回到未登录状态
+    pub fn logout(&self) {
+        let mut sessions = self.write_recovering();
+        if !sessions.is_empty() {
+            drop(sessions.remove(0));
+        }
+    }
+
+    /// 清除所有会话
+    ///
+    /// 即使锁已中毒也会清除并恢复锁，保证令牌和保险库密钥在登出时被清零
+    pub fn clear(&self) {
+        self.write_recovering().clear();
+    }
+
+    /// 获取写锁，锁已中毒时恢复
+    fn write_recovering(&self) -> RwLockWriteGuard<'_, Vec<DurianState>> {
+        self.inner.write().unwrap_or_else(|poisoned| {
+            self.inner.clear_poison();
+            poisoned.into_inner()
+        })
+    }
 
-/// 清除全局状态（用于测试或登出）
-pub fn clear_state() {
-    let mut guard = DURIAN_STATE.write().expect("写锁定失败");
-    *guard = None;
+    /// 按修改后的服务器配置重建相关会话的 API 客户端
+    ///
+    /// # Arguments
+    /// * `profile` - 修改后的服务器配置
+    pub fn reconfigure_http(&self, profile: &ServerProfile) -> DurianResult<()> {
+        let mut sessions = self.inner.write().map_err(|_| DurianError::StateLockError)?;
+        for session in sessions.iter_mut() {
+            if session.api_base_url.trim_end_matches('/') == profile.api_base_url {
+                let http = HttpClient::new(profile)?;
+                session.api = Arc::new(HttpApi::new(http, &session.api_base_url));
+            }
+        }
+        Ok(())
+    }
+
+    /// 按各会话的自动锁定设置锁定保险库（由自动锁定线程调用）
+    ///
+    /// 未登录、已锁定或状态锁不可用时不做任何事
+    ///
+    /// # Arguments
+    /// * `suspended` - 系统是否刚从休眠中恢复
+    pub fn auto_lock(&self, suspended: bool) {
+        let Ok(mut sessions) = self.inner.write() else {
+            return;
+        };
+        for session in sessions.iter_mut() {
+            if session.should_auto_lock(suspended) {
+                session.lock();
+            }
+        }
+    }
+
+    /// 获取当前用户名
+    pub fn username(&self) -> DurianResult<String> {
+        Ok(self.get()?.username.clone())
+    }
+
+    /// 获取当前会话标识
+    pub fn session_id(&self) -> DurianResult<String> {
+        Ok(self.get()?.session_id.clone())
+    }
+
+    /// 获取当前认证令牌
+    pub fn token(&self) -> DurianResult<SecretString> {
+        Ok(self.get()?.token.clone())
+    }
+
+    /// 获取 API 基础 URL
+    pub fn api_base_url(&self) -> DurianResult<String> {
+        Ok(self.get()?.api_base_url.clone())
+    }
+
+    /// 获取当前会话的服务器连接信息
+    pub fn connection(&self) -> DurianResult<Connection> {
+        Ok(self.get()?.connection())
+    }
 }
 
 // ============================================
 // 状态守卫类型
 // ============================================
 
-/// 只读状态守卫，提供对 DurianState 的安全读取访问
+/// 只读状态守卫，提供对某个会话（默认为当前会话）的安全读取访问
 pub struct StateReadGuard<'a> {
-    guard: RwLockReadGuard<'a, Option<DurianState>>,
+    guard: RwLockReadGuard<'a, Vec<DurianState>>,
+    index: usize,
 }
 
 impl<'a> std::ops::Deref for StateReadGuard<'a> {
     type Target = DurianState;
     
     fn deref(&self) -> &Self::Target {
-        // 安全：我们在 get_state() 中已经检查了 is_some()
-        self.guard.as_ref().unwrap()
+        // 安全：创建守卫时已经检查了会话存在
+        &self.guard[self.index]
     }
 }
 
-/// 可写状态守卫，提供对 DurianState 的安全写入访问
-#[allow(dead_code)]
+/// 可写状态守卫，提供对某个会话（默认为当前会话）的安全写入访问
 pub struct StateWriteGuard<'a> {
-    guard: RwLockWriteGuard<'a, Option<DurianState>>,
+    guard: RwLockWriteGuard<'a, Vec<DurianState>>,
+    index: usize,
 }
 
 impl<'a> std::ops::Deref for StateWriteGuard<'a> {
     type Target = DurianState;
     
     fn deref(&self) -> &Self::Target {
-        self.guard.as_ref().unwrap()
+        &self.guard[self.index]
     }
 }
 
 impl<'a> std::ops::DerefMut for StateWriteGuard<'a> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        self.guard.as_mut().unwrap()
+        &mut self.guard[self.index]
     }
 }
 
-// ============================================
-// 便捷访问函数
-// ============================================
-
-/// 获取当前用户名
-pub fn get_username() -> DurianResult<String> {
-    let state = get_state()?;
-    Ok(state.username.clone())
-}
-
-/// 获取当前认证令牌
-pub fn get_token() -> DurianResult<String> {
-    let state = get_state()?;
-    Ok(state.token.clone())
-}
-
-/// 获取核心密码
-pub fn get_core_password() -> DurianResult<String> {
-    let state = get_state()?;
-    Ok(state.core_password.clone())
-}
-
-/// 获取 API 基础 URL
-pub fn get_api_base_url() -> DurianResult<String> {
-    let state = get_state()?;
-    Ok(state.api_base_url.clone())
-}
-
 // ============================================
 // 单元测试
 // ============================================
@@ -261,16 +1005,242 @@ pub fn get_api_base_url() -> DurianResult<String> {
 mod tests {
     use super::*;
 
+    fn test_state(file: &tempfile::NamedTempFile) -> DurianState {
+        test_session(file, "test_user")
+    }
+
+    fn test_session(file: &tempfile::NamedTempFile, username: &str) -> DurianState {
+        test_session_on(file, username, "http://localhost")
+    }
+
+    fn test_session_on(
+        file: &tempfile::NamedTempFile,
+        username: &str,
+        api_base_url: &str,
+    ) -> DurianState {
+        database::init_database(file.path()).unwrap();
+        let username = username.to_string();
+        let vault_key =
+            VaultKey::derive("core_password", &KdfParams::legacy_vault(&username)).unwrap();
+        DurianState::with_vault_key(
+            username,
+            vault_key,
+            SecretString::from("token"),
+            file.path().to_path_buf(),
+            api_base_url.to_string(),
+            Arc::new(HttpApi::new(
+                HttpClient::new(&ServerProfile::unsaved(api_base_url)).unwrap(),
+                api_base_url,
+            )),
+        )
+        .unwrap()
+    }
+
     #[test]
     fn test_state_not_initialized() {
-        clear_state();
-        let result = get_state();
-        assert!(result.is_err());
+        let app_state = AppState::new();
+        assert!(matches!(app_state.get(), Err(DurianError::StateNotInitialized)));
+        assert!(matches!(app_state.get_mut(), Err(DurianError::StateNotInitialized)));
     }
 
     #[test]
     fn test_is_state_initialized() {
-        clear_state();
-        assert!(!is_state_initialized());
+        let app_state = AppState::new();
+        assert!(!app_state.is_initialized());
+        app_state.clear();
+        assert!(!app_state.is_initialized());
+    }
+
+    #[test]
+    fn test_independent_instances() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let app_state = AppState::new();
+        let other = AppState::new();
+        let shared = app_state.clone();
+
+        app_state.set(test_state(&file)).unwrap();
+        assert!(app_state.is_initialized());
+        assert!(shared.is_initialized());
+        assert!(!other.is_initialized());
+        assert_eq!(shared.username().unwrap(), "test_user");
+        assert_eq!(shared.token().unwrap().expose(), "token");
+
+        shared.clear();
+        assert!(!app_state.is_initialized());
+    }
+
+    #[test]
+    fn test_poisoned_lock() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let app_state = AppState::new();
+        app_state.set(test_state(&file)).unwrap();
+
+        let poisoner = app_state.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.get_mut().unwrap();
+            panic!("poison the state lock");
+        })
+        .join();
+
+        assert!(matches!(app_state.get(), Err(DurianError::StateLockError)));
+        assert!(matches!(
+            app_state.set(test_state(&file)),
+            Err(DurianError::StateLockError)
+        ));
+
+        // 登出可以恢复中毒的锁
+        app_state.clear();
+        assert!(!app_state.is_initialized());
+        app_state.set(test_state(&file)).unwrap();
+        assert!(app_state.get().is_ok());
+    }
+
+    #[test]
+    fn test_lock_and_unlock() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut state = test_state(&file);
+        assert!(state.vault_key().is_ok());
+        assert!(!state.should_auto_lock(false));
+        assert!(state.should_auto_lock(true));
+
+        state.lock();
+        assert!(state.is_locked());
+        assert!(matches!(state.vault_key(), Err(DurianError::Locked)));
+        assert!(matches!(state.load_cache_data(), Err(DurianError::Locked)));
+        assert!(!state.should_auto_lock(true));
+        assert_eq!(state.token.expose(), "token");
+
+        assert!(state.unlock("wrong_password").is_err());
+        assert!(state.is_locked());
+
+        state.unlock("core_password").unwrap();
+        assert!(state.vault_key().is_ok());
+
+        // 在会话之外派生密钥，再装入会话
+        state.lock();
+        let context = state.unlock_context();
+        assert!(context.derive_keys("wrong_password").is_err());
+        state.install_keys(context.derive_keys("core_password").unwrap());
+        assert!(state.vault_key().is_ok());
+
+        // 空闲超时
+        state
+            .set_lock_settings(LockSettings {
+                idle_timeout_secs: 0,
+                lock_on_suspend: false,
+            })
+            .unwrap();
+        assert!(!state.should_auto_lock(true));
+        *state.last_activity.lock().unwrap() -= std::time::Duration::from_secs(10);
+        state.lock_settings.idle_timeout_secs = 5;
+        assert!(state.should_auto_lock(false));
+    }
+
+    #[test]
+    fn test_multiple_sessions() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let app_state = AppState::new();
+        app_state.set(test_session(&file, "personal")).unwrap();
+        app_state.set(test_session(&file, "work")).unwrap();
+        assert_eq!(app_state.username().unwrap(), "work");
+
+        // 锁定状态按会话独立
+        let personal = session_id("http://localhost", "personal");
+        let work = session_id("http://localhost", "work");
+        app_state.get_mut().unwrap().lock();
+        app_state.switch_session(&personal).unwrap();
+        assert_eq!(app_state.username().unwrap(), "personal");
+        assert!(app_state.get().unwrap().vault_key().is_ok());
+
+        let sessions = app_state.sessions().unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].username, "personal");
+        assert!(sessions[0].active && !sessions[0].locked);
+        assert_eq!(sessions[1].username, "work");
+        assert!(!sessions[1].active && sessions[1].locked);
+
+        assert!(matches!(
+            app_state.switch_session("nobody"),
+            Err(DurianError::SessionNotFound(_))
+        ));
+
+        // 按会话标识访问非当前会话
+        assert!(app_state.session(&work).unwrap().is_locked());
+        app_state.session_mut(&work).unwrap().unlock("core_password").unwrap();
+        assert_eq!(app_state.username().unwrap(), "personal");
+        assert!(app_state.session("nobody").is_err());
+
+        // 重新登录同一用户替换旧会话
+        app_state.set(test_session(&file, "work")).unwrap();
+        assert_eq!(app_state.sessions().unwrap().len(), 2);
+        assert!(!app_state.get().unwrap().is_locked());
+
+        // 登出当前会话后回到最近使用的会话
+        app_state.logout();
+        assert_eq!(app_state.username().unwrap(), "personal");
+        app_state.logout();
+        assert!(!app_state.is_initialized());
+    }
+
+    #[test]
+    fn test_same_username_on_different_servers() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let app_state = AppState::new();
+        app_state.set(test_session_on(&file, "alice", "https://a.example")).unwrap();
+        app_state.set(test_session_on(&file, "alice", "https://b.example/")).unwrap();
+
+        // 两个会话同时存在，按会话标识区分
+        let sessions = app_state.sessions().unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_ne!(sessions[0].id, sessions[1].id);
+        let first = session_id("https://a.example", "alice");
+        app_state.switch_session(&first).unwrap();
+        assert_eq!(app_state.api_base_url().unwrap(), "https://a.example");
+
+        // 本地缓存和设置互不影响
+        let record = AccountRecord::new(
+            1,
+            "alice".to_string(),
+            "a.example".to_string(),
+            "alice".to_string(),
+            "secret".to_string(),
+        );
+        app_state
+            .get()
+            .unwrap()
+            .save_cache_data(&CacheData::new("alice".to_string(), 100, vec![record]), PullMode::PullAll)
+            .unwrap();
+        app_state
+            .get_mut()
+            .unwrap()
+            .set_sync_settings(SyncSettings { interval_secs: 0 })
+            .unwrap();
+
+        let second = session_id("https://b.example", "alice");
+        assert!(app_state.session(&second).unwrap().load_cache_data().unwrap().is_none());
+        let settings = database::get_sync_settings(file.path(), &second).unwrap();
+        assert_ne!(settings.interval_secs, 0);
+
+        // 返回给前端的记录使用用户名
+        let data = app_state.get().unwrap().load_cache_data().unwrap().unwrap();
+        assert_eq!(data.username, "alice");
+        assert_eq!(data.accounts[0].username, "alice");
+    }
+
+    #[test]
+    fn test_legacy_rows_adopted_by_first_session() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        database::init_database(file.path()).unwrap();
+        database::set_kdf_upgraded(file.path(), "alice").unwrap();
+
+        let id = session_id("https://a.example", "alice");
+        database::adopt_user_rows(file.path(), "alice", &id).unwrap();
+        assert!(database::is_kdf_upgraded(file.path(), &id).unwrap());
+        assert!(!database::is_kdf_upgraded(file.path(), "alice").unwrap());
+
+        // 已有数据的会话不再接收旧数据
+        database::set_kdf_upgraded(file.path(), "alice").unwrap();
+        database::adopt_user_rows(file.path(), "alice", &id).unwrap();
+        assert!(database::is_kdf_upgraded(file.path(), "alice").unwrap());
     }
 }
@@ -0,0 +1,221 @@
+//! 离线写入队列模块
+//!
+//! 插入、修改和删除账户时先将操作写入本地 `pending_ops` 表并乐观地应用到缓存，
+//! 再按加入队列的顺序发送到服务器。服务器不可达时写入不会失败，
+//! 联网后（下次写入、同步或前端调用 `sync_pending_ops` 时）继续回放
+//!
+//! # 回放规则
+//! - 发送成功：移出队列；插入操作的临时记录 ID 替换为服务器分配的 ID。
+//!   插入携带操作 ID，未收到响应而重新发送时服务器返回原记录 ID，不会重复插入
+//! - 删除的记录在服务器上已不存在（已被其他设备删除）：视为成功，移出队列
+//! - 网络错误、请求被取消或令牌失效：保留操作并记录错误，停止本次回放，
+//!   其后的操作保持顺序继续等待
+//! - 服务器拒绝：标记为失败不再重试（见 [`crate::database::reject_pending_op`]），
+//!   继续回放其后的操作
+//! - 修改冲突（记录已被其他设备修改，修订号不一致）：同时保存本地和服务器版本，
+//!   等待用户处理（见 [`crate::database::record_conflict`]），继续回放其后的操作
+//!
+//! 同一会话的回放由 [`Connection::write_queue`] 串行化，避免同一操作被发送两次。
+//! 写入命令在持有该锁时加密并加入队列，修改核心密码期间同样持有该锁（见 [`crate::rekey`]），
+//! 因此不会有写入使用即将失效的密钥加密，或在重新加密期间被发送
+//!
+//! # 拉取更新
+//! [`pull_accounts`] 先回放队列，全部发送后再从服务器拉取更新写入缓存，
+//! 供 `query_accounts` 命令和后台同步（见 [`crate::sync_worker`]）共用。
+//! 拉取期间同样持有 [`Connection::write_queue`]，写入不会被拉取结果覆盖
+
+use crate::crypto::decrypt_account_field;
+use crate::error::{DurianError, DurianResult, CODE_CONFLICT, CODE_NOT_FOUND, CODE_UNAUTHORIZED};
+use crate::i18n::{tr, Message};
+use crate::models::{
+    AccountItem, AccountRecord, ApiResponse, CacheData, FlushReport, PendingOp, PendingOpKind,
+    SyncChanges,
+};
+use crate::state::{AppState, Connection, DurianState};
+
+/// 按顺序回放离线写入队列
+///
+/// 只在读写本地队列时短暂持有状态锁，等待服务器响应期间不阻塞其他命令
+///
+/// # Arguments
+/// * `app_state` - 应用状态
+/// * `connection` - 会话的服务器连接信息
+///
+/// # Returns
+/// 本次回放的结果；网络错误不会作为错误返回，而是计入 `pending`
+pub async fn flush_pending_ops(
+    app_state: &AppState,
+    connection: &Connection,
+) -> DurianResult<FlushReport> {
+    let _guard = connection.write_queue.lock().await;
+    flush_pending_ops_locked(app_state, connection).await
+}
+
+/// 按顺序回放离线写入队列，调用方需已持有 [`Connection::write_queue`]
+pub(crate) async fn flush_pending_ops_locked(
+    app_state: &AppState,
+    connection: &Connection,
+) -> DurianResult<FlushReport> {
+    let session_id = connection.session_id.as_str();
+    let mut report = FlushReport::default();
+
+    loop {
+        // 状态锁不能跨越 await 持有，取出操作后立即释放
+        let next = app_state.session(session_id)?.next_pending_op()?;
+        let Some(op) = next else {
+            break;
+        };
+
+        match send(connection, &op).await {
+            Ok(response) if response.is_success() => {
+                let server_value = response.data.as_ref().and_then(|data| data.as_i64());
+                app_state
+                    .session(session_id)?
+                    .complete_pending_op(&op, server_value)?;
+                report.synced += 1;
+            }
+            // 记录已被其他设备删除，删除的目的已经达到
+            Ok(response) if response.code == CODE_NOT_FOUND && op.kind == PendingOpKind::Delete => {
+                app_state
+                    .session(session_id)?
+                    .complete_pending_op(&op, None)?;
+                report.synced += 1;
+            }
+            Ok(response) if response.code == CODE_CONFLICT && op.kind == PendingOpKind::Update => {
+                let server = response
+                    .data
+                    .and_then(|data| serde_json::from_value::<AccountItem>(data).ok());
+                let state = app_state.session(session_id)?;
+                match server {
+                    Some(server) => {
+                        let server = open_server_item(&state, &server)?;
+                        if state.record_conflict(&op, &server)? {
+                            report.conflicts += 1;
+                        }
+                    }
+                    None => {
+                        state.reject_pending_op(&op, &response.msg)?;
+                        report.rejected += 1;
+                    }
+                }
+            }
+            Ok(response) if response.code == CODE_UNAUTHORIZED => {
+                app_state
+                    .session(session_id)?
+                    .record_pending_op_error(op.id, &response.msg)?;
+                break;
+            }
+            Ok(response) => {
+                app_state
+                    .session(session_id)?
+                    .reject_pending_op(&op, &response.msg)?;
+                report.rejected += 1;
+            }
+            Err(e) => {
+                app_state
+                    .session(session_id)?
+                    .record_pending_op_error(op.id, &e.to_string())?;
+                break;
+            }
+        }
+    }
+
+    report.pending = app_state.session(session_id)?.sync_status()?.pending;
+    Ok(report)
+}
+
+/// 回放离线写入队列后从服务器拉取更新，写入本地缓存
+///
+/// 回放和拉取期间持有 [`Connection::write_queue`]：拉取期间的写入等待拉取完成后再加入队列，
+/// 其乐观修改不会被拉取前的服务器数据覆盖
+///
+/// # Arguments
+/// * `app_state` - 应用状态
+/// * `connection` - 会话的服务器连接信息
+///
+/// # Returns
+/// 本地缓存的变化；仍有写入未能发送（离线）时不拉取，返回 `None`，
+/// 避免服务器数据覆盖乐观修改
+pub async fn pull_accounts(
+    app_state: &AppState,
+    connection: &Connection,
+) -> DurianResult<Option<SyncChanges>> {
+    let session_id = connection.session_id.as_str();
+    let _guard = connection.write_queue.lock().await;
+    let report = flush_pending_ops_locked(app_state, connection).await?;
+    if report.pending > 0 {
+        return Ok(None);
+    }
+
+    // 获取最后更新时间（回放中被拒绝的操作会将其重置为 0，缺少修订号的缓存全量拉取）
+    let last_update_time = app_state.session(session_id)?.sync_cursor()?;
+    let response = connection
+        .api
+        .query_accounts(connection.token.expose(), last_update_time)
+        .await?;
+    let data = match response.data {
+        Some(data) if response.code == 0 => data,
+        _ => {
+            return Err(DurianError::ApiError {
+                code: response.code,
+                message: tr(Message::QueryFailed(&response.msg)),
+                status: response.status,
+            })
+        }
+    };
+
+    // 网站和账户解密后写入本地索引，同时删除其他设备已删除的记录
+    let state = app_state.session(session_id)?;
+    let accounts = data
+        .accounts
+        .iter()
+        .map(|item| open_server_item(&state, item))
+        .collect::<DurianResult<Vec<_>>>()?;
+    let cache_data = CacheData::new(state.username.clone(), data.update_time, accounts);
+    let changes = state.apply_sync(&cache_data, &data.deleted_rids, data.pull_mode)?;
+    Ok(Some(changes))
+}
+
+/// 将服务器记录转换为本地记录，解密其中加密的网站和账户字段
+pub(crate) fn open_server_item(
+    state: &DurianState,
+    item: &AccountItem,
+) -> DurianResult<AccountRecord> {
+    let vault_key = state.sync_vault_key()?;
+    let mut record = AccountRecord::from_item(item, &state.username);
+    record.website = decrypt_account_field(&item.website, item.fields_encrypted, vault_key)?;
+    record.account = decrypt_account_field(&item.account, item.fields_encrypted, vault_key)?;
+    Ok(record)
+}
+
+/// 将一个写入操作发送到服务器
+///
+/// 使用 [`Connection::background`] 发送，前端取消请求时回放不会中断
+async fn send(
+    connection: &Connection,
+    op: &PendingOp,
+) -> DurianResult<ApiResponse<serde_json::Value>> {
+    let token = connection.token.expose();
+    let item = &op.item;
+
+    // 临时记录 ID 说明其插入操作未能同步，服务器上不存在该记录
+    if op.kind != PendingOpKind::Insert && item.rid < 0 {
+        return Ok(ApiResponse {
+            code: 1,
+            msg: tr(Message::RecordNotSynced),
+            data: None,
+            status: None,
+        });
+    }
+
+    match op.kind {
+        PendingOpKind::Insert => {
+            connection
+                .background
+                .insert_account(token, item, Some(&op.op_id))
+                .await
+        }
+        PendingOpKind::Update => connection.background.update_account(token, item).await,
+        PendingOpKind::Delete => connection.background.delete_account(token, item.rid).await,
+    }
+}
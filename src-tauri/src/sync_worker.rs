@@ -0,0 +1,400 @@
+//! 后台定期同步模块
+//!
+//! 会话初始化（登录或 `init_state`）后启动后台任务，按设定的间隔回放离线写入并拉取服务器更新
+//! （见 [`crate::sync::pull_accounts`]），通过 Tauri 事件通知前端：
+//! - `sync-started`：开始同步，载荷为 `{ username }`
+//! - `sync-finished`：同步完成，载荷为 `{ username, added, updated, deleted }`
+//! - `sync-failed`：同步失败，载荷为 `{ username, error }`，`error` 为结构化错误
+//!   （见 [`ErrorPayload`]）；仍有离线写入未能发送（服务器不可达）时同样视为失败
+//!
+//! # 间隔与退避
+//! 两次同步之间等待设定的间隔，并随机缩短至多五分之一，避免多个客户端同时请求；
+//! 连续失败时等待时间逐次加倍（不超过 [`MAX_BACKOFF`] 或设定的间隔中较长者），成功后恢复
+//!
+//! # 停止
+//! 任务由会话持有的 [`SyncWorker`] 控制，释放时停止：锁定保险库、登出或会话被替换时随之停止，
+//! 解锁后重新启动。停止时进行中的同步会完成，但不再发送事件
+//!
+//! 后台同步不算作用户活动：同步读写记录时不刷新会话的空闲计时（见 [`crate::state::DurianState::sync_vault_key`]），
+//! 不会推迟自动锁定
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Runtime};
+use tokio_util::sync::CancellationToken;
+use ts_rs::TS;
+
+use crate::crypto::random_bytes;
+use crate::error::{DurianError, DurianResult, ErrorPayload};
+use crate::i18n::{tr, Field, Message};
+use crate::models::SyncChanges;
+use crate::state::AppState;
+use crate::sync;
+
+// ============================================
+// 配置常量
+// ============================================
+
+/// 默认同步间隔（秒）
+pub const DEFAULT_SYNC_INTERVAL_SECS: u64 = 300;
+
+/// 同步间隔下限（秒）
+pub const MIN_SYNC_INTERVAL_SECS: u64 = 30;
+
+/// 同步间隔上限（秒）
+pub const MAX_SYNC_INTERVAL_SECS: u64 = 24 * 60 * 60;
+
+/// 连续失败时的最长等待时间
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// 开始同步的事件
+pub const EVENT_SYNC_STARTED: &str = "sync-started";
+
+/// 同步完成的事件
+pub const EVENT_SYNC_FINISHED: &str = "sync-finished";
+
+/// 同步失败的事件
+pub const EVENT_SYNC_FAILED: &str = "sync-failed";
+
+// ============================================
+// 同步设置
+// ============================================
+
+/// 后台同步设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SyncSettings {
+    /// 同步间隔（秒），0 表示不在后台同步
+    #[ts(type = "number")]
+    pub interval_secs: u64,
+}
+
+impl Default for SyncSettings {
+    fn default() -> Self {
+        Self {
+            interval_secs: DEFAULT_SYNC_INTERVAL_SECS,
+        }
+    }
+}
+
+impl SyncSettings {
+    /// 同步间隔，不在后台同步时为 `None`
+    pub fn interval(&self) -> Option<Duration> {
+        (self.interval_secs > 0).then(|| Duration::from_secs(self.interval_secs))
+    }
+
+    /// 校验设置是否有效
+    pub fn validate(&self) -> DurianResult<()> {
+        if self.interval_secs == 0 {
+            return Ok(());
+        }
+        if !(MIN_SYNC_INTERVAL_SECS..=MAX_SYNC_INTERVAL_SECS).contains(&self.interval_secs) {
+            return Err(DurianError::validation(tr(Message::SecondsOutOfRange(
+                Field::SyncInterval,
+                MIN_SYNC_INTERVAL_SECS,
+                MAX_SYNC_INTERVAL_SECS,
+            ))));
+        }
+        Ok(())
+    }
+}
+
+/// 连续失败 `failures` 次后到下次同步的等待时间
+///
+/// `jitter` 为 0 到 255 的随机数，等待时间随之缩短至多五分之一
+fn next_delay(interval: Duration, failures: u32, jitter: u8) -> Duration {
+    let delay = interval
+        .saturating_mul(2u32.saturating_pow(failures))
+        .min(MAX_BACKOFF.max(interval));
+    delay - delay / 5 * u32::from(jitter) / 255
+}
+
+// ============================================
+// 事件
+// ============================================
+
+/// 同步事件的接收方（应用中为 Tauri 的 [`AppHandle`]）
+pub trait SyncEvents: Send + Sync + 'static {
+    /// 发送事件
+    fn emit<S: Serialize + Clone>(&self, event: &str, payload: S);
+}
+
+impl<R: Runtime> SyncEvents for AppHandle<R> {
+    fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        // 前端未监听或窗口已关闭时忽略
+        let _ = Emitter::emit(self, event, payload);
+    }
+}
+
+/// `sync-started` 和 `sync-failed` 事件的载荷
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SyncEventPayload {
+    pub username: String,
+    /// 失败原因（只在 `sync-failed` 中出现）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub error: Option<ErrorPayload>,
+}
+
+/// `sync-finished` 事件的载荷
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SyncFinishedPayload {
+    pub username: String,
+    #[serde(flatten)]
+    pub changes: SyncChanges,
+}
+
+// ============================================
+// 后台任务
+// ============================================
+
+/// 后台同步任务的句柄，释放时停止任务
+#[derive(Debug)]
+pub struct SyncWorker {
+    cancel: CancellationToken,
+}
+
+impl Drop for SyncWorker {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+/// 按设置启动会话的后台同步任务
+///
+/// # Arguments
+/// * `app_state` - 应用状态（与 Tauri 托管的实例共享）
+/// * `session_id` - 会话标识
+/// * `username` - 会话的用户名（事件载荷）
+/// * `settings` - 会话的后台同步设置
+/// * `events` - 事件接收方
+///
+/// # Returns
+/// 任务句柄，设置为不在后台同步时返回 `None`
+pub fn spawn<E: SyncEvents>(
+    app_state: AppState,
+    session_id: String,
+    username: String,
+    settings: SyncSettings,
+    events: E,
+) -> Option<SyncWorker> {
+    settings
+        .interval()
+        .map(|interval| spawn_with_interval(app_state, session_id, username, interval, events))
+}
+
+/// 以指定间隔启动后台同步任务
+fn spawn_with_interval<E: SyncEvents>(
+    app_state: AppState,
+    session_id: String,
+    username: String,
+    interval: Duration,
+    events: E,
+) -> SyncWorker {
+    let cancel = CancellationToken::new();
+    let token = cancel.clone();
+
+    tauri::async_runtime::spawn(async move {
+        let mut failures = 0u32;
+        loop {
+            let jitter = random_bytes::<1>().map(|b| b[0]).unwrap_or(0);
+            tokio::select! {
+                _ = token.cancelled() => break,
+                _ = tokio::time::sleep(next_delay(interval, failures, jitter)) => {}
+            }
+
+            let result = sync_once(&app_state, &session_id, &username, &events).await;
+            if token.is_cancelled() {
+                break;
+            }
+            match result {
+                Ok(changes) => {
+                    failures = 0;
+                    let username = username.clone();
+                    events.emit(
+                        EVENT_SYNC_FINISHED,
+                        SyncFinishedPayload { username, changes },
+                    );
+                }
+                // 会话已登出
+                Err(DurianError::SessionNotFound(_)) => break,
+                Err(e) => {
+                    failures = failures.saturating_add(1);
+                    let payload = SyncEventPayload {
+                        username: username.clone(),
+                        error: Some(e.to_payload()),
+                    };
+                    events.emit(EVENT_SYNC_FAILED, payload);
+                }
+            }
+        }
+    });
+
+    SyncWorker { cancel }
+}
+
+/// 执行一次同步
+async fn sync_once<E: SyncEvents>(
+    app_state: &AppState,
+    session_id: &str,
+    username: &str,
+    events: &E,
+) -> DurianResult<SyncChanges> {
+    let connection = app_state.session(session_id)?.connection().detached();
+
+    let payload = SyncEventPayload {
+        username: username.to_string(),
+        error: None,
+    };
+    events.emit(EVENT_SYNC_STARTED, payload);
+    sync::pull_accounts(app_state, &connection)
+        .await?
+        .ok_or_else(|| DurianError::network(tr(Message::ServerUnreachable)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    use crate::api_client::ApiClient;
+    use crate::crypto::VaultKey;
+    use crate::database;
+    use crate::fake_api::FakeServer;
+    use crate::kdf::KdfSuite;
+    use crate::secret::SecretString;
+    use crate::state::DurianState;
+
+    /// 记录收到的事件
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<(String, serde_json::Value)>>>);
+
+    impl SyncEvents for Recorder {
+        fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) {
+            let payload = serde_json::to_value(payload).unwrap();
+            self.0.lock().unwrap().push((event.to_string(), payload));
+        }
+    }
+
+    impl Recorder {
+        fn events(&self) -> Vec<(String, serde_json::Value)> {
+            self.0.lock().unwrap().clone()
+        }
+
+        /// 等待收到指定的事件
+        async fn wait_for(&self, event: &str) {
+            for _ in 0..200 {
+                if self.events().iter().any(|(name, _)| name == event) {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            panic!("未收到事件 {}: {:?}", event, self.events());
+        }
+    }
+
+    #[test]
+    fn test_sync_settings() {
+        let settings = SyncSettings::default();
+        assert_eq!(settings.interval(), Some(Duration::from_secs(300)));
+        assert!(settings.validate().is_ok());
+
+        let disabled = SyncSettings { interval_secs: 0 };
+        assert_eq!(disabled.interval(), None);
+        assert!(disabled.validate().is_ok());
+
+        assert!(SyncSettings { interval_secs: 1 }.validate().is_err());
+        let too_long = SyncSettings {
+            interval_secs: MAX_SYNC_INTERVAL_SECS + 1,
+        };
+        assert!(too_long.validate().is_err());
+    }
+
+    #[test]
+    fn test_next_delay() {
+        let interval = Duration::from_secs(300);
+        assert_eq!(next_delay(interval, 0, 0), interval);
+        // 随机缩短至多五分之一
+        assert_eq!(next_delay(interval, 0, 255), Duration::from_secs(240));
+        // 连续失败时加倍，不超过上限
+        assert_eq!(next_delay(interval, 2, 0), Duration::from_secs(1200));
+        assert_eq!(next_delay(interval, 10, 0), MAX_BACKOFF);
+        assert_eq!(next_delay(interval, u32::MAX, 0), MAX_BACKOFF);
+        // 间隔本身超过上限时按间隔等待
+        let daily = Duration::from_secs(MAX_SYNC_INTERVAL_SECS);
+        assert_eq!(next_delay(daily, 3, 0), daily);
+    }
+
+    #[test]
+    fn test_worker_emits_events_and_stops_on_lock() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        database::init_database(file.path()).unwrap();
+        let server = Arc::new(FakeServer::new());
+        let app_state = AppState::new();
+
+        tauri::async_runtime::block_on(async {
+            let kdf = KdfSuite::generate().unwrap();
+            server
+                .register("test_user", "password", "core_password", &kdf)
+                .await
+                .unwrap();
+            let durian_state = DurianState::with_vault_key(
+                "test_user".to_string(),
+                VaultKey::derive("core_password", kdf.vault.as_ref().unwrap()).unwrap(),
+                SecretString::from(server.issue_token("test_user").unwrap()),
+                file.path().to_path_buf(),
+                "http://fake".to_string(),
+                server.clone(),
+            )
+            .unwrap();
+            app_state.set(durian_state).unwrap();
+
+            let recorder = Recorder::default();
+            let session_id = app_state.session_id().unwrap();
+            let worker = spawn_with_interval(
+                app_state.clone(),
+                session_id.clone(),
+                "test_user".to_string(),
+                Duration::from_millis(20),
+                recorder.clone(),
+            );
+            app_state
+                .session_mut(&session_id)
+                .unwrap()
+                .set_sync_worker(Some(worker));
+            let idle_since = app_state.session(&session_id).unwrap().last_activity();
+
+            recorder.wait_for(EVENT_SYNC_FINISHED).await;
+            // 后台同步不刷新空闲计时
+            let state = app_state.session(&session_id).unwrap();
+            assert_eq!(state.last_activity(), idle_since);
+            drop(state);
+            let events = recorder.events();
+            assert_eq!(events[0].0, EVENT_SYNC_STARTED);
+            assert_eq!(events[1].0, EVENT_SYNC_FINISHED);
+            assert_eq!(events[1].1["username"], "test_user");
+            assert_eq!(events[1].1["added"], 0);
+
+            // 服务器不可达时发送失败事件
+            server.set_offline(true);
+            recorder.wait_for(EVENT_SYNC_FAILED).await;
+            let (_, failed) = recorder
+                .events()
+                .into_iter()
+                .find(|(name, _)| name == EVENT_SYNC_FAILED)
+                .unwrap();
+            assert_eq!(failed["error"]["kind"], "network");
+            assert_eq!(failed["error"]["retryable"], true);
+
+            // 锁定后停止，不再发送事件
+            app_state.session_mut(&session_id).unwrap().lock();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let count = recorder.events().len();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert_eq!(recorder.events().len(), count);
+        });
+    }
+}
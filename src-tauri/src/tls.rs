@@ -0,0 +1,406 @@
+//! TLS 策略模块
+//!
+//! 按服务器配置构建 rustls 客户端配置
+//!
+//! # 校验规则
+//! 1. 配置了公钥指纹时，只接受 SPKI 的 SHA-256 与其中之一匹配的证书（适用于自签名部署），
+//!    不匹配时返回 [`DurianError::CertificatePinMismatch`]
+//! 2. 否则使用内置根证书（以及可选的自定义 CA）完整校验证书链和主机名
+//! 3. 启用首次信任（TOFU）且证书链校验失败时返回 [`DurianError::UntrustedCertificate`]，
+//!    由前端展示指纹并询问用户是否信任；用户确认后指纹保存到服务器配置中
+//!
+//! 握手签名始终由 rustls 校验，因此固定的公钥必须与服务器持有的私钥对应
+
+use std::sync::{Arc, Mutex};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use ring::digest;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+
+use crate::error::{DurianError, DurianResult};
+use crate::i18n::{tr, Message};
+use crate::settings::TlsOptions;
+
+// ============================================
+// 校验失败记录
+// ============================================
+
+/// 证书校验失败的原因
+///
+/// rustls 的错误经过 reqwest 包装后无法还原，因此连接失败时发送请求的一方
+/// 使用带 [`TlsFailureSlot`] 的独立客户端重新握手，由校验器记录失败原因，
+/// 再取出并转换为对应的 `DurianError`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TlsFailure {
+    /// 公钥指纹与固定的指纹不匹配
+    PinMismatch { fingerprint: String },
+    /// 证书链不受信任（首次信任模式）
+    Untrusted { fingerprint: String },
+}
+
+impl From<TlsFailure> for DurianError {
+    fn from(failure: TlsFailure) -> Self {
+        match failure {
+            TlsFailure::PinMismatch { fingerprint } => {
+                DurianError::CertificatePinMismatch { fingerprint }
+            }
+            TlsFailure::Untrusted { fingerprint } => {
+                DurianError::UntrustedCertificate { fingerprint }
+            }
+        }
+    }
+}
+
+/// 证书校验失败的记录（每次诊断使用各自的记录）
+pub type TlsFailureSlot = Arc<Mutex<Option<TlsFailure>>>;
+
+// ============================================
+// 公钥指纹
+// ============================================
+
+/// 计算证书公钥（SPKI）的 SHA-256 指纹，Base64 编码
+pub fn spki_fingerprint(cert: &CertificateDer<'_>) -> DurianResult<String> {
+    let cert = webpki::EndEntityCert::try_from(cert)
+        .map_err(|e| DurianError::crypto(tr(Message::InvalidServerCertificate(&format!("{:?}", e)))))?;
+    let spki = cert.subject_public_key_info();
+    Ok(BASE64.encode(digest::digest(&digest::SHA256, spki.as_ref())))
+}
+
+/// 校验并规范化公钥指纹（32 字节 SHA-256 的 Base64 编码）
+pub fn normalize_fingerprint(value: &str) -> DurianResult<String> {
+    let value = value.trim();
+    match BASE64.decode(value) {
+        Ok(bytes) if bytes.len() == digest::SHA256_OUTPUT_LEN => Ok(value.to_string()),
+        _ => Err(DurianError::validation(tr(Message::InvalidFingerprint(value)))),
+    }
+}
+
+// ============================================
+// 证书校验器
+// ============================================
+
+/// 按 TLS 选项校验服务器证书
+#[derive(Debug)]
+struct PolicyVerifier {
+    /// 证书链校验器
+    webpki: Arc<WebPkiServerVerifier>,
+    /// 固定的公钥指纹
+    pins: Vec<String>,
+    /// 证书链校验失败时是否提示首次信任
+    trust_on_first_use: bool,
+    /// 校验失败记录
+    failure: Option<TlsFailureSlot>,
+}
+
+impl PolicyVerifier {
+    fn record(&self, failure: TlsFailure) {
+        if let Some(Ok(mut slot)) = self.failure.as_ref().map(|slot| slot.lock()) {
+            *slot = Some(failure);
+        }
+    }
+}
+
+impl ServerCertVerifier for PolicyVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if !self.pins.is_empty() {
+            let fingerprint = spki_fingerprint(end_entity).map_err(|_| {
+                rustls::Error::InvalidCertificate(rustls::CertificateError::BadEncoding)
+            })?;
+            if self.pins.contains(&fingerprint) {
+                return Ok(ServerCertVerified::assertion());
+            }
+            self.record(TlsFailure::PinMismatch { fingerprint });
+            return Err(rustls::Error::InvalidCertificate(
+                rustls::CertificateError::ApplicationVerificationFailure,
+            ));
+        }
+
+        let result = self.webpki.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        );
+        if result.is_err() && self.trust_on_first_use {
+            if let Ok(fingerprint) = spki_fingerprint(end_entity) {
+                self.record(TlsFailure::Untrusted { fingerprint });
+            }
+        }
+        result
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.webpki.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.webpki.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.webpki.supported_verify_schemes()
+    }
+}
+
+// ============================================
+// 客户端配置
+// ============================================
+
+/// 按 TLS 选项构建 rustls 客户端配置
+///
+/// # Arguments
+/// * `options` - 服务器的 TLS 选项
+/// * `failure` - 证书校验失败记录，`None` 表示不记录
+pub fn client_config(
+    options: &TlsOptions,
+    failure: Option<TlsFailureSlot>,
+) -> DurianResult<ClientConfig> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+    let mut roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    if let Some(path) = &options.ca_cert_path {
+        for cert in load_ca_certs(path)? {
+            roots
+                .add(cert)
+                .map_err(|e| DurianError::config(tr(Message::InvalidCaCertificate(&e.to_string()))))?;
+        }
+    }
+
+    let webpki = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+        .build()
+        .map_err(|e| DurianError::config(tr(Message::CertificateVerifierFailed(&e.to_string()))))?;
+
+    let verifier = PolicyVerifier {
+        webpki,
+        pins: options.pinned_spki_sha256.clone(),
+        trust_on_first_use: options.trust_on_first_use,
+        failure,
+    };
+
+    Ok(ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| DurianError::config(tr(Message::TlsConfigFailed(&e.to_string()))))?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth())
+}
+
+/// 读取 PEM 格式的 CA 证书文件
+fn load_ca_certs(path: &str) -> DurianResult<Vec<CertificateDer<'static>>> {
+    let pem = std::fs::read(path)
+        .map_err(|e| DurianError::config(tr(Message::CaFileUnreadable(path, &e.to_string()))))?;
+    let certs = CertificateDer::pem_slice_iter(&pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| DurianError::config(tr(Message::CaFileInvalid(path, &e.to_string()))))?;
+    if certs.is_empty() {
+        return Err(DurianError::config(tr(Message::CaFileEmpty(path))));
+    }
+    Ok(certs)
+}
+
+// ============================================
+// 单元测试
+// ============================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_client::{self, HttpClient};
+    use crate::settings::ServerProfile;
+    use rustls::pki_types::PrivateKeyDer;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    const CA_PEM: &[u8] = include_bytes!("../testdata/tls/ca.pem");
+    const SERVER_PEM: &[u8] = include_bytes!("../testdata/tls/server.pem");
+    const SERVER_KEY: &[u8] = include_bytes!("../testdata/tls/server.key");
+
+    /// 测试服务器证书的公钥指纹（由 openssl 计算）
+    const SERVER_FINGERPRINT: &str = "2dI73OFVn4drcOBKmONbsPfG3KVQo3TLPJ38goDY7wo=";
+
+    /// 启动本地 rustls 测试服务器，对所有请求返回 200，返回 API 基础 URL
+    fn start_server() -> String {
+        let cert = CertificateDer::from_pem_slice(SERVER_PEM).unwrap();
+        let key = PrivateKeyDer::from_pem_slice(SERVER_KEY).unwrap();
+        let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(vec![cert], key)
+        .unwrap();
+        let config = Arc::new(config);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("https://127.0.0.1:{}", listener.local_addr().unwrap().port());
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let connection = rustls::ServerConnection::new(config.clone()).unwrap();
+                let mut stream = rustls::StreamOwned::new(connection, stream);
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                // 握手失败（客户端拒绝证书）时读取出错，直接处理下一个连接
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match stream.read(&mut buf) {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let _ = stream.write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                );
+                stream.conn.send_close_notify();
+                let _ = stream.flush();
+            }
+        });
+        url
+    }
+
+    fn ca_file() -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(CA_PEM).unwrap();
+        file
+    }
+
+    fn profile(url: &str, tls: &TlsOptions) -> ServerProfile {
+        ServerProfile {
+            tls: tls.clone(),
+            ..ServerProfile::unsaved(url)
+        }
+    }
+
+    async fn verify(url: &str, tls: &TlsOptions) -> DurianResult<bool> {
+        let client = HttpClient::new(&profile(url, tls))?;
+        api_client::api_verify(&client, url, "token").await
+    }
+
+    #[test]
+    fn test_spki_fingerprint() {
+        let cert = CertificateDer::from_pem_slice(SERVER_PEM).unwrap();
+        assert_eq!(spki_fingerprint(&cert).unwrap(), SERVER_FINGERPRINT);
+
+        assert!(normalize_fingerprint(&format!(" {} ", SERVER_FINGERPRINT)).is_ok());
+        assert!(normalize_fingerprint("c2hvcnQ=").is_err());
+        assert!(normalize_fingerprint("not base64").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verification_enabled_by_default() {
+        let url = start_server();
+        let err = verify(&url, &TlsOptions::default()).await.unwrap_err();
+        assert!(matches!(err, DurianError::NetworkError(_)));
+    }
+
+    #[tokio::test]
+    async fn test_custom_ca() {
+        let url = start_server();
+        let ca = ca_file();
+        let tls = TlsOptions {
+            ca_cert_path: Some(ca.path().to_string_lossy().into_owned()),
+            ..TlsOptions::default()
+        };
+        assert!(verify(&url, &tls).await.unwrap());
+
+        let missing = TlsOptions {
+            ca_cert_path: Some("/nonexistent/ca.pem".to_string()),
+            ..TlsOptions::default()
+        };
+        assert!(matches!(
+            HttpClient::new(&profile(&url, &missing)),
+            Err(DurianError::ConfigError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pinning() {
+        let url = start_server();
+        let pinned = TlsOptions {
+            pinned_spki_sha256: vec![SERVER_FINGERPRINT.to_string()],
+            ..TlsOptions::default()
+        };
+        assert!(verify(&url, &pinned).await.unwrap());
+
+        // 即使证书链可信，公钥不匹配时也拒绝连接
+        let ca = ca_file();
+        let wrong = TlsOptions {
+            ca_cert_path: Some(ca.path().to_string_lossy().into_owned()),
+            pinned_spki_sha256: vec![BASE64.encode([0u8; 32])],
+            ..TlsOptions::default()
+        };
+        match verify(&url, &wrong).await {
+            Err(DurianError::CertificatePinMismatch { fingerprint }) => {
+                assert_eq!(fingerprint, SERVER_FINGERPRINT)
+            }
+            other => panic!("expected pin mismatch, got {:?}", other),
+        }
+
+        // 同一客户端上同时进行的请求各自得到校验失败的原因
+        let client = HttpClient::new(&profile(&url, &wrong)).unwrap();
+        let (first, second) = tokio::join!(
+            api_client::api_verify(&client, &url, "token"),
+            api_client::api_verify(&client, &url, "token"),
+        );
+        for result in [first, second] {
+            assert!(matches!(result, Err(DurianError::CertificatePinMismatch { .. })));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_trust_on_first_use() {
+        let url = start_server();
+        let tofu = TlsOptions {
+            trust_on_first_use: true,
+            ..TlsOptions::default()
+        };
+        match verify(&url, &tofu).await {
+            Err(DurianError::UntrustedCertificate { fingerprint }) => {
+                assert_eq!(fingerprint, SERVER_FINGERPRINT)
+            }
+            other => panic!("expected untrusted certificate, got {:?}", other),
+        }
+
+        assert_eq!(
+            HttpClient::probe_fingerprint(&profile(&url, &TlsOptions::default()))
+                .await
+                .unwrap(),
+            Some(SERVER_FINGERPRINT.to_string())
+        );
+
+        let ca = ca_file();
+        let trusted = TlsOptions {
+            ca_cert_path: Some(ca.path().to_string_lossy().into_owned()),
+            ..TlsOptions::default()
+        };
+        assert_eq!(
+            HttpClient::probe_fingerprint(&profile(&url, &trusted))
+                .await
+                .unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! 保险库解锁模块
+//!
+//! 负责在登录后获取本次会话的保险库密钥
+//!
+//! # 信封加密
+//! 记录由随机数据密钥加密，数据密钥由核心密码包装后保存在服务器上（`key_id` 为 `core`），
+//! 并缓存到本地数据库以支持离线解锁。
+//!
+//! # 解锁流程
+//! 1. 使用账户的保险库密钥参数从核心密码派生密钥
+//! 2. 从服务器获取包装后的数据密钥，网络不可用时使用本地缓存
+//! 3. 存在 `core` 包装时解包并缓存
+//! 4. 不存在时生成新的数据密钥并上传；服务器已有其他设备上传的包装时改用该包装。
+//!    这是一次性的迁移：本地缓存随后由派生密钥重新加密到数据密钥下
+//!    （见 [`crate::database::prepare_cache`]），已有记录在登录后迁移
+//!    （见 [`crate::rekey::migrate_legacy_entries`]）
+//! 5. 服务器不支持信封加密时，直接使用派生密钥（旧模式）
+//!
+//! 全字段加密设置与数据密钥包装一起保存在服务器上，联网解锁时一并缓存到本地，
+//! 供离线写入时使用
+//!
+//! 自动锁定后使用 [`unlock_cached`] 在本地重新解锁
+
+use std::path::Path;
+
+use crate::api_client::ApiClient;
+use crate::crypto::{generate_data_key, unwrap_data_key, wrap_data_key, VaultKey};
+use crate::database;
+use crate::error::{DurianError, DurianResult};
+use crate::kdf::KdfParams;
+use crate::models::WrappedKey;
+
+/// 核心密码对应的数据密钥包装标识
+pub const CORE_KEY_ID: &str = "core";
+
+/// 解锁保险库，返回本次会话的保险库密钥
+///
+/// # Arguments
+/// * `api` - API 客户端
+/// * `db_path` - 数据库文件路径（缓存包装后的数据密钥）
+/// * `session_id` - 会话标识（本地缓存按其区分）
+/// * `vault_kdf` - 保险库密钥参数（见 [`crate::kdf::KdfSuite::vault_params`]）
+/// * `token` - 认证令牌
+/// * `core_password` - 核心密码
+pub async fn unlock_vault(
+    api: &dyn ApiClient,
+    db_path: &Path,
+    session_id: &str,
+    vault_kdf: &KdfParams,
+    token: &str,
+    core_password: &str,
+) -> DurianResult<VaultKey> {
+    let key = VaultKey::derive(core_password, vault_kdf)?;
+
+    let (keys, online) = match api.get_vault_keys(token).await {
+        Ok(Some(data)) => {
+            database::set_full_field_encryption(db_path, session_id, data.full_field_encryption)?;
+            (data.keys, true)
+        }
+        Ok(None) | Err(DurianError::NetworkError(_)) => {
+            (database::load_wrapped_keys(db_path, session_id)?, false)
+        }
+        Err(e) => return Err(e),
+    };
+
+    if let Some(wrapped) = find_core_key(&keys) {
+        let data_key = unwrap_data_key(wrapped, core_password)?;
+        database::save_wrapped_key(db_path, session_id, wrapped)?;
+        return Ok(key.with_data_key(data_key));
+    }
+
+    // 离线或服务器不支持时无法安全地创建数据密钥，暂时使用派生密钥
+    if !online {
+        return Ok(key);
+    }
+
+    let data_key = generate_data_key()?;
+    let wrapped = wrap_data_key(data_key.expose(), CORE_KEY_ID, core_password)?;
+    let response = api.create_vault_key(token, &wrapped).await?;
+    if response.is_success() {
+        database::save_wrapped_key(db_path, session_id, &wrapped)?;
+        return Ok(key.with_data_key(data_key));
+    }
+
+    // 其他设备已抢先创建数据密钥
+    let keys = api
+        .get_vault_keys(token)
+        .await?
+        .map(|data| data.keys)
+        .unwrap_or_default();
+    match find_core_key(&keys) {
+        Some(wrapped) => {
+            let data_key = unwrap_data_key(wrapped, core_password)?;
+            database::save_wrapped_key(db_path, session_id, wrapped)?;
+            Ok(key.with_data_key(data_key))
+        }
+        None => Err(DurianError::from_response(response)),
+    }
+}
+
+/// 使用本地缓存的包装解锁保险库（用于锁定后重新解锁）
+///
+/// 不访问网络；本地没有包装时使用派生密钥。
+/// 调用方需自行校验核心密码是否正确（例如比较缓存密钥校验值）
+///
+/// # Arguments
+/// * `db_path` - 数据库文件路径
+/// * `session_id` - 会话标识
+/// * `vault_kdf` - 登录时使用的保险库密钥参数
+/// * `core_password` - 核心密码
+pub fn unlock_cached(
+    db_path: &Path,
+    session_id: &str,
+    vault_kdf: &KdfParams,
+    core_password: &str,
+) -> DurianResult<VaultKey> {
+    let key = VaultKey::derive(core_password, vault_kdf)?;
+    let keys = database::load_wrapped_keys(db_path, session_id)?;
+    match find_core_key(&keys) {
+        Some(wrapped) => Ok(key.with_data_key(unwrap_data_key(wrapped, core_password)?)),
+        None => Ok(key),
+    }
+}
+
+/// 查找核心密码对应的包装
+fn find_core_key(keys: &[WrappedKey]) -> Option<&WrappedKey> {
+    keys.iter().find(|key| key.key_id == CORE_KEY_ID)
+}
@@ -0,0 +1,175 @@
+//! 缓存完整性校验模块
+//!
+//! 本地缓存可能与服务器不一致（例如全量写入中途崩溃，或漏掉了墓碑），
+//! 此前只能清除缓存后全量拉取。校验时将缓存中的 `(rid, revision)` 按记录 ID 分桶，
+//! 每个分桶计算一个摘要发送到服务器；服务器返回摘要不一致的分桶，
+//! 客户端只重新拉取这些分桶中的记录并替换缓存，记录很多时也只需传输少量数据
+//!
+//! # 摘要
+//! 记录按 `rid.rem_euclid(分桶数)` 分桶；分桶摘要为按 `rid` 升序拼接的 `"{rid}:{revision}\n"`
+//! 的 SHA-256（小写十六进制），空分桶为空字符串的摘要。参考服务器使用相同的算法
+//!
+//! # 分桶数
+//! 按缓存记录数选择：约每个分桶 [`RECORDS_PER_BUCKET`] 条记录，取 2 的幂，
+//! 在 [`MIN_BUCKETS`] 和 [`MAX_BUCKETS`] 之间
+//!
+//! # 未同步的写入
+//! 校验前先回放离线写入队列并增量拉取；替换分桶时保留仍有离线写入的记录，不覆盖乐观修改
+
+use ring::digest;
+
+use crate::error::{DurianError, DurianResult};
+use crate::i18n::{tr, Message};
+use crate::models::VerifyReport;
+use crate::state::{AppState, Connection};
+use crate::sync;
+
+// ============================================
+// 配置常量
+// ============================================
+
+/// 每个分桶的目标记录数
+pub const RECORDS_PER_BUCKET: usize = 16;
+
+/// 最少分桶数
+pub const MIN_BUCKETS: usize = 16;
+
+/// 最多分桶数
+pub const MAX_BUCKETS: usize = 1024;
+
+// ============================================
+// 分桶与摘要
+// ============================================
+
+/// 按缓存记录数选择分桶数
+pub fn bucket_count(records: usize) -> usize {
+    records
+        .div_ceil(RECORDS_PER_BUCKET)
+        .next_power_of_two()
+        .clamp(MIN_BUCKETS, MAX_BUCKETS)
+}
+
+/// 记录所在的分桶
+pub fn bucket_of(rid: i64, bucket_count: usize) -> usize {
+    rid.rem_euclid(bucket_count as i64) as usize
+}
+
+/// 计算每个分桶的摘要
+///
+/// # Arguments
+/// * `records` - 记录的 `(rid, revision)`，顺序不限
+/// * `bucket_count` - 分桶数
+pub fn bucket_digests(records: &[(i64, i64)], bucket_count: usize) -> Vec<String> {
+    let mut sorted = records.to_vec();
+    sorted.sort_unstable();
+
+    let mut contexts: Vec<digest::Context> = (0..bucket_count)
+        .map(|_| digest::Context::new(&digest::SHA256))
+        .collect();
+    for (rid, revision) in sorted {
+        let line = format!("{}:{}\n", rid, revision);
+        contexts[bucket_of(rid, bucket_count)].update(line.as_bytes());
+    }
+    contexts
+        .into_iter()
+        .map(|context| hex::encode(context.finish()))
+        .collect()
+}
+
+// ============================================
+// 校验
+// ============================================
+
+/// 校验本地缓存并修复与服务器不一致的分桶
+///
+/// # Arguments
+/// * `app_state` - 应用状态
+/// * `connection` - 会话的服务器连接信息
+///
+/// # Returns
+/// 校验结果；仍有离线写入未能发送时返回网络错误
+pub async fn verify_cache(
+    app_state: &AppState,
+    connection: &Connection,
+) -> DurianResult<VerifyReport> {
+    let session_id = connection.session_id.as_str();
+    let token = connection.token.expose();
+
+    if sync::pull_accounts(app_state, connection).await?.is_none() {
+        return Err(DurianError::network(tr(Message::VerifyNeedsNetwork)));
+    }
+
+    let revisions = app_state.session(session_id)?.account_revisions()?;
+    let buckets = bucket_count(revisions.len());
+    let digests = bucket_digests(&revisions, buckets);
+    let response = connection.api.verify_accounts(token, &digests).await?;
+    let mut mismatched = match response.data {
+        Some(data) if response.is_success() => data.buckets,
+        _ => return Err(DurianError::from_response(response)),
+    };
+    mismatched.retain(|bucket| *bucket < buckets);
+    mismatched.sort_unstable();
+    mismatched.dedup();
+
+    let mut report = VerifyReport {
+        buckets,
+        mismatched: mismatched.len(),
+        ..VerifyReport::default()
+    };
+    if mismatched.is_empty() {
+        return Ok(report);
+    }
+
+    let response = connection
+        .api
+        .query_account_buckets(token, buckets, &mismatched)
+        .await?;
+    let accounts = match response.data {
+        Some(data) if response.is_success() => data.accounts,
+        _ => return Err(DurianError::from_response(response)),
+    };
+    let state = app_state.session(session_id)?;
+    let records = accounts
+        .iter()
+        .map(|item| sync::open_server_item(&state, item))
+        .collect::<DurianResult<Vec<_>>>()?;
+    report.changes = state.replace_buckets(buckets, &mismatched, &records)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_count() {
+        assert_eq!(bucket_count(0), MIN_BUCKETS);
+        assert_eq!(bucket_count(100), MIN_BUCKETS);
+        assert_eq!(bucket_count(1000), 64);
+        assert_eq!(bucket_count(1_000_000), MAX_BUCKETS);
+    }
+
+    #[test]
+    fn test_bucket_digests() {
+        let records = [(1, 1), (17, 2), (2, 1)];
+        let digests = bucket_digests(&records, 16);
+        assert_eq!(digests.len(), 16);
+
+        // 与顺序无关
+        assert_eq!(bucket_digests(&[(2, 1), (17, 2), (1, 1)], 16), digests);
+
+        // 空分桶为空字符串的摘要
+        let empty = hex::encode(digest::digest(&digest::SHA256, b""));
+        assert_eq!(digests[3], empty);
+        let expected = hex::encode(digest::digest(&digest::SHA256, b"1:1\n17:2\n"));
+        assert_eq!(digests[1], expected);
+
+        // 只影响所在的分桶
+        let changed = bucket_digests(&[(1, 1), (17, 3), (2, 1)], 16);
+        let differ: Vec<usize> = (0..16).filter(|&i| changed[i] != digests[i]).collect();
+        assert_eq!(differ, vec![1]);
+
+        // 临时记录 ID 为负数
+        assert_eq!(bucket_of(-1, 16), 15);
+    }
+}
@@ -31,7 +31,7 @@ function InsertForm() {
       );
 
       if (code === 0) {
-        message.success("插入成功");
+        message.success(msg || "插入成功");
         form.resetFields();
       } else {
         message.error(msg || "插入失败");
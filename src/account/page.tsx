@@ -1,12 +1,21 @@
+import { useEffect } from "react";
 import { Tabs } from "antd";
 // import { useNavigate } from "react-router-dom";
 import { ManagerBoard } from "./components/manager-board";
 import { QueryManager } from "./query";
 import { InsertManager } from "./insert";
+import { cancelRequests } from "../libs/tauri";
 
 export default function AccountApp() {
   // const navigate = useNavigate();
 
+  // 离开页面时取消进行中的请求
+  useEffect(() => {
+    return () => {
+      void cancelRequests();
+    };
+  }, []);
+
   return (
     <ManagerBoard>
       <div>
@@ -172,6 +172,8 @@ function DataTable() {
           website: acc.website.trim(),
           account: acc.account,
           password: acc.password,
+          revision: acc.revision,
+          updated_at: acc.updated_at,
         }));
         setData(accountsData);
         setFilteredData(accountsData);
@@ -211,6 +213,8 @@ function DataTable() {
           website: acc.website.trim(),
           account: acc.account,
           password: acc.password,
+          revision: acc.revision,
+          updated_at: acc.updated_at,
         }));
         setData(accountsData);
         setFilteredData(accountsData);
@@ -12,6 +12,7 @@ import {
 } from "react";
 import type { ReactNode } from "react";
 import { useNavigate } from "react-router-dom";
+import { message } from "antd";
 import * as api from "../libs/tauri";
 import type { User, AuthContextType, LoginFormData } from "../types";
 
@@ -54,6 +55,13 @@ export function AuthProvider({ children }: AuthProviderProps) {
       );
 
       if (response.code === 0 && response.data) {
+        // 登录后的 KDF 升级或旧格式记录迁移失败不影响登录，提示原因
+        const { kdf_upgrade_error, migration_error } = response.data;
+        for (const warning of [kdf_upgrade_error, migration_error]) {
+          if (warning) {
+            message.warning(warning.message);
+          }
+        }
         setUser({ username: data.username });
         return true;
       } else {
@@ -3,56 +3,202 @@
  * 封装与 Tauri 后端的所有交互逻辑
  */
 import { invoke } from "@tauri-apps/api/core";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
 import type {
   CacheData,
   ApiResponse,
   LoginResponseData,
   QueryResponseData,
+  PullMode,
   AccountItem,
+  LockSettings,
+  SessionInfo,
+  ServerProfile,
+  SyncStatus,
+  FlushReport,
+  AccountConflict,
+  ConflictChoice,
+  SyncSettings,
+  SyncEventPayload,
+  SyncFinishedPayload,
+  VerifyReport,
+  ErrorPayload,
+  Locale,
 } from "../types";
 
-// API 基础 URL（从环境变量获取）
+// 没有保存的服务器配置时使用的 API 基础 URL（从环境变量获取）
 const API_BASE_URL = import.meta.env.VITE_API_BASE_URL || 'http://localhost:7224/api';
 
 // ============================================
 // 认证相关 API
 // ============================================
 
-/** 用户登录 */
+/** 没有保存的服务器配置时，按环境变量中的 API 基础 URL 创建默认服务器 */
+async function ensureDefaultProfile(): Promise<void> {
+  if ((await listProfiles()).length > 0) {
+    return;
+  }
+  await invoke("save_profile", {
+    profile: {
+      name: "default",
+      api_base_url: API_BASE_URL,
+      tls: { ca_cert_path: null, pinned_spki_sha256: [], trust_on_first_use: false },
+      timeouts: { request_timeout_secs: 30, connect_timeout_secs: 10 },
+      proxy_url: null,
+      user_agent: null,
+      is_default: true,
+    } satisfies ServerProfile,
+  });
+}
+
+/** 用户登录（`profile` 为服务器名称，未指定时使用默认服务器） */
 export async function login(
   username: string,
   password: string,
-  corePassword: string
+  corePassword: string,
+  profile?: string
 ): Promise<ApiResponse<LoginResponseData>> {
   try {
-    const token = await invoke<string>("login", {
-      apiBaseUrl: API_BASE_URL,
+    await ensureDefaultProfile();
+    // KDF 升级或旧格式记录迁移失败不影响登录，原因在返回值中
+    const data = await invoke<LoginResponseData>("login", {
+      profile: profile ?? null,
       username,
       password,
       corePassword,
     });
-    return { code: 0, msg: "登录成功", data: { token } };
+    return { code: 0, msg: "登录成功", data };
   } catch (error) {
-    return { code: -1, msg: getErrorMessage(error) };
+    return failure(error);
   }
 }
 
-/** 用户注册 */
+/** 用户注册（`profile` 为服务器名称，未指定时使用默认服务器） */
 export async function register(
   username: string,
   password: string,
-  corePassword: string
+  corePassword: string,
+  profile?: string
 ): Promise<ApiResponse<void>> {
   try {
+    await ensureDefaultProfile();
     await invoke("register", {
-      apiBaseUrl: API_BASE_URL,
+      profile: profile ?? null,
       username,
       password,
       corePassword,
     });
     return { code: 0, msg: "注册成功" };
   } catch (error) {
-    return { code: -1, msg: getErrorMessage(error) };
+    return failure(error);
+  }
+}
+
+/** 修改核心密码（会重新加密所有记录，中断后以相同参数再次调用即可继续） */
+export async function changeCorePassword(
+  oldCorePassword: string,
+  newCorePassword: string
+): Promise<ApiResponse<void>> {
+  try {
+    await invoke("change_core_password", { oldCorePassword, newCorePassword });
+    return { code: 0, msg: "核心密码修改成功" };
+  } catch (error) {
+    return failure(error);
+  }
+}
+
+/** 检查是否存在未完成的核心密码修改 */
+export async function hasPendingCorePasswordChange(): Promise<boolean> {
+  try {
+    return await invoke<boolean>("has_pending_core_password_change");
+  } catch {
+    return false;
+  }
+}
+
+/** 锁定保险库（清除内存中的密钥，保留登录状态） */
+export async function lock(): Promise<void> {
+  await invoke("lock");
+}
+
+/** 使用核心密码解锁保险库 */
+export async function unlock(corePassword: string): Promise<ApiResponse<void>> {
+  try {
+    await invoke("unlock", { corePassword });
+    return { code: 0, msg: "解锁成功" };
+  } catch (error) {
+    return failure(error);
+  }
+}
+
+/** 检查保险库是否已锁定 */
+export async function isLocked(): Promise<boolean> {
+  try {
+    return await invoke<boolean>("is_locked");
+  } catch {
+    return false;
+  }
+}
+
+/** 获取自动锁定设置 */
+export async function getLockSettings(): Promise<LockSettings | null> {
+  try {
+    return await invoke<LockSettings>("get_lock_settings");
+  } catch {
+    return null;
+  }
+}
+
+/** 修改自动锁定设置（idleTimeoutSecs 为 0 表示不按空闲时间锁定） */
+export async function setLockSettings(
+  idleTimeoutSecs: number,
+  lockOnSuspend: boolean
+): Promise<ApiResponse<void>> {
+  try {
+    await invoke("set_lock_settings", { idleTimeoutSecs, lockOnSuspend });
+    return { code: 0, msg: "设置已保存" };
+  } catch (error) {
+    return failure(error);
+  }
+}
+
+/** 开启或关闭全字段加密（开启时会加密已有记录的网站和账户） */
+export async function setFullFieldEncryption(
+  enabled: boolean
+): Promise<ApiResponse<void>> {
+  try {
+    const msg = await invoke<string>("set_full_field_encryption", { enabled });
+    return { code: 0, msg };
+  } catch (error) {
+    return failure(error);
+  }
+}
+
+/** 检查是否开启了全字段加密 */
+export async function getFullFieldEncryption(): Promise<boolean> {
+  try {
+    return await invoke<boolean>("get_full_field_encryption");
+  } catch {
+    return false;
+  }
+}
+
+/** 列出所有已登录的会话（当前会话在最前） */
+export async function listSessions(): Promise<SessionInfo[]> {
+  try {
+    return await invoke<SessionInfo[]>("list_sessions");
+  } catch {
+    return [];
+  }
+}
+
+/** 切换当前会话（`id` 为 `listSessions` 返回的会话标识；无需重新登录，目标会话已锁定时需单独解锁） */
+export async function switchSession(id: string): Promise<ApiResponse<void>> {
+  try {
+    await invoke("switch_session", { id });
+    return { code: 0, msg: "切换成功" };
+  } catch (error) {
+    return failure(error);
   }
 }
 
@@ -74,12 +220,78 @@ export async function getUsername(): Promise<string | null> {
   }
 }
 
-/** 获取认证令牌 */
-export async function getToken(): Promise<string | null> {
+// ============================================
+// 服务器配置 API
+// ============================================
+
+/** 列出保存的服务器配置 */
+export async function listProfiles(): Promise<ServerProfile[]> {
   try {
-    return await invoke<string>("get_token");
+    return await invoke<ServerProfile[]>("list_profiles");
   } catch {
-    return null;
+    return [];
+  }
+}
+
+/** 保存服务器配置（同名配置会被替换） */
+export async function saveProfile(profile: ServerProfile): Promise<ApiResponse<void>> {
+  try {
+    await invoke("save_profile", { profile });
+    return { code: 0, msg: "服务器配置已保存" };
+  } catch (error) {
+    return failure(error);
+  }
+}
+
+/** 删除服务器配置 */
+export async function deleteProfile(name: string): Promise<ApiResponse<void>> {
+  try {
+    await invoke("delete_profile", { name });
+    return { code: 0, msg: "服务器配置已删除" };
+  } catch (error) {
+    return failure(error);
+  }
+}
+
+/** 获取服务器证书的公钥指纹（证书已受信任时返回 null） */
+export async function probeServerCertificate(name: string): Promise<ApiResponse<string | null>> {
+  try {
+    const fingerprint = await invoke<string | null>("probe_server_certificate", { name });
+    return { code: 0, msg: "获取成功", data: fingerprint };
+  } catch (error) {
+    return failure(error);
+  }
+}
+
+/** 信任服务器证书（用户核对指纹后调用） */
+export async function trustServerCertificate(
+  name: string,
+  fingerprint: string
+): Promise<ApiResponse<void>> {
+  try {
+    await invoke("trust_server_certificate", { name, fingerprint });
+    return { code: 0, msg: "已信任服务器证书" };
+  } catch (error) {
+    return failure(error);
+  }
+}
+
+// ============================================
+// 界面语言 API
+// ============================================
+
+/** 获取当前界面语言（后端错误和状态消息使用的语言） */
+export async function getLocale(): Promise<Locale> {
+  return await invoke<Locale>("get_locale");
+}
+
+/** 设置界面语言（null 表示跟随系统），返回生效的语言 */
+export async function setLocale(locale: Locale | null): Promise<ApiResponse<Locale>> {
+  try {
+    const effective = await invoke<Locale>("set_locale", { locale });
+    return { code: 0, msg: "设置已保存", data: effective };
+  } catch (error) {
+    return failure(error);
   }
 }
 
@@ -92,15 +304,7 @@ export async function queryAccounts(
   forceRefresh: boolean = false
 ): Promise<ApiResponse<QueryResponseData>> {
   try {
-    const result = await invoke<string>("query_accounts", { forceRefresh });
-    if (!result || result === "{}") {
-      return {
-        code: 0,
-        msg: "无数据",
-        data: { pull_mode: "PULL_NOTHING", update_time: 0, accounts: [] },
-      };
-    }
-    const data = JSON.parse(result) as CacheData;
+    const data = await invoke<CacheData>("query_accounts", { forceRefresh });
     return {
       code: 0,
       msg: "查询成功",
@@ -108,10 +312,11 @@ export async function queryAccounts(
         pull_mode: "PULL_ALL",
         update_time: data.update_time,
         accounts: data.accounts,
+        deleted_rids: [],
       },
     };
   } catch (error) {
-    return { code: -1, msg: getErrorMessage(error) };
+    return failure(error);
   }
 }
 
@@ -122,10 +327,11 @@ export async function insertAccount(
   password: string
 ): Promise<ApiResponse<void>> {
   try {
-    await invoke("insert_account", { website, account, password });
-    return { code: 0, msg: "插入成功" };
+    // 离线时写入保存在本地队列中，消息会提示稍后同步
+    const msg = await invoke<string>("insert_account", { website, account, password });
+    return { code: 0, msg };
   } catch (error) {
-    return { code: -1, msg: getErrorMessage(error) };
+    return failure(error);
   }
 }
 
@@ -137,20 +343,110 @@ export async function updateAccount(
   password: string
 ): Promise<ApiResponse<void>> {
   try {
-    await invoke("update_account", { rid, website, account, password });
-    return { code: 0, msg: "更新成功" };
+    // 离线时写入保存在本地队列中，消息会提示稍后同步
+    const msg = await invoke<string>("update_account", { rid, website, account, password });
+    return { code: 0, msg };
   } catch (error) {
-    return { code: -1, msg: getErrorMessage(error) };
+    return failure(error);
   }
 }
 
 /** 删除账户 */
 export async function deleteAccount(rid: number): Promise<ApiResponse<void>> {
   try {
-    await invoke("delete_account", { rid });
-    return { code: 0, msg: "删除成功" };
+    // 离线时写入保存在本地队列中，消息会提示稍后同步
+    const msg = await invoke<string>("delete_account", { rid });
+    return { code: 0, msg };
+  } catch (error) {
+    return failure(error);
+  }
+}
+
+/** 回放离线写入队列（网络恢复时调用） */
+export async function syncPendingOps(): Promise<ApiResponse<FlushReport>> {
+  try {
+    const report = await invoke<FlushReport>("sync_pending_ops");
+    return { code: 0, msg: "同步成功", data: report };
   } catch (error) {
-    return { code: -1, msg: getErrorMessage(error) };
+    return failure(error);
+  }
+}
+
+/** 获取离线写入队列的状态 */
+export async function getSyncStatus(): Promise<SyncStatus | null> {
+  try {
+    return await invoke<SyncStatus>("get_sync_status");
+  } catch {
+    return null;
+  }
+}
+
+/** 清除被服务器拒绝的写入操作 */
+export async function clearSyncFailures(): Promise<void> {
+  await invoke("clear_sync_failures");
+}
+
+/** 获取后台同步设置 */
+export async function getSyncSettings(): Promise<SyncSettings | null> {
+  try {
+    return await invoke<SyncSettings>("get_sync_settings");
+  } catch {
+    return null;
+  }
+}
+
+/** 修改后台同步间隔（intervalSecs 为 0 表示不在后台同步） */
+export async function setSyncSettings(intervalSecs: number): Promise<ApiResponse<void>> {
+  try {
+    await invoke("set_sync_settings", { intervalSecs });
+    return { code: 0, msg: "设置已保存" };
+  } catch (error) {
+    return failure(error);
+  }
+}
+
+/** 监听后台同步事件，返回取消监听的函数 */
+export async function onBackgroundSync(handlers: {
+  started?: (payload: SyncEventPayload) => void;
+  finished?: (payload: SyncFinishedPayload) => void;
+  failed?: (payload: SyncEventPayload) => void;
+}): Promise<UnlistenFn> {
+  const unlisteners = await Promise.all([
+    listen<SyncEventPayload>("sync-started", (event) => handlers.started?.(event.payload)),
+    listen<SyncFinishedPayload>("sync-finished", (event) => handlers.finished?.(event.payload)),
+    listen<SyncEventPayload>("sync-failed", (event) => handlers.failed?.(event.payload)),
+  ]);
+  return () => unlisteners.forEach((unlisten) => unlisten());
+}
+
+/** 列出与其他设备的修改冲突 */
+export async function listConflicts(): Promise<AccountConflict[]> {
+  try {
+    return await invoke<AccountConflict[]>("list_conflicts");
+  } catch {
+    return [];
+  }
+}
+
+/** 处理修改冲突：保留本地版本、采用服务器版本或两者都保留 */
+export async function resolveConflict(
+  rid: number,
+  choice: ConflictChoice
+): Promise<ApiResponse<void>> {
+  try {
+    const msg = await invoke<string>("resolve_conflict", { rid, choice });
+    return { code: 0, msg };
+  } catch (error) {
+    return failure(error);
+  }
+}
+
+/** 取消当前会话进行中的网络请求（离开页面时调用） */
+export async function cancelRequests(): Promise<void> {
+  try {
+    await invoke("cancel_requests");
+  } catch {
+    // 未登录时没有需要取消的请求
   }
 }
 
@@ -196,16 +492,12 @@ export async function decryptAccounts(
 
 /** 保存查询缓存 */
 export async function saveQueryCache(
-  pullMode: string,
+  pullMode: PullMode,
   updateTime: number,
   accounts: AccountItem[]
 ): Promise<void> {
   try {
-    await invoke("save_query_cache", {
-      pullMode,
-      updateTime,
-      accountsJson: JSON.stringify(accounts),
-    });
+    await invoke("save_query_cache", { pullMode, updateTime, accounts });
   } catch (error) {
     console.error("保存缓存失败:", error);
   }
@@ -214,9 +506,7 @@ export async function saveQueryCache(
 /** 加载查询缓存 */
 export async function loadQueryCache(): Promise<CacheData | null> {
   try {
-    const result = await invoke<string>("load_query_cache");
-    if (!result || result === "{}") return null;
-    return JSON.parse(result) as CacheData;
+    return await invoke<CacheData | null>("load_query_cache");
   } catch (error) {
     console.error("加载缓存失败:", error);
     return null;
@@ -228,13 +518,43 @@ export async function getLastUpdateTime(): Promise<number> {
   return invoke<number>("get_last_update_time");
 }
 
+/** 校验本地缓存，重新拉取与服务器不一致的部分 */
+export async function verifyCache(): Promise<ApiResponse<VerifyReport>> {
+  try {
+    const report = await invoke<VerifyReport>("verify_cache");
+    return { code: 0, msg: "校验完成", data: report };
+  } catch (error) {
+    return failure(error);
+  }
+}
+
 // ============================================
 // 工具函数
 // ============================================
 
+/** 判断是否为命令返回的结构化错误 */
+export function isErrorPayload(error: unknown): error is ErrorPayload {
+  return (
+    typeof error === 'object' &&
+    error !== null &&
+    typeof (error as ErrorPayload).kind === 'string' &&
+    typeof (error as ErrorPayload).message === 'string'
+  );
+}
+
 /** 获取错误消息 */
 function getErrorMessage(error: unknown): string {
+  if (isErrorPayload(error)) return error.message;
   if (error instanceof Error) return error.message;
   if (typeof error === 'string') return error;
   return '未知错误';
 }
+
+/** 将命令错误转换为失败的响应，保留结构化错误 */
+function failure<T>(error: unknown): ApiResponse<T> {
+  return {
+    code: -1,
+    msg: getErrorMessage(error),
+    error: isErrorPayload(error) ? error : undefined,
+  };
+}
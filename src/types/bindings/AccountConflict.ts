@@ -0,0 +1,21 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AccountRecord } from "./AccountRecord";
+
+/**
+ * 本地修改与其他设备的修改冲突的记录
+ *
+ * 两个版本的网站和账户均为明文，密码为密文
+ */
+export type AccountConflict = { rid: number, 
+/**
+ * 本地未能同步的版本（`revision` 为其所基于的修订号）
+ */
+local: AccountRecord, 
+/**
+ * 服务器上的当前版本
+ */
+server: AccountRecord, 
+/**
+ * 发现冲突的时间（Unix 秒）
+ */
+detected_at: number, };
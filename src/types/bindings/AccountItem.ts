@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * 账户项（API返回的格式）
+ */
+export type AccountItem = { rid: number, website: string, account: string, password: string, 
+/**
+ * 服务器上的修订号，更新时必须提交以检测冲突（服务器拒绝为 0 的更新）
+ */
+revision: number, 
+/**
+ * 服务器上最后修改的时间（毫秒）
+ */
+updated_at: number, 
+/**
+ * 网站和账户字段是否为加密后的密文（全字段加密）
+ */
+fields_encrypted: boolean, };
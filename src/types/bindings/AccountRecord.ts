@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * 账户记录（本地存储格式）
+ */
+export type AccountRecord = { rid: number, username: string, website: string, account: string, password: string, 
+/**
+ * 本地缓存所基于的服务器修订号
+ */
+revision: number, 
+/**
+ * 服务器上最后修改的时间（毫秒）
+ */
+updated_at: number, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AccountRecord } from "./AccountRecord";
+
+/**
+ * 缓存数据结构
+ */
+export type CacheData = { username: string, update_time: number, accounts: Array<AccountRecord>, };
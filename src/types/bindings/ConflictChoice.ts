@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * 处理修改冲突时保留的版本
+ */
+export type ConflictChoice = "local" | "server" | "keep_both";
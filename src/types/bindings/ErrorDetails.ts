@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * 错误的附加信息
+ */
+export type ErrorDetails = { 
+/**
+ * HTTP 状态码
+ */
+status?: number, 
+/**
+ * 服务器证书公钥指纹
+ */
+fingerprint?: string, 
+/**
+ * 相关的用户名
+ */
+username?: string, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * 稳定的错误类别
+ *
+ * 前端按类别决定如何处理错误（重新登录、解锁、重试等），新增类别不改变已有类别的含义
+ */
+export type ErrorKind = "not_logged_in" | "unauthorized" | "invalid_credentials" | "locked" | "certificate_pin_mismatch" | "untrusted_certificate" | "network" | "cancelled" | "conflict" | "rate_limited" | "server" | "rejected" | "validation" | "database" | "crypto" | "serialization" | "io" | "config" | "internal" | "unknown";
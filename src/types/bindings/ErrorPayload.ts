@@ -0,0 +1,28 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ErrorDetails } from "./ErrorDetails";
+import type { ErrorKind } from "./ErrorKind";
+
+/**
+ * 命令返回给前端的错误
+ */
+export type ErrorPayload = { 
+/**
+ * 错误类别
+ */
+kind: ErrorKind, 
+/**
+ * 服务器返回的 API 错误码（仅 API 错误）
+ */
+code: number | null, 
+/**
+ * 错误描述
+ */
+message: string, 
+/**
+ * 稍后重试是否可能成功
+ */
+retryable: boolean, 
+/**
+ * 附加信息
+ */
+details: ErrorDetails, };
@@ -0,0 +1,22 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * 一次回放离线写入队列的结果
+ */
+export type FlushReport = { 
+/**
+ * 成功发送的操作数
+ */
+synced: number, 
+/**
+ * 被服务器拒绝的操作数
+ */
+rejected: number, 
+/**
+ * 因网络不可用仍在等待的操作数
+ */
+pending: number, 
+/**
+ * 与其他设备的修改冲突、等待用户处理的操作数
+ */
+conflicts: number, };
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * 自动锁定设置
+ */
+export type LockSettings = { 
+/**
+ * 空闲超时时间（秒），0 表示不自动锁定
+ */
+idle_timeout_secs: number, 
+/**
+ * 系统休眠后是否锁定
+ */
+lock_on_suspend: boolean, };
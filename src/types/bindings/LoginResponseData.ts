@@ -0,0 +1,21 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ErrorPayload } from "./ErrorPayload";
+
+/**
+ * `login` 命令的返回值（令牌只保存在后端会话中，不返回前端）
+ *
+ * 登录后的维护步骤失败不影响登录，原因随结果返回，下次登录时重试
+ */
+export type LoginResponseData = { 
+/**
+ * 登录的用户名
+ */
+username: string, 
+/**
+ * 升级过时的 KDF 参数失败的原因
+ */
+kdf_upgrade_error?: ErrorPayload, 
+/**
+ * 迁移旧格式记录失败的原因（记录仍可读取）
+ */
+migration_error?: ErrorPayload, };
@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PendingOpKind } from "./PendingOpKind";
+
+/**
+ * 待同步写入操作的摘要（不包含账户内容）
+ */
+export type PendingOpInfo = { id: number, kind: PendingOpKind, rid: number, 
+/**
+ * 加入队列的时间（Unix 秒）
+ */
+created_at: number, 
+/**
+ * 已尝试发送的次数
+ */
+attempts: number, last_error: string | null, 
+/**
+ * 是否已被服务器拒绝（不再重试）
+ */
+failed: boolean, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * 待同步写入操作的类型
+ */
+export type PendingOpKind = "insert" | "update" | "delete";
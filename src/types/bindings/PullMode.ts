@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * 数据拉取模式
+ */
+export type PullMode = "PULL_ALL" | "PULL_UPDATED" | "PULL_NOTHING";
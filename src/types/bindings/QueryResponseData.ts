@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AccountItem } from "./AccountItem";
+import type { PullMode } from "./PullMode";
+
+/**
+ * 查询响应数据
+ */
+export type QueryResponseData = { pull_mode: PullMode, update_time: number, accounts: Array<AccountItem>, 
+/**
+ * 自 `update_time` 以来被删除的记录 ID（墓碑），增量拉取时使用
+ */
+deleted_rids: Array<number>, };
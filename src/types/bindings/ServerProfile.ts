@@ -0,0 +1,36 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { TimeoutOptions } from "./TimeoutOptions";
+import type { TlsOptions } from "./TlsOptions";
+
+/**
+ * 服务器配置
+ */
+export type ServerProfile = { 
+/**
+ * 名称（唯一）
+ */
+name: string, 
+/**
+ * API 基础 URL
+ */
+api_base_url: string, 
+/**
+ * TLS 选项
+ */
+tls: TlsOptions, 
+/**
+ * 超时时间
+ */
+timeouts: TimeoutOptions, 
+/**
+ * 代理服务器 URL（http、https、socks5 或 socks5h），为空时使用系统代理环境变量
+ */
+proxy_url: string | null, 
+/**
+ * 自定义 User-Agent，为空时使用默认值
+ */
+user_agent: string | null, 
+/**
+ * 是否为默认服务器
+ */
+is_default: boolean, };
@@ -0,0 +1,26 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * 已登录会话的摘要信息
+ */
+export type SessionInfo = { 
+/**
+ * 会话标识，用于切换会话
+ */
+id: string, 
+/**
+ * 用户名
+ */
+username: string, 
+/**
+ * API 基础 URL
+ */
+api_base_url: string, 
+/**
+ * 保险库是否已锁定
+ */
+locked: boolean, 
+/**
+ * 是否为当前会话
+ */
+active: boolean, };
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * 一次从服务器拉取更新后本地缓存的变化
+ */
+export type SyncChanges = { 
+/**
+ * 新增的记录数
+ */
+added: number, 
+/**
+ * 被修改的记录数
+ */
+updated: number, 
+/**
+ * 被删除的记录数
+ */
+deleted: number, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ErrorPayload } from "./ErrorPayload";
+
+/**
+ * `sync-started` 和 `sync-failed` 事件的载荷
+ */
+export type SyncEventPayload = { username: string, 
+/**
+ * 失败原因（只在 `sync-failed` 中出现）
+ */
+error?: ErrorPayload, };
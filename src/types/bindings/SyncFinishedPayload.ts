@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * `sync-finished` 事件的载荷
+ */
+export type SyncFinishedPayload = { username: string, 
+/**
+ * 新增的记录数
+ */
+added: number, 
+/**
+ * 被修改的记录数
+ */
+updated: number, 
+/**
+ * 被删除的记录数
+ */
+deleted: number, };
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * 后台同步设置
+ */
+export type SyncSettings = { 
+/**
+ * 同步间隔（秒），0 表示不在后台同步
+ */
+interval_secs: number, };
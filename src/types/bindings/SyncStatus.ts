@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PendingOpInfo } from "./PendingOpInfo";
+
+/**
+ * 离线写入队列的状态
+ */
+export type SyncStatus = { 
+/**
+ * 等待发送的操作数
+ */
+pending: number, 
+/**
+ * 被服务器拒绝的操作数
+ */
+failed: number, 
+/**
+ * 所有操作，按加入队列的顺序
+ */
+operations: Array<PendingOpInfo>, };
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * 超时时间
+ */
+export type TimeoutOptions = { 
+/**
+ * 请求超时时间（秒）
+ */
+request_timeout_secs: number, 
+/**
+ * 连接超时时间（秒）
+ */
+connect_timeout_secs: number, };
@@ -0,0 +1,20 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * TLS 选项
+ *
+ * 默认使用内置根证书完整校验服务器证书，校验规则见 [`crate::tls`]
+ */
+export type TlsOptions = { 
+/**
+ * 自定义 CA 证书文件路径（PEM）
+ */
+ca_cert_path: string | null, 
+/**
+ * 固定的服务器公钥指纹（SPKI 的 SHA-256，Base64 编码）
+ */
+pinned_spki_sha256: Array<string>, 
+/**
+ * 证书不受信任时是否提示用户首次信任
+ */
+trust_on_first_use: boolean, };
@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SyncChanges } from "./SyncChanges";
+
+/**
+ * 一次缓存校验的结果
+ */
+export type VerifyReport = { 
+/**
+ * 分桶数
+ */
+buckets: number, 
+/**
+ * 与服务器不一致、重新拉取的分桶数
+ */
+mismatched: number, 
+/**
+ * 修复时缓存的变化
+ */
+changes: SyncChanges, };
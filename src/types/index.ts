@@ -1,8 +1,45 @@
 /**
  * 统一类型定义文件
  * 包含所有前端使用的接口和类型
+ *
+ * 与后端命令交互的类型由 Rust 类型生成（`src-tauri` 中运行 `cargo test`），
+ * 位于 ./bindings，不要手动修改
  */
 
+import type { AccountItem } from "./bindings/AccountItem";
+import type { ErrorPayload } from "./bindings/ErrorPayload";
+
+// ============================================
+// 后端类型（由 Rust 类型生成）
+// ============================================
+
+export type { AccountItem } from "./bindings/AccountItem";
+export type { AccountRecord } from "./bindings/AccountRecord";
+export type { CacheData } from "./bindings/CacheData";
+export type { PullMode } from "./bindings/PullMode";
+export type { QueryResponseData } from "./bindings/QueryResponseData";
+export type { LoginResponseData } from "./bindings/LoginResponseData";
+export type { LockSettings } from "./bindings/LockSettings";
+export type { SessionInfo } from "./bindings/SessionInfo";
+export type { PendingOpKind } from "./bindings/PendingOpKind";
+export type { PendingOpInfo } from "./bindings/PendingOpInfo";
+export type { SyncStatus } from "./bindings/SyncStatus";
+export type { FlushReport } from "./bindings/FlushReport";
+export type { SyncSettings } from "./bindings/SyncSettings";
+export type { SyncEventPayload } from "./bindings/SyncEventPayload";
+export type { SyncFinishedPayload } from "./bindings/SyncFinishedPayload";
+export type { SyncChanges } from "./bindings/SyncChanges";
+export type { VerifyReport } from "./bindings/VerifyReport";
+export type { ConflictChoice } from "./bindings/ConflictChoice";
+export type { AccountConflict } from "./bindings/AccountConflict";
+export type { TlsOptions } from "./bindings/TlsOptions";
+export type { TimeoutOptions } from "./bindings/TimeoutOptions";
+export type { ServerProfile } from "./bindings/ServerProfile";
+export type { ErrorKind } from "./bindings/ErrorKind";
+export type { ErrorDetails } from "./bindings/ErrorDetails";
+export type { ErrorPayload } from "./bindings/ErrorPayload";
+export type { Locale } from "./bindings/Locale";
+
 // ============================================
 // 用户相关类型
 // ============================================
@@ -30,26 +67,11 @@ export interface RegisterFormData {
 // 账户相关类型
 // ============================================
 
-/** 账户基础信息（从服务器返回） */
-export interface AccountItem {
-  rid: number;
-  website: string;
-  account: string;
-  password: string;
-}
-
 /** 账户数据（用于表格显示） */
 export interface AccountDataType extends AccountItem {
   key: string;
 }
 
-/** 缓存数据结构 */
-export interface CacheData {
-  username: string;
-  update_time: number;
-  accounts: AccountItem[];
-}
-
 /** 插入账户表单数据 */
 export interface InsertAccountFormData {
   website: string;
@@ -74,18 +96,8 @@ export interface ApiResponse<T = unknown> {
   code: number;
   msg: string;
   data?: T;
-}
-
-/** 登录响应数据 */
-export interface LoginResponseData {
-  token: string;
-}
-
-/** 查询响应数据 */
-export interface QueryResponseData {
-  pull_mode: string;
-  update_time: number;
-  accounts: AccountItem[];
+  /** 命令失败时的结构化错误（按 `kind` 区分错误类别） */
+  error?: ErrorPayload;
 }
 
 // ============================================